@@ -1,24 +1,34 @@
+//! Articles: their metadata from arXiv ([`ArticleMetadata`], stored in the database) and
+//! the user's state ([`ArticleState`]: seen versions, tags, notes and attachments, stored in
+//! the article directories), combined in [`Article`].
+
 use std::{
     collections::{BTreeSet, HashMap},
     ffi::OsStr,
     fmt::Display,
-    fs::{File, create_dir},
-    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    fs::{File, create_dir, remove_file, rename},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
     process::Command,
     str::FromStr,
 };
 
 use anyhow::{Context, bail};
-use chrono::{DateTime, FixedOffset};
-use reqwest::header::HeaderValue;
-use rusqlite::{Row, Transaction, params};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use rusqlite::{Connection, Row, Transaction, params, params_from_iter};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     config::{Highlight, TagName},
-    rate_limited_client::Client,
-    util::{highlight_matches, read_if_exists, write_then_rename},
+    db,
+    downloads::{DownloadDir, DownloadKind, parse_download_name},
+    filter::PreparedFilter,
+    keywords, markdown, message, progress,
+    rate_limited_client::{Client, Download, DownloadDeclined},
+    util::{
+        self, OpenAs, highlight_matches, highlight_whole, read_if_exists, tmp_path,
+        write_then_rename,
+    },
 };
 
 /// Article metadata as received from arXiv.
@@ -45,6 +55,7 @@ pub struct ArticleMetadata {
     pub sets: Option<Vec<String>>,
 }
 
+/// An arXiv identifier without version, such as "2301.01234" or "math/9901001".
 #[derive(Debug, Serialize, Hash, PartialEq, Eq, Clone)]
 pub struct ArxivId(String);
 
@@ -69,20 +80,53 @@ impl FromStr for ArxivId {
 
 impl ArxivId {
     /// Parse arXiv id with an optional version specifier, such as "1234.56789" or "1234.56789v3".
+    /// Also accepts "arXiv:1234.56789" and links such as "https://arxiv.org/abs/1234.56789v3"
+    /// or "https://arxiv.org/pdf/1234.56789.pdf".
     pub fn parse_with_version(s: &str) -> anyhow::Result<(ArxivId, Option<u32>)> {
-        if let Some((a, b)) = s.split_once('v') {
-            Ok((a.parse()?, Some(b.parse()?)))
-        } else {
-            Ok((s.parse()?, None))
+        let input = s;
+        let mut s = s.trim();
+        if let Some(url) = s
+            .strip_prefix("https://")
+            .or_else(|| s.strip_prefix("http://"))
+        {
+            let url = url
+                .strip_prefix("www.")
+                .or_else(|| url.strip_prefix("export."))
+                .unwrap_or(url);
+            let path = url
+                .strip_prefix("arxiv.org/")
+                .with_context(|| format!("not a link to arXiv: {input:?}"))?;
+            s = ["abs/", "pdf/", "src/", "html/", "e-print/"]
+                .iter()
+                .find_map(|prefix| path.strip_prefix(prefix))
+                .with_context(|| format!("not a link to an arXiv article: {input:?}"))?;
+            s = s.split(['?', '#']).next().unwrap().trim_end_matches('/');
+            s = s.strip_suffix(".pdf").unwrap_or(s);
+        } else if let Some(rest) = s
+            .strip_prefix("arXiv:")
+            .or_else(|| s.strip_prefix("arxiv:"))
+        {
+            s = rest;
+        }
+        match s.rsplit_once('v') {
+            Some((a, b)) if !b.is_empty() && b.chars().all(|c| c.is_ascii_digit()) => {
+                Ok((a.parse()?, Some(b.parse()?)))
+            }
+            _ => Ok((s.parse()?, None)),
         }
     }
+
+    /// Parses an id as in `parse_with_version`, ignoring the version.
+    pub fn parse_ignoring_version(s: &str) -> anyhow::Result<ArxivId> {
+        Ok(Self::parse_with_version(s)?.0)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
 
-    use crate::article::ArxivId;
+    use crate::article::{ArxivId, SeenLine, safe_file_name};
 
     #[test]
     fn bla() {
@@ -93,6 +137,72 @@ mod test {
         assert!(ArxivId::from_str("ä").is_err());
         assert!(ArxivId::from_str("12345678901234567890").is_err());
     }
+
+    #[test]
+    fn links() {
+        let parse = |s| ArxivId::parse_with_version(s).unwrap();
+        let id = |s: &str| s.parse::<ArxivId>().unwrap();
+        assert_eq!(parse("2301.01234v2"), (id("2301.01234"), Some(2)));
+        assert_eq!(parse("arXiv:2301.01234"), (id("2301.01234"), None));
+        assert_eq!(
+            parse("https://arxiv.org/abs/2301.01234v2"),
+            (id("2301.01234"), Some(2))
+        );
+        assert_eq!(
+            parse("http://export.arxiv.org/pdf/math/9901001v1.pdf"),
+            (id("math/9901001"), Some(1))
+        );
+        assert_eq!(
+            parse("https://arxiv.org/abs/solv-int/9901001?context=math"),
+            (id("solv-int/9901001"), None)
+        );
+        assert!(ArxivId::parse_with_version("https://example.com/abs/2301.01234").is_err());
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(
+            safe_file_name("Referee report (2).pdf"),
+            "Referee_report__2_.pdf"
+        );
+        assert_eq!(safe_file_name(".bashrc"), "_bashrc");
+        assert_eq!(safe_file_name("Folie_ü.key"), "Folie__.key");
+    }
+
+    #[test]
+    fn seen_lines() {
+        let old = SeenLine::parse("2301.01234 2 true false").unwrap();
+        assert_eq!((old.version, old.journal, old.doi), (2, true, false));
+        assert!(old.time.is_none() && !old.reset);
+        let line = SeenLine::parse("2301.01234 1 false false 2025-10-01T10:00:00+02:00 reset");
+        assert!(line.unwrap().reset);
+        // Columns of later versions are ignored.
+        let line = SeenLine::parse("2301.01234 1 false false 2025-10-01T10:00:00+02:00 x reset");
+        assert!(line.unwrap().reset);
+        let line = SeenLine::parse("2301.01234 1 false false 2025-10-01T10:00:00+02:00 x");
+        assert!(!line.unwrap().reset);
+        assert!(SeenLine::parse("2301.01234 1 false false yesterday").is_err());
+    }
+}
+
+/// Replaces characters other than ASCII letters, digits, '.', '-' and '_' by '_', and
+/// avoids names starting with a dot.
+fn safe_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.strip_prefix('.') {
+        Some(rest) => format!("_{rest}"),
+        None if name.is_empty() => "_".to_string(),
+        None => name,
+    }
 }
 
 impl Display for ArxivId {
@@ -137,6 +247,7 @@ impl ArxivId {
     }
 }
 
+/// A version of an article.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Version {
     pub number: u32,
@@ -237,8 +348,38 @@ impl ArticleMetadata {
     /// Loads from the sqlite database a list of all articles.
     pub fn load(tr: &Transaction) -> anyhow::Result<HashMap<ArxivId, ArticleMetadata>> {
         let mut metadatas = HashMap::new();
+        Self::for_each(tr, |metadata| {
+            metadatas.insert(metadata.id.clone(), metadata);
+            Ok(())
+        })?;
+        Ok(metadatas)
+    }
+
+    /// Calls f for each article in the sqlite database, without loading all of them at once.
+    pub fn for_each(
+        tr: &Transaction,
+        mut f: impl FnMut(ArticleMetadata) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
         let mut get = tr.prepare("SELECT id, submitter, versions, title, authors, categories, comments, proxy, report_no, acm_classes, msc_classes, journal_ref, doi, license, abstract, last_change, sets FROM article")?;
         let mut rows = get.query([])?;
+        while let Some(row) = rows.next()? {
+            f(ArticleMetadata::from_row(row)?)?;
+        }
+        Ok(())
+    }
+
+    /// Loads from the sqlite database the articles that might match the filter, see
+    /// [`PreparedFilter::sql`].
+    pub fn load_matching(
+        tr: &Transaction,
+        filter: &PreparedFilter,
+    ) -> anyhow::Result<HashMap<ArxivId, ArticleMetadata>> {
+        let Some((condition, params)) = filter.sql() else {
+            return Self::load(tr);
+        };
+        let mut metadatas = HashMap::new();
+        let mut get = tr.prepare(&format!("SELECT id, submitter, versions, title, authors, categories, comments, proxy, report_no, acm_classes, msc_classes, journal_ref, doi, license, abstract, last_change, sets FROM article WHERE {condition}"))?;
+        let mut rows = get.query(params_from_iter(params))?;
         while let Some(row) = rows.next()? {
             let metadata = ArticleMetadata::from_row(row)?;
             metadatas.insert(metadata.id.clone(), metadata);
@@ -261,6 +402,13 @@ impl ArticleMetadata {
     }
 
     pub fn write(&self, tr: &Transaction) -> anyhow::Result<()> {
+        // The full-text index of the metadata (see `fulltext::search_metadata`) refers to
+        // the rows of the article table by rowid and only stores the words, so an entry is
+        // removed by passing the old values.
+        tr.prepare_cached(
+            "INSERT INTO metadata_fts (metadata_fts, rowid, title, abstract, authors, comments) SELECT 'delete', rowid, title, abstract, authors, comments FROM article WHERE id = ?1",
+        )?
+        .execute(params![self.id.to_string()])?;
         let mut get = tr.prepare_cached("INSERT OR REPLACE INTO article (id, submitter, versions, title, authors, categories, comments, proxy, report_no, acm_classes, msc_classes, journal_ref, doi, license, abstract, last_change, sets) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)")?;
         get.execute(params![
             self.id.to_string(),
@@ -281,6 +429,101 @@ impl ArticleMetadata {
             self.last_change,
             serde_json::to_string(&self.sets)?,
         ])?;
+        tr.prepare_cached(
+            "INSERT INTO metadata_fts (rowid, title, abstract, authors, comments) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?
+        .execute(params![
+            tr.last_insert_rowid(),
+            self.title,
+            self.abstract_,
+            self.authors,
+            self.comments,
+        ])?;
+        db::write_authors(tr, &self.id.to_string(), &self.authors)?;
+        Ok(())
+    }
+}
+
+/// A line of seen-articles, see `Article::write_seen_line`.
+pub struct SeenLine {
+    pub id: ArxivId,
+    pub version: u32,
+    pub journal: bool,
+    pub doi: bool,
+    /// Older versions did not write a timestamp.
+    pub time: Option<DateTime<FixedOffset>>,
+    /// Lines written by `unsee` replace the previous state instead of adding to it.
+    pub reset: bool,
+}
+
+impl SeenLine {
+    pub fn parse(line: &str) -> anyhow::Result<SeenLine> {
+        let mut parts = line.split(' ');
+        let id = parts.next().context("missing id in seen-articles")?;
+        let id: ArxivId = id
+            .parse()
+            .with_context(|| format!("invalid id in seen-articles: {id:?}"))?;
+        let version = parts.next().context("missing version in seen-articles")?;
+        let version = version
+            .parse()
+            .with_context(|| format!("invalid version in seen-articles: {version:?}"))?;
+        let journal = parts.next() == Some("true");
+        let doi = parts.next() == Some("true");
+        let mut column = parts.next();
+        let time = match column {
+            Some(c) if c != "reset" => {
+                column = parts.next();
+                Some(
+                    DateTime::parse_from_rfc3339(c)
+                        .with_context(|| format!("invalid timestamp in seen-articles: {c:?}"))?,
+                )
+            }
+            _ => None,
+        };
+        // Other columns may be added by later versions, and are ignored.
+        let reset = column.into_iter().chain(parts).any(|c| c == "reset");
+        Ok(SeenLine {
+            id,
+            version,
+            journal,
+            doi,
+            time,
+            reset,
+        })
+    }
+
+    /// Updates the seen version of the article and whether its journal reference and DOI
+    /// were seen, which were given by the previous lines.
+    pub fn apply(&self, version: &mut u32, journal: &mut bool, doi: &mut bool) {
+        if self.reset {
+            *version = self.version;
+            *journal = self.journal;
+            *doi = self.doi;
+        } else {
+            *version = (*version).max(self.version);
+            *journal |= self.journal;
+            *doi |= self.doi;
+        }
+    }
+}
+
+impl std::fmt::Display for SeenLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.id, self.version, self.journal, self.doi
+        )?;
+        if let Some(time) = self.time {
+            write!(
+                f,
+                " {}",
+                time.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+            )?;
+        }
+        if self.reset {
+            write!(f, " reset")?;
+        }
         Ok(())
     }
 }
@@ -303,34 +546,65 @@ impl Version {
         !self.probably_withdrawn() && self.source_type.as_deref() != Some("H")
     }
 
+    /// Whether arXiv (or ar5iv) probably has an HTML version: submitted as HTML, or
+    /// converted from latex (which fails for docx and withdrawn submissions).
+    pub fn probably_has_html(&self) -> bool {
+        !self.probably_withdrawn() && !self.source_type.as_ref().is_some_and(|t| t.contains('X'))
+    }
+
     pub fn probably_has_src(&self) -> bool {
         !self.probably_withdrawn() && !self.probably_src_secret()
     }
+
+    /// The size of the sources in bytes, parsed from strings such as "123kb".
+    pub fn size_in_bytes(&self) -> Option<u64> {
+        let (number, unit) = self
+            .size
+            .split_at(self.size.find(|c: char| !c.is_ascii_digit())?);
+        let number: u64 = number.parse().ok()?;
+        match unit {
+            "b" => Some(number),
+            "kb" => Some(number * 1000),
+            "mb" => Some(number * 1000 * 1000),
+            _ => None,
+        }
+    }
 }
 
+/// What the user did with an article: the seen versions, tags, notes and attachments.
 pub struct ArticleState {
     last_seen_at: usize,
+    /// When the article was last marked as seen (unknown for entries in seen-articles
+    /// written by older versions).
+    last_seen_time: Option<DateTime<FixedOffset>>,
     last_seen_version: u32,
     seen_journal: bool,
     seen_doi: bool,
     /// The names of the bookmark symlinks, relative to the tag directory.
     tags: BTreeSet<TagName>,
     notes: Option<String>,
+    /// The names of the files in the attachments directory.
+    attachments: Vec<String>,
+    /// The keywords saved by `keywords::update`, the most important one first.
+    keywords: Vec<String>,
 }
 
 impl ArticleState {
     fn new() -> Self {
         Self {
             last_seen_at: 0,
+            last_seen_time: None,
             last_seen_version: 0,
             seen_journal: false,
             seen_doi: false,
             tags: BTreeSet::new(),
             notes: None,
+            attachments: Vec::new(),
+            keywords: Vec::new(),
         }
     }
 
-    fn get_tags(base_dir: &Path, id: &ArxivId) -> anyhow::Result<BTreeSet<TagName>> {
+    pub fn get_tags(base_dir: &Path, id: &ArxivId) -> anyhow::Result<BTreeSet<TagName>> {
         read_if_exists(id.directory(base_dir).join("tags"), |reader| {
             let mut res = BTreeSet::new();
             for line in reader.lines() {
@@ -344,7 +618,45 @@ impl ArticleState {
         .with_context(|| format!("reading tags for {}", id))
     }
 
-    fn get_notes(base_dir: &Path, id: &ArxivId) -> anyhow::Result<Option<String>> {
+    /// The tags of all articles.
+    pub fn all_tags(base_dir: &Path, tr: &Transaction) -> anyhow::Result<BTreeSet<TagName>> {
+        if Self::cache_enabled(tr)? && Self::cache_is_current(tr, base_dir)? {
+            let mut res = BTreeSet::new();
+            for (tags, _, _) in Self::load_cache(tr)?.into_values() {
+                res.extend(tags);
+            }
+            return Ok(res);
+        }
+        let mut res = BTreeSet::new();
+        for dir_entry in
+            std::fs::read_dir(base_dir.join("articles")).context("reading articles directory")?
+        {
+            let dir_entry = dir_entry.context("reading articles directory")?;
+            if let Some(id) = ArxivId::from_os_dir_name(&dir_entry.file_name()) {
+                res.extend(Self::get_tags(base_dir, &id)?);
+            }
+        }
+        Ok(res)
+    }
+
+    fn get_attachments(base_dir: &Path, id: &ArxivId) -> anyhow::Result<Vec<String>> {
+        let dir = id.directory(base_dir).join("attachments");
+        let mut res = Vec::new();
+        match std::fs::read_dir(&dir) {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry.with_context(|| format!("reading {dir:?}"))?;
+                    res.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => Err(err).with_context(|| format!("reading {dir:?}"))?,
+        }
+        res.sort();
+        Ok(res)
+    }
+
+    pub fn get_notes(base_dir: &Path, id: &ArxivId) -> anyhow::Result<Option<String>> {
         read_if_exists(id.directory(base_dir).join("notes.txt"), |reader| {
             let mut res = String::new();
             reader.read_to_string(&mut res)?;
@@ -352,8 +664,372 @@ impl ArticleState {
         })
         .with_context(|| format!("reading notes.txt for {}", id))
     }
+
+    /// Whether the tags, notes and attachments are read from the state_cache table instead
+    /// of the article directories (see `arxiv-reader state cache`).
+    pub fn cache_enabled(tr: &Transaction) -> anyhow::Result<bool> {
+        let mut get = tr.prepare_cached("SELECT value FROM setting WHERE key = 'state_cache'")?;
+        let value: Option<String> = get.query(())?.next()?.map(|row| row.get(0)).transpose()?;
+        Ok(value.as_deref() == Some("true"))
+    }
+
+    /// Enables the cache and fills it from the article directories and seen-articles
+    /// (returning the number of articles with tags, notes or attachments), or disables and
+    /// empties it.
+    pub fn set_cache_enabled(
+        tr: &Transaction,
+        base_dir: &Path,
+        enabled: bool,
+    ) -> anyhow::Result<usize> {
+        tr.execute("DELETE FROM state_cache", ())?;
+        tr.execute("DELETE FROM seen_cache", ())?;
+        tr.execute(
+            "DELETE FROM setting WHERE key IN ('seen_cache', 'state_cache_articles_modified')",
+            (),
+        )?;
+        tr.execute(
+            "INSERT OR REPLACE INTO setting (key, value) VALUES ('state_cache', ?1)",
+            params![enabled.to_string()],
+        )?;
+        if !enabled {
+            return Ok(0);
+        }
+        let mut seen = SeenLog::default();
+        seen.read(base_dir)?;
+        seen.save(tr)?;
+        let mut count = 0;
+        for dir_entry in
+            std::fs::read_dir(base_dir.join("articles")).context("reading articles directory")?
+        {
+            let dir_entry = dir_entry.context("reading articles directory")?;
+            if let Some(id) = ArxivId::from_os_dir_name(&dir_entry.file_name()) {
+                let state = Self::read_files(base_dir, &id)?;
+                if state.write_cache(tr, base_dir, &id)? {
+                    count += 1;
+                }
+            }
+        }
+        Self::save_articles_dir_modified(tr, base_dir)?;
+        Ok(count)
+    }
+
+    /// Rereads the tags, notes, attachments and seen-articles into the database if the
+    /// cache is enabled, for example after a git pull or the pre-pull hook might have
+    /// changed them. Other changes to the files of existing articles (such as by hand) are
+    /// not noticed until then (see `cache_is_current`).
+    pub fn refresh_cache(conn: &mut Connection, base_dir: &Path) -> anyhow::Result<()> {
+        db::with_write_transaction(conn, base_dir, |tr| {
+            if Self::cache_enabled(&tr)? {
+                Self::set_cache_enabled(&tr, base_dir, true)?;
+                tr.commit()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Saves the seen state of the lines appended to seen-articles since the cache was last
+    /// updated, if it is enabled.
+    pub fn update_seen_cache(conn: &mut Connection, base_dir: &Path) -> anyhow::Result<()> {
+        db::with_write_transaction(conn, base_dir, |tr| {
+            if Self::cache_enabled(&tr)? {
+                SeenLog::load(&tr, base_dir)?.save(&tr)?;
+                tr.commit()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads the tags, notes and attachments of an article from its directory.
+    fn read_files(base_dir: &Path, id: &ArxivId) -> anyhow::Result<Self> {
+        let mut state = Self::new();
+        state.tags = Self::get_tags(base_dir, id)?;
+        state.notes = Self::get_notes(base_dir, id)?;
+        state.attachments = Self::get_attachments(base_dir, id)?;
+        Ok(state)
+    }
+
+    /// The modification time (in nanoseconds since the epoch) of the articles directory,
+    /// which changes when an article directory is created or removed.
+    fn articles_dir_modified(base_dir: &Path) -> anyhow::Result<Option<i64>> {
+        let path = base_dir.join("articles");
+        let modified = match path.metadata() {
+            Ok(metadata) => metadata
+                .modified()
+                .with_context(|| format!("reading the modification time of {path:?}"))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).with_context(|| format!("reading {path:?}")),
+        };
+        let nanos = modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Ok(Some(i64::try_from(nanos).unwrap_or(i64::MAX)))
+    }
+
+    /// Whether the articles directory was not changed from outside since the cache was
+    /// last written, so that article directories created or removed by hand (or by other
+    /// programs) are noticed with a single system call. Otherwise the files are read
+    /// instead of the cache until it is refreshed.
+    fn cache_is_current(tr: &Transaction, base_dir: &Path) -> anyhow::Result<bool> {
+        let mut get = tr.prepare_cached(
+            "SELECT value FROM setting WHERE key = 'state_cache_articles_modified'",
+        )?;
+        let saved: Option<String> = get.query(())?.next()?.map(|row| row.get(0)).transpose()?;
+        let current = Self::articles_dir_modified(base_dir)?;
+        Ok(saved == Some(serde_json::to_string(&current)?))
+    }
+
+    /// Remembers the modification time of the articles directory for `cache_is_current`.
+    fn save_articles_dir_modified(tr: &Transaction, base_dir: &Path) -> anyhow::Result<()> {
+        tr.prepare_cached(
+            "INSERT OR REPLACE INTO setting (key, value) VALUES ('state_cache_articles_modified', ?1)",
+        )?
+        .execute(params![serde_json::to_string(
+            &Self::articles_dir_modified(base_dir)?
+        )?])?;
+        Ok(())
+    }
+
+    /// Saves the tags, notes and attachments in the cache (if it is enabled). Returns
+    /// whether there was anything to save.
+    fn write_cache(&self, tr: &Transaction, base_dir: &Path, id: &ArxivId) -> anyhow::Result<bool> {
+        if !Self::cache_enabled(tr)? {
+            return Ok(false);
+        }
+        let tags: Vec<&str> = self.tags.iter().map(|t| t.0.as_str()).collect();
+        tr.prepare_cached(
+            "INSERT OR REPLACE INTO state_cache (id, tags, notes, attachments) VALUES (?1, ?2, ?3, ?4)",
+        )?
+        .execute(params![
+            id.to_string(),
+            serde_json::to_string(&tags)?,
+            self.notes,
+            serde_json::to_string(&self.attachments)?,
+        ])?;
+        // Writing the files may have created the article directory.
+        Self::save_articles_dir_modified(tr, base_dir)?;
+        Ok(!(self.tags.is_empty() && self.notes.is_none() && self.attachments.is_empty()))
+    }
+
+    /// Loads the tags, notes and attachments of all articles from the cache.
+    #[allow(clippy::type_complexity)]
+    fn load_cache(
+        tr: &Transaction,
+    ) -> anyhow::Result<HashMap<ArxivId, (BTreeSet<TagName>, Option<String>, Vec<String>)>> {
+        let mut get = tr.prepare_cached("SELECT id, tags, notes, attachments FROM state_cache")?;
+        let mut rows = get.query(())?;
+        let mut res = HashMap::new();
+        while let Some(row) = rows.next()? {
+            let id: String = row.get(0)?;
+            let tags: String = row.get(1)?;
+            let tags: Vec<String> = serde_json::from_str(&tags)
+                .with_context(|| format!("parsing cached tags of {id}"))?;
+            let tags = tags
+                .iter()
+                .map(|t| t.parse())
+                .collect::<anyhow::Result<_>>()?;
+            let attachments: String = row.get(3)?;
+            let attachments = serde_json::from_str(&attachments)
+                .with_context(|| format!("parsing cached attachments of {id}"))?;
+            res.insert(id.parse()?, (tags, row.get(2)?, attachments));
+        }
+        Ok(res)
+    }
+}
+
+/// The seen state of an article given by the lines of seen-articles about it.
+#[derive(Default)]
+struct SeenState {
+    /// The number of the last line about the article.
+    line: usize,
+    time: Option<DateTime<FixedOffset>>,
+    version: u32,
+    journal: bool,
+    doi: bool,
+}
+
+/// The seen states of the articles in (the beginning of) seen-articles.
+#[derive(Default)]
+struct SeenLog {
+    states: HashMap<ArxivId, SeenState>,
+    /// The number of lines and bytes read.
+    lines: usize,
+    offset: u64,
+    /// The last line read, with its line break, to notice if the file was rewritten.
+    last_line: String,
+}
+
+/// The position in seen-articles up to which `SeenLog::save` saved the seen states,
+/// kept in the setting table.
+#[derive(Serialize, Deserialize)]
+struct SeenCachePosition {
+    lines: usize,
+    offset: u64,
+    last_line: String,
+}
+
+impl SeenLog {
+    /// Reads the lines of seen-articles after the ones read so far.
+    fn read(&mut self, base_dir: &Path) -> anyhow::Result<()> {
+        let mut file = match File::open(base_dir.join("seen-articles")) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err).context("opening seen-articles"),
+        };
+        file.seek(SeekFrom::Start(self.offset))
+            .context("reading seen-articles")?;
+        let mut reader = BufReader::new(file);
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            let len = reader
+                .read_line(&mut buf)
+                .context("reading seen-articles")?;
+            if len == 0 {
+                return Ok(());
+            }
+            let line = SeenLine::parse(buf.strip_suffix('\n').unwrap_or(&buf))?;
+            let state = self.states.entry(line.id.clone()).or_default();
+            state.line = self.lines;
+            state.time = line.time;
+            line.apply(&mut state.version, &mut state.journal, &mut state.doi);
+            self.lines += 1;
+            self.offset += len as u64;
+            self.last_line.clone_from(&buf);
+        }
+    }
+
+    /// Loads the seen states saved by `save` and reads the lines appended since then. If
+    /// the file was rewritten (for example by a merge), it is read again from the start.
+    fn load(tr: &Transaction, base_dir: &Path) -> anyhow::Result<Self> {
+        let mut get = tr.prepare_cached("SELECT value FROM setting WHERE key = 'seen_cache'")?;
+        let value: Option<String> = get.query(())?.next()?.map(|row| row.get(0)).transpose()?;
+        let position: Option<SeenCachePosition> = value
+            .map(|value| serde_json::from_str(&value).context("parsing the seen cache"))
+            .transpose()?;
+        let mut log = Self::default();
+        if let Some(position) = position
+            && Self::ends_with(base_dir, &position)?
+        {
+            let mut get =
+                tr.prepare_cached("SELECT id, line, time, version, journal, doi FROM seen_cache")?;
+            let mut rows = get.query(())?;
+            while let Some(row) = rows.next()? {
+                let id: String = row.get(0)?;
+                let time: Option<String> = row.get(2)?;
+                let time = time
+                    .map(|time| DateTime::parse_from_rfc3339(&time))
+                    .transpose()
+                    .with_context(|| format!("parsing the cached seen time of {id}"))?;
+                log.states.insert(
+                    id.parse()?,
+                    SeenState {
+                        line: row.get(1)?,
+                        time,
+                        version: row.get(3)?,
+                        journal: row.get(4)?,
+                        doi: row.get(5)?,
+                    },
+                );
+            }
+            log.lines = position.lines;
+            log.offset = position.offset;
+            log.last_line = position.last_line;
+        }
+        log.read(base_dir)?;
+        Ok(log)
+    }
+
+    /// Whether the first `position.offset` bytes of seen-articles end with the line that
+    /// was read last, which means that the file was only appended to since.
+    fn ends_with(base_dir: &Path, position: &SeenCachePosition) -> anyhow::Result<bool> {
+        let len = position.last_line.len() as u64;
+        if position.offset < len {
+            return Ok(false);
+        }
+        let mut file = match File::open(base_dir.join("seen-articles")) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(position.offset == 0),
+            Err(err) => return Err(err).context("opening seen-articles"),
+        };
+        file.seek(SeekFrom::Start(position.offset - len))
+            .context("reading seen-articles")?;
+        let mut buf = vec![0; len as usize];
+        if file.read_exact(&mut buf).is_err() {
+            return Ok(false);
+        }
+        Ok(buf == position.last_line.as_bytes())
+    }
+
+    /// Saves the seen states in the seen_cache table.
+    fn save(&self, tr: &Transaction) -> anyhow::Result<()> {
+        tr.execute("DELETE FROM seen_cache", ())?;
+        let mut insert = tr.prepare_cached(
+            "INSERT INTO seen_cache (id, line, time, version, journal, doi) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for (id, state) in &self.states {
+            insert.execute(params![
+                id.to_string(),
+                state.line,
+                state.time.map(|time| time.to_rfc3339()),
+                state.version,
+                state.journal,
+                state.doi
+            ])?;
+        }
+        let position = SeenCachePosition {
+            lines: self.lines,
+            offset: self.offset,
+            last_line: self.last_line.clone(),
+        };
+        tr.execute(
+            "INSERT OR REPLACE INTO setting (key, value) VALUES ('seen_cache', ?1)",
+            params![serde_json::to_string(&position)?],
+        )?;
+        Ok(())
+    }
+}
+
+/// Checks that the file starts with the pdf header and that an end-of-file marker
+/// appears near the end of the file.
+pub fn pdf_looks_valid(path: &Path) -> anyhow::Result<bool> {
+    let mut file = File::open(path).with_context(|| format!("opening {path:?}"))?;
+    let mut header = [0; 5];
+    if file.read_exact(&mut header).is_err() || &header != b"%PDF-" {
+        return Ok(false);
+    }
+    let len = file
+        .metadata()
+        .with_context(|| format!("reading {path:?}"))?
+        .len();
+    file.seek(SeekFrom::Start(len.saturating_sub(1024)))
+        .with_context(|| format!("reading {path:?}"))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)
+        .with_context(|| format!("reading {path:?}"))?;
+    Ok(tail.windows(5).any(|w| w == b"%%EOF"))
 }
 
+/// Checks that the file starts with the gzip magic number.
+pub fn gzip_looks_valid(path: &Path) -> anyhow::Result<bool> {
+    let mut file = File::open(path).with_context(|| format!("opening {path:?}"))?;
+    let mut header = [0; 2];
+    Ok(file.read_exact(&mut header).is_ok() && header == [0x1f, 0x8b])
+}
+
+/// A file of an article that can be downloaded from arXiv: the pdf or the sources of a
+/// version.
+struct Content {
+    path: PathBuf,
+    version: u32,
+    description: &'static str,
+    /// The directory on arxiv.org, such as "pdf" for https://arxiv.org/pdf/2501.12345v2.
+    url_dir: &'static str,
+    content_type: &'static str,
+    is_valid: fn(&Path) -> anyhow::Result<bool>,
+}
+
+/// An article with its metadata and state.
 pub struct Article {
     pub metadata: ArticleMetadata,
     pub state: ArticleState,
@@ -411,6 +1087,10 @@ impl Article {
         self.metadata.report_no.as_ref()
     }
 
+    pub fn sets(&self) -> Option<&Vec<String>> {
+        self.metadata.sets.as_ref()
+    }
+
     pub fn acm_classes(&self) -> Option<&String> {
         self.metadata.acm_classes.as_ref()
     }
@@ -448,10 +1128,52 @@ impl Article {
         self.state.seen_doi
     }
 
+    /// The metadata together with the user's data about the article, for `--output json`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(&self.metadata).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.insert(
+            "tags".to_string(),
+            self.tags().iter().map(|t| t.to_string()).collect(),
+        );
+        object.insert("notes".to_string(), self.notes().cloned().into());
+        object.insert("attachments".to_string(), self.attachments().clone().into());
+        object.insert("keywords".to_string(), self.keywords().clone().into());
+        object.insert(
+            "last_seen_version".to_string(),
+            self.last_seen_version().into(),
+        );
+        object.insert("seen_journal".to_string(), self.seen_journal().into());
+        object.insert("seen_doi".to_string(), self.seen_doi().into());
+        object.insert(
+            "last_seen_time".to_string(),
+            self.last_seen_time().map(|t| t.to_rfc3339()).into(),
+        );
+        value
+    }
+
+    /// Whether the article was seen, but has a newer version, journal reference or DOI
+    /// that has not been seen.
+    pub fn has_update(&self) -> bool {
+        self.last_seen_version() > 0
+            && (self.last_seen_version() < self.last_version().number
+                || self.has_unseen_publication())
+    }
+
+    /// Whether the article got a journal reference or DOI that has not been seen yet.
+    pub fn has_unseen_publication(&self) -> bool {
+        (self.journal_ref().is_some() && !self.seen_journal())
+            || (self.doi().is_some() && !self.seen_doi())
+    }
+
     pub fn last_seen_at(&self) -> usize {
         self.state.last_seen_at
     }
 
+    pub fn last_seen_time(&self) -> Option<&DateTime<FixedOffset>> {
+        self.state.last_seen_time.as_ref()
+    }
+
     pub fn is_bookmarked(&self) -> bool {
         !self.state.tags.is_empty()
     }
@@ -464,8 +1186,31 @@ impl Article {
         self.state.notes.as_ref()
     }
 
+    /// The lines of the notes starting with "TODO" or "- [ ]" (ignoring leading
+    /// whitespace).
+    pub fn todos(&self) -> Vec<&str> {
+        self.notes()
+            .map(|notes| {
+                notes
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| l.starts_with("TODO") || l.starts_with("- [ ]"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn attachments(&self) -> &Vec<String> {
+        &self.state.attachments
+    }
+
+    pub fn keywords(&self) -> &Vec<String> {
+        &self.state.keywords
+    }
+
     fn load_state(
         base_dir: &Path,
+        tr: &Transaction,
         metadatas: HashMap<ArxivId, ArticleMetadata>,
     ) -> anyhow::Result<HashMap<ArxivId, Article>> {
         let mut articles: HashMap<ArxivId, Article> = HashMap::new();
@@ -475,46 +1220,34 @@ impl Article {
         }
 
         // Read list of seen articles.
-        match File::open(base_dir.join("seen-articles")) {
-            Ok(file) => {
-                let reader = BufReader::new(file);
-                for (linenr, line) in reader.lines().enumerate() {
-                    let line = line.context("reading seen-articles")?;
-                    let mut parts = line.split(' ');
-                    let id = parts.next().context("missing id in seen-articles")?;
-                    let id: ArxivId = id
-                        .parse()
-                        .with_context(|| format!("invalid id in seen-articles: {id:?}"))?;
-                    let version = parts.next().context("missing version in seen-articles")?;
-                    let version = version.parse().with_context(|| {
-                        format!("invalid version in seen-articles: {version:?}")
-                    })?;
-                    let journal = parts.next() == Some("true");
-                    let doi = parts.next() == Some("true");
-                    if parts.next().is_some() {
-                        bail!("too many columns in seen-articles");
-                    }
-                    // Ignore if there is an unknown article id. (It might have been deleted from the file system.)
-                    if let Some(article) = articles.get_mut(&id) {
-                        article.state.last_seen_at = linenr;
-                        if article.state.last_seen_version < version {
-                            article.state.last_seen_version = version;
-                        }
-                        if journal {
-                            article.state.seen_journal = true;
-                        }
-                        if doi {
-                            article.state.seen_doi = true;
-                        }
-                    }
-                }
+        let cache_enabled = ArticleState::cache_enabled(tr)?;
+        let seen = if cache_enabled {
+            SeenLog::load(tr, base_dir)?
+        } else {
+            let mut seen = SeenLog::default();
+            seen.read(base_dir)?;
+            seen
+        };
+        // Ignore unknown article ids. (They might have been deleted from the file system.)
+        for (id, seen) in seen.states {
+            if let Some(article) = articles.get_mut(&id) {
+                article.state.last_seen_at = seen.line;
+                article.state.last_seen_time = seen.time;
+                article.state.last_seen_version = seen.version;
+                article.state.seen_journal = seen.journal;
+                article.state.seen_doi = seen.doi;
             }
-            Err(err) => {
-                if err.kind() == ErrorKind::NotFound {
-                } else {
-                    Err(err).context("reading seen-articles")?
+        }
+
+        if cache_enabled && ArticleState::cache_is_current(tr, base_dir)? {
+            for (id, (tags, notes, attachments)) in ArticleState::load_cache(tr)? {
+                if let Some(article) = articles.get_mut(&id) {
+                    article.state.tags = tags;
+                    article.state.notes = notes;
+                    article.state.attachments = attachments;
                 }
             }
+            return Ok(articles);
         }
 
         // Read tags and notes. For efficiency, we don't try to load tags and notes for each article,
@@ -529,6 +1262,7 @@ impl Article {
             if let Some(article) = articles.get_mut(&id) {
                 article.state.tags = ArticleState::get_tags(base_dir, &id)?;
                 article.state.notes = ArticleState::get_notes(base_dir, &id)?;
+                article.state.attachments = ArticleState::get_attachments(base_dir, &id)?;
             }
         }
 
@@ -539,18 +1273,49 @@ impl Article {
     pub fn load(base_dir: &Path, conn: &Transaction) -> anyhow::Result<HashMap<ArxivId, Article>> {
         // Read metadata of all articles.
         let metadatas = ArticleMetadata::load(conn)?;
-        Self::load_state(base_dir, metadatas)
+        let mut articles = Self::load_state(base_dir, conn, metadatas)?;
+        for (id, terms) in keywords::load(conn)? {
+            if let Some(article) = articles.get_mut(&id) {
+                article.state.keywords = terms;
+            }
+        }
+        Ok(articles)
+    }
+
+    /// Loads from the sqlite database the articles matching the (prepared) filter. The
+    /// conditions on the metadata are evaluated by sqlite, so that only the candidates
+    /// need to be read.
+    pub fn load_matching(
+        base_dir: &Path,
+        tr: &Transaction,
+        filter: &PreparedFilter,
+    ) -> anyhow::Result<HashMap<ArxivId, Article>> {
+        if filter.sql().is_none() {
+            let mut articles = Self::load(base_dir, tr)?;
+            articles.retain(|_, article| filter.matches(article));
+            return Ok(articles);
+        }
+        let metadatas = ArticleMetadata::load_matching(tr, filter)?;
+        let mut articles = Self::load_state(base_dir, tr, metadatas)?;
+        for article in articles.values_mut() {
+            article.state.keywords = keywords::load_one(tr, &article.metadata.id)?;
+        }
+        articles.retain(|_, article| filter.matches(article));
+        Ok(articles)
     }
 
     /// Loads from the sqlite database a single article.
-    #[allow(unused)]
     pub fn load_one(base_dir: &Path, tr: &Transaction, id: &ArxivId) -> anyhow::Result<Article> {
         // Read metadata.
         let metadata = ArticleMetadata::load_one(tr, id)?
             .with_context(|| format!("found no article with id {}", id))?;
         let mut metadatas: HashMap<ArxivId, ArticleMetadata> = HashMap::new();
         metadatas.insert(id.clone(), metadata);
-        Ok(Self::load_state(base_dir, metadatas)?.remove(id).unwrap())
+        let mut article = Self::load_state(base_dir, tr, metadatas)?
+            .remove(id)
+            .unwrap();
+        article.state.keywords = keywords::load_one(tr, id)?;
+        Ok(article)
     }
 
     pub fn mark_as_seen(&mut self, writer: &mut File) -> anyhow::Result<()> {
@@ -563,19 +1328,79 @@ impl Article {
         if self.doi().is_some() {
             self.state.seen_doi = true;
         }
-        writeln!(
+        self.write_seen_line(
             writer,
-            "{} {} {} {}",
-            self.metadata.id,
             self.metadata.last_version().number,
             self.journal_ref().is_some(),
             self.doi().is_some(),
+            false,
+        )
+    }
+
+    /// Appends a line to seen-articles. Each line consists of the id, the seen version,
+    /// whether the journal reference and DOI were seen, the current time, and optionally
+    /// "reset" if the line replaces the previous state instead of adding to it. The
+    /// "reset" column was added with `unsee`; older versions reject lines that have it.
+    fn write_seen_line(
+        &mut self,
+        writer: &mut File,
+        version: u32,
+        journal: bool,
+        doi: bool,
+        reset: bool,
+    ) -> anyhow::Result<()> {
+        let now = chrono::Local::now().fixed_offset();
+        writeln!(
+            writer,
+            "{} {} {} {} {}{}",
+            self.metadata.id,
+            version,
+            journal,
+            doi,
+            now.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            if reset { " reset" } else { "" },
         )
         .context("writing seen-articles")?;
         writer.flush().context("writing seen-articles")?;
+        self.state.last_seen_time = Some(now);
         Ok(())
     }
 
+    /// Marks the journal reference and DOI as seen, but not any new versions.
+    pub fn mark_publication_as_seen(&mut self, writer: &mut File) -> anyhow::Result<()> {
+        if self.journal_ref().is_some() {
+            self.state.seen_journal = true;
+        }
+        if self.doi().is_some() {
+            self.state.seen_doi = true;
+        }
+        self.write_seen_line(
+            writer,
+            self.state.last_seen_version,
+            self.journal_ref().is_some(),
+            self.doi().is_some(),
+            false,
+        )
+    }
+
+    /// Forgets that versions after `version`, the journal reference and the DOI were seen,
+    /// so that the article shows up again in `arxiv-reader news`.
+    pub fn unsee(&mut self, writer: &mut File, version: u32) -> anyhow::Result<()> {
+        self.state.last_seen_version = version;
+        self.state.seen_journal = false;
+        self.state.seen_doi = false;
+        self.write_seen_line(writer, version, false, false, true)
+    }
+
+    /// Opens the list of seen articles for appending.
+    pub fn open_seen_file(base_dir: &Path) -> anyhow::Result<File> {
+        File::options()
+            .append(true)
+            .create(true)
+            .open(base_dir.join("seen-articles"))
+            .context("opening seen-articles file")
+    }
+
     fn write_tags(&self, base_dir: &Path) -> anyhow::Result<()> {
         let id = self.id();
         id.mkdir(base_dir)?;
@@ -589,184 +1414,538 @@ impl Article {
         Ok(())
     }
 
-    pub fn toggle_tag(&mut self, base_dir: &Path, tag_name: &TagName) -> anyhow::Result<()> {
+    pub fn toggle_tag(
+        &mut self,
+        base_dir: &Path,
+        tr: &Transaction,
+        tag_name: &TagName,
+    ) -> anyhow::Result<()> {
         if self.state.tags.contains(tag_name) {
             self.state.tags.remove(tag_name);
         } else {
             self.state.tags.insert(tag_name.clone());
         }
-        self.write_tags(base_dir)
+        self.write_tags(base_dir)?;
+        self.state.write_cache(tr, base_dir, self.id())?;
+        Ok(())
     }
 
-    pub fn set_tag(&mut self, base_dir: &Path, tag_name: &TagName) -> anyhow::Result<()> {
+    pub fn set_tag(
+        &mut self,
+        base_dir: &Path,
+        tr: &Transaction,
+        tag_name: &TagName,
+    ) -> anyhow::Result<()> {
         if !self.state.tags.contains(tag_name) {
             self.state.tags.insert(tag_name.clone());
             self.write_tags(base_dir)?;
+            self.state.write_cache(tr, base_dir, self.id())?;
         }
         Ok(())
     }
 
-    pub fn pdf_path(&self, base_dir: &Path) -> PathBuf {
-        self.id()
-            .directory(base_dir)
+    /// Rereads the tags, notes and attachments from the article directory, for example
+    /// after running a command that might have changed them.
+    pub fn reload_state(&mut self, base_dir: &Path, tr: &Transaction) -> anyhow::Result<()> {
+        let state = ArticleState::read_files(base_dir, self.id())?;
+        self.state.tags = state.tags;
+        self.state.notes = state.notes;
+        self.state.attachments = state.attachments;
+        self.state.write_cache(tr, base_dir, self.id())?;
+        Ok(())
+    }
+
+    pub fn pdf_path(&self, download_dir: &DownloadDir) -> PathBuf {
+        download_dir
+            .directory(self.id())
             .join(format!("v{}.pdf", self.last_version().number))
     }
 
+    /// The pdf of the last version (if `src` is false) or its sources.
+    fn content(&self, download_dir: &DownloadDir, src: bool) -> Content {
+        let version = self.last_version().number;
+        if src {
+            self.src_content(download_dir, version)
+        } else {
+            Content {
+                path: self.pdf_path(download_dir),
+                version,
+                description: "pdf",
+                url_dir: "pdf",
+                content_type: "application/pdf",
+                is_valid: pdf_looks_valid,
+            }
+        }
+    }
+
+    fn src_content(&self, download_dir: &DownloadDir, version: u32) -> Content {
+        Content {
+            path: self.src_path_version(download_dir, version),
+            version,
+            description: "sources",
+            url_dir: "src",
+            content_type: "application/gzip",
+            is_valid: gzip_looks_valid,
+        }
+    }
+
+    fn content_url(&self, content: &Content) -> String {
+        format!(
+            "https://arxiv.org/{}/{}v{}",
+            content.url_dir,
+            self.id(),
+            content.version
+        )
+    }
+
+    /// Whether there already is a file that passes the `is_valid` check.
+    fn has_content(&self, content: &Content) -> anyhow::Result<bool> {
+        if !content.path.is_file() {
+            return Ok(false);
+        }
+        if (content.is_valid)(&content.path)? {
+            return Ok(true);
+        }
+        progress::println(&format!(
+            "The {} for {}v{} seems to be corrupt.",
+            content.description,
+            self.id(),
+            content.version
+        ));
+        Ok(false)
+    }
+
+    /// Moves a file downloaded to `tmp_path(&content.path)` into place. Returns whether it
+    /// passes the `is_valid` check. Otherwise, or if it is not saved for another reason,
+    /// the downloaded file is removed.
+    fn save_content(
+        &self,
+        download_dir: &DownloadDir,
+        content: &Content,
+        res: Download,
+    ) -> anyhow::Result<bool> {
+        let Content {
+            path,
+            version,
+            description,
+            ..
+        } = content;
+        let tmp = tmp_path(path);
+        let valid = self.check_content(download_dir, content, &tmp, &res);
+        if !matches!(valid, Ok(true)) {
+            let _ = remove_file(&tmp);
+            return valid;
+        }
+        rename(&tmp, path).with_context(|| {
+            format!(
+                "saving {description} from arXiv for {}v{}",
+                self.id(),
+                version
+            )
+        })?;
+        Ok(true)
+    }
+
+    /// Checks the content type of a downloaded file and that it was not truncated, and
+    /// makes room for it in the download directory.
+    fn check_content(
+        &self,
+        download_dir: &DownloadDir,
+        content: &Content,
+        tmp: &Path,
+        res: &Download,
+    ) -> anyhow::Result<bool> {
+        if res.content_type.as_deref() != Some(content.content_type) {
+            bail!(
+                "wrong content type (expected {}, received {:?})",
+                content.content_type,
+                res.content_type
+            );
+        }
+        if !(content.is_valid)(tmp)? {
+            return Ok(false);
+        }
+        download_dir.make_room(self.id(), &content.path, res.len)?;
+        Ok(true)
+    }
+
+    /// Downloads the file unless there already is a file that passes the `is_valid` check.
+    /// If the downloaded file does not pass the check, we try once more.
     fn download_content(
         &self,
+        download_dir: &DownloadDir,
         client: &mut Client,
-        path: PathBuf,
-        description: &str,
-        url_dir: &str,
-        content_type: &'static str,
+        content: &Content,
     ) -> anyhow::Result<()> {
-        if !path.is_file() {
-            println!(
+        if self.has_content(content)? {
+            return Ok(());
+        }
+        let Content {
+            version,
+            description,
+            ..
+        } = content;
+        for attempt in 1..=2 {
+            progress::status(&format!(
                 "Downloading {description} for {}v{}...",
                 self.id(),
-                self.last_version().number
-            );
+                version
+            ));
             // Download.
-            let mut res = client.with(|client| {
-                client
-                    .get(format!(
-                        "https://arxiv.org/{url_dir}/{}v{}",
+            let tmp = tmp_path(&content.path);
+            let res = client.download(&self.content_url(content), &tmp, |downloaded, total| {
+                progress::set_detail(|| {
+                    format!(
+                        "{description} for {}v{}: {}",
                         self.id(),
-                        self.last_version().number
-                    ))
-                    .send()
-                    .and_then(|res| res.error_for_status())
-                    .with_context(|| {
-                        format!(
-                            "requesting {description} from arXiv for {}v{}",
-                            self.id(),
-                            self.last_version().number
-                        )
-                    })
-            })?;
-            // Check content type.
-            let res_content_type = res.headers().get("Content-Type");
-            if res_content_type != Some(&HeaderValue::from_static(content_type)) {
-                bail!(
-                    "wrong content type (expected {content_type}, received {res_content_type:?})",
-                );
+                        version,
+                        progress::format_bytes(downloaded, total)
+                    )
+                })
+            });
+            if res.is_err() {
+                let _ = remove_file(&tmp);
+            }
+            let res = match res {
+                Ok(res) => res,
+                Err(err) if err.is::<DownloadDeclined>() => {
+                    progress::println(&format!(
+                        "Skipped the {description} for {}v{}.",
+                        self.id(),
+                        version
+                    ));
+                    return Ok(());
+                }
+                Err(err) => {
+                    return Err(err.context(format!(
+                        "requesting {description} from arXiv for {}v{}",
+                        self.id(),
+                        version
+                    )));
+                }
+            };
+            if self.save_content(download_dir, content, res)? {
+                return Ok(());
+            }
+            if attempt == 1 {
+                message!("The downloaded {description} seems to be corrupt. Retrying.");
             }
-            // Write file.
-            write_then_rename(path, |writer| {
-                std::io::copy(&mut res, writer)?;
-                Ok(())
-            })
-            .with_context(|| {
-                format!(
-                    "saving {description} from arXiv for {}v{}",
-                    self.id(),
-                    self.last_version().number
-                )
-            })?;
         }
-        Ok(())
+        bail!(
+            "the {description} received from arXiv for {}v{} seems to be corrupt",
+            self.id(),
+            version
+        )
     }
 
     /// Download the pdf file if necessary.
-    pub fn download_pdf(&self, base_dir: &Path, client: &mut Client) -> anyhow::Result<()> {
-        self.id().mkdir(base_dir)?;
-        self.download_content(
-            client,
-            self.pdf_path(base_dir),
-            "pdf",
-            "pdf",
-            "application/pdf",
-        )
+    pub fn download_pdf(
+        &self,
+        download_dir: &DownloadDir,
+        client: &mut Client,
+    ) -> anyhow::Result<()> {
+        download_dir.mkdir(self.id())?;
+        self.download_content(download_dir, client, &self.content(download_dir, false))
+    }
+
+    /// Whether the pdf (if `src` is false) or the sources of the last version still have
+    /// to be downloaded, from where, and to which temporary file. Used to download many
+    /// files at once with `Client::download_all`, whose responses are then passed to
+    /// `save_download`. Creates the download directory if needed.
+    pub fn pending_download(
+        &self,
+        download_dir: &DownloadDir,
+        src: bool,
+    ) -> anyhow::Result<Option<(String, PathBuf)>> {
+        let content = self.content(download_dir, src);
+        if self.has_content(&content)? {
+            return Ok(None);
+        }
+        download_dir.mkdir(self.id())?;
+        Ok(Some((self.content_url(&content), tmp_path(&content.path))))
+    }
+
+    /// Moves a file downloaded as returned by `pending_download` into place.
+    pub fn save_download(
+        &self,
+        download_dir: &DownloadDir,
+        src: bool,
+        res: Download,
+    ) -> anyhow::Result<()> {
+        let content = self.content(download_dir, src);
+        if !self.save_content(download_dir, &content, res)? {
+            bail!(
+                "the {} received from arXiv for {}v{} seems to be corrupt",
+                content.description,
+                self.id(),
+                content.version
+            );
+        }
+        Ok(())
+    }
+
+    pub fn src_path(&self, download_dir: &DownloadDir) -> PathBuf {
+        self.src_path_version(download_dir, self.last_version().number)
+    }
+
+    pub fn src_path_version(&self, download_dir: &DownloadDir, version: u32) -> PathBuf {
+        download_dir
+            .directory(self.id())
+            .join(format!("v{version}.tar.gz"))
+    }
+
+    /// The downloaded pdfs and sources of all versions, i.e., the files named like `v3.pdf`
+    /// or `v3.tar.gz` in the article directory.
+    pub fn downloaded_files(&self, download_dir: &DownloadDir) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = download_dir.directory(self.id());
+        let mut res = Vec::new();
+        if !dir.is_dir() {
+            return Ok(res);
+        }
+        for dir_entry in std::fs::read_dir(&dir).with_context(|| format!("reading {dir:?}"))? {
+            let dir_entry = dir_entry.with_context(|| format!("reading {dir:?}"))?;
+            let name = dir_entry.file_name();
+            if name
+                .to_str()
+                .and_then(parse_download_name)
+                .is_some_and(|(kind, _)| kind != DownloadKind::Figures)
+            {
+                res.push(dir_entry.path());
+            }
+        }
+        res.sort();
+        Ok(res)
     }
 
-    pub fn src_path(&self, base_dir: &Path) -> PathBuf {
-        self.id()
-            .directory(base_dir)
-            .join(format!("v{}.tar.gz", self.last_version().number))
+    /// Describes which pdf and sources are downloaded, such as "pdf v2, src v1 (old)".
+    pub fn download_status(&self, download_dir: &DownloadDir) -> String {
+        let files = self.downloaded_files(download_dir).unwrap_or_default();
+        let newest = |suffix: &str| {
+            files
+                .iter()
+                .filter_map(|f| {
+                    f.file_name()?
+                        .to_str()?
+                        .strip_prefix('v')?
+                        .strip_suffix(suffix)
+                })
+                .filter_map(|n| n.parse::<u32>().ok())
+                .max()
+        };
+        let last_version = self.last_version();
+        let describe = |name: &str, newest: Option<u32>, available: bool| match newest {
+            Some(v) if v == last_version.number => format!("{name} v{v}"),
+            Some(v) => format!("{name} v{v} (old)"),
+            None if available => format!("{name} -"),
+            None if last_version.source_type.as_deref() == Some("H") => {
+                format!("{name}: html only")
+            }
+            None => format!("no {name}"),
+        };
+        format!(
+            "{}, {}",
+            describe("pdf", newest(".pdf"), last_version.probably_has_pdf()),
+            describe("src", newest(".tar.gz"), last_version.probably_has_src()),
+        )
     }
 
     /// Download the src file if necessary.
-    pub fn download_src(&self, base_dir: &Path, client: &mut Client) -> anyhow::Result<()> {
-        self.id().mkdir(base_dir)?;
+    pub fn download_src(
+        &self,
+        download_dir: &DownloadDir,
+        client: &mut Client,
+    ) -> anyhow::Result<()> {
+        self.download_src_version(download_dir, client, self.last_version().number)
+    }
+
+    /// Download the src file of the given version if necessary.
+    pub fn download_src_version(
+        &self,
+        download_dir: &DownloadDir,
+        client: &mut Client,
+        version: u32,
+    ) -> anyhow::Result<()> {
+        download_dir.mkdir(self.id())?;
         self.download_content(
+            download_dir,
             client,
-            self.src_path(base_dir),
-            "sources",
-            "src",
-            "application/gzip",
+            &self.src_content(download_dir, version),
+        )
+    }
+
+    /// The address of the article's arXiv webpage.
+    pub fn abs_url(&self) -> String {
+        format!("https://arxiv.org/abs/{}", self.id())
+    }
+
+    /// The address of the pdf of the last version on arXiv.
+    pub fn pdf_url(&self) -> String {
+        format!(
+            "https://arxiv.org/pdf/{}v{}",
+            self.id(),
+            self.last_version().number
+        )
+    }
+
+    /// The address of the HTML version of the last version, if there probably is one.
+    /// arXiv converts articles submitted since December 2023 itself; older ones are on
+    /// ar5iv (which always shows the last version).
+    pub fn html_url(&self) -> Option<String> {
+        let version = self.last_version();
+        if !version.probably_has_html() {
+            None
+        } else if version.source_type.as_deref() == Some("H")
+            || version.date.date_naive() >= NaiveDate::from_ymd_opt(2023, 12, 1).unwrap()
+        {
+            Some(format!(
+                "https://arxiv.org/html/{}v{}",
+                self.id(),
+                version.number
+            ))
+        } else {
+            Some(format!("https://ar5iv.labs.arxiv.org/html/{}", self.id()))
+        }
+    }
+
+    /// Open the HTML version, or the arXiv webpage if there is probably none.
+    pub fn open_html(&self) -> anyhow::Result<()> {
+        util::open(
+            OpenAs::Url,
+            self.html_url().unwrap_or_else(|| self.abs_url()),
         )
     }
 
     /// Open the article's arXiv webpage.
     pub fn open_abs(&self) -> anyhow::Result<()> {
-        let status = Command::new("xdg-open")
-            .arg(format!("https://arxiv.org/abs/{}", self.id()))
-            .output()?
-            .status;
-        if !status.success() {
-            bail!("xdg-open failed");
-        }
-        Ok(())
+        util::open(OpenAs::Url, self.abs_url())
     }
 
     /// Open the (previously downloaded) pdf file.
-    pub fn open_pdf(&self, base_dir: &Path) -> anyhow::Result<()> {
-        let status = Command::new("xdg-open")
-            .arg(self.pdf_path(base_dir))
-            .output()?
-            .status;
-        if !status.success() {
-            bail!("xdg-open failed");
-        }
-        Ok(())
+    pub fn open_pdf(&self, download_dir: &DownloadDir) -> anyhow::Result<()> {
+        util::open(OpenAs::Pdf, self.pdf_path(download_dir))
     }
 
     /// Open the data directory for this article.
     pub fn open_dir(&self, base_dir: &Path) -> anyhow::Result<()> {
         self.id().mkdir(base_dir)?;
-        let status = Command::new("xdg-open")
-            .arg(self.id().directory(base_dir))
-            .output()?
-            .status;
-        if !status.success() {
-            bail!("xdg-open failed");
+        util::open(OpenAs::Directory, self.id().directory(base_dir))
+    }
+
+    /// Runs a user-defined shell command with information about this article in
+    /// environment variables. Returns the last line of its output.
+    pub fn run_command(
+        &self,
+        base_dir: &Path,
+        download_dir: &DownloadDir,
+        command: &str,
+    ) -> anyhow::Result<String> {
+        let output = util::shell_command(command)
+            .env("ARXIV_ID", self.id().to_string())
+            .env("ARXIV_VERSION", self.last_version().number.to_string())
+            .env("ARXIV_TITLE", self.title())
+            .env("ARXIV_AUTHORS", self.authors())
+            .env("ARXIV_URL", self.abs_url())
+            .env("ARXIV_PDF_PATH", self.pdf_path(download_dir))
+            .env("ARXIV_SRC_PATH", self.src_path(download_dir))
+            .env("ARXIV_DIR", self.id().directory(base_dir))
+            .env("ARXIV_NOTES_PATH", self.notes_file(base_dir))
+            .stdin(std::process::Stdio::null())
+            .output()
+            .with_context(|| format!("running {command:?}"))?;
+        let last_line = |bytes: &[u8]| {
+            String::from_utf8_lossy(bytes)
+                .lines()
+                .rfind(|l| !l.trim().is_empty())
+                .map(|l| l.trim().to_string())
+        };
+        let message = last_line(&output.stdout).or_else(|| last_line(&output.stderr));
+        if !output.status.success() {
+            match message {
+                Some(message) => bail!("{command:?} failed ({}): {message}", output.status),
+                None => bail!("{command:?} failed ({})", output.status),
+            }
         }
-        Ok(())
+        Ok(message.unwrap_or_default())
     }
 
     pub fn notes_file(&self, base_dir: &Path) -> PathBuf {
         self.id().directory(base_dir).join("notes.txt")
     }
 
-    /// Open notes file in the default editor.
-    pub fn edit_notes(&mut self, base_dir: &Path) -> anyhow::Result<()> {
+    /// Copies a file into the attachments directory of the article. Returns the name of
+    /// the copy, which only contains safe characters and does not replace an existing file.
+    pub fn attach(
+        &mut self,
+        base_dir: &Path,
+        tr: &Transaction,
+        file: &Path,
+    ) -> anyhow::Result<String> {
+        let dir = self.id().directory(base_dir).join("attachments");
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {dir:?}"))?;
+        let name = file
+            .file_name()
+            .with_context(|| format!("{file:?} has no file name"))?;
+        let name = safe_file_name(&name.to_string_lossy());
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+            _ => (name.as_str(), String::new()),
+        };
+        let mut target = name.clone();
+        let mut i = 2;
+        while dir.join(&target).exists() {
+            target = format!("{stem}-{i}{extension}");
+            i += 1;
+        }
+        std::fs::copy(file, dir.join(&target))
+            .with_context(|| format!("copying {file:?} to {dir:?}"))?;
+        self.state.attachments = ArticleState::get_attachments(base_dir, self.id())?;
+        self.state.write_cache(tr, base_dir, self.id())?;
+        Ok(target)
+    }
+
+    /// Open notes file in the default editor. Call `reload_state` afterwards to read the
+    /// changes.
+    pub fn edit_notes(&self, base_dir: &Path) -> anyhow::Result<()> {
         self.id().mkdir(base_dir)?;
-        let editor = std::env::var_os("EDITOR").unwrap_or_else(|| "vi".to_string().into());
+        let editor = std::env::var_os("EDITOR").unwrap_or_else(|| util::default_editor().into());
         let status = Command::new(editor)
             .arg(self.notes_file(base_dir))
             .status()?;
         if !status.success() {
             bail!("editor failed");
         }
-        self.state.notes = ArticleState::get_notes(base_dir, self.id())?;
         Ok(())
     }
 
     /// Prints article metadata, bookmarks, and notes.
     /// `show_updates` specifies whether we should highlight unseen versions, journal refs, etc.
     pub fn print(&self, highlight: &Highlight, show_updates: bool, latex_to_unicode: bool) {
+        for line in self.lines(highlight, show_updates, latex_to_unicode) {
+            message!("{line}");
+        }
+    }
+
+    /// The lines printed by `print`. Some of them (such as the abstract) contain line
+    /// breaks.
+    pub fn lines(
+        &self,
+        highlight: &Highlight,
+        show_updates: bool,
+        latex_to_unicode: bool,
+    ) -> Vec<String> {
         let bold_if_updated = |cond: bool, s: &str| {
             if cond && show_updates {
-                println!(
+                format!(
                     "{}{}{}",
                     termion::color::LightRed.fg_str(),
                     s,
                     termion::color::Reset.fg_str()
-                );
+                )
             } else {
-                println!("{}", s);
+                s.to_string()
             }
         };
+        let mut res = Vec::new();
 
         let to_unicode = |text: &str| -> String {
             if latex_to_unicode {
@@ -776,7 +1955,7 @@ impl Article {
             }
         };
 
-        println!("{}", self.id());
+        res.push(self.id().to_string());
         for version in self.versions() {
             let mut line = format!(
                 "Date (v{}): {}",
@@ -786,74 +1965,78 @@ impl Article {
             if version.probably_withdrawn() {
                 line += " (withdrawn?)";
             }
-            bold_if_updated(version.number > self.last_seen_version(), &line);
+            res.push(bold_if_updated(
+                version.number > self.last_seen_version(),
+                &line,
+            ));
         }
-        println!();
-        println!(
+        res.push(String::new());
+        res.push(format!(
             "Title: {}",
-            highlight_matches(&to_unicode(self.title()), true, &highlight.keywords)
-        );
-        println!(
+            highlight_matches(&to_unicode(self.title()), &highlight.keywords)
+        ));
+        res.push(format!(
             "Authors: {}",
-            highlight_matches(&to_unicode(self.authors()), false, &highlight.authors)
-        );
-        println!(
+            highlight_matches(&to_unicode(self.authors()), &highlight.authors)
+        ));
+        res.push(format!(
             "Categories: {}",
             self.categories()
                 .iter()
-                .map(|c| if highlight.categories.contains(c) {
-                    format!(
-                        "{}{}{}",
-                        termion::color::LightRed.fg_str(),
-                        c,
-                        termion::color::Reset.fg_str()
-                    )
-                } else {
-                    c.to_string()
-                })
+                .map(|c| highlight_whole(c, &highlight.categories))
                 .collect::<Vec<_>>()
                 .join(" ")
-        );
+        ));
         if let Some(comments) = self.comments() {
-            println!(
+            res.push(format!(
                 "Comments: {}",
-                highlight_matches(&to_unicode(comments), true, &highlight.keywords)
-            );
+                highlight_matches(&to_unicode(comments), &highlight.keywords)
+            ));
         }
         if let Some(acm_classes) = self.acm_classes() {
-            println!(
+            res.push(format!(
                 "ACM-class: {}",
-                highlight_matches(acm_classes, false, &highlight.acm_classes)
-            );
+                highlight_matches(acm_classes, &highlight.acm_classes)
+            ));
         }
         if let Some(msc_classes) = self.msc_classes() {
-            println!(
+            res.push(format!(
                 "MSC-class: {}",
-                highlight_matches(msc_classes, false, &highlight.msc_classes)
-            );
+                highlight_matches(msc_classes, &highlight.msc_classes)
+            ));
+        }
+        if !self.keywords().is_empty() {
+            res.push(format!("Keywords: {}", self.keywords().join(", ")));
         }
         if let Some(journal_ref) = self.journal_ref() {
-            bold_if_updated(
+            res.push(bold_if_updated(
                 !self.seen_journal(),
                 &format!("Journal ref: {}", journal_ref),
-            );
+            ));
         }
         if let Some(doi) = self.doi() {
-            bold_if_updated(!self.seen_doi(), &format!("DOI: https://doi.org/{}", doi));
+            res.push(bold_if_updated(
+                !self.seen_doi(),
+                &format!("DOI: https://doi.org/{}", doi),
+            ));
         }
-        println!();
-        println!(
-            "{}",
-            highlight_matches(&to_unicode(self.abstract_()), true, &highlight.keywords)
-        );
-        println!();
-        println!("------------------------------------------------------------------");
+        res.push(String::new());
+        res.push(highlight_matches(
+            &to_unicode(self.abstract_()),
+            &highlight.keywords,
+        ));
+        res.push(String::new());
+        res.push("------------------------------------------------------------------".to_string());
         for tag_name in self.tags() {
-            println!("Tag: {tag_name}");
+            res.push(format!("Tag: {tag_name}"));
+        }
+        for attachment in self.attachments() {
+            res.push(format!("Attachment: {attachment}"));
         }
-        println!();
+        res.push(String::new());
         if let Some(notes) = self.notes() {
-            println!("{}", notes);
+            res.push(markdown::render(notes));
         }
+        res
     }
 }
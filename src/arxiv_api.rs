@@ -0,0 +1,94 @@
+//! Searches with the arXiv API (see https://info.arxiv.org/help/api/user-manual.html),
+//! such as `au:Tao_T` or `all:"elliptic curves"`, to follow authors or topics across all
+//! categories.
+//!
+//! The API only returns part of the metadata (for example, not the dates of all
+//! versions), so the articles it finds are then downloaded with [`oai::fetch`] unless the
+//! database already has their latest version.
+
+use anyhow::{Context, bail};
+use rusqlite::Transaction;
+use serde::Deserialize;
+
+use crate::{
+    article::{ArticleMetadata, ArxivId},
+    oai, progress,
+    rate_limited_client::Client,
+};
+
+/// The number of most recently updated articles requested per search.
+pub const MAX_RESULTS: usize = 50;
+
+/// The ids and latest version numbers of the most recently updated articles matching the
+/// query.
+pub fn search(client: &mut Client, query: &str) -> anyhow::Result<Vec<(ArxivId, u32)>> {
+    let url = reqwest::Url::parse_with_params(
+        "https://export.arxiv.org/api/query",
+        &[
+            ("search_query", query),
+            ("sortBy", "lastUpdatedDate"),
+            ("sortOrder", "descending"),
+            ("max_results", &MAX_RESULTS.to_string()),
+        ],
+    )?;
+    progress::status(&format!("Searching for {query}..."));
+    let res = client
+        .get(url.as_str())
+        .context("requesting data from export.arxiv.org")?;
+    let res = str::from_utf8(&res.body).context("reading data from export.arxiv.org (non-utf8)")?;
+    let feed: Feed =
+        quick_xml::de::from_str(res).context("parsing response from export.arxiv.org")?;
+    let mut ids = Vec::new();
+    for entry in feed.entries {
+        // Errors are reported as entries with a special id.
+        if entry.id.contains("arxiv.org/api/errors") {
+            bail!(
+                "invalid search {query:?}: {}",
+                entry.summary.unwrap_or_default().trim()
+            );
+        }
+        let (id, version) = ArxivId::parse_with_version(&entry.id)
+            .context("parsing response from export.arxiv.org")?;
+        let version = version
+            .context("parsing response from export.arxiv.org")
+            .with_context(|| format!("missing version in {:?}", entry.id))?;
+        ids.push((id, version));
+    }
+    Ok(ids)
+}
+
+/// Searches for the query and saves the metadata of the articles found that are not in
+/// the database yet or have new versions. Returns the ids of the articles that were not
+/// in the database before.
+pub fn download_matches(
+    tr: &Transaction,
+    client: &mut Client,
+    query: &str,
+) -> anyhow::Result<Vec<ArxivId>> {
+    let mut new_ids = Vec::new();
+    for (id, version) in search(client, query)? {
+        let known = ArticleMetadata::load_one(tr, &id)?;
+        if known.is_some_and(|m| m.last_version().number >= version) {
+            continue;
+        }
+        if oai::fetch(tr, client, &id)? {
+            new_ids.push(id);
+        }
+    }
+    Ok(new_ids)
+}
+
+// The parts of the Atom feed returned by the API that we need.
+
+#[derive(Deserialize)]
+struct Feed {
+    #[serde(default, rename = "entry")]
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    /// The address of the abstract page, such as "http://arxiv.org/abs/2501.12345v2".
+    id: String,
+    summary: Option<String>,
+}
@@ -0,0 +1,581 @@
+//! Recognizes different spellings of the same author, such as "C. F. Gauss",
+//! "Carl Friedrich Gau\ss" and "Carl-Friedrich Gauss".
+//!
+//! Names are first normalized (latex accents, diacritics, case, hyphens). Two normalized
+//! names are compatible if they have the same family name and their given names agree,
+//! where an initial agrees with every name starting with this letter. Compatible names
+//! are put into the same cluster if this is unambiguous or if they share a co-author.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+use crate::{config::AuthorList, share::one_line};
+
+/// Splits the authors field of an article into the names of the individual authors.
+/// Affiliations in parentheses are removed.
+pub fn split_authors(authors: &str) -> Vec<String> {
+    let mut without_parentheses = String::new();
+    let mut depth = 0usize;
+    for c in authors.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => without_parentheses.push(c),
+            _ => {}
+        }
+    }
+    without_parentheses
+        .replace('\n', " ")
+        .split(',')
+        .flat_map(|part| part.split(" and "))
+        .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// An author of an article, as parsed from its authors field.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Author {
+    /// The name as written, such as "C. F. Gau\ss".
+    pub name: String,
+    /// The name for matching, see `normalize_name`.
+    pub normalized: String,
+    /// The normalized family name of a person, such as "gauss" (see `Name`).
+    pub family: Option<String>,
+    /// Whether this is a collaboration such as "ATLAS Collaboration" rather than a person.
+    pub collaboration: bool,
+}
+
+/// Whether a name refers to a group of people rather than a person.
+fn is_collaboration(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["collaboration", "consortium", "group", "team"]
+        .iter()
+        .any(|word| name.split_whitespace().any(|w| w == *word))
+}
+
+/// Parses the authors field of an article (see `split_authors`). Names such as "A. Smith
+/// for the ATLAS Collaboration" are split into the person and the collaboration.
+pub fn parse_authors(authors: &str) -> Vec<Author> {
+    let mut res = Vec::new();
+    for name in split_authors(authors) {
+        let mut parts = vec![name.as_str()];
+        for separator in [" for the ", " on behalf of the "] {
+            if let Some((person, collaboration)) = name.split_once(separator) {
+                parts = vec![person, collaboration];
+                break;
+            }
+        }
+        for part in parts {
+            let part = part.trim_start_matches("the ").trim();
+            if part.is_empty() {
+                continue;
+            }
+            let collaboration = is_collaboration(part);
+            res.push(Author {
+                name: part.to_string(),
+                normalized: normalize_name(part),
+                family: (!collaboration)
+                    .then(|| Name::normalize(part).map(|name| name.family))
+                    .flatten(),
+                collaboration,
+            });
+        }
+    }
+    res
+}
+
+/// A name without latex, accents, case and punctuation, with the given names first, such
+/// as "carl friedrich gauss" for "Gau\ss, Carl-Friedrich", so that names can be matched
+/// regardless of how they are written.
+pub fn normalize_name(name: &str) -> String {
+    if is_collaboration(name) {
+        return collation_key(name);
+    }
+    parse_name(name).map_or_else(String::new, |name| name.joined())
+}
+
+/// Like `Name::normalize`, but also understands "Family, Given" (as in a search).
+pub fn parse_name(name: &str) -> Option<Name> {
+    match name.split_once(',') {
+        Some((family, given)) => Name::normalize(&format!("{given} {family}")),
+        None => Name::normalize(name),
+    }
+}
+
+/// Formats the authors field of an article on one line as configured. If nothing is
+/// configured, the field is left as it is (up to line breaks).
+pub fn format_authors(authors: &str, style: &AuthorList) -> String {
+    let too_many = style
+        .max
+        .is_some_and(|max| split_authors(authors).len() > max);
+    if !too_many && !style.family_name_first {
+        return one_line(authors);
+    }
+    let mut names = split_authors(authors);
+    if let Some(max) = style.max {
+        names.truncate(max);
+    }
+    let mut res = if style.family_name_first {
+        names
+            .iter()
+            .map(|name| match name.rsplit_once(' ') {
+                // "ATLAS Collaboration" has no family name.
+                Some((given, family)) if !is_collaboration(name) => {
+                    format!("{family}, {given}")
+                }
+                _ => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    } else {
+        names.join(", ")
+    };
+    if too_many {
+        res += " et al.";
+    }
+    res
+}
+
+/// A key for sorting texts alphabetically: latex is decoded, and case and diacritics
+/// are ignored, so that "\'Esnault", "Ésnault" and "esnault" get the same key.
+pub fn collation_key(text: &str) -> String {
+    let text = unicodeit::replace(&strip_latex_accents(text));
+    let mut res = String::new();
+    for c in text.nfd().filter(|c| !is_combining_mark(*c)) {
+        match c {
+            'ß' => res.push_str("ss"),
+            'ø' | 'Ø' => res.push('o'),
+            'ł' | 'Ł' => res.push('l'),
+            _ if c.is_alphanumeric() => res.extend(c.to_lowercase()),
+            _ if !res.is_empty() && !res.ends_with(' ') => res.push(' '),
+            _ => {}
+        }
+    }
+    res.trim_end().to_string()
+}
+
+/// A key for sorting articles by their authors: by the family name and then by the given
+/// names of the first author, and so on.
+pub fn author_sort_key(authors: &str) -> Vec<(String, Vec<String>)> {
+    split_authors(authors)
+        .iter()
+        .filter_map(|name| Name::normalize(name))
+        .map(|name| (name.family, name.given))
+        .collect()
+}
+
+/// Removes latex accent commands (such as `\"` in `G{\"o}del`) and braces.
+/// The accents would be removed during normalization anyway.
+fn strip_latex_accents(name: &str) -> String {
+    let mut res = String::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => {}
+            '\\' => match chars.peek() {
+                Some('"' | '\'' | '`' | '^' | '~' | '=' | '.') => {
+                    chars.next();
+                }
+                Some(&l) if "vuHckr".contains(l) => {
+                    let mut rest = chars.clone();
+                    rest.next();
+                    if matches!(rest.peek(), Some('{' | ' ')) {
+                        chars.next();
+                        if chars.peek() == Some(&' ') {
+                            chars.next();
+                        }
+                    } else {
+                        res.push(c);
+                    }
+                }
+                _ => res.push(c),
+            },
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// A normalized name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Name {
+    pub family: String,
+    /// The given names, or their initials.
+    pub given: Vec<String>,
+}
+
+/// Suffixes that are not part of the family name.
+const SUFFIXES: [&str; 5] = ["jr", "sr", "ii", "iii", "iv"];
+
+impl Name {
+    /// Normalizes a name as it appears in the authors field, such as "C.-F. Gau\ss".
+    /// Returns None if there is nothing left after normalization.
+    pub fn normalize(name: &str) -> Option<Name> {
+        let name = unicodeit::replace(&strip_latex_accents(name));
+        let mut normalized = String::new();
+        for c in name.nfd().filter(|c| !is_combining_mark(*c)) {
+            match c {
+                'ß' => normalized.push_str("ss"),
+                'ø' | 'Ø' => normalized.push('o'),
+                'ł' | 'Ł' => normalized.push('l'),
+                // Hyphens, dots, ... separate names.
+                _ if c.is_alphanumeric() || c == '\'' => normalized.push(c),
+                _ => normalized.push(' '),
+            }
+        }
+        let mut parts: Vec<&str> = normalized
+            .split_whitespace()
+            .map(|part| part.trim_matches('\''))
+            .filter(|part| !part.is_empty())
+            .collect();
+        while parts.len() > 1 && SUFFIXES.contains(&parts.last().unwrap().to_lowercase().as_str()) {
+            parts.pop();
+        }
+        let family = parts.pop()?.to_lowercase();
+        let given = parts
+            .into_iter()
+            .flat_map(|part| {
+                // Initials without separating dots, such as "CF Gauss".
+                if (2..=3).contains(&part.len()) && part.chars().all(|c| c.is_uppercase()) {
+                    part.chars().map(|c| c.to_lowercase().to_string()).collect()
+                } else {
+                    vec![part.to_lowercase()]
+                }
+            })
+            .collect();
+        Some(Name { family, given })
+    }
+
+    /// Whether the two names might refer to the same person.
+    pub fn compatible(&self, other: &Name) -> bool {
+        let agree = |a: &String, b: &String| {
+            a == b
+                || (a.chars().count() == 1 && b.starts_with(a.as_str()))
+                || (b.chars().count() == 1 && a.starts_with(b.as_str()))
+        };
+        self.family == other.family
+            && (self.given.is_empty()
+                || other.given.is_empty()
+                || self
+                    .given
+                    .iter()
+                    .zip(other.given.iter())
+                    .all(|(a, b)| agree(a, b)))
+    }
+
+    /// How complete the name is: the number of given names that are not abbreviated,
+    /// and then their total length.
+    fn completeness(&self) -> (usize, usize) {
+        (
+            self.given.iter().filter(|g| g.chars().count() > 1).count(),
+            self.given.iter().map(|g| g.len()).sum(),
+        )
+    }
+
+    /// The name as a single string, such as "carl friedrich gauss".
+    fn joined(&self) -> String {
+        let mut parts = self.given.clone();
+        parts.push(self.family.clone());
+        parts.join(" ")
+    }
+}
+
+/// A set of names that presumably refer to the same person.
+pub struct Cluster {
+    /// The most complete spelling of the name, for display.
+    pub canonical: String,
+    /// All spellings of the name together with the number of articles using them.
+    pub variants: BTreeMap<String, usize>,
+}
+
+/// The clusters of all authors of a collection of articles.
+pub struct AuthorClusters {
+    clusters: Vec<Cluster>,
+    /// The cluster of each spelling.
+    cluster_of: HashMap<String, usize>,
+    /// Each spelling with its normalized form (see `Name::joined`) and its cluster.
+    normalized: Vec<(String, String, usize)>,
+}
+
+impl AuthorClusters {
+    /// Clusters the authors of the given articles, each given by its authors field.
+    pub fn new<'a>(articles: impl IntoIterator<Item = &'a str>) -> Self {
+        // The distinct normalized names, the spellings used for them and the family
+        // names of their co-authors.
+        let mut names: Vec<Name> = Vec::new();
+        let mut index: HashMap<Name, usize> = HashMap::new();
+        let mut spellings: Vec<BTreeMap<String, usize>> = Vec::new();
+        let mut coauthors: Vec<HashSet<String>> = Vec::new();
+        for authors in articles {
+            let article_names: Vec<(String, Name)> = split_authors(authors)
+                .into_iter()
+                .filter_map(|raw| Name::normalize(&raw).map(|name| (raw, name)))
+                .collect();
+            for (raw, name) in &article_names {
+                let i = *index.entry(name.clone()).or_insert_with(|| {
+                    names.push(name.clone());
+                    spellings.push(BTreeMap::new());
+                    coauthors.push(HashSet::new());
+                    names.len() - 1
+                });
+                *spellings[i].entry(raw.clone()).or_default() += 1;
+                for (_, other) in &article_names {
+                    if other.family != name.family {
+                        coauthors[i].insert(other.family.clone());
+                    }
+                }
+            }
+        }
+        // Group by family name and first initial, since only names in the same group
+        // can be compatible.
+        let mut groups: HashMap<(&str, Option<char>), Vec<usize>> = HashMap::new();
+        for (i, name) in names.iter().enumerate() {
+            let initial = name.given.first().and_then(|g| g.chars().next());
+            groups.entry((&name.family, initial)).or_default().push(i);
+        }
+        // Names without given names are compatible with everything with the same
+        // family name, so they are only merged in case of co-author overlap.
+        let mut compatible: Vec<Vec<usize>> = vec![Vec::new(); names.len()];
+        for ((family, initial), group) in &groups {
+            let mut candidates = group.clone();
+            if initial.is_none() {
+                for ((other_family, _), other_group) in &groups {
+                    if other_family == family {
+                        candidates.extend(other_group);
+                    }
+                }
+            }
+            for &a in group {
+                for &b in &candidates {
+                    if a != b && names[a].compatible(&names[b]) {
+                        compatible[a].push(b);
+                        compatible[b].push(a);
+                    }
+                }
+            }
+        }
+        for list in &mut compatible {
+            list.sort();
+            list.dedup();
+        }
+        // Merge clusters, but never put incompatible names into the same cluster.
+        let mut members: Vec<Vec<usize>> = (0..names.len()).map(|i| vec![i]).collect();
+        let mut cluster: Vec<usize> = (0..names.len()).collect();
+        let mut merge = |a: usize, b: usize| {
+            let (ca, cb) = (cluster[a], cluster[b]);
+            if ca == cb
+                || !members[ca]
+                    .iter()
+                    .all(|&x| members[cb].iter().all(|&y| names[x].compatible(&names[y])))
+            {
+                return;
+            }
+            let moved = std::mem::take(&mut members[cb]);
+            for &x in &moved {
+                cluster[x] = ca;
+            }
+            members[ca].extend(moved);
+        };
+        for a in 0..names.len() {
+            for &b in &compatible[a] {
+                let unambiguous = compatible[a].len() == 1 && compatible[b].len() == 1;
+                let overlap = !coauthors[a].is_disjoint(&coauthors[b]);
+                if (unambiguous && !names[a].given.is_empty() && !names[b].given.is_empty())
+                    || overlap
+                {
+                    merge(a, b);
+                }
+            }
+        }
+        let mut clusters = Vec::new();
+        let mut cluster_of = HashMap::new();
+        let mut normalized = Vec::new();
+        for group in members.into_iter().filter(|m| !m.is_empty()) {
+            let best = *group
+                .iter()
+                .max_by_key(|&&i| {
+                    (
+                        names[i].completeness(),
+                        spellings[i].values().sum::<usize>(),
+                    )
+                })
+                .unwrap();
+            let canonical = spellings[best]
+                .iter()
+                .max_by_key(|(_, count)| **count)
+                .unwrap()
+                .0
+                .clone();
+            let mut variants = BTreeMap::new();
+            for i in group {
+                for (raw, count) in &spellings[i] {
+                    *variants.entry(raw.clone()).or_default() += count;
+                    cluster_of.insert(raw.clone(), clusters.len());
+                    normalized.push((raw.clone(), names[i].joined(), clusters.len()));
+                }
+            }
+            clusters.push(Cluster {
+                canonical,
+                variants,
+            });
+        }
+        AuthorClusters {
+            clusters,
+            cluster_of,
+            normalized,
+        }
+    }
+
+    /// The cluster containing this spelling of a name.
+    pub fn cluster(&self, name: &str) -> Option<&Cluster> {
+        self.cluster_of.get(name).map(|&i| &self.clusters[i])
+    }
+
+    /// The canonical spelling of a name, for display.
+    pub fn canonical<'a>(&'a self, name: &'a str) -> &'a str {
+        self.cluster(name).map_or(name, |c| c.canonical.as_str())
+    }
+
+    /// The clusters containing a spelling that contains `word`, either literally or
+    /// after normalization.
+    pub fn search(&self, word: &str) -> Vec<&Cluster> {
+        let word_normalized = Name::normalize(word).map(|name| name.joined());
+        let mut found = BTreeSet::new();
+        for (raw, normalized, i) in &self.normalized {
+            if raw.contains(word)
+                || word_normalized
+                    .as_ref()
+                    .is_some_and(|w| normalized.contains(w.as_str()))
+            {
+                found.insert(*i);
+            }
+        }
+        let mut found: Vec<&Cluster> = found.into_iter().map(|i| &self.clusters[i]).collect();
+        found.sort_by_cached_key(|cluster| author_sort_key(&cluster.canonical));
+        found
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn collation() {
+        assert_eq!(collation_key("\\'Esnault"), "esnault");
+        assert_eq!(collation_key("Ésnault"), "esnault");
+        assert_eq!(
+            collation_key("On $p$-adic {\\\"U}bergänge"),
+            "on p adic ubergange"
+        );
+        assert!(author_sort_key("\\'Eric Esnault") < author_sort_key("H. Esnault and A. Bauer"));
+        assert!(author_sort_key("H. Esnault and A. Bauer") < author_sort_key("A. Euler"));
+    }
+
+    #[test]
+    fn format() {
+        let authors =
+            "Carl Friedrich Gauss (G\\\"ottingen), Leonhard Euler and\n ATLAS Collaboration";
+        let style = |max, family_name_first| AuthorList {
+            max,
+            family_name_first,
+        };
+        assert_eq!(
+            format_authors("A. B, C.  D", &style(None, false)),
+            "A. B, C. D"
+        );
+        assert_eq!(
+            format_authors(authors, &style(Some(2), false)),
+            "Carl Friedrich Gauss, Leonhard Euler et al."
+        );
+        assert_eq!(
+            format_authors(authors, &style(Some(3), true)),
+            "Gauss, Carl Friedrich; Euler, Leonhard; ATLAS Collaboration"
+        );
+        assert_eq!(
+            format_authors("Event Horizon Telescope Consortium", &style(None, true)),
+            "Event Horizon Telescope Consortium"
+        );
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_authors("A. Smith (1), B. Jones (2) and C.\n Doe ((1) MIT, (2) ETH)"),
+            vec!["A. Smith", "B. Jones", "C. Doe"]
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let authors = parse_authors(
+            "Carl-Friedrich Gau\\ss, A. Smith for the ATLAS Collaboration and Kurt G{\\\"o}del",
+        );
+        let names: Vec<(&str, &str, Option<&str>)> = authors
+            .iter()
+            .map(|a| (a.name.as_str(), a.normalized.as_str(), a.family.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (
+                    "Carl-Friedrich Gau\\ss",
+                    "carl friedrich gauss",
+                    Some("gauss")
+                ),
+                ("A. Smith", "a smith", Some("smith")),
+                ("ATLAS Collaboration", "atlas collaboration", None),
+                ("Kurt G{\\\"o}del", "kurt godel", Some("godel")),
+            ]
+        );
+        assert!(authors[2].collaboration);
+        assert_eq!(normalize_name("Gauß, C. F."), "c f gauss");
+        assert_eq!(normalize_name("Gauß"), "gauss");
+    }
+
+    #[test]
+    fn normalize() {
+        let gauss = Name {
+            family: "gauss".to_string(),
+            given: vec!["carl".to_string(), "friedrich".to_string()],
+        };
+        assert_eq!(
+            Name::normalize("Carl-Friedrich Gau\\ss"),
+            Some(gauss.clone())
+        );
+        assert_eq!(Name::normalize("Carl Friedrich Gauß"), Some(gauss.clone()));
+        assert!(Name::normalize("C. F. Gauss").unwrap().compatible(&gauss));
+        assert!(!Name::normalize("K. F. Gauss").unwrap().compatible(&gauss));
+        assert_eq!(
+            Name::normalize("Kurt G\\\"odel"),
+            Name::normalize("Kurt Gödel")
+        );
+        assert_eq!(
+            Name::normalize("Kurt G{\\\"o}del"),
+            Name::normalize("kurt godel")
+        );
+        assert_eq!(
+            Name::normalize("Pavel \\v{S}eba"),
+            Name::normalize("Pavel Šeba")
+        );
+    }
+
+    #[test]
+    fn clusters() {
+        let clusters = AuthorClusters::new([
+            "Carl Friedrich Gauss and Adrien-Marie Legendre",
+            "C. F. Gauss",
+            "J. Smith and A. Wiles",
+            "John Smith and Andrew Wiles",
+            "Jane Smith",
+        ]);
+        assert_eq!(clusters.canonical("C. F. Gauss"), "Carl Friedrich Gauss");
+        // "J. Smith" could also be Jane Smith, but shares a co-author with John Smith.
+        assert_eq!(clusters.canonical("J. Smith"), "John Smith");
+        assert_eq!(clusters.canonical("Jane Smith"), "Jane Smith");
+        assert_eq!(clusters.search("Gauss").len(), 1);
+        assert_eq!(clusters.search("Smith").len(), 2);
+    }
+}
@@ -1,19 +1,107 @@
 use std::{
+    cmp::min,
     collections::HashMap,
     fs::File,
-    io::{BufReader, Read, Write, stdin, stdout},
+    io::{BufReader, Read, stdin},
     path::Path,
 };
 
 use anyhow::{Context, bail};
 use biblatex::{Bibliography, Chunk};
 use rusqlite::Transaction;
+use serde::Serialize;
 
 use crate::{
     article::{Article, ArxivId},
+    authors::{Name, split_authors},
     config::TagName,
+    crossref, message, output,
+    share::one_line,
 };
 
+/// The key template used if the config file does not set `bibtex.key`.
+pub const DEFAULT_KEY: &str = "{author}{year}{title}";
+
+/// The citation key of the article according to a template, in which `{author}` is the
+/// family name of the first author, `{authors}` the family names of the first three
+/// authors, `{year}` the year of the first version, `{title}` the first word of the title
+/// with at least four letters, and `{id}` the arXiv identifier. For example, the default
+/// template gives keys such as "gauss2023distribution".
+pub fn key(article: &Article, template: &str) -> anyhow::Result<String> {
+    let families: Vec<String> = split_authors(article.authors())
+        .iter()
+        .filter_map(|name| Name::normalize(name))
+        .map(|name| name.family)
+        .collect();
+    let alphanumeric =
+        |s: &str| -> String { s.chars().filter(|c| c.is_ascii_alphanumeric()).collect() };
+    let mut res = String::new();
+    let mut rest = template;
+    while let Some((before, after)) = rest.split_once('{') {
+        res += before;
+        let (placeholder, after) = after
+            .split_once('}')
+            .with_context(|| format!("missing '}}' in bibtex key template {template:?}"))?;
+        res += &match placeholder {
+            "author" => alphanumeric(families.first().map_or("", String::as_str)),
+            "authors" => alphanumeric(&families.iter().take(3).cloned().collect::<String>()),
+            "year" => article.first_version().date.format("%Y").to_string(),
+            "title" => article
+                .title()
+                .split(|c: char| !c.is_ascii_alphabetic())
+                .find(|w| w.len() >= 4)
+                .unwrap_or_default()
+                .to_lowercase(),
+            "id" => article.id().to_string().replace('/', ":"),
+            _ => bail!("unknown placeholder {{{placeholder}}} in bibtex key template {template:?}"),
+        };
+        rest = after;
+    }
+    res += rest;
+    Ok(res)
+}
+
+/// A bibtex entry for the article with the given key, similar to the ones on arXiv.
+pub fn entry(article: &Article, key: &str) -> String {
+    let authors = split_authors(article.authors());
+    let year = article.first_version().date.format("%Y").to_string();
+    let mut res = format!("@misc{{{key},\n");
+    res += &format!("  title = {{{}}},\n", one_line(article.title()));
+    res += &format!("  author = {{{}}},\n", authors.join(" and "));
+    res += &format!("  year = {{{year}}},\n");
+    res += &format!("  eprint = {{{}}},\n", article.id());
+    res += "  archivePrefix = {arXiv},\n";
+    res += &format!("  primaryClass = {{{}}},\n", article.primary_category());
+    if let Some(doi) = article.doi() {
+        res += &format!("  doi = {{{doi}}},\n");
+    }
+    if let Some(journal_ref) = article.journal_ref() {
+        res += &format!("  note = {{{}}},\n", one_line(journal_ref));
+    }
+    res += "}\n";
+    res
+}
+
+/// Bibtex entries for the articles with keys according to the template. If several
+/// articles get the same key, the letters b, c, ... are appended to the later ones.
+pub fn export(articles: &[Article], template: &str) -> anyhow::Result<String> {
+    let mut used: HashMap<String, usize> = HashMap::new();
+    let mut res = String::new();
+    for article in articles {
+        let mut key = key(article, template)?;
+        let count = used.entry(key.clone()).or_default();
+        if *count > 0 {
+            key.push(char::from(b'a' + min(*count, 25) as u8));
+        }
+        *count += 1;
+        if !res.is_empty() {
+            res += "\n";
+        }
+        res += &entry(article, &key);
+    }
+    Ok(res)
+}
+
 pub fn bookmark(
     base_dir: &Path,
     conn: &Transaction,
@@ -69,13 +157,13 @@ pub fn bookmark(
             // create a bookmark.
             if let Some(article) = article {
                 if !article.tags().contains(tag_name) {
-                    println!("Adding bookmark for {id}.");
-                    article.set_tag(base_dir, tag_name)?;
-                    println!();
+                    message!("Adding bookmark for {id}.");
+                    article.set_tag(base_dir, conn, tag_name)?;
+                    message!();
                 }
             } else {
-                println!("Article {id} not found.");
-                println!();
+                message!("Article {id} not found.");
+                message!();
             }
         } else if let Ok(doi) = entry.doi() {
             // If the entry has a DOI, try to identify the article that way.
@@ -96,25 +184,24 @@ pub fn bookmark(
                     .iter()
                     .any(|id| articles.get(id).unwrap().tags().contains(tag_name))
             {
-                println!("Article https://doi.org/{doi}");
-                println!("  by {}", authors.join(" and "));
+                message!("Article https://doi.org/{doi}");
+                message!("  by {}", authors.join(" and "));
                 let title: Vec<String> = entry
                     .title()
                     .with_context(|| format!("reading bibtex entry {key}"))?
                     .iter()
                     .map(|c| c.v.to_biblatex_string(false))
                     .collect();
-                println!("  titled {}", title.join(""));
-                println!("could be:");
+                message!("  titled {}", title.join(""));
+                message!("could be:");
                 for (i, id) in ids.iter().enumerate() {
-                    println!("[{}] {id}", i + 1);
+                    message!("[{}] {id}", i + 1);
                     let article = articles.get(id).unwrap();
-                    println!("  by {}", article.authors());
-                    println!("  titled {}", article.title());
+                    message!("  by {}", article.authors());
+                    message!("  titled {}", article.title());
                 }
                 let i = loop {
-                    print!("Please select one (0 means none): ");
-                    stdout().flush()?;
+                    output::write(format_args!("Please select one (0 means none): "));
                     let mut response = String::new();
                     stdin().read_line(&mut response)?;
                     let i: Result<usize, _> = response.trim().parse();
@@ -123,23 +210,50 @@ pub fn bookmark(
                     {
                         break i;
                     } else {
-                        println!("Not a number between 0 and {}", ids.len());
+                        message!("Not a number between 0 and {}", ids.len());
                     }
                 };
                 if i > 0 {
                     let id = ids.get(i - 1).unwrap();
                     let article = articles.get_mut(id).unwrap();
-                    println!("Adding bookmark named {key} for {id}.");
-                    article.set_tag(base_dir, tag_name)?;
+                    message!("Adding bookmark named {key} for {id}.");
+                    article.set_tag(base_dir, conn, tag_name)?;
                 }
-                println!();
+                message!();
             }
         }
     }
     Ok(())
 }
 
-pub fn check(base_dir: &Path, conn: &Transaction, file: &Path) -> anyhow::Result<()> {
+/// Something `check` noticed about an entry of the bibtex file.
+#[derive(Serialize)]
+pub struct Finding {
+    pub key: String,
+    pub id: ArxivId,
+    #[serde(flatten)]
+    pub kind: FindingKind,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FindingKind {
+    /// The entry refers to an old version.
+    NewerVersion { version: u32, last_version: u32 },
+    /// The article seems to have been published.
+    Published {
+        journal_ref: Option<String>,
+        doi: Option<String>,
+    },
+    /// The article has no journal reference on arXiv, but Crossref knows a published
+    /// version (see [`crossref`]).
+    FoundOnCrossref { journal_ref: String, doi: String },
+    /// The article is not in the database.
+    NotFound,
+}
+
+/// Looks for outdated arXiv entries in a bibtex file, prints what it finds and returns it.
+pub fn check(base_dir: &Path, conn: &Transaction, file: &Path) -> anyhow::Result<Vec<Finding>> {
     // Parse the BibTeX file.
     let file = File::open(file).context("opening bibtex file")?;
     let mut reader = BufReader::new(file);
@@ -152,7 +266,9 @@ pub fn check(base_dir: &Path, conn: &Transaction, file: &Path) -> anyhow::Result
 
     // Load the articles.
     let mut articles = Article::load(base_dir, conn)?;
+    let publications = crossref::load(conn)?;
 
+    let mut findings = Vec::new();
     // Go through entries in the bibtex file.
     for entry in bib.iter() {
         // Extract the key.
@@ -173,27 +289,62 @@ pub fn check(base_dir: &Path, conn: &Transaction, file: &Path) -> anyhow::Result
                 if let Some(version) = version
                     && article.last_version().number > version
                 {
-                    println!(
+                    message!(
                         "Entry {key} refers to {id}, version {version}, but there is a newer version {}",
                         article.last_version().number
                     );
+                    findings.push(Finding {
+                        key: key.clone(),
+                        id: id.clone(),
+                        kind: FindingKind::NewerVersion {
+                            version,
+                            last_version: article.last_version().number,
+                        },
+                    });
                 }
                 // If the article has an associated doi, tell the user.
                 if article.journal_ref().is_some() {
-                    println!("Entry {key} refers to {id}, which seems to have been published:");
+                    message!("Entry {key} refers to {id}, which seems to have been published:");
                     if let Some(journal_ref) = article.journal_ref() {
-                        println!("  Journal ref: {}", journal_ref);
+                        message!("  Journal ref: {}", journal_ref);
                     }
                     if let Some(doi) = article.doi() {
-                        println!("  DOI: https://doi.org/{}", doi)
+                        message!("  DOI: https://doi.org/{}", doi)
                     }
-                    println!();
+                    message!();
+                    findings.push(Finding {
+                        key: key.clone(),
+                        id: id.clone(),
+                        kind: FindingKind::Published {
+                            journal_ref: article.journal_ref().cloned(),
+                            doi: article.doi().cloned(),
+                        },
+                    });
+                } else if let Some(publication) = publications.get(&id) {
+                    let journal_ref = publication.citation();
+                    message!("Entry {key} refers to {id}, which Crossref lists as published:");
+                    message!("  Journal ref: {journal_ref}");
+                    message!("  DOI: https://doi.org/{}", publication.doi);
+                    message!();
+                    findings.push(Finding {
+                        key: key.clone(),
+                        id: id.clone(),
+                        kind: FindingKind::FoundOnCrossref {
+                            journal_ref,
+                            doi: publication.doi.clone(),
+                        },
+                    });
                 }
             } else {
-                println!("Article {id} not found.");
-                println!();
+                message!("Article {id} not found.");
+                message!();
+                findings.push(Finding {
+                    key: key.clone(),
+                    id,
+                    kind: FindingKind::NotFound,
+                });
             }
         }
     }
-    Ok(())
+    Ok(findings)
 }
@@ -0,0 +1,276 @@
+//! The hierarchies of MSC classes (such as "11R32") and ACM classes (such as "F.2.2").
+
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    Msc,
+    Acm,
+}
+
+/// The top level of the Mathematics Subject Classification 2020.
+const MSC_CLASSES: &[(&str, &str)] = &[
+    ("00", "General and overarching topics; collections"),
+    ("01", "History and biography"),
+    ("03", "Mathematical logic and foundations"),
+    ("05", "Combinatorics"),
+    ("06", "Order, lattices, ordered algebraic structures"),
+    ("08", "General algebraic systems"),
+    ("11", "Number theory"),
+    ("12", "Field theory and polynomials"),
+    ("13", "Commutative algebra"),
+    ("14", "Algebraic geometry"),
+    ("15", "Linear and multilinear algebra; matrix theory"),
+    ("16", "Associative rings and algebras"),
+    ("17", "Nonassociative rings and algebras"),
+    ("18", "Category theory; homological algebra"),
+    ("19", "K-theory"),
+    ("20", "Group theory and generalizations"),
+    ("22", "Topological groups, Lie groups"),
+    ("26", "Real functions"),
+    ("28", "Measure and integration"),
+    ("30", "Functions of a complex variable"),
+    ("31", "Potential theory"),
+    ("32", "Several complex variables and analytic spaces"),
+    ("33", "Special functions"),
+    ("34", "Ordinary differential equations"),
+    ("35", "Partial differential equations"),
+    ("37", "Dynamical systems and ergodic theory"),
+    ("39", "Difference and functional equations"),
+    ("40", "Sequences, series, summability"),
+    ("41", "Approximations and expansions"),
+    ("42", "Harmonic analysis on Euclidean spaces"),
+    ("43", "Abstract harmonic analysis"),
+    ("44", "Integral transforms, operational calculus"),
+    ("45", "Integral equations"),
+    ("46", "Functional analysis"),
+    ("47", "Operator theory"),
+    (
+        "49",
+        "Calculus of variations and optimal control; optimization",
+    ),
+    ("51", "Geometry"),
+    ("52", "Convex and discrete geometry"),
+    ("53", "Differential geometry"),
+    ("54", "General topology"),
+    ("55", "Algebraic topology"),
+    ("57", "Manifolds and cell complexes"),
+    ("58", "Global analysis, analysis on manifolds"),
+    ("60", "Probability theory and stochastic processes"),
+    ("62", "Statistics"),
+    ("65", "Numerical analysis"),
+    ("68", "Computer science"),
+    ("70", "Mechanics of particles and systems"),
+    ("74", "Mechanics of deformable solids"),
+    ("76", "Fluid mechanics"),
+    ("78", "Optics, electromagnetic theory"),
+    ("80", "Classical thermodynamics, heat transfer"),
+    ("81", "Quantum theory"),
+    ("82", "Statistical mechanics, structure of matter"),
+    ("83", "Relativity and gravitational theory"),
+    ("85", "Astronomy and astrophysics"),
+    ("86", "Geophysics"),
+    ("90", "Operations research, mathematical programming"),
+    (
+        "91",
+        "Game theory, economics, finance, and other social and behavioral sciences",
+    ),
+    ("92", "Biology and other natural sciences"),
+    ("93", "Systems theory; control"),
+    ("94", "Information and communication theory, circuits"),
+    ("97", "Mathematics education"),
+];
+
+/// The top level of the ACM Computing Classification System 1998.
+const ACM_CLASSES: &[(&str, &str)] = &[
+    ("A", "General Literature"),
+    ("B", "Hardware"),
+    ("C", "Computer Systems Organization"),
+    ("D", "Software"),
+    ("E", "Data"),
+    ("F", "Theory of Computation"),
+    ("G", "Mathematics of Computing"),
+    ("H", "Information Systems"),
+    ("I", "Computing Methodologies"),
+    ("J", "Computer Applications"),
+    ("K", "Computing Milieux"),
+];
+
+impl Scheme {
+    /// Normalizes a class or a prefix of one: "11R32" stays as it is, "11Rxx" becomes
+    /// "11R", "11-XX" becomes "11", and "F.2.2" stays as it is. Returns `None` if `code`
+    /// is not in the format of the scheme.
+    pub fn normalize(self, code: &str) -> Option<String> {
+        match self {
+            Scheme::Msc => {
+                let c = code.as_bytes();
+                let digits = |s: &[u8]| s.iter().all(u8::is_ascii_digit);
+                let wildcard = |s: &[u8]| s == b"xx" || s == b"XX";
+                match c.len() {
+                    2 if digits(c) => Some(code.to_string()),
+                    3 if digits(&c[..2]) && c[2].is_ascii_uppercase() => Some(code.to_string()),
+                    5 if digits(&c[..2]) && c[2] == b'-' && wildcard(&c[3..]) => {
+                        Some(code[..2].to_string())
+                    }
+                    5 if digits(&c[..2]) && c[2].is_ascii_uppercase() && wildcard(&c[3..]) => {
+                        Some(code[..3].to_string())
+                    }
+                    5 if digits(&c[..2])
+                        && (c[2] == b'-' || c[2].is_ascii_uppercase())
+                        && digits(&c[3..]) =>
+                    {
+                        Some(code.to_string())
+                    }
+                    _ => None,
+                }
+            }
+            Scheme::Acm => {
+                let mut parts = code.split('.');
+                let top = parts.next()?;
+                let valid = top.len() == 1
+                    && ('A'..='K').contains(&top.chars().next()?)
+                    && parts.all(|p| {
+                        p == "m" || (!p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+                    });
+                valid.then(|| code.to_string())
+            }
+        }
+    }
+
+    /// The classes mentioned in the free-text field `field` of an article, normalized.
+    pub fn codes(self, field: &str) -> BTreeSet<String> {
+        field
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+            .map(|word| word.trim_matches('.'))
+            // A single letter is more likely an initial than a top-level ACM class.
+            .filter(|word| self == Scheme::Msc || word.contains('.'))
+            .filter_map(|word| self.normalize(word))
+            .collect()
+    }
+
+    /// Whether the class `code` lies below (or is) the normalized class `prefix`.
+    pub fn is_below(self, code: &str, prefix: &str) -> bool {
+        match self {
+            Scheme::Msc => code.starts_with(prefix),
+            Scheme::Acm => {
+                code == prefix
+                    || code
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            }
+        }
+    }
+
+    /// Whether the free-text field `field` mentions a class below `prefix`.
+    pub fn matches(self, field: &str, prefix: &str) -> bool {
+        self.codes(field).iter().any(|c| self.is_below(c, prefix))
+    }
+
+    /// The class directly above `code`, if any.
+    pub fn parent(self, code: &str) -> Option<&str> {
+        match self {
+            Scheme::Msc => match code.len() {
+                5 if code.as_bytes()[2] == b'-' => Some(&code[..2]),
+                5 => Some(&code[..3]),
+                3 => Some(&code[..2]),
+                _ => None,
+            },
+            Scheme::Acm => code.rsplit_once('.').map(|(parent, _)| parent),
+        }
+    }
+
+    /// How the class `code` is usually written.
+    pub fn display(self, code: &str) -> String {
+        match self {
+            Scheme::Msc => match code.len() {
+                2 => format!("{code}-XX"),
+                3 => format!("{code}xx"),
+                _ => code.to_string(),
+            },
+            Scheme::Acm => code.to_string(),
+        }
+    }
+
+    /// The description of a top-level class.
+    pub fn description(self, code: &str) -> Option<&'static str> {
+        let classes = match self {
+            Scheme::Msc => MSC_CLASSES,
+            Scheme::Acm => ACM_CLASSES,
+        };
+        classes
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, description)| *description)
+    }
+
+    /// Counts the articles in each class and in each class above, given the free-text
+    /// fields of the articles.
+    pub fn count<'a>(self, fields: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for field in fields {
+            let mut classes = BTreeSet::new();
+            for code in self.codes(field) {
+                let mut code = code.as_str();
+                classes.insert(code.to_string());
+                while let Some(parent) = self.parent(code) {
+                    classes.insert(parent.to_string());
+                    code = parent;
+                }
+            }
+            for class in classes {
+                *counts.entry(class).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    /// The depth of `code` in the hierarchy, starting at 0 for the top level.
+    pub fn depth(self, code: &str) -> usize {
+        let mut depth = 0;
+        let mut code = code;
+        while let Some(parent) = self.parent(code) {
+            depth += 1;
+            code = parent;
+        }
+        depth
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn msc() {
+        let field = "11R32 (Primary), 11R29; 14G-xx 11-02 (Secondary), see p. 110";
+        assert_eq!(
+            Scheme::Msc.codes(field).into_iter().collect::<Vec<_>>(),
+            vec!["11-02", "11R29", "11R32"]
+        );
+        assert!(Scheme::Msc.matches(field, "11R"));
+        assert!(Scheme::Msc.matches(field, "11"));
+        assert!(!Scheme::Msc.matches(field, "110"));
+        assert!(!Scheme::Msc.matches(field, "14"));
+        assert_eq!(Scheme::Msc.normalize("11Rxx").as_deref(), Some("11R"));
+        assert_eq!(Scheme::Msc.normalize("11-XX").as_deref(), Some("11"));
+        assert_eq!(Scheme::Msc.normalize("2020"), None);
+    }
+
+    #[test]
+    fn acm() {
+        let field = "F.2.2; I.2.7, G.2";
+        assert!(Scheme::Acm.matches(field, "F.2"));
+        assert!(Scheme::Acm.matches(field, "G"));
+        assert!(!Scheme::Acm.matches(field, "F.2.1"));
+        assert!(!Scheme::Acm.matches("F.21", "F.2"));
+        assert!(Scheme::Acm.codes("by A. Turing").is_empty());
+    }
+
+    #[test]
+    fn count() {
+        let counts = Scheme::Msc.count(["11R32, 11R29", "11-02"]);
+        assert_eq!(counts["11"], 2);
+        assert_eq!(counts["11R"], 1);
+        assert_eq!(counts["11R32"], 1);
+    }
+}
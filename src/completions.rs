@@ -0,0 +1,49 @@
+//! Shell code appended to the completion scripts generated by clap_complete.
+//!
+//! The generated scripts only know about the static structure of the command line.
+//! The code below additionally completes arXiv identifiers and tag names by calling
+//! the hidden `arxiv-reader complete-values` subcommand.
+
+use clap_complete::Shell;
+
+const BASH: &str = r#"
+_arxiv_reader_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local kind=""
+    if [[ "$prev" == "--id" ]]; then
+        kind=ids
+    elif [[ ( "${COMP_WORDS[1]}" == "figures" || "${COMP_WORDS[1]}" == "attach" ) && $COMP_CWORD -eq 2 ]]; then
+        kind=ids
+    elif [[ "${COMP_WORDS[1]}" == "unsee" && "$cur" != -* && "$prev" != "--version" ]]; then
+        kind=ids
+    elif [[ "${COMP_WORDS[1]}" == "bibtex" && "${COMP_WORDS[2]}" == "bookmark" && $COMP_CWORD -eq 4 ]]; then
+        kind=tags
+    fi
+    if [[ -n "$kind" ]]; then
+        COMPREPLY=( $(compgen -W "$(arxiv-reader complete-values "$kind" "$cur" 2>/dev/null)" -- "$cur") )
+        return 0
+    fi
+    _arxiv-reader "$@"
+}
+
+complete -F _arxiv_reader_dynamic -o bashdefault -o default arxiv-reader
+"#;
+
+const FISH: &str = r#"
+complete -c arxiv-reader -n "__fish_arxiv_reader_using_subcommand find" -l id -f -a '(arxiv-reader complete-values ids (commandline -ct) 2>/dev/null)'
+complete -c arxiv-reader -n "__fish_arxiv_reader_using_subcommand prefetch" -l id -f -a '(arxiv-reader complete-values ids (commandline -ct) 2>/dev/null)'
+complete -c arxiv-reader -n "__fish_arxiv_reader_using_subcommand figures" -f -a '(arxiv-reader complete-values ids (commandline -ct) 2>/dev/null)'
+complete -c arxiv-reader -n "__fish_arxiv_reader_using_subcommand attach" -a '(arxiv-reader complete-values ids (commandline -ct) 2>/dev/null)'
+complete -c arxiv-reader -n "__fish_arxiv_reader_using_subcommand unsee" -f -a '(arxiv-reader complete-values ids (commandline -ct) 2>/dev/null)'
+complete -c arxiv-reader -n "__fish_arxiv_reader_using_subcommand bibtex; and __fish_seen_subcommand_from bookmark" -a '(arxiv-reader complete-values tags 2>/dev/null)'
+"#;
+
+/// Returns the code to append to the generated completion script for the given shell.
+pub fn dynamic(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH,
+        Shell::Fish => FISH,
+        _ => "",
+    }
+}
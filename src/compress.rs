@@ -0,0 +1,153 @@
+//! Optional gzip or zstd compression of database dumps. Like the extraction of sources,
+//! this runs the gzip and zstd programs.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write, stdin, stdout},
+    path::Path,
+    process::{Child, Command, Stdio},
+};
+
+use anyhow::{Context, bail};
+use clap::ValueEnum;
+
+use crate::util::write_then_rename;
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression suggested by the extension of the file name (.gz or .zst).
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The compression of data starting with these bytes.
+    fn detect(start: &[u8]) -> Option<Self> {
+        if start.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    fn command(self, decompress: bool) -> Command {
+        let mut command = Command::new(self.program());
+        command.arg(if decompress { "-dc" } else { "-c" });
+        if self == Compression::Zstd {
+            command.arg("-q");
+        }
+        command
+    }
+}
+
+fn wait(mut child: Child, program: &str) -> anyhow::Result<()> {
+    let status = child.wait().with_context(|| format!("running {program}"))?;
+    if !status.success() {
+        bail!("{program} failed ({status})");
+    }
+    Ok(())
+}
+
+/// Lets `f` write to the compression program, whose output goes to `output`.
+fn write_compressed(
+    output: Stdio,
+    compression: Compression,
+    f: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let program = compression.program();
+    let mut child = compression
+        .command(false)
+        .stdin(Stdio::piped())
+        .stdout(output)
+        .spawn()
+        .with_context(|| format!("running {program}"))?;
+    let mut input = BufWriter::new(child.stdin.take().unwrap());
+    let res = f(&mut input);
+    let flushed = input.flush();
+    drop(input);
+    let waited = wait(child, program);
+    res?;
+    flushed.with_context(|| format!("writing to {program}"))?;
+    waited
+}
+
+/// Lets `f` write to the file (or to stdout). The output is compressed as given or, if no
+/// compression is given, as suggested by the extension of the file.
+pub fn write(
+    file: Option<&Path>,
+    compression: Option<Compression>,
+    f: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    match file {
+        Some(file) => write_then_rename(file.to_path_buf(), |writer| {
+            match compression.or(Compression::from_extension(file)) {
+                Some(compression) => {
+                    writer.flush()?;
+                    let output = writer.get_ref().try_clone()?;
+                    write_compressed(output.into(), compression, f)
+                }
+                None => f(writer),
+            }
+        })
+        .with_context(|| format!("writing {file:?}")),
+        None => match compression {
+            Some(compression) => write_compressed(Stdio::inherit(), compression, f),
+            None => {
+                let mut writer = BufWriter::new(stdout().lock());
+                f(&mut writer)?;
+                writer.flush()?;
+                Ok(())
+            }
+        },
+    }
+}
+
+/// Lets `f` read from the file (or from stdin), decompressing it if it is compressed.
+pub fn read<T>(
+    file: Option<&Path>,
+    f: impl FnOnce(&mut dyn BufRead) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let input: Box<dyn Read + Send> = match file {
+        Some(file) => Box::new(File::open(file).with_context(|| format!("reading {file:?}"))?),
+        None => Box::new(stdin()),
+    };
+    let mut input = BufReader::new(input);
+    let start = input.fill_buf().context("reading the input")?;
+    let Some(compression) = Compression::detect(start) else {
+        return f(&mut input);
+    };
+    let program = compression.program();
+    let mut child = compression
+        .command(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running {program}"))?;
+    // Feed the input (including what was already read) to the program in the background.
+    let mut child_stdin = child.stdin.take().unwrap();
+    let feeder = std::thread::spawn(move || std::io::copy(&mut input, &mut child_stdin));
+    let res = f(&mut BufReader::new(child.stdout.take().unwrap()));
+    let waited = wait(child, program);
+    let fed = feeder.join().unwrap();
+    let res = res?;
+    waited?;
+    fed.with_context(|| format!("writing to {program}"))?;
+    Ok(res)
+}
@@ -1,9 +1,10 @@
-use std::{collections::HashSet, fmt::Display, str::FromStr};
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf, str::FromStr};
 
 use anyhow::bail;
+use regex::{Regex, RegexBuilder};
 use serde::Deserialize;
 
-use crate::filter::Filter;
+use crate::{filter::Filter, oai::Harvest};
 
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct TagName(pub String);
@@ -43,15 +44,67 @@ impl Display for TagName {
 pub struct Config {
     /// Which categories to subscribe to. See https://arxiv.org/category_taxonomy for a list of all categories.
     pub categories: Vec<String>,
+    /// OAI sets to subscribe to in addition to the categories.
+    #[serde(default)]
+    pub subscriptions: Vec<Subscription>,
+    /// Searches of the arXiv API (such as "au:Tao_T") whose results `arxiv-reader pull`
+    /// also downloads, see `arxiv_api`.
+    #[serde(default)]
+    pub searches: Vec<String>,
+    /// How `arxiv-reader pull` downloads the changed records of the categories and
+    /// subscriptions.
+    #[serde(default)]
+    pub harvest: Harvest,
     #[serde(default)]
     pub latex_to_unicode: bool,
+    /// Whether `arxiv-reader pull` should index the text of downloaded pdfs (requires pdftotext).
+    #[serde(default)]
+    pub index_fulltext: bool,
+    /// Whether to build the pdf from the sources with latexmk if arXiv has no pdf.
+    #[serde(default)]
+    pub build_pdf_from_source: bool,
+    /// Whether `arxiv-reader pull` should look up bookmarked articles without journal
+    /// reference on Crossref.
+    #[serde(default)]
+    pub crossref: bool,
     #[serde(default)]
     pub tags: Vec<(char, TagName)>,
+    /// Shell commands that the interactive viewer runs on the current article when the
+    /// given key is pressed.
+    #[serde(default)]
+    pub commands: BTreeMap<char, String>,
     pub filters: Filters,
     #[serde(default)]
     pub hooks: Hooks,
     #[serde(default)]
     pub highlight: Highlight,
+    #[serde(default)]
+    pub downloads: Downloads,
+    #[serde(default)]
+    pub authors: AuthorList,
+    #[serde(default)]
+    pub bibtex: Bibtex,
+    #[serde(default)]
+    pub network: Network,
+    #[serde(default)]
+    pub programs: Programs,
+    /// Web services to notify about new articles after pulling.
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// Rules for tagging new articles automatically.
+    #[serde(default)]
+    pub auto_tag: Vec<AutoTag>,
+}
+
+/// One or more OAI sets that are harvested without looking them up as categories, such as
+/// "math" (a whole archive), "physics:hep-th" or "math:math:AG". See
+/// https://oaipmh.arxiv.org/oai?verb=ListSets for a list of all sets.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Subscription {
+    /// The name of the group of its articles in `arxiv-reader news --group`.
+    pub name: String,
+    pub sets: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -61,6 +114,12 @@ pub struct Filters {
     pub new: Filter,
     /// Only show updates (new versions, journals, doi) for articles additionally satisfying these conditions.
     pub update: Filter,
+    /// Named filters for which `arxiv-reader pull` reports the number of new matching articles.
+    #[serde(default)]
+    pub alerts: BTreeMap<String, Filter>,
+    /// Filters that other filters can refer to as `@name`.
+    #[serde(default)]
+    pub named: BTreeMap<String, Filter>,
 }
 
 #[derive(Deserialize, Default)]
@@ -70,19 +129,416 @@ pub struct Hooks {
     pub pre_pull: Option<String>,
     /// Command to run for pushing.
     pub push: Option<String>,
+    /// Run `arxiv-reader sync` instead of the pre_pull and push commands.
+    #[serde(default)]
+    pub git_sync: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    /// The URL to which a JSON body is posted.
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// Which new articles to announce (default: filters.new).
+    pub filter: Option<Filter>,
+}
+
+/// `arxiv-reader pull` tags the new articles matching `filter` with `tag`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutoTag {
+    pub filter: Filter,
+    pub tag: TagName,
+}
+
+/// The shape of the JSON body posted to a webhook.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// The list of articles with their metadata.
+    #[default]
+    Json,
+    /// A message for a Slack incoming webhook.
+    Slack,
+    /// A message with plain text and html versions, as accepted by matrix-hookshot.
+    Matrix,
+}
+
+/// How author lists are shown in the formats with one line per article (`find -s one-line`,
+/// `find -s short`, `share`, webhooks, ...).
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorList {
+    /// Show at most this many authors, followed by "et al.".
+    pub max: Option<usize>,
+    /// Write the family name first, such as "Gauss, Carl Friedrich". The names are then
+    /// separated by semicolons.
+    #[serde(default)]
+    pub family_name_first: bool,
+}
+
+/// How bibtex entries are generated (`bibtex export`, `find -s bibtex`).
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Bibtex {
+    /// The template for citation keys (default: `bibtex::DEFAULT_KEY`).
+    pub key: Option<String>,
+}
+
+/// The programs used to open things and to run hooks and commands, instead of the
+/// defaults of the platform (see `util::open` and `util::shell_command`).
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Programs {
+    /// A program (optionally followed by arguments) to open pdfs with.
+    pub pdf_viewer: Option<String>,
+    /// A program to open arXiv pages with.
+    pub browser: Option<String>,
+    /// A program to open directories with.
+    pub file_manager: Option<String>,
+    /// The shell to run hooks and commands with (as `SHELL -c SCRIPT`).
+    pub shell: Option<String>,
+    /// A program (optionally followed by arguments) that copies its input to the clipboard.
+    pub clipboard: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Network {
+    /// How often to resend a request after a transient failure (default:
+    /// `rate_limited_client::DEFAULT_MAX_RETRIES`).
+    pub max_retries: Option<u32>,
+    /// An email address to add to the User-Agent header of all requests.
+    pub contact: Option<String>,
+    /// The least number of seconds between two requests to the same host (default:
+    /// `rate_limited_client::DEFAULT_MIN_INTERVAL`).
+    pub min_interval_secs: Option<f64>,
+    /// The least number of seconds between two requests to particular hosts, such as
+    /// `{ "export.arxiv.org" = 5 }`.
+    #[serde(default)]
+    pub host_min_interval_secs: BTreeMap<String, f64>,
+    /// Wait up to this many seconds longer between two requests, chosen at random.
+    pub jitter_secs: Option<f64>,
+    /// How many concurrent downloads may start at once after a pause (default: 1).
+    pub burst: Option<u32>,
+    /// How many pdfs and sources `arxiv-reader pull` downloads at the same time (default:
+    /// `rate_limited_client::DEFAULT_CONCURRENCY`).
+    pub concurrency: Option<usize>,
+    /// The number of seconds after which a request that cannot connect or receives no data
+    /// is aborted and retried.
+    pub timeout_secs: Option<f64>,
 }
 
 #[derive(Deserialize, Default)]
 #[serde(deny_unknown_fields)]
+pub struct Downloads {
+    /// Delete downloaded files of non-bookmarked articles that have not been used for this
+    /// many days (see `GcPolicy::keep_days`).
+    pub keep_days: Option<u64>,
+    /// Where to store downloaded files, relative to the base directory (default: articles).
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Ask before downloading a pdf or source that is larger than this many megabytes. Where
+    /// there is nobody to ask (in the interactive view, or if stdin is not a terminal), such
+    /// files are skipped.
+    pub confirm_above_mb: Option<u64>,
+    /// The most space in megabytes that all downloaded files together may take.
+    pub quota_mb: Option<u64>,
+    #[serde(default)]
+    pub quota_policy: QuotaPolicy,
+    /// What `arxiv-reader gc` deletes if no options are given.
+    #[serde(default)]
+    pub gc: GcPolicy,
+}
+
+/// Which downloaded files `arxiv-reader gc` deletes.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct GcPolicy {
+    /// The pdfs and sources of versions other than the last one.
+    pub old_versions: bool,
+    /// All downloaded files of articles that are not bookmarked.
+    pub non_bookmarked: bool,
+    /// All downloaded files that have been neither downloaded nor opened for this many days.
+    pub unused_days: Option<u64>,
+    /// Never delete the files of bookmarked articles, even if selected by the other options.
+    pub keep_bookmarked: bool,
+}
+
+impl GcPolicy {
+    /// What `downloads.keep_days` deletes: the files of articles that are not bookmarked
+    /// and have been neither downloaded nor opened for this many days.
+    pub fn keep_days(days: u64) -> Self {
+        Self {
+            old_versions: false,
+            non_bookmarked: false,
+            unused_days: Some(days),
+            keep_bookmarked: true,
+        }
+    }
+}
+
+impl Default for GcPolicy {
+    fn default() -> Self {
+        Self {
+            old_versions: true,
+            non_bookmarked: false,
+            unused_days: None,
+            keep_bookmarked: false,
+        }
+    }
+}
+
+/// What to do if a download would exceed the quota.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPolicy {
+    /// Do not download the file.
+    #[default]
+    Refuse,
+    /// Delete the least recently used files of articles that are not bookmarked first.
+    Evict,
+}
+
+/// How downloaded files are distributed into subdirectories of the download directory.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// One directory per article.
+    #[default]
+    Flat,
+    /// One directory per article inside a directory per year, such as 2023/2301.01234.
+    Year,
+    /// One directory per article inside one of 256 directories chosen by a hash of the id.
+    Hash,
+}
+
+/// What `arxiv-reader news` and `find -s full` highlight. Keywords are matched in the
+/// title, comments and abstract ignoring case.
+#[derive(Deserialize, Default)]
+#[serde(try_from = "HighlightConfig")]
 pub struct Highlight {
+    pub keywords: HighlightRules,
+    pub authors: HighlightRules,
+    /// Categories are only highlighted if a rule matches all of the category.
+    pub categories: HighlightRules,
+    pub acm_classes: HighlightRules,
+    pub msc_classes: HighlightRules,
+}
+
+/// The [highlight] section as written in the config file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HighlightConfig {
     #[serde(default)]
-    pub keywords: Vec<String>,
+    keywords: Vec<HighlightRule>,
     #[serde(default)]
-    pub authors: Vec<String>,
+    authors: Vec<HighlightRule>,
     #[serde(default)]
-    pub categories: HashSet<String>,
+    categories: Vec<HighlightRule>,
     #[serde(default)]
-    pub acm_classes: Vec<String>,
+    acm_classes: Vec<HighlightRule>,
     #[serde(default)]
-    pub msc_classes: Vec<String>,
+    msc_classes: Vec<HighlightRule>,
+}
+
+impl TryFrom<HighlightConfig> for Highlight {
+    type Error = regex::Error;
+
+    fn try_from(config: HighlightConfig) -> Result<Self, Self::Error> {
+        Ok(Highlight {
+            keywords: HighlightRules::new(config.keywords, true)?,
+            authors: HighlightRules::new(config.authors, false)?,
+            categories: HighlightRules::new(config.categories, false)?,
+            acm_classes: HighlightRules::new(config.acm_classes, false)?,
+            msc_classes: HighlightRules::new(config.msc_classes, false)?,
+        })
+    }
+}
+
+/// A list of highlight rules with their regular expressions, compiled once.
+#[derive(Default)]
+pub struct HighlightRules {
+    pub rules: Vec<HighlightRule>,
+    /// All rules as alternatives, or `None` if there are no rules.
+    any: Option<Regex>,
+    /// The capture group of each rule in `any`.
+    groups: Vec<usize>,
+    /// Each rule anchored at both ends.
+    whole: Vec<Regex>,
+}
+
+impl HighlightRules {
+    pub fn new(rules: Vec<HighlightRule>, case_insensitive: bool) -> Result<Self, regex::Error> {
+        if rules.is_empty() {
+            return Ok(Self::default());
+        }
+        let pattern = rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| format!("(?P<rule{i}>{})", rule.regex()))
+            .collect::<Vec<_>>()
+            .join("|");
+        let any = RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()?;
+        let names: Vec<Option<&str>> = any.capture_names().collect();
+        let groups = (0..rules.len())
+            .map(|i| {
+                let name = format!("rule{i}");
+                names
+                    .iter()
+                    .position(|n| *n == Some(name.as_str()))
+                    .unwrap()
+            })
+            .collect();
+        let whole = rules
+            .iter()
+            .map(|rule| {
+                RegexBuilder::new(&format!("^(?:{})$", rule.regex()))
+                    .case_insensitive(case_insensitive)
+                    .build()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(HighlightRules {
+            rules,
+            any: Some(any),
+            groups,
+            whole,
+        })
+    }
+
+    /// The regular expression with all rules as alternatives, and the capture group of
+    /// each rule in it.
+    pub fn any(&self) -> Option<(&Regex, &[usize])> {
+        self.any.as_ref().map(|any| (any, self.groups.as_slice()))
+    }
+
+    /// The first rule matching all of `text`.
+    pub fn matching_whole(&self, text: &str) -> Option<&HighlightRule> {
+        self.whole
+            .iter()
+            .position(|re| re.is_match(text))
+            .map(|i| &self.rules[i])
+    }
+}
+
+/// Something to highlight. In the config file, either a string, which is matched
+/// literally, or a table such as `{ regex = "p-?adic", whole_word = true, style = "green" }`
+/// (with `text` instead of `regex` for a literal string).
+#[derive(Clone)]
+pub struct HighlightRule {
+    /// The literal text or the regular expression.
+    pub pattern: String,
+    pub is_regex: bool,
+    /// Only match at word boundaries, so that "adic" does not match in "p-adic".
+    pub whole_word: bool,
+    pub style: HighlightStyle,
+}
+
+impl HighlightRule {
+    /// The regular expression matching what this rule highlights.
+    pub fn regex(&self) -> String {
+        let pattern = if self.is_regex {
+            format!("(?:{})", self.pattern)
+        } else {
+            regex::escape(&self.pattern)
+        };
+        if self.whole_word {
+            format!(r"\b{pattern}\b")
+        } else {
+            pattern
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HighlightRule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Table {
+            text: Option<String>,
+            regex: Option<String>,
+            #[serde(default)]
+            whole_word: bool,
+            #[serde(default)]
+            style: HighlightStyle,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Spec {
+            Text(String),
+            Table(Table),
+        }
+
+        let rule = match Spec::deserialize(deserializer)? {
+            Spec::Text(text) => HighlightRule {
+                pattern: text,
+                is_regex: false,
+                whole_word: false,
+                style: HighlightStyle::default(),
+            },
+            Spec::Table(table) => {
+                let (pattern, is_regex) = match (table.text, table.regex) {
+                    (Some(text), None) => (text, false),
+                    (None, Some(regex)) => (regex, true),
+                    _ => {
+                        return Err(serde::de::Error::custom(
+                            "a highlight rule needs exactly one of text and regex",
+                        ));
+                    }
+                };
+                HighlightRule {
+                    pattern,
+                    is_regex,
+                    whole_word: table.whole_word,
+                    style: table.style,
+                }
+            }
+        };
+        Regex::new(&rule.regex()).map_err(serde::de::Error::custom)?;
+        Ok(rule)
+    }
+}
+
+/// How a highlight rule marks its matches.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightStyle {
+    #[default]
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Bold,
+    Underline,
+}
+
+impl HighlightStyle {
+    /// The escape sequences starting and ending the style.
+    pub fn escapes(self) -> (&'static str, &'static str) {
+        use termion::color;
+        let reset = color::Reset.fg_str();
+        match self {
+            HighlightStyle::Red => (color::LightRed.fg_str(), reset),
+            HighlightStyle::Green => (color::LightGreen.fg_str(), reset),
+            HighlightStyle::Yellow => (color::LightYellow.fg_str(), reset),
+            HighlightStyle::Blue => (color::LightBlue.fg_str(), reset),
+            HighlightStyle::Magenta => (color::LightMagenta.fg_str(), reset),
+            HighlightStyle::Cyan => (color::LightCyan.fg_str(), reset),
+            // 22 is normal intensity. 21 (termion's NoBold) is a double underline in many
+            // terminals.
+            HighlightStyle::Bold => ("\x1b[1m", "\x1b[22m"),
+            HighlightStyle::Underline => ("\x1b[4m", "\x1b[24m"),
+        }
+    }
 }
@@ -0,0 +1,313 @@
+//! Journal metadata from the Crossref REST API (see https://api.crossref.org), for
+//! articles whose authors did not add a journal reference on arXiv after publication.
+//!
+//! An article is looked up by its DOI if arXiv has one, and otherwise by its title and
+//! first author. Title matches are only accepted if the titles agree up to case,
+//! punctuation and markup. The results (including the articles for which nothing was
+//! found) are saved in the crossref table, so that an article is only looked up again
+//! after [`RECHECK_DAYS`] days.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use rusqlite::{Transaction, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    article::{Article, ArxivId},
+    authors::{Name, split_authors},
+    message,
+    progress::{self, Progress},
+    rate_limited_client::Client,
+};
+
+/// How many days to wait before looking up an article again for which nothing was found.
+pub const RECHECK_DAYS: i64 = 30;
+
+/// The number of results requested per search by title.
+const ROWS: usize = 5;
+
+/// A published version of an article according to Crossref.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Publication {
+    pub doi: String,
+    pub journal: String,
+    pub volume: Option<String>,
+    pub issue: Option<String>,
+    pub page: Option<String>,
+    pub year: Option<i32>,
+}
+
+impl Publication {
+    /// A journal reference in the style of arXiv's, such as "Ann. of Math. 12 (1911),
+    /// no. 3, 45-67".
+    pub fn citation(&self) -> String {
+        let mut res = self.journal.clone();
+        if let Some(volume) = &self.volume {
+            res += &format!(" {volume}");
+        }
+        if let Some(year) = self.year {
+            res += &format!(" ({year})");
+        }
+        if let Some(issue) = &self.issue {
+            res += &format!(", no. {issue}");
+        }
+        if let Some(page) = &self.page {
+            res += &format!(", {page}");
+        }
+        res
+    }
+}
+
+/// Compares titles up to case, punctuation, whitespace and markup (such as `<i>` in
+/// Crossref titles or `$` in arXiv titles).
+fn normalize_title(title: &str) -> String {
+    let mut res = String::new();
+    let mut in_tag = false;
+    for c in title.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag && c.is_alphanumeric() => res.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+    res
+}
+
+/// Asks Crossref for a published version of the article.
+pub fn lookup(client: &mut Client, article: &Article) -> anyhow::Result<Option<Publication>> {
+    progress::status(&format!("Looking up {} on Crossref...", article.id()));
+    let items = if let Some(doi) = article.doi() {
+        let work: Work = request(client, work_url(doi)?.as_str())?;
+        vec![work.message]
+    } else {
+        let author = split_authors(article.authors())
+            .first()
+            .and_then(|name| Name::normalize(name))
+            .map(|name| name.family)
+            .unwrap_or_default();
+        let url = reqwest::Url::parse_with_params(
+            "https://api.crossref.org/works",
+            &[
+                ("query.bibliographic", article.title().as_str()),
+                ("query.author", &author),
+                ("rows", &ROWS.to_string()),
+            ],
+        )?;
+        let works: Works = request(client, url.as_str())?;
+        let title = normalize_title(article.title());
+        works
+            .message
+            .items
+            .into_iter()
+            .filter(|item| item.title.iter().any(|t| normalize_title(t) == title))
+            .collect()
+    };
+    Ok(items.into_iter().find_map(Item::publication))
+}
+
+/// The URL of the work with the given DOI. DOIs may contain any printable characters
+/// (such as `/`, `#` or `?`), so the DOI is percent-encoded as a single path segment.
+fn work_url(doi: &str) -> anyhow::Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse("https://api.crossref.org/works")?;
+    url.path_segments_mut()
+        .map_err(|()| anyhow::anyhow!("invalid Crossref URL"))?
+        .push(doi);
+    Ok(url)
+}
+
+fn request<T: for<'de> Deserialize<'de>>(client: &mut Client, url: &str) -> anyhow::Result<T> {
+    let res = client
+        .get(url)
+        .context("requesting data from api.crossref.org")?;
+    serde_json::from_slice(&res.body).context("parsing response from api.crossref.org")
+}
+
+/// Saves the result of a lookup.
+pub fn save(
+    tr: &Transaction,
+    id: &ArxivId,
+    publication: Option<&Publication>,
+    checked: NaiveDate,
+) -> anyhow::Result<()> {
+    tr.execute(
+        "INSERT OR REPLACE INTO crossref (id, checked, doi, journal, volume, issue, page, year) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            id.to_string(),
+            checked.to_string(),
+            publication.map(|p| &p.doi),
+            publication.map(|p| &p.journal),
+            publication.and_then(|p| p.volume.as_ref()),
+            publication.and_then(|p| p.issue.as_ref()),
+            publication.and_then(|p| p.page.as_ref()),
+            publication.and_then(|p| p.year),
+        ],
+    )?;
+    Ok(())
+}
+
+/// The publications found by previous lookups.
+pub fn load(tr: &Transaction) -> anyhow::Result<HashMap<ArxivId, Publication>> {
+    let mut get = tr.prepare_cached(
+        "SELECT id, doi, journal, volume, issue, page, year FROM crossref WHERE doi IS NOT NULL",
+    )?;
+    let mut rows = get.query(())?;
+    let mut res = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        res.insert(
+            id.parse()?,
+            Publication {
+                doi: row.get(1)?,
+                journal: row.get(2)?,
+                volume: row.get(3)?,
+                issue: row.get(4)?,
+                page: row.get(5)?,
+                year: row.get(6)?,
+            },
+        );
+    }
+    Ok(res)
+}
+
+/// The dates of the previous lookups.
+fn checked(tr: &Transaction) -> anyhow::Result<HashMap<String, NaiveDate>> {
+    let mut get = tr.prepare_cached("SELECT id, checked FROM crossref")?;
+    let mut rows = get.query(())?;
+    let mut res = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let checked: String = row.get(1)?;
+        res.insert(
+            row.get(0)?,
+            checked.parse().context("parsing crossref table")?,
+        );
+    }
+    Ok(res)
+}
+
+/// Looks up the given articles that have no journal reference, except those looked up in
+/// the last [`RECHECK_DAYS`] days (unless `recheck` is true). Failed lookups are reported
+/// and skipped. Returns the number of articles for which a publication was found.
+pub fn update(
+    tr: &Transaction,
+    client: &mut Client,
+    articles: &[&Article],
+    recheck: bool,
+) -> anyhow::Result<usize> {
+    let today = chrono::Local::now().date_naive();
+    let checked = checked(tr)?;
+    let due: Vec<&&Article> = articles
+        .iter()
+        .filter(|a| a.journal_ref().is_none())
+        .filter(|a| {
+            recheck
+                || checked
+                    .get(&a.id().to_string())
+                    .is_none_or(|date| (today - *date).num_days() >= RECHECK_DAYS)
+        })
+        .collect();
+    let progress = Progress::new("Looking up articles on Crossref:", Some(due.len() as u64));
+    let mut found = 0;
+    for article in due {
+        match lookup(client, article) {
+            Ok(publication) => {
+                if publication.is_some() {
+                    found += 1;
+                }
+                save(tr, article.id(), publication.as_ref(), today)?;
+            }
+            Err(err) => message!("Could not look up {} on Crossref: {err:#}", article.id()),
+        }
+        progress.inc(1);
+    }
+    Ok(found)
+}
+
+// The parts of the responses of the Crossref API that we need.
+
+#[derive(Deserialize)]
+struct Work {
+    message: Item,
+}
+
+#[derive(Deserialize)]
+struct Works {
+    message: Items,
+}
+
+#[derive(Deserialize)]
+struct Items {
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    #[serde(rename = "DOI")]
+    doi: String,
+    #[serde(rename = "type")]
+    type_: String,
+    #[serde(default)]
+    title: Vec<String>,
+    #[serde(default, rename = "container-title")]
+    container_title: Vec<String>,
+    volume: Option<String>,
+    issue: Option<String>,
+    page: Option<String>,
+    issued: Option<Issued>,
+}
+
+#[derive(Deserialize)]
+struct Issued {
+    #[serde(rename = "date-parts", default)]
+    date_parts: Vec<Vec<Option<i32>>>,
+}
+
+impl Item {
+    /// The publication, if this is a journal article or a paper in proceedings (and not,
+    /// for example, the arXiv preprint itself).
+    fn publication(self) -> Option<Publication> {
+        if !["journal-article", "proceedings-article"].contains(&self.type_.as_str()) {
+            return None;
+        }
+        let journal = self.container_title.into_iter().next()?;
+        let year = self
+            .issued
+            .and_then(|issued| issued.date_parts.first()?.first().copied().flatten());
+        Some(Publication {
+            doi: self.doi,
+            journal,
+            volume: self.volume,
+            issue: self.issue,
+            page: self.page,
+            year,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn titles() {
+        assert_eq!(
+            normalize_title("On the <i>p</i>-adic  Langlands\nprogram"),
+            normalize_title("On the $p$-adic Langlands program")
+        );
+        assert_ne!(
+            normalize_title("On primes"),
+            normalize_title("On twin primes")
+        );
+    }
+
+    #[test]
+    fn doi_urls() {
+        assert_eq!(
+            work_url("10.1002/(SICI)1097-4571#x?y").unwrap().as_str(),
+            "https://api.crossref.org/works/10.1002%2F(SICI)1097-4571%23x%3Fy"
+        );
+    }
+}
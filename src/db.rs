@@ -1,20 +1,32 @@
+//! The sqlite database `db.sqlite` in the arxiv-reader directory, which holds the
+//! article metadata and caches derived from it.
+//!
+//! Open it with [`open`] and access it in transactions with [`with_transaction`], which
+//! also upgrades the schema of databases written by older versions.
+
 use std::{
     collections::HashMap,
     fs::{read_link, remove_dir, remove_file},
-    io::{Write, stdin, stdout},
+    io::{BufRead, Write},
     path::Path,
 };
 
 use anyhow::{Context, bail};
+use clap::ValueEnum;
 use rusqlite::{Connection, Row, Transaction, params};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    article::{ArticleMetadata, ArxivId},
+    article::{Article, ArticleMetadata, ArxivId},
+    authors::parse_authors,
+    filter::Filter,
+    message,
     oai::Continuation,
+    progress,
     util::write_then_rename,
 };
 
+/// Opens the database in the arxiv-reader directory `base_dir`, which must exist.
 pub fn open(base_dir: &Path) -> anyhow::Result<Connection> {
     let db_path = base_dir.join("db.sqlite");
     if !db_path.exists() {
@@ -23,6 +35,7 @@ pub fn open(base_dir: &Path) -> anyhow::Result<Connection> {
     Connection::open(db_path.clone()).context("could not open sqlite database")
 }
 
+/// Creates the database in the arxiv-reader directory `base_dir`.
 pub fn create(base_dir: &Path) -> anyhow::Result<()> {
     let db_path = base_dir.join("db.sqlite");
     if db_path.exists() {
@@ -83,6 +96,26 @@ pub fn with_write_transaction<T, F: FnOnce(Transaction) -> anyhow::Result<T>>(
     }
 }
 
+/// Replaces the rows of the author table for the article with the given authors field.
+pub fn write_authors(tr: &Transaction, id: &str, authors: &str) -> anyhow::Result<()> {
+    tr.prepare_cached("DELETE FROM author WHERE id = ?1")?
+        .execute(params![id])?;
+    let mut insert = tr.prepare_cached(
+        "INSERT INTO author (id, position, name, normalized, collaboration, family) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for (position, author) in parse_authors(authors).into_iter().enumerate() {
+        insert.execute(params![
+            id,
+            position,
+            author.name,
+            author.normalized,
+            author.collaboration,
+            author.family
+        ])?;
+    }
+    Ok(())
+}
+
 fn get_version(conn: &Connection) -> anyhow::Result<String> {
     conn.query_one("SELECT version FROM db_version", (), |row: &Row| row.get(0))
         .context("reading database version")
@@ -166,6 +199,101 @@ fn upgrade_step<'c>(
             "5"
         }
         "5" => {
+            tr.execute(
+                "CREATE VIRTUAL TABLE fulltext USING fts5(id UNINDEXED, version UNINDEXED, text)",
+                (),
+            )?;
+            "6"
+        }
+        "6" => {
+            // The keywords of each article and its distinct words, whose numbers of articles
+            // are in keyword_frequency.
+            tr.execute(
+                "CREATE TABLE keyword (id TEXT PRIMARY KEY, terms TEXT, words TEXT)",
+                (),
+            )?;
+            tr.execute(
+                "CREATE TABLE keyword_frequency (word TEXT PRIMARY KEY, articles INTEGER NOT NULL)",
+                (),
+            )?;
+            "7"
+        }
+        "7" => {
+            tr.execute(
+                "CREATE TABLE failed_download (id TEXT, kind TEXT, error TEXT, time TEXT, PRIMARY KEY (id, kind))",
+                (),
+            )?;
+            "8"
+        }
+        "8" => {
+            // A full-text index of the metadata keyed by the rowid of the article, so that
+            // an entry can be found without going through the whole index. The text is
+            // taken from the article table instead of storing a copy.
+            tr.execute(
+                "CREATE VIRTUAL TABLE metadata_fts USING fts5(title, abstract, authors, comments, content='article', content_rowid='rowid')",
+                (),
+            )?;
+            tr.execute(
+                "INSERT INTO metadata_fts (metadata_fts) VALUES ('rebuild')",
+                (),
+            )?;
+            "9"
+        }
+        "9" => {
+            // A cache of the tags, notes and attachments in the article directories, used
+            // instead of reading all directories if the setting state_cache is true.
+            tr.execute(
+                "CREATE TABLE setting (key TEXT PRIMARY KEY, value TEXT)",
+                (),
+            )?;
+            tr.execute(
+                "CREATE TABLE state_cache (id TEXT PRIMARY KEY, tags TEXT, notes TEXT, attachments TEXT)",
+                (),
+            )?;
+            "10"
+        }
+        "10" => {
+            // Journal metadata found on Crossref for articles without journal reference.
+            // Articles for which nothing was found have a row whose doi is NULL.
+            tr.execute(
+                "CREATE TABLE crossref (id TEXT PRIMARY KEY, checked TEXT NOT NULL, doi TEXT, journal TEXT, volume TEXT, issue TEXT, page TEXT, year INTEGER)",
+                (),
+            )?;
+            "11"
+        }
+        "11" => {
+            // The authors of each article, parsed from the authors field, for author
+            // conditions in filters, with indexes to find authors by name without going
+            // through the whole table.
+            tr.execute(
+                "CREATE TABLE author (id TEXT, position INTEGER, name TEXT, normalized TEXT, collaboration INTEGER, family TEXT, PRIMARY KEY (id, position))",
+                (),
+            )?;
+            let mut get = tr.prepare("SELECT id, authors FROM article")?;
+            let mut rows = get.query(())?;
+            while let Some(row) = rows.next()? {
+                let id: String = row.get(0)?;
+                let authors: String = row.get(1)?;
+                write_authors(&tr, &id, &authors)?;
+            }
+            tr.execute("CREATE INDEX author_family ON author (family)", ())?;
+            tr.execute("CREATE INDEX author_normalized ON author (normalized)", ())?;
+            // Collaborations are matched by their words, which needs to look at all of them.
+            tr.execute(
+                "CREATE INDEX author_collaboration ON author (normalized) WHERE collaboration",
+                (),
+            )?;
+            "12"
+        }
+        "12" => {
+            // A copy of the seen state of each article in seen-articles.
+            tr.execute(
+                "CREATE TABLE seen_cache (id TEXT PRIMARY KEY, line INTEGER NOT NULL, time TEXT, version INTEGER NOT NULL, journal INTEGER NOT NULL, doi INTEGER NOT NULL)",
+                (),
+            )?;
+            "13"
+        }
+        "13" => {
             return Ok(Some(tr));
         }
         _ => {
@@ -178,55 +306,159 @@ fn upgrade_step<'c>(
     Ok(None)
 }
 
+/// The format of `dump`.
+#[derive(ValueEnum, Clone, Copy)]
+pub enum DumpFormat {
+    /// A single json document.
+    Json,
+    /// One json object per line, so that large dumps can be written and read without
+    /// holding them in memory.
+    JsonLines,
+}
+
 #[derive(Serialize, Deserialize)]
 struct DbDump {
     articles: Vec<ArticleMetadata>,
     last_update: HashMap<String, String>,
 }
 
-pub fn dump(tr: &Transaction) -> anyhow::Result<()> {
-    let articles: Vec<_> = ArticleMetadata::load(tr)?.into_values().collect();
-    let last_update = Continuation::read_all(tr)?;
-    let last_update = last_update
-        .into_iter()
-        .map(|(set, cont)| (set, cont.last_update.unwrap()))
-        .collect();
-    let db = DbDump {
-        articles,
-        last_update,
+/// A line of a dump in the json lines format.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DumpLine {
+    Article(Box<ArticleMetadata>),
+    LastUpdate { set: String, date: String },
+}
+
+/// What `load` reads: a whole dump in the json format or a line of one in the json lines
+/// format.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DumpEntry {
+    Line(DumpLine),
+    Whole(DbDump),
+}
+
+/// Writes the metadata of the articles matching the filter (or of all articles). Only
+/// complete dumps contain the dates of the last updates, since loading them declares the
+/// database up to date.
+pub fn dump(
+    tr: &Transaction,
+    base_dir: &Path,
+    filter: Option<&Filter>,
+    format: DumpFormat,
+    writer: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let last_update: HashMap<String, String> = match filter {
+        Some(_) => HashMap::new(),
+        None => Continuation::read_all(tr)?
+            .into_iter()
+            .map(|(set, cont)| (set, cont.last_update.unwrap()))
+            .collect(),
     };
-    serde_json::to_writer_pretty(stdout(), &db)?;
-    println!();
+    let matching = match filter {
+        Some(filter) => {
+            let filter = filter.prepare(tr)?;
+            let mut articles: Vec<ArticleMetadata> = Article::load_matching(base_dir, tr, &filter)?
+                .into_values()
+                .map(|article| article.metadata)
+                .collect();
+            articles.sort_by_key(|m| m.id.to_string());
+            Some(articles)
+        }
+        None => None,
+    };
+    match format {
+        DumpFormat::Json => {
+            let articles = match matching {
+                Some(articles) => articles,
+                None => ArticleMetadata::load(tr)?.into_values().collect(),
+            };
+            let db = DbDump {
+                articles,
+                last_update,
+            };
+            serde_json::to_writer_pretty(&mut *writer, &db)?;
+            writeln!(writer)?;
+        }
+        DumpFormat::JsonLines => {
+            let mut write_line = |line: &DumpLine| -> anyhow::Result<()> {
+                serde_json::to_writer(&mut *writer, line)?;
+                writeln!(writer)?;
+                Ok(())
+            };
+            for (set, date) in last_update {
+                write_line(&DumpLine::LastUpdate { set, date })?;
+            }
+            match matching {
+                Some(articles) => {
+                    for article in articles {
+                        write_line(&DumpLine::Article(Box::new(article)))?;
+                    }
+                }
+                None => ArticleMetadata::for_each(tr, |article| {
+                    write_line(&DumpLine::Article(Box::new(article)))
+                })?,
+            }
+        }
+    }
     Ok(())
 }
 
-pub fn load(tr: Transaction) -> anyhow::Result<()> {
-    let db: DbDump = serde_json::from_reader(stdin())?;
-    println!("Loading {} articles", db.articles.len());
-    for mut article in db.articles.into_iter() {
-        let id = article.id.clone();
-        if let Some(old_article) = ArticleMetadata::load_one(&tr, &id)? {
-            for (i, old_version) in old_article.versions.into_iter().enumerate() {
-                if let Some(new_version) = article.versions.get_mut(i)
-                    && new_version.first_encounter > old_version.first_encounter
-                {
-                    new_version.first_encounter = old_version.first_encounter;
+/// Loads a dump written by `dump` in either format.
+pub fn load(tr: Transaction, reader: &mut dyn BufRead) -> anyhow::Result<()> {
+    let mut count = 0;
+    let mut last_updates = HashMap::new();
+    for entry in serde_json::Deserializer::from_reader(reader).into_iter::<DumpEntry>() {
+        match entry.context("parsing the dump")? {
+            DumpEntry::Whole(db) => {
+                message!("Loading {} articles", db.articles.len());
+                for article in db.articles {
+                    load_article(&tr, article)?;
                 }
+                last_updates.extend(db.last_update);
+            }
+            DumpEntry::Line(DumpLine::Article(article)) => {
+                load_article(&tr, *article)?;
+                count += 1;
+                if count % 10000 == 0 {
+                    progress::status(&format!("Loaded {count} articles..."));
+                }
+            }
+            DumpEntry::Line(DumpLine::LastUpdate { set, date }) => {
+                last_updates.insert(set, date);
             }
         }
-        article
-            .validate()
-            .with_context(|| format!("invalid metadata of article {id}"))?;
-        article.write(&tr)?;
     }
-    for last_update in db.last_update.values() {
+    if count > 0 {
+        message!("Loaded {count} articles");
+    }
+    for last_update in last_updates.values() {
         // We have updated some articles with this response date.
         // Any later record updates may have been overwritten.
         Continuation::reset_last_update(&tr, last_update)?;
     }
-    for (set, last_update) in &db.last_update {
+    for (set, last_update) in &last_updates {
         Continuation::update_last_update(&tr, set, last_update)?;
     }
     tr.commit()?;
     Ok(())
 }
+
+fn load_article(tr: &Transaction, mut article: ArticleMetadata) -> anyhow::Result<()> {
+    let id = article.id.clone();
+    if let Some(old_article) = ArticleMetadata::load_one(tr, &id)? {
+        for (i, old_version) in old_article.versions.into_iter().enumerate() {
+            if let Some(new_version) = article.versions.get_mut(i)
+                && new_version.first_encounter > old_version.first_encounter
+            {
+                new_version.first_encounter = old_version.first_encounter;
+            }
+        }
+    }
+    article
+        .validate()
+        .with_context(|| format!("invalid metadata of article {id}"))?;
+    article.write(tr)?;
+    Ok(())
+}
@@ -0,0 +1,241 @@
+//! Consistency checks between the database and the files in the arxiv-reader directory.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, remove_dir, remove_file},
+    io::{BufRead, BufReader, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use rusqlite::Transaction;
+use serde::Serialize;
+
+use crate::{
+    article::{Article, ArxivId},
+    downloads::DownloadDir,
+    message, oai,
+    rate_limited_client::Client,
+};
+
+/// Something `check` noticed.
+#[derive(Serialize)]
+pub struct Problem {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub kind: ProblemKind,
+    /// Whether `fix` repaired it.
+    pub fixed: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// An entry of the articles directory whose name is not an arXiv id.
+    UnexpectedEntry,
+    /// The directory of an article that is not in the database. `has_state` says whether it
+    /// contains tags, notes or attachments.
+    OrphanDirectory { id: ArxivId, has_state: bool },
+    /// Lines of seen-articles about an article that is not in the database.
+    UnknownSeen { id: ArxivId, lines: usize },
+    /// A bookmarked article whose pdf has not been downloaded.
+    MissingPdf { id: ArxivId },
+    /// A temporary file left behind by an interrupted write.
+    TemporaryFile,
+}
+
+impl Problem {
+    pub fn describe(&self) -> String {
+        let path = &self.path;
+        match &self.kind {
+            ProblemKind::UnexpectedEntry => format!("{path:?} is not named after an arXiv id."),
+            ProblemKind::OrphanDirectory { id, has_state } => {
+                if *has_state {
+                    format!(
+                        "{path:?} has tags, notes or attachments of {id}, which is not in the database."
+                    )
+                } else {
+                    format!("{path:?} belongs to {id}, which is not in the database.")
+                }
+            }
+            ProblemKind::UnknownSeen { id, lines } => {
+                format!("{path:?} has {lines} lines about {id}, which is not in the database.")
+            }
+            ProblemKind::MissingPdf { id } => {
+                format!("{id} is bookmarked, but its pdf has not been downloaded.")
+            }
+            ProblemKind::TemporaryFile => {
+                format!("{path:?} is a temporary file left behind by an interrupted write.")
+            }
+        }
+    }
+}
+
+/// Cross-checks the database against the articles directory and the seen-articles file.
+pub fn check(
+    base_dir: &Path,
+    tr: &Transaction,
+    download_dir: &DownloadDir,
+) -> anyhow::Result<Vec<Problem>> {
+    let articles = Article::load(base_dir, tr)?;
+    let mut problems = Vec::new();
+    let mut problem = |path: PathBuf, kind: ProblemKind| {
+        problems.push(Problem {
+            path,
+            kind,
+            fixed: false,
+        })
+    };
+
+    // Article directories.
+    let articles_dir = base_dir.join("articles");
+    for dir_entry in std::fs::read_dir(&articles_dir).context("reading articles directory")? {
+        let dir_entry = dir_entry.context("reading articles directory")?;
+        let path = dir_entry.path();
+        match ArxivId::from_os_dir_name(&dir_entry.file_name()) {
+            Some(id) if !articles.contains_key(&id) => {
+                let has_state = ["tags", "notes.txt", "attachments"]
+                    .iter()
+                    .any(|name| path.join(name).exists());
+                problem(path, ProblemKind::OrphanDirectory { id, has_state });
+            }
+            Some(_) => {}
+            // The download directory may share the articles directory and distribute the
+            // downloads into subdirectories.
+            None if download_dir.has_buckets_in(&articles_dir) && path.is_dir() => {}
+            None => problem(path, ProblemKind::UnexpectedEntry),
+        }
+    }
+
+    // The seen-articles log.
+    let seen_file = base_dir.join("seen-articles");
+    match File::open(&seen_file) {
+        Ok(file) => {
+            let mut unknown: HashMap<ArxivId, usize> = HashMap::new();
+            for line in BufReader::new(file).lines() {
+                let line = line.context("reading seen-articles")?;
+                if let Some(Ok(id)) = line.split(' ').next().map(str::parse::<ArxivId>)
+                    && !articles.contains_key(&id)
+                {
+                    *unknown.entry(id).or_default() += 1;
+                }
+            }
+            let mut unknown: Vec<_> = unknown.into_iter().collect();
+            unknown.sort_by_key(|(id, _)| id.to_string());
+            for (id, lines) in unknown {
+                problem(seen_file.clone(), ProblemKind::UnknownSeen { id, lines });
+            }
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => Err(err).context("reading seen-articles")?,
+    }
+
+    // Bookmarked articles without pdf.
+    let mut bookmarked: Vec<&Article> = articles.values().filter(|a| a.is_bookmarked()).collect();
+    bookmarked.sort_by_key(|a| a.id().to_string());
+    for article in bookmarked {
+        let path = article.pdf_path(download_dir);
+        if !path.is_file() {
+            problem(
+                path,
+                ProblemKind::MissingPdf {
+                    id: article.id().clone(),
+                },
+            );
+        }
+    }
+
+    // Temporary files written by `util::write_then_rename`.
+    let mut temporary = Vec::new();
+    find_temporary_files(base_dir, &mut temporary)?;
+    if !download_dir.root().starts_with(base_dir) && download_dir.root().is_dir() {
+        find_temporary_files(download_dir.root(), &mut temporary)?;
+    }
+    temporary.sort();
+    for path in temporary {
+        problem(path, ProblemKind::TemporaryFile);
+    }
+
+    Ok(problems)
+}
+
+/// Whether a file with this name is a temporary file of `util::write_then_rename`. Other
+/// files ending in `~`, such as backups of text editors, are left alone.
+fn is_temporary(name: &str) -> bool {
+    let Some(name) = name.strip_suffix('~') else {
+        return false;
+    };
+    name == "tags"
+        || name == "update.xml"
+        || name.starts_with('v') && (name.ends_with(".pdf") || name.ends_with(".tar.gz"))
+}
+
+/// Collects the temporary files in `dir` and its subdirectories (except .git).
+fn find_temporary_files(dir: &Path, res: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for dir_entry in std::fs::read_dir(dir).with_context(|| format!("reading {dir:?}"))? {
+        let dir_entry = dir_entry.with_context(|| format!("reading {dir:?}"))?;
+        let name = dir_entry.file_name();
+        let file_type = dir_entry
+            .file_type()
+            .with_context(|| format!("reading {dir:?}"))?;
+        if file_type.is_dir() {
+            if name != ".git" {
+                find_temporary_files(&dir_entry.path(), res)?;
+            }
+        } else if is_temporary(&name.to_string_lossy()) {
+            res.push(dir_entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Repairs what can be repaired: downloads the metadata of unknown articles that have
+/// tags, notes or seen-articles lines, removes empty orphan directories and temporary
+/// files, and downloads missing pdfs. Nothing the user wrote is deleted.
+pub fn fix(
+    base_dir: &Path,
+    tr: &Transaction,
+    download_dir: &DownloadDir,
+    client: &mut Client,
+    problems: &mut [Problem],
+) -> anyhow::Result<()> {
+    let mut fetched: HashSet<ArxivId> = HashSet::new();
+    for problem in problems.iter_mut() {
+        match &problem.kind {
+            ProblemKind::UnexpectedEntry => {}
+            ProblemKind::OrphanDirectory {
+                has_state: false, ..
+            } => {
+                // Only empty directories are removed, since any other files were put there
+                // by someone.
+                problem.fixed = remove_dir(&problem.path).is_ok();
+            }
+            ProblemKind::OrphanDirectory { id, .. } | ProblemKind::UnknownSeen { id, .. } => {
+                if fetched.contains(id) {
+                    problem.fixed = true;
+                    continue;
+                }
+                match oai::fetch(tr, client, id) {
+                    Ok(_) => {
+                        fetched.insert(id.clone());
+                        problem.fixed = true;
+                    }
+                    Err(err) => message!("Could not fetch {id}: {err:#}"),
+                }
+            }
+            ProblemKind::MissingPdf { id } => {
+                let article = Article::load_one(base_dir, tr, id)?;
+                match article.download_pdf(download_dir, client) {
+                    Ok(()) => problem.fixed = true,
+                    Err(err) => message!("Could not download the pdf of {id}: {err:#}"),
+                }
+            }
+            ProblemKind::TemporaryFile => {
+                remove_file(&problem.path)
+                    .with_context(|| format!("removing {:?}", problem.path))?;
+                problem.fixed = true;
+            }
+        }
+    }
+    Ok(())
+}
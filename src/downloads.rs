@@ -0,0 +1,733 @@
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, remove_dir_all, remove_file},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use rusqlite::{Transaction, params};
+use serde::Serialize;
+
+use crate::{
+    article::{Article, ArticleMetadata, ArticleState, ArxivId},
+    config::{Downloads, GcPolicy, Layout, QuotaPolicy},
+    progress,
+    rate_limited_client::{Client, ConfirmationNeeded, DownloadDeclined},
+    util::format_size,
+};
+
+/// Where downloaded pdfs and sources are stored.
+pub struct DownloadDir {
+    /// The arxiv-reader directory, which contains the tags of the articles.
+    base_dir: PathBuf,
+    root: PathBuf,
+    layout: Layout,
+    /// The quota in bytes.
+    quota: Option<u64>,
+    quota_policy: QuotaPolicy,
+    /// The downloaded files of all articles, listed by the first call to `make_room`
+    /// and kept up to date by the later ones.
+    files: Mutex<Option<Vec<DownloadedFile>>>,
+}
+
+/// A downloaded file (or directory of extracted figures) counted towards the quota.
+struct DownloadedFile {
+    id: ArxivId,
+    path: PathBuf,
+    len: u64,
+    last_used: Option<SystemTime>,
+}
+
+impl DownloadDir {
+    pub fn new(base_dir: &Path, config: &Downloads) -> Self {
+        Self {
+            base_dir: base_dir.to_path_buf(),
+            root: match &config.dir {
+                Some(dir) => base_dir.join(dir),
+                None => base_dir.join("articles"),
+            },
+            layout: config.layout,
+            quota: config.quota_mb.map(|mb| mb * 1_000_000),
+            quota_policy: config.quota_policy,
+            files: Mutex::new(None),
+        }
+    }
+
+    /// The directory containing the download directories of all articles.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether the download directories are distributed into subdirectories of `dir`.
+    pub fn has_buckets_in(&self, dir: &Path) -> bool {
+        self.root == dir && !matches!(self.layout, Layout::Flat)
+    }
+
+    /// The directory containing the downloaded files for this id.
+    pub fn directory(&self, id: &ArxivId) -> PathBuf {
+        match self.layout {
+            Layout::Flat => self.root.join(id.dir_name()),
+            Layout::Year => self.root.join(year(id)).join(id.dir_name()),
+            Layout::Hash => self
+                .root
+                .join(format!("{:02x}", fnv1a(id.to_string().as_bytes()) as u8))
+                .join(id.dir_name()),
+        }
+    }
+
+    /// The ids of all articles with a download directory and these directories.
+    fn article_dirs(&self) -> anyhow::Result<Vec<(ArxivId, PathBuf)>> {
+        let depth = match self.layout {
+            Layout::Flat => 1,
+            Layout::Year | Layout::Hash => 2,
+        };
+        let mut dirs = vec![self.root.clone()];
+        for _ in 0..depth {
+            let mut subdirs = vec![];
+            for dir in dirs {
+                if !dir.is_dir() {
+                    continue;
+                }
+                for dir_entry in
+                    std::fs::read_dir(&dir).with_context(|| format!("reading {dir:?}"))?
+                {
+                    let dir_entry = dir_entry.with_context(|| format!("reading {dir:?}"))?;
+                    subdirs.push(dir_entry.path());
+                }
+            }
+            dirs = subdirs;
+        }
+        Ok(dirs
+            .into_iter()
+            .filter(|dir| dir.is_dir())
+            .filter_map(|dir| Some((ArxivId::from_os_dir_name(dir.file_name()?)?, dir)))
+            .collect())
+    }
+
+    /// Makes sure that `needed` more bytes can be downloaded to `path` (in the directory of
+    /// the article `id`) without exceeding the quota, deleting the least recently used
+    /// downloaded files of articles that are not bookmarked if the quota policy allows it.
+    pub fn make_room(&self, id: &ArxivId, path: &Path, needed: u64) -> anyhow::Result<()> {
+        let Some(quota) = self.quota else {
+            return Ok(());
+        };
+        let mut files = self.files.lock().unwrap();
+        if files.is_none() {
+            let mut list = Vec::new();
+            for (id, dir) in self.article_dirs()? {
+                for path in downloaded_paths(&dir)? {
+                    list.push(DownloadedFile {
+                        id: id.clone(),
+                        len: size(&path)?,
+                        last_used: last_used(&path)?,
+                        path,
+                    });
+                }
+            }
+            *files = Some(list);
+        }
+        let files = files.as_mut().unwrap();
+        // The file will be replaced.
+        files.retain(|file| file.path != path);
+        let mut used: u64 = files.iter().map(|file| file.len).sum();
+        if used + needed > quota {
+            let refuse = || {
+                anyhow::anyhow!(
+                    "downloading {} would exceed the quota of {} ({} used; see `arxiv-reader du`)",
+                    format_size(needed),
+                    format_size(quota),
+                    format_size(used)
+                )
+            };
+            if matches!(self.quota_policy, QuotaPolicy::Refuse) {
+                return Err(refuse());
+            }
+            let mut bookmarked = HashMap::new();
+            let mut evictable = Vec::new();
+            for (i, file) in files.iter().enumerate() {
+                if file.id == *id {
+                    continue;
+                }
+                if !bookmarked.contains_key(&file.id) {
+                    let tags = ArticleState::get_tags(&self.base_dir, &file.id)?;
+                    bookmarked.insert(file.id.clone(), !tags.is_empty());
+                }
+                if !bookmarked[&file.id] {
+                    evictable.push(i);
+                }
+            }
+            let evictable_len: u64 = evictable.iter().map(|i| files[*i].len).sum();
+            if used - evictable_len + needed > quota {
+                return Err(refuse());
+            }
+            evictable.sort_by_key(|i| files[*i].last_used);
+            let mut evicted = Vec::new();
+            let mut reclaimed = 0;
+            for i in evictable {
+                if used + needed <= quota {
+                    break;
+                }
+                let path = &files[i].path;
+                if path.is_dir() {
+                    remove_dir_all(path).with_context(|| format!("removing {path:?}"))?;
+                } else {
+                    remove_file(path).with_context(|| format!("removing {path:?}"))?;
+                }
+                used -= files[i].len;
+                reclaimed += files[i].len;
+                evicted.push(path.clone());
+            }
+            progress::println(&format!(
+                "Deleted {} old downloads ({}) to stay within the quota.",
+                evicted.len(),
+                format_size(reclaimed)
+            ));
+            files.retain(|file| !evicted.contains(&file.path));
+        }
+        files.push(DownloadedFile {
+            id: id.clone(),
+            path: path.to_path_buf(),
+            len: needed,
+            last_used: Some(SystemTime::now()),
+        });
+        Ok(())
+    }
+
+    /// Deletes the downloaded files selected by the policy (or with `dry_run`, only counts
+    /// them). `last_version` returns the number of the last version of an article, if it is
+    /// in the database. Only the pdfs, sources and extracted figures are deleted; tags,
+    /// notes, attachments and any other files are never touched.
+    pub fn gc(
+        &self,
+        policy: &GcPolicy,
+        last_version: impl Fn(&ArxivId) -> Option<u32>,
+        dry_run: bool,
+    ) -> anyhow::Result<GcSummary> {
+        let cutoff = policy.unused_days.and_then(cutoff);
+        let mut summary = GcSummary::default();
+        for (id, dir) in self.article_dirs()? {
+            let bookmarked = !ArticleState::get_tags(&self.base_dir, &id)?.is_empty();
+            if policy.keep_bookmarked && bookmarked {
+                continue;
+            }
+            let last_version = last_version(&id);
+            for path in downloaded_paths(&dir)? {
+                let old_version = path
+                    .file_name()
+                    .and_then(|name| parse_download_name(name.to_str()?))
+                    .map(|(_, version)| version)
+                    .zip(last_version)
+                    .is_some_and(|(version, last)| version < last);
+                let delete = (policy.non_bookmarked && !bookmarked)
+                    || (policy.old_versions && old_version)
+                    || cutoff.is_some_and(|cutoff| {
+                        last_used(&path).is_ok_and(|t| t.is_some_and(|t| t < cutoff))
+                    });
+                if !delete {
+                    continue;
+                }
+                let len = size(&path)?;
+                if !dry_run {
+                    if path.is_dir() {
+                        remove_dir_all(&path).with_context(|| format!("removing {path:?}"))?;
+                    } else {
+                        remove_file(&path).with_context(|| format!("removing {path:?}"))?;
+                    }
+                }
+                summary.files += 1;
+                summary.bytes += len;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Create the download directory for this id if it doesn't exist.
+    pub fn mkdir(&self, id: &ArxivId) -> anyhow::Result<()> {
+        let path = self.directory(id);
+        if !path.is_dir() {
+            create_dir_all(&path).with_context(|| format!("creating {path:?}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The year of submission encoded in the id, such as "2023" for "2301.01234" or "1999"
+/// for "math/9901001". Returns "unknown" if there is no such year.
+fn year(id: &ArxivId) -> String {
+    let id = id.to_string();
+    let number = id.rsplit('/').next().unwrap();
+    match number.get(0..2).and_then(|yy| yy.parse::<u32>().ok()) {
+        Some(yy) if yy >= 91 => format!("19{yy:02}"),
+        Some(yy) => format!("20{yy:02}"),
+        None => "unknown".to_string(),
+    }
+}
+
+/// The 64-bit FNV-1a hash, which (unlike the hashers in std) is guaranteed to be stable.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Downloads the pdf (if `src` is false) or the sources of an article. If this fails, the
+/// error is recorded in the database so that `retry_failed` can try again later, and
+/// printed. Returns whether the download succeeded.
+pub fn download_or_record(
+    tr: &Transaction,
+    download_dir: &DownloadDir,
+    client: &mut Client,
+    article: &Article,
+    src: bool,
+) -> anyhow::Result<bool> {
+    let res = if src {
+        article.download_src(download_dir, client)
+    } else {
+        article.download_pdf(download_dir, client)
+    };
+    record(tr, article, src, res)
+}
+
+/// Records the result of a download in the failed_download table (and prints the error).
+/// Returns whether the download succeeded.
+fn record(
+    tr: &Transaction,
+    article: &Article,
+    src: bool,
+    res: anyhow::Result<()>,
+) -> anyhow::Result<bool> {
+    let kind = if src { "src" } else { "pdf" };
+    match res {
+        Ok(()) => {
+            tr.prepare_cached("DELETE FROM failed_download WHERE id = ?1 AND kind = ?2")?
+                .execute(params![article.id().to_string(), kind])?;
+            Ok(true)
+        }
+        Err(err) => {
+            progress::println(&format!("{}: {err:#}", article.id()));
+            tr.prepare_cached(
+                "INSERT OR REPLACE INTO failed_download (id, kind, error, time) VALUES (?1, ?2, ?3, ?4)",
+            )?
+            .execute(params![
+                article.id().to_string(),
+                kind,
+                format!("{err:#}"),
+                chrono::Local::now().to_rfc3339()
+            ])?;
+            Ok(false)
+        }
+    }
+}
+
+/// Downloads the given pdfs (if the flag is false) and sources unless they have already
+/// been downloaded, `concurrency` at a time (see `Client::download_all`), recording failures
+/// like `download_or_record`. Files larger than `downloads.confirm_above_mb` are
+/// downloaded afterwards, one at a time, after asking. Returns the number of successful
+/// and failed downloads.
+pub fn download_all(
+    tr: &Transaction,
+    download_dir: &DownloadDir,
+    client: &mut Client,
+    files: &[(&Article, bool)],
+    concurrency: usize,
+) -> anyhow::Result<(usize, usize)> {
+    let mut pending = Vec::new();
+    let mut downloads = Vec::new();
+    for &(article, src) in files {
+        if let Some(download) = article.pending_download(download_dir, src)? {
+            pending.push((article, src));
+            downloads.push(download);
+        }
+    }
+    if pending.is_empty() {
+        return Ok((0, 0));
+    }
+    let progress =
+        progress::Progress::new("Downloading pdfs and sources:", Some(pending.len() as u64));
+    let mut large = Vec::new();
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut bytes = 0;
+    client.download_all(&downloads, concurrency, |i, res| {
+        let (article, src) = pending[i];
+        if res.is_err() {
+            let _ = remove_file(&downloads[i].1);
+        }
+        let description = if src { "sources" } else { "pdf" };
+        let version = article.last_version().number;
+        let res = match res {
+            Err(err) if err.is::<ConfirmationNeeded>() => {
+                large.push((article, src));
+                return Ok(());
+            }
+            Err(err) if err.is::<DownloadDeclined>() => {
+                progress::println(&format!(
+                    "Skipped the {description} for {}v{version}.",
+                    article.id()
+                ));
+                progress.inc(1);
+                return Ok(());
+            }
+            Ok(res) => {
+                bytes += res.len;
+                progress::set_detail(|| format!("{} downloaded", format_size(bytes)));
+                article.save_download(download_dir, src, res)
+            }
+            Err(err) => Err(err.context(format!(
+                "requesting {description} from arXiv for {}v{version}",
+                article.id()
+            ))),
+        };
+        if record(tr, article, src, res)? {
+            succeeded += 1;
+        } else {
+            failed += 1;
+        }
+        progress.inc(1);
+        Ok(())
+    })?;
+    for (article, src) in large {
+        if download_or_record(tr, download_dir, client, article, src)? {
+            succeeded += 1;
+        } else {
+            failed += 1;
+        }
+        progress.inc(1);
+    }
+    drop(progress);
+    Ok((succeeded, failed))
+}
+
+/// Tries again to download the files whose download failed in `download_or_record`.
+/// Returns the number of successful and failed downloads.
+pub fn retry_failed(
+    tr: &Transaction,
+    base_dir: &Path,
+    download_dir: &DownloadDir,
+    client: &mut Client,
+) -> anyhow::Result<(usize, usize)> {
+    let failed: Vec<(String, String)> = tr
+        .prepare("SELECT id, kind FROM failed_download ORDER BY time")?
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    if failed.is_empty() {
+        return Ok((0, 0));
+    }
+    let progress = progress::Progress::new("Retrying failed downloads:", Some(failed.len() as u64));
+    let mut succeeded = 0;
+    let mut still_failed = 0;
+    for (id, kind) in &failed {
+        let parsed: ArxivId = id.parse()?;
+        if ArticleMetadata::load_one(tr, &parsed)?.is_none() {
+            // The article is gone, so there is nothing to retry.
+            tr.execute(
+                "DELETE FROM failed_download WHERE id = ?1 AND kind = ?2",
+                params![id, kind],
+            )?;
+        } else {
+            let article = Article::load_one(base_dir, tr, &parsed)?;
+            if download_or_record(tr, download_dir, client, &article, kind == "src")? {
+                succeeded += 1;
+            } else {
+                still_failed += 1;
+            }
+        }
+        progress.inc(1);
+    }
+    drop(progress);
+    Ok((succeeded, still_failed))
+}
+
+/// The disk space in bytes used by the downloaded files of an article.
+#[derive(Serialize, Default, Clone, Copy)]
+pub struct Usage {
+    pub pdf: u64,
+    pub src: u64,
+    /// Directories extracted from the sources, such as the figures.
+    pub extracted: u64,
+}
+
+impl Usage {
+    pub fn total(&self) -> u64 {
+        self.pdf + self.src + self.extracted
+    }
+
+    pub fn add(&mut self, other: &Usage) {
+        self.pdf += other.pdf;
+        self.src += other.src;
+        self.extracted += other.extracted;
+    }
+}
+
+/// What a file in the download directory of an article contains.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DownloadKind {
+    /// A pdf, such as `v3.pdf`.
+    Pdf,
+    /// The sources, such as `v3.tar.gz`.
+    Src,
+    /// A directory of figures extracted from the sources, such as `v3-figures`.
+    Figures,
+}
+
+/// The kind and version of a downloaded file (or directory of extracted figures) with this
+/// name, such as (`DownloadKind::Src`, 3) for `v3.tar.gz`, or `None` for other files.
+pub fn parse_download_name(name: &str) -> Option<(DownloadKind, u32)> {
+    let rest = name.strip_prefix('v')?;
+    let (number, kind) = if let Some(number) = rest.strip_suffix(".pdf") {
+        (number, DownloadKind::Pdf)
+    } else if let Some(number) = rest.strip_suffix(".tar.gz") {
+        (number, DownloadKind::Src)
+    } else {
+        (rest.strip_suffix("-figures")?, DownloadKind::Figures)
+    };
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((kind, number.parse().ok()?))
+}
+
+/// The downloaded files in the download directory of an article: pdfs, sources and
+/// directories of figures extracted from them (see `parse_download_name`).
+fn downloaded_paths(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut res = Vec::new();
+    for dir_entry in std::fs::read_dir(dir).with_context(|| format!("reading {dir:?}"))? {
+        let dir_entry = dir_entry.with_context(|| format!("reading {dir:?}"))?;
+        let name = dir_entry.file_name();
+        let Some((kind, _)) = name.to_str().and_then(parse_download_name) else {
+            continue;
+        };
+        let is_dir = dir_entry
+            .file_type()
+            .with_context(|| format!("reading {dir:?}"))?
+            .is_dir();
+        if is_dir == (kind == DownloadKind::Figures) {
+            res.push(dir_entry.path());
+        }
+    }
+    Ok(res)
+}
+
+/// The time `days` days ago, or `None` if that is before the earliest representable time.
+fn cutoff(days: u64) -> Option<SystemTime> {
+    let secs = days.checked_mul(24 * 60 * 60)?;
+    SystemTime::now().checked_sub(Duration::from_secs(secs))
+}
+
+/// What `DownloadDir::gc` deleted (or would delete).
+#[derive(Serialize, Default)]
+pub struct GcSummary {
+    /// The number of deleted files and directories extracted from sources.
+    pub files: usize,
+    /// Their total size in bytes.
+    pub bytes: u64,
+}
+
+/// When a file was last downloaded or opened. The access time is not always updated, so
+/// we also look at the modification time.
+fn last_used(path: &Path) -> anyhow::Result<Option<SystemTime>> {
+    let metadata = path
+        .metadata()
+        .with_context(|| format!("reading metadata of {path:?}"))?;
+    Ok([metadata.accessed().ok(), metadata.modified().ok()]
+        .into_iter()
+        .flatten()
+        .max())
+}
+
+/// The size of a file, or of all files in a directory and its subdirectories.
+pub fn size(path: &Path) -> anyhow::Result<u64> {
+    let metadata = path
+        .symlink_metadata()
+        .with_context(|| format!("reading metadata of {path:?}"))?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for dir_entry in std::fs::read_dir(path).with_context(|| format!("reading {path:?}"))? {
+        let dir_entry = dir_entry.with_context(|| format!("reading {path:?}"))?;
+        total += size(&dir_entry.path())?;
+    }
+    Ok(total)
+}
+
+/// Measures the disk space used by the downloaded files of an article. Tags, notes and
+/// attachments are not counted.
+pub fn usage(download_dir: &DownloadDir, article: &Article) -> anyhow::Result<Usage> {
+    let mut usage = Usage::default();
+    for path in article.downloaded_files(download_dir)? {
+        let len = size(&path)?;
+        if path.extension().is_some_and(|e| e == "pdf") {
+            usage.pdf += len;
+        } else {
+            usage.src += len;
+        }
+    }
+    let dir = download_dir.directory(article.id());
+    if dir.is_dir() {
+        for dir_entry in std::fs::read_dir(&dir).with_context(|| format!("reading {dir:?}"))? {
+            let dir_entry = dir_entry.with_context(|| format!("reading {dir:?}"))?;
+            let is_dir = dir_entry
+                .file_type()
+                .with_context(|| format!("reading {dir:?}"))?
+                .is_dir();
+            let is_figures = dir_entry
+                .file_name()
+                .to_str()
+                .and_then(parse_download_name)
+                .is_some_and(|(kind, _)| kind == DownloadKind::Figures);
+            if is_dir && is_figures {
+                usage.extracted += size(&dir_entry.path())?;
+            }
+        }
+    }
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::TempDir;
+
+    #[test]
+    fn layout() {
+        let config = Downloads {
+            keep_days: None,
+            dir: Some(PathBuf::from("/cache")),
+            layout: Layout::Year,
+            ..Downloads::default()
+        };
+        let download_dir = DownloadDir::new(Path::new("/base"), &config);
+        let id: ArxivId = "2301.01234".parse().unwrap();
+        assert_eq!(
+            download_dir.directory(&id),
+            PathBuf::from("/cache/2023/2301.01234")
+        );
+        let id: ArxivId = "math/9901001".parse().unwrap();
+        assert_eq!(
+            download_dir.directory(&id),
+            PathBuf::from("/cache/1999/math_9901001")
+        );
+    }
+
+    /// Writes a file of `len` bytes to `path` in the download directory that was last used
+    /// `age` seconds ago.
+    fn write(base_dir: &Path, path: &str, len: usize, age: u64) {
+        let path = base_dir.join("articles").join(path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, vec![0; len]).unwrap();
+        let time = SystemTime::now() - Duration::from_secs(age);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_times(
+                std::fs::FileTimes::new()
+                    .set_accessed(time)
+                    .set_modified(time),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn download_names() {
+        assert_eq!(parse_download_name("v3.pdf"), Some((DownloadKind::Pdf, 3)));
+        assert_eq!(
+            parse_download_name("v12.tar.gz"),
+            Some((DownloadKind::Src, 12))
+        );
+        assert_eq!(
+            parse_download_name("v2-figures"),
+            Some((DownloadKind::Figures, 2))
+        );
+        assert_eq!(parse_download_name("v.pdf"), None);
+        assert_eq!(parse_download_name("v+1.pdf"), None);
+        assert_eq!(parse_download_name("notes.txt"), None);
+        assert_eq!(parse_download_name("figures"), None);
+    }
+
+    #[test]
+    fn quota() {
+        let tmp = TempDir::new("quota").unwrap();
+        let base_dir = tmp.path();
+        // Files with larger `age` were used longer ago.
+        write(base_dir, "2301.00001/v1.pdf", 400_000, 200);
+        write(base_dir, "2301.00002/v1.pdf", 400_000, 100);
+        write(base_dir, "2301.00003/v1.pdf", 100_000, 300);
+        std::fs::write(base_dir.join("articles/2301.00003/tags"), "read\n").unwrap();
+        // Files that were not downloaded are neither counted nor deleted.
+        write(base_dir, "2301.00001/mine/draft.tex", 900_000, 400);
+        let mut config = Downloads {
+            quota_mb: Some(1),
+            ..Downloads::default()
+        };
+        let id: ArxivId = "2301.00004".parse().unwrap();
+        let path = base_dir.join("articles/2301.00004/v1.pdf");
+        assert!(
+            DownloadDir::new(base_dir, &config)
+                .make_room(&id, &path, 100_000)
+                .is_ok()
+        );
+        assert!(
+            DownloadDir::new(base_dir, &config)
+                .make_room(&id, &path, 200_000)
+                .is_err()
+        );
+        config.quota_policy = QuotaPolicy::Evict;
+        let download_dir = DownloadDir::new(base_dir, &config);
+        assert!(download_dir.make_room(&id, &path, 200_000).is_ok());
+        assert!(!base_dir.join("articles/2301.00001/v1.pdf").exists());
+        assert!(base_dir.join("articles/2301.00001/mine/draft.tex").exists());
+        // The bookmarked article is kept.
+        let path = base_dir.join("articles/2301.00004/v1.tar.gz");
+        assert!(download_dir.make_room(&id, &path, 950_000).is_err());
+        assert!(base_dir.join("articles/2301.00003/v1.pdf").exists());
+    }
+
+    #[test]
+    fn gc() {
+        let tmp = TempDir::new("gc").unwrap();
+        let base_dir = tmp.path();
+        write(base_dir, "2301.00001/v1.pdf", 1000, 0);
+        write(base_dir, "2301.00001/v2.pdf", 2000, 0);
+        write(base_dir, "2301.00001/v2.tar.gz", 3000, 0);
+        write(base_dir, "2301.00001/notes.txt", 10, 0);
+        std::fs::write(base_dir.join("articles/2301.00001/tags"), "read\n").unwrap();
+        write(base_dir, "2301.00002/v1.pdf", 4000, 0);
+        write(base_dir, "2301.00002/v1-figures/a.png", 500, 0);
+        write(base_dir, "2301.00002/mine/draft.tex", 600, 0);
+        let download_dir = DownloadDir::new(base_dir, &Downloads::default());
+        let policy = GcPolicy::default();
+        let summary = download_dir.gc(&policy, |_| Some(2), true).unwrap();
+        assert_eq!((summary.files, summary.bytes), (3, 5500));
+        assert!(base_dir.join("articles/2301.00001/v1.pdf").exists());
+        let summary = download_dir.gc(&policy, |_| Some(2), false).unwrap();
+        assert_eq!((summary.files, summary.bytes), (3, 5500));
+        assert!(!base_dir.join("articles/2301.00001/v1.pdf").exists());
+        assert!(base_dir.join("articles/2301.00001/v2.pdf").exists());
+        assert!(base_dir.join("articles/2301.00001/notes.txt").exists());
+        assert!(!base_dir.join("articles/2301.00002/v1-figures").exists());
+        assert!(base_dir.join("articles/2301.00002/mine/draft.tex").exists());
+        let policy = GcPolicy {
+            non_bookmarked: true,
+            unused_days: Some(u64::MAX),
+            ..GcPolicy::default()
+        };
+        let summary = download_dir.gc(&policy, |_| Some(2), false).unwrap();
+        assert_eq!((summary.files, summary.bytes), (0, 0));
+        assert!(base_dir.join("articles/2301.00002/mine/draft.tex").exists());
+        // keep_days spares the bookmarked article.
+        write(base_dir, "2301.00001/v1.pdf", 1000, 3 * 24 * 60 * 60);
+        write(base_dir, "2301.00002/v1.pdf", 4000, 3 * 24 * 60 * 60);
+        let summary = download_dir
+            .gc(&GcPolicy::keep_days(2), |_| Some(2), false)
+            .unwrap();
+        assert_eq!((summary.files, summary.bytes), (1, 4000));
+        assert!(base_dir.join("articles/2301.00001/v1.pdf").exists());
+    }
+}
@@ -0,0 +1,96 @@
+//! Exit codes that let scripts tell different kinds of failures apart.
+
+use std::fmt::Display;
+
+use rusqlite::ErrorCode;
+
+/// Some error not covered by the other exit codes.
+pub const OTHER: u8 = 1;
+/// Invalid command-line arguments (used by clap).
+pub const USAGE: u8 = 2;
+/// No articles matched the search.
+pub const NO_MATCHES: u8 = 3;
+/// The configuration file is missing or invalid.
+pub const CONFIG: u8 = 4;
+/// A request to arXiv failed.
+pub const NETWORK: u8 = 5;
+/// arXiv asked us to slow down or is temporarily unavailable.
+pub const RATE_LIMITED: u8 = 6;
+/// The database is locked by another process.
+pub const DATABASE_LOCKED: u8 = 7;
+
+/// The exit codes for `--help`.
+pub const EXIT_CODES: &str = "Exit codes:
+  1  other error
+  2  invalid arguments
+  3  no matching articles
+  4  missing or invalid configuration file
+  5  network failure
+  6  arXiv is rate limiting or temporarily unavailable
+  7  database locked by another process";
+
+/// Returned when a search finds nothing.
+#[derive(Debug)]
+pub struct NoMatches;
+
+impl Display for NoMatches {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no matching articles")
+    }
+}
+
+impl std::error::Error for NoMatches {}
+
+/// Context attached to errors while loading the configuration file.
+#[derive(Debug)]
+pub struct ConfigError;
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "could not load the configuration")
+    }
+}
+
+/// Chooses the exit code for an error by looking at its chain of causes.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    if err.downcast_ref::<NoMatches>().is_some() {
+        return NO_MATCHES;
+    }
+    if err.downcast_ref::<ConfigError>().is_some() {
+        return CONFIG;
+    }
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return match err.status().map(|s| s.as_u16()) {
+                Some(429 | 503) => RATE_LIMITED,
+                _ => NETWORK,
+            };
+        }
+        if let Some(err) = cause.downcast_ref::<rusqlite::Error>()
+            && let Some(code) = err.sqlite_error_code()
+            && matches!(code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+        {
+            return DATABASE_LOCKED;
+        }
+    }
+    OTHER
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn codes() {
+        let err = anyhow::Error::from(NoMatches);
+        assert_eq!(exit_code(&err), NO_MATCHES);
+        let err = Err::<(), _>(std::io::Error::other("missing"))
+            .context("reading config.toml")
+            .context(ConfigError)
+            .unwrap_err();
+        assert_eq!(exit_code(&err), CONFIG);
+        assert_eq!(exit_code(&anyhow::anyhow!("something else")), OTHER);
+    }
+}
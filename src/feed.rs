@@ -0,0 +1,159 @@
+//! Atom and RSS feeds of the articles that arrived in the recent runs of
+//! `arxiv-reader pull`, for feed readers.
+
+use anyhow::Context;
+use chrono::{DateTime, FixedOffset};
+use clap::ValueEnum;
+use rusqlite::{Transaction, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    article::{Article, ArxivId},
+    authors::split_authors,
+    share::one_line,
+    util::escape_html,
+};
+
+#[derive(ValueEnum, Copy, Clone)]
+pub enum FeedFormat {
+    Atom,
+    /// RSS 2.0.
+    Rss,
+}
+
+/// How many days of pulls a feed covers, so that feed readers that look at it less often
+/// than `arxiv-reader pull` runs do not miss articles.
+pub const FEED_DAYS: i64 = 7;
+
+/// The new articles of a pull. The pulls of the last `FEED_DAYS` days are saved in the
+/// setting table.
+#[derive(Serialize, Deserialize)]
+pub struct LastPull {
+    pub time: DateTime<FixedOffset>,
+    pub ids: Vec<ArxivId>,
+}
+
+impl LastPull {
+    /// Saves the pull, and forgets the pulls more than `FEED_DAYS` days before it.
+    pub fn save(self, tr: &Transaction) -> anyhow::Result<()> {
+        let cutoff = self.time - chrono::Duration::days(FEED_DAYS);
+        let mut pulls = Self::load_recent(tr)?;
+        pulls.retain(|pull| pull.time > cutoff);
+        pulls.push(self);
+        tr.execute(
+            "INSERT OR REPLACE INTO setting (key, value) VALUES ('recent_pulls', ?1)",
+            params![serde_json::to_string(&pulls)?],
+        )?;
+        // Earlier versions only saved the last pull.
+        tr.execute("DELETE FROM setting WHERE key = 'last_pull'", ())?;
+        Ok(())
+    }
+
+    /// The saved pulls, oldest first.
+    pub fn load_recent(tr: &Transaction) -> anyhow::Result<Vec<LastPull>> {
+        let mut get = tr.prepare_cached(
+            "SELECT key, value FROM setting WHERE key IN ('recent_pulls', 'last_pull')",
+        )?;
+        let mut rows = get.query(())?;
+        let mut pulls = Vec::new();
+        while let Some(row) = rows.next()? {
+            let key: String = row.get(0)?;
+            let value: String = row.get(1)?;
+            if key == "last_pull" {
+                pulls.push(serde_json::from_str(&value).context("parsing the last pull")?);
+            } else {
+                pulls.extend(
+                    serde_json::from_str::<Vec<LastPull>>(&value)
+                        .context("parsing the recent pulls")?,
+                );
+            }
+        }
+        Ok(pulls)
+    }
+}
+
+/// Returns the feed of the given articles, which arrived at `updated`. `convert` is
+/// applied to the titles and authors, for example to convert latex to unicode.
+pub fn write(
+    format: FeedFormat,
+    updated: &DateTime<FixedOffset>,
+    articles: &[&Article],
+    convert: impl Fn(&str) -> String,
+) -> String {
+    let title = "arxiv-reader: new articles";
+    let text = |s: &str| escape_html(&one_line(&convert(s)));
+    let mut res = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".to_string();
+    match format {
+        FeedFormat::Atom => {
+            res += "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n";
+            res += &format!("  <title>{title}</title>\n");
+            res += "  <id>urn:arxiv-reader:news</id>\n";
+            res += &format!("  <updated>{}</updated>\n", updated.to_rfc3339());
+            for article in articles {
+                let url = escape_html(&article.abs_url());
+                res += "  <entry>\n";
+                res += &format!("    <title>{}</title>\n", text(article.title()));
+                res += &format!("    <id>{url}</id>\n");
+                res += &format!("    <link href=\"{url}\"/>\n");
+                res += &format!(
+                    "    <published>{}</published>\n",
+                    article.first_version().date.to_rfc3339()
+                );
+                res += &format!(
+                    "    <updated>{}</updated>\n",
+                    article.last_version().date.to_rfc3339()
+                );
+                for name in split_authors(article.authors()) {
+                    res += &format!("    <author><name>{}</name></author>\n", text(&name));
+                }
+                for category in article.categories() {
+                    res += &format!("    <category term=\"{}\"/>\n", escape_html(category));
+                }
+                res += &format!(
+                    "    <summary>{}</summary>\n",
+                    escape_html(article.abstract_().trim())
+                );
+                res += "  </entry>\n";
+            }
+            res += "</feed>\n";
+        }
+        FeedFormat::Rss => {
+            res += "<rss version=\"2.0\">\n";
+            res += "  <channel>\n";
+            res += &format!("    <title>{title}</title>\n");
+            res += "    <link>https://arxiv.org/</link>\n";
+            res += "    <description>New arXiv articles matching filters.new</description>\n";
+            res += &format!(
+                "    <lastBuildDate>{}</lastBuildDate>\n",
+                updated.to_rfc2822()
+            );
+            for article in articles {
+                let url = escape_html(&article.abs_url());
+                res += "    <item>\n";
+                res += &format!("      <title>{}</title>\n", text(article.title()));
+                res += &format!("      <link>{url}</link>\n");
+                res += &format!("      <guid isPermaLink=\"true\">{url}</guid>\n");
+                res += &format!(
+                    "      <pubDate>{}</pubDate>\n",
+                    article.first_version().date.to_rfc2822()
+                );
+                for category in article.categories() {
+                    res += &format!("      <category>{}</category>\n", escape_html(category));
+                }
+                // RSS only has authors with email addresses, so they go into the description.
+                res += &format!(
+                    "      <description>{}</description>\n",
+                    escape_html(&format!(
+                        "{}\n\n{}",
+                        one_line(&convert(article.authors())),
+                        article.abstract_().trim()
+                    ))
+                );
+                res += "    </item>\n";
+            }
+            res += "  </channel>\n";
+            res += "</rss>\n";
+        }
+    }
+    res
+}
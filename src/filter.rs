@@ -1,10 +1,72 @@
+//! Conditions selecting articles, as written with `--filter` and in the config file.
+//!
+//! Parse a [`Filter`] with `str::parse`, call [`Filter::prepare`] once and then
+//! [`Filter::matches`] for each article.
+
 use anyhow::{Context, anyhow, bail};
-use std::{collections::VecDeque, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    str::FromStr,
+    sync::OnceLock,
+};
 
+use rusqlite::{Transaction, params};
 use serde::Deserialize;
 
-use crate::config::TagName;
+use crate::{
+    article::ArxivId,
+    authors::{AuthorClusters, Name, collation_key, normalize_name, parse_name},
+    classes::Scheme,
+    config::TagName,
+    fulltext,
+};
+
+/// A case-insensitive regular expression of a `*_regex` condition. Filters are compared
+/// by the patterns of their regular expressions.
+#[derive(Clone)]
+pub struct Regex(regex::Regex);
+
+impl Regex {
+    pub fn new(pattern: &str) -> anyhow::Result<Regex> {
+        let re = regex::RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .with_context(|| format!("invalid regex {pattern:?}"))?;
+        Ok(Regex(re))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Whether the pattern matches somewhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Regex {}
 
+impl std::fmt::Debug for Regex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Regex({:?})", self.as_str())
+    }
+}
+
+static NAMED: OnceLock<BTreeMap<String, Filter>> = OnceLock::new();
+
+/// Makes `@name` conditions refer to the named filters from the config file.
+pub fn set_named(named: BTreeMap<String, Filter>) {
+    let _ = NAMED.set(named);
+}
+
+/// A condition on articles, parsed from the syntax described in `arxiv-reader help find`.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Filter {
     PrimaryCategoryIs(String),
@@ -12,42 +74,189 @@ pub enum Filter {
     FirstVersionEncounteredAfter(String),
     FirstVersionSubmittedAfter(String),
     Title(String),
+    TitleRegex(Regex),
     Author(String),
+    AuthorRegex(Regex),
+    /// The ids of the articles matching an `Author` condition, see `Filter::prepare`.
+    AuthorMatches(BTreeSet<String>),
     ACMClass(String),
     MSCClass(String),
     Abstract(String),
+    AbstractRegex(Regex),
     Comments(String),
+    HasJournal,
+    HasDoi,
+    License(String),
+    /// The last version is withdrawn.
+    Withdrawn,
     Bookmarked,
     Seen,
+    /// Seen, but with a newer version, journal reference or DOI that has not been seen.
+    HasUpdate,
     Tag(TagName),
+    /// The article has no tags.
+    Untagged,
+    /// The article has at least the first and at most the second number of tags.
+    TagCount(usize, usize),
+    /// The article has a tag other than these.
+    TagNot(BTreeSet<TagName>),
     Notes(String),
+    HasTodo,
+    Keyword(String),
+    FullText(String),
+    /// The ids of the articles matching a `FullText` or `Fts` condition, see
+    /// `Filter::prepare`.
+    FullTextMatches(BTreeSet<String>),
+    /// A full-text query on the title, abstract, authors and comments.
+    Fts(String),
     Any(String),
     Not(Box<Filter>),
     And(Box<Filter>, Box<Filter>),
     Or(Box<Filter>, Box<Filter>),
     Id(String),
+    /// A reference `@name` to a filter in filters.named, replaced by it in
+    /// `Filter::prepare`.
+    Named(String),
     True,
     False,
 }
 
 impl Filter {
+    /// Evaluates the conditions that need to look at the database instead of the article,
+    /// giving a filter that can be checked against articles.
+    pub fn prepare(&self, tr: &Transaction) -> anyhow::Result<PreparedFilter> {
+        Ok(PreparedFilter(self.prepare_with(tr, &[])?))
+    }
+
+    /// Like `prepare`. `resolving` are the names of the named filters that are being
+    /// prepared, to detect cycles.
+    fn prepare_with(&self, tr: &Transaction, resolving: &[&str]) -> anyhow::Result<Filter> {
+        Ok(match self {
+            Filter::Named(name) => {
+                if resolving.contains(&name.as_str()) {
+                    bail!("the named filter @{name} refers to itself");
+                }
+                let filter = NAMED
+                    .get()
+                    .and_then(|named| named.get(name))
+                    .with_context(|| {
+                        format!("unknown filter @{name} (see filters.named in the config file)")
+                    })?;
+                filter
+                    .prepare_with(tr, &[resolving, &[name.as_str()]].concat())
+                    .with_context(|| format!("in the named filter @{name}"))?
+            }
+            Filter::FullText(word) => Filter::FullTextMatches(fulltext::search(tr, word)?),
+            Filter::Fts(query) => Filter::FullTextMatches(fulltext::search_metadata(tr, query)?),
+            Filter::Author(word) => Filter::AuthorMatches(author_matches(tr, word)?),
+            Filter::Not(a) => Filter::Not(Box::new(a.prepare_with(tr, resolving)?)),
+            Filter::And(a, b) => Filter::And(
+                Box::new(a.prepare_with(tr, resolving)?),
+                Box::new(b.prepare_with(tr, resolving)?),
+            ),
+            Filter::Or(a, b) => Filter::Or(
+                Box::new(a.prepare_with(tr, resolving)?),
+                Box::new(b.prepare_with(tr, resolving)?),
+            ),
+            _ => self.clone(),
+        })
+    }
+
+    /// See `PreparedFilter::sql`. The filter must have been prepared.
+    fn sql(&self) -> Option<(String, Vec<String>)> {
+        let like = |column: &str, word: &str| {
+            let word = word
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            Some((
+                format!("{column} LIKE ? ESCAPE '\\'"),
+                vec![format!("%{word}%")],
+            ))
+        };
+        match self {
+            Filter::PrimaryCategoryIs(name) => Some((
+                "json_extract(categories, '$[0]') = ?".to_string(),
+                vec![name.clone()],
+            )),
+            Filter::InCategory(name) => Some((
+                "EXISTS (SELECT 1 FROM json_each(categories) WHERE value = ?)".to_string(),
+                vec![name.clone()],
+            )),
+            Filter::FirstVersionEncounteredAfter(date) => Some((
+                "json_extract(versions, '$[0].first_encounter') >= ?".to_string(),
+                vec![date.clone()],
+            )),
+            Filter::FirstVersionSubmittedAfter(date) => Some((
+                "date(json_extract(versions, '$[0].date')) >= ?".to_string(),
+                vec![date.clone()],
+            )),
+            // LIKE ignores the case of ASCII letters, as does `matches`.
+            Filter::Title(word) => like("title", word),
+            Filter::Abstract(word) => like("abstract", word),
+            Filter::Comments(word) => like("comments", word),
+            Filter::License(word) => like("license", word),
+            Filter::HasJournal => Some(("journal_ref IS NOT NULL".to_string(), vec![])),
+            Filter::HasDoi => Some(("doi IS NOT NULL".to_string(), vec![])),
+            Filter::Author(word) => Some(("instr(authors, ?) > 0".to_string(), vec![word.clone()])),
+            Filter::FullTextMatches(ids) | Filter::AuthorMatches(ids) => Some((
+                "id IN (SELECT value FROM json_each(?))".to_string(),
+                vec![serde_json::to_string(ids).unwrap()],
+            )),
+            Filter::Id(id) => Some(("id = ?".to_string(), vec![id.clone()])),
+            Filter::And(a, b) => match (a.sql(), b.sql()) {
+                (Some((a, mut params)), Some((b, params_b))) => {
+                    params.extend(params_b);
+                    Some((format!("({a} AND {b})"), params))
+                }
+                (a, b) => a.or(b),
+            },
+            Filter::Or(a, b) => {
+                let (a, mut params) = a.sql()?;
+                let (b, params_b) = b.sql()?;
+                params.extend(params_b);
+                Some((format!("({a} OR {b})"), params))
+            }
+            Filter::False => Some(("0".to_string(), vec![])),
+            _ => None,
+        }
+    }
+
+    /// See `PreparedFilter::matches`. The filter must have been prepared.
     #[rustfmt::skip]
-    pub fn matches(&self, article: &crate::article::Article) -> bool {
+    fn matches(&self, article: &crate::article::Article) -> bool {
         match self {
             Filter::PrimaryCategoryIs(name) => article.primary_category().as_str() == name,
             Filter::InCategory(name) => article.categories().contains(name),
             Filter::FirstVersionEncounteredAfter(date) => article.first_version().first_encounter >= *date,
             Filter::FirstVersionSubmittedAfter(date) => article.first_version().date.naive_utc().date().to_string() >= *date,
             Filter::Title(word) => article.title().to_ascii_lowercase().contains(&word.to_ascii_lowercase()),
+            Filter::TitleRegex(re) => re.is_match(article.title()),
             Filter::Author(word) => article.authors().contains(word),
-            Filter::ACMClass(pattern) => article.acm_classes().is_some_and(|c| c.contains(pattern)),
-            Filter::MSCClass(pattern) => article.msc_classes().is_some_and(|c| c.contains(pattern)),
+            Filter::AuthorRegex(re) => re.is_match(article.authors()),
+            Filter::AuthorMatches(ids) => ids.contains(&article.id().to_string()),
+            Filter::ACMClass(class) => article.acm_classes().is_some_and(|c| Scheme::Acm.matches(c, class)),
+            Filter::MSCClass(class) => article.msc_classes().is_some_and(|c| Scheme::Msc.matches(c, class)),
             Filter::Abstract(word) => article.abstract_().to_ascii_lowercase().contains(&word.to_ascii_lowercase()),
+            Filter::AbstractRegex(re) => re.is_match(article.abstract_()),
             Filter::Comments(word) => article.comments().is_some_and(|c| c.to_ascii_lowercase().contains(&word.to_ascii_lowercase())),
+            Filter::HasJournal => article.journal_ref().is_some(),
+            Filter::HasDoi => article.doi().is_some(),
+            Filter::License(word) => article.license().is_some_and(|l| l.to_ascii_lowercase().contains(&word.to_ascii_lowercase())),
+            Filter::Withdrawn => article.last_version().probably_withdrawn(),
             Filter::Bookmarked => article.is_bookmarked(),
             Filter::Seen => article.last_seen_version() > 0,
+            Filter::HasUpdate => article.has_update(),
             Filter::Tag(tag) => article.tags().contains(tag),
+            Filter::Untagged => article.tags().is_empty(),
+            Filter::TagCount(min, max) => (*min..=*max).contains(&article.tags().len()),
+            Filter::TagNot(tags) => article.tags().iter().any(|tag| !tags.contains(tag)),
             Filter::Notes(pattern) => article.notes().is_some_and(|c| c.to_ascii_lowercase().contains(&pattern.to_ascii_lowercase())),
+            Filter::HasTodo => !article.todos().is_empty(),
+            Filter::Keyword(word) => article.keywords().contains(&word.to_lowercase()),
+            Filter::FullText(_) => unreachable!("fulltext condition was not prepared"),
+            Filter::FullTextMatches(ids) => ids.contains(&article.id().to_string()),
+            Filter::Fts(_) => unreachable!("fts condition was not prepared"),
             Filter::Any(word) => {
                 article.categories().contains(word)
                     || article.title().to_ascii_lowercase().contains(&word.to_ascii_lowercase())
@@ -62,12 +271,38 @@ impl Filter {
             Filter::And(a, b) => a.matches(article) && b.matches(article),
             Filter::Or(a, b) => a.matches(article) || b.matches(article),
             Filter::Id(id) => article.id().to_string() == *id,
+            Filter::Named(name) => unreachable!("named filter @{name} was not prepared"),
             Filter::True => true,
             Filter::False => false,
         }
     }
 }
 
+/// A filter returned by `Filter::prepare`, in which the conditions that need the database
+/// have been evaluated.
+#[derive(Clone, Debug)]
+pub struct PreparedFilter(Filter);
+
+impl PreparedFilter {
+    /// Whether the article satisfies the filter.
+    pub fn matches(&self, article: &crate::article::Article) -> bool {
+        self.0.matches(article)
+    }
+
+    /// A condition on the article table that holds for all articles matching the filter,
+    /// as an SQL expression with its parameters, or `None` if any article might match.
+    /// Only the conditions on the metadata are translated, so the articles satisfying it
+    /// must still be checked with `matches`.
+    pub fn sql(&self) -> Option<(String, Vec<String>)> {
+        self.0.sql()
+    }
+
+    /// The filter matching the articles that match both filters.
+    pub fn and(self, other: PreparedFilter) -> PreparedFilter {
+        PreparedFilter(Filter::And(Box::new(self.0), Box::new(other.0)))
+    }
+}
+
 #[derive(Debug)]
 enum Token {
     EscapedString(String),
@@ -133,6 +368,13 @@ fn one_or_more_strings(input: &mut VecDeque<SpannedToken>) -> anyhow::Result<Vec
     Ok(res)
 }
 
+fn regexes(input: &mut VecDeque<SpannedToken>) -> anyhow::Result<Vec<Regex>> {
+    one_or_more_strings(input)?
+        .iter()
+        .map(|s| Regex::new(s))
+        .collect()
+}
+
 fn unescaped_string(
     input: &mut VecDeque<SpannedToken>,
     expected: &str,
@@ -174,12 +416,36 @@ fn date(input: &mut VecDeque<SpannedToken>) -> anyhow::Result<String> {
     })
 }
 
-fn acm_or_msc_class(input: &mut VecDeque<SpannedToken>) -> anyhow::Result<String> {
-    unescaped_string(input, "acm or msc class", |s| {
-        s.len() <= 5
-            && s.chars()
-                .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
-    })
+fn class(input: &mut VecDeque<SpannedToken>, scheme: Scheme) -> anyhow::Result<String> {
+    let expected = match scheme {
+        Scheme::Msc => "msc class",
+        Scheme::Acm => "acm class",
+    };
+    let class = unescaped_string(input, expected, |s| scheme.normalize(s).is_some())?;
+    Ok(scheme.normalize(&class).unwrap())
+}
+
+/// A number of tags, optionally preceded by a comparison, as the range of numbers it allows.
+fn tag_count(input: &mut VecDeque<SpannedToken>) -> anyhow::Result<(usize, usize)> {
+    let parse = |s: &str| -> Option<(usize, usize)> {
+        let (op, n) = match s.find(|c: char| c.is_ascii_digit()) {
+            Some(i) => s.split_at(i),
+            None => return None,
+        };
+        let n: usize = n.parse().ok()?;
+        match op {
+            "" | "=" => Some((n, n)),
+            ">=" => Some((n, usize::MAX)),
+            ">" => Some((n.checked_add(1)?, usize::MAX)),
+            "<=" => Some((0, n)),
+            "<" => Some((0, n.checked_sub(1)?)),
+            _ => None,
+        }
+    };
+    let s = unescaped_string(input, "number of tags (such as 2 or >=2)", |s| {
+        parse(s).is_some()
+    })?;
+    Ok(parse(&s).unwrap())
 }
 
 fn fold_and<T>(cond: impl Fn(T) -> Filter, params: Vec<T>) -> Filter {
@@ -189,7 +455,7 @@ fn fold_and<T>(cond: impl Fn(T) -> Filter, params: Vec<T>) -> Filter {
 }
 
 fn fold_or<T>(cond: impl Fn(T) -> Filter, params: Vec<T>) -> Filter {
-    params.into_iter().fold(Filter::True, |res, s| {
+    params.into_iter().fold(Filter::False, |res, s| {
         Filter::Or(Box::new(res), Box::new(cond(s)))
     })
 }
@@ -207,19 +473,35 @@ fn term(input: &mut VecDeque<SpannedToken>) -> anyhow::Result<Filter> {
                 "first_version_encountered_after" => Some(Filter::FirstVersionEncounteredAfter(date(input)?)),
                 "first_version_submitted_after" => Some(Filter::FirstVersionSubmittedAfter(date(input)?)),
                 "title" => Some(fold_and(Filter::Title, one_or_more_strings(input)?)),
+                "title_regex" => Some(fold_and(Filter::TitleRegex, regexes(input)?)),
                 "author" => Some(fold_and(Filter::Author, one_or_more_strings(input)?)),
-                "acm" => Some(Filter::ACMClass(acm_or_msc_class(input)?)),
-                "msc" => Some(Filter::MSCClass(acm_or_msc_class(input)?)),
+                "author_regex" => Some(fold_and(Filter::AuthorRegex, regexes(input)?)),
+                "acm" => Some(Filter::ACMClass(class(input, Scheme::Acm)?)),
+                "msc" => Some(Filter::MSCClass(class(input, Scheme::Msc)?)),
                 "abstract" => Some(fold_and(Filter::Abstract, one_or_more_strings(input)?)),
+                "abstract_regex" => Some(fold_and(Filter::AbstractRegex, regexes(input)?)),
                 "comments" => Some(fold_and(Filter::Comments, one_or_more_strings(input)?)),
+                "has_journal" => Some(Filter::HasJournal),
+                "has_doi" => Some(Filter::HasDoi),
+                "license" => Some(fold_and(Filter::License, one_or_more_strings(input)?)),
+                "withdrawn" => Some(Filter::Withdrawn),
                 "bookmarked" => Some(Filter::Bookmarked),
                 "seen" => Some(Filter::Seen),
+                "has_update" => Some(Filter::HasUpdate),
+                "has_todo" => Some(Filter::HasTodo),
+                "keyword" => Some(fold_and(Filter::Keyword, one_or_more_strings(input)?)),
                 "tag" => Some(fold_and(Filter::Tag, one_or_more_strings(input)?.iter().map(|s| s.parse::<TagName>()).collect::<Result<_,_>>()?)),
+                "untagged" => Some(Filter::Untagged),
+                "tag_count" => { let (min, max) = tag_count(input)?; Some(Filter::TagCount(min, max)) }
+                "tag_not" => Some(Filter::TagNot(one_or_more_strings(input)?.iter().map(|s| s.parse::<TagName>()).collect::<Result<_,_>>()?)),
                 "notes" => Some(fold_and(Filter::Notes, one_or_more_strings(input)?)),
+                "fulltext" => Some(fold_and(Filter::FullText, one_or_more_strings(input)?)),
+                "fts" => Some(fold_and(Filter::Fts, one_or_more_strings(input)?)),
                 "any" => Some(fold_and(Filter::Any, one_or_more_strings(input)?)),
-                "id" => Some(fold_or(Filter::Id, one_or_more_strings(input)?)),
+                "id" => Some(fold_or(Filter::Id, one_or_more_strings(input)?.iter().map(|s| ArxivId::parse_ignoring_version(s).map(|id| id.to_string())).collect::<Result<_,_>>()?)),
                 "true" => Some(Filter::True),
                 "false" => Some(Filter::False),
+                name if name.len() > 1 && name.starts_with('@') => Some(Filter::Named(name[1..].to_string())),
                 _ => None,
             },
             _ => None,
@@ -335,7 +617,6 @@ fn tokenize<'a>(text: &'a str) -> anyhow::Result<VecDeque<SpannedToken<'a>>> {
                 // Quoted string.
                 let mut r = String::new();
                 loop {
-                    let j = it.pos;
                     let d = match it.take() {
                         Some(d) if d == c => {
                             break;
@@ -344,8 +625,10 @@ fn tokenize<'a>(text: &'a str) -> anyhow::Result<VecDeque<SpannedToken<'a>>> {
                             Some('\'') => '\'',
                             Some('"') => '"',
                             Some('\\') => '\\',
+                            // Keep other escapes, such as "\b" in a regex.
                             Some(e) => {
-                                bail!("expected escaped character, found '{e:?}' at index {j}");
+                                r.push('\\');
+                                e
                             }
                             None => {
                                 bail!("expected escaped character, found end");
@@ -383,6 +666,61 @@ fn tokenize<'a>(text: &'a str) -> anyhow::Result<VecDeque<SpannedToken<'a>>> {
     Ok(res)
 }
 
+/// The ids of the articles with an author matching `word`. Names are compared as a whole,
+/// ignoring accents and the like, so that "Li" does not find "Julia": persons by family
+/// name and compatible given names (such as "Adrien-Marie Legendre" for "Legendre, A.-M."),
+/// together with the other spellings in their clusters, and collaborations by their words.
+fn author_matches(tr: &Transaction, word: &str) -> anyhow::Result<BTreeSet<String>> {
+    let mut ids = BTreeSet::new();
+    if let Some(name) = parse_name(word) {
+        // Only names with the same family name can be in the same cluster, so it is enough
+        // to cluster the articles with an author of this family name.
+        let mut get = tr.prepare_cached(
+            "SELECT authors FROM article WHERE id IN (SELECT id FROM author WHERE family = ?1)",
+        )?;
+        let authors: Vec<String> = get
+            .query_map(params![name.family], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        let clusters = AuthorClusters::new(authors.iter().map(String::as_str));
+        let mut get = tr.prepare_cached("SELECT DISTINCT name FROM author WHERE family = ?1")?;
+        let spellings: Vec<String> = get
+            .query_map(params![name.family], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        let mut normalized = BTreeSet::new();
+        for spelling in spellings {
+            if !Name::normalize(&spelling).is_some_and(|other| other.compatible(&name)) {
+                continue;
+            }
+            normalized.insert(normalize_name(&spelling));
+            for variant in clusters
+                .cluster(&spelling)
+                .into_iter()
+                .flat_map(|c| c.variants.keys())
+            {
+                normalized.insert(normalize_name(variant));
+            }
+        }
+        let mut get = tr.prepare_cached(
+            "SELECT DISTINCT id FROM author WHERE normalized IN (SELECT value FROM json_each(?1))",
+        )?;
+        let mut rows = get.query(params![serde_json::to_string(&normalized)?])?;
+        while let Some(row) = rows.next()? {
+            ids.insert(row.get(0)?);
+        }
+    }
+    let words = collation_key(word);
+    if !words.is_empty() {
+        let mut get = tr.prepare_cached(
+            "SELECT DISTINCT id FROM author INDEXED BY author_collaboration WHERE collaboration AND ' ' || normalized || ' ' LIKE '% ' || ?1 || ' %'",
+        )?;
+        let mut rows = get.query(params![words])?;
+        while let Some(row) = rows.next()? {
+            ids.insert(row.get(0)?);
+        }
+    }
+    Ok(ids)
+}
+
 #[allow(unused)]
 impl FromStr for Filter {
     type Err = anyhow::Error;
@@ -436,4 +774,112 @@ mod test {
         );
         assert_eq!(a.unwrap(), b);
     }
+
+    #[test]
+    fn fulltext() {
+        let a = Filter::from_str("fulltext 'class field' tower");
+        let b = Filter::And(
+            Box::new(Filter::And(
+                Box::new(Filter::True),
+                Box::new(Filter::FullText("class field".to_string())),
+            )),
+            Box::new(Filter::FullText("tower".to_string())),
+        );
+        assert_eq!(a.unwrap(), b);
+    }
+
+    #[test]
+    fn named() {
+        let a = Filter::from_str("@ntheory && !seen");
+        let b = Filter::And(
+            Box::new(Filter::Named("ntheory".to_string())),
+            Box::new(Filter::Not(Box::new(Filter::Seen))),
+        );
+        assert_eq!(a.unwrap(), b);
+        assert!(Filter::from_str("@").is_err());
+    }
+
+    #[test]
+    fn publication() {
+        let a = Filter::from_str("bookmarked && !has_journal && !withdrawn && license by").unwrap();
+        let (condition, params) = a.sql().unwrap();
+        assert!(condition.contains("license LIKE ? ESCAPE"));
+        assert!(!condition.contains("journal_ref"));
+        assert_eq!(params, vec!["%by%"]);
+        let (condition, _) = Filter::from_str("has_doi").unwrap().sql().unwrap();
+        assert_eq!(condition, "doi IS NOT NULL");
+        let a = Filter::from_str("bookmarked && has_update").unwrap();
+        assert_eq!(
+            a,
+            Filter::And(Box::new(Filter::Bookmarked), Box::new(Filter::HasUpdate))
+        );
+        assert!(a.sql().is_none());
+    }
+
+    #[test]
+    fn regex() {
+        let re = Regex::new(r"\bmodular forms?\b").unwrap();
+        assert!(re.is_match("Congruences between Modular Forms"));
+        assert!(re.is_match("On a modular form of weight 2"));
+        assert!(!re.is_match("Quasimodular forms"));
+        assert!(!re.is_match("modular formula"));
+        let re = Regex::new(r"^(class|ring) (field|class)").unwrap();
+        assert!(re.is_match("Class field towers"));
+        assert!(!re.is_match("On class field towers"));
+        assert!(Regex::new("(").is_err());
+        assert_eq!(
+            Filter::from_str("title_regex 'a+'").unwrap(),
+            Filter::And(
+                Box::new(Filter::True),
+                Box::new(Filter::TitleRegex(Regex::new("a+").unwrap()))
+            )
+        );
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            Filter::from_str("tag_count >1 || tag_count <=1 || tag_count 0").unwrap(),
+            Filter::Or(
+                Box::new(Filter::Or(
+                    Box::new(Filter::TagCount(2, usize::MAX)),
+                    Box::new(Filter::TagCount(0, 1))
+                )),
+                Box::new(Filter::TagCount(0, 0))
+            )
+        );
+        assert!(Filter::from_str("tag_count <0").is_err());
+        assert!(Filter::from_str("tag_count many").is_err());
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.parse().unwrap()).collect();
+        assert_eq!(
+            Filter::from_str("tag_not read paper-done && !untagged").unwrap(),
+            Filter::And(
+                Box::new(Filter::TagNot(tags(&["read", "paper-done"]))),
+                Box::new(Filter::Not(Box::new(Filter::Untagged)))
+            )
+        );
+        assert!(Filter::from_str("tag_not 'no spaces'").is_err());
+    }
+
+    #[test]
+    fn sql() {
+        let a = Filter::from_str("category math.NT && (title 50% || bookmarked) && id 2301.01234")
+            .unwrap();
+        let (condition, params) = a.sql().unwrap();
+        assert_eq!(
+            condition,
+            "(EXISTS (SELECT 1 FROM json_each(categories) WHERE value = ?) AND (0 OR id = ?))"
+        );
+        assert_eq!(params, vec!["math.NT", "2301.01234"]);
+        let a = Filter::from_str("title 50% && !category math.NT").unwrap();
+        let (condition, params) = a.sql().unwrap();
+        assert!(condition.contains("title LIKE ? ESCAPE"));
+        assert_eq!(params, vec!["%50\\%%"]);
+        assert!(
+            Filter::from_str("!category math.NT")
+                .unwrap()
+                .sql()
+                .is_none()
+        );
+    }
 }
@@ -0,0 +1,80 @@
+use std::{collections::BTreeSet, process::Command};
+
+use anyhow::{Context, bail};
+use rusqlite::{Transaction, params};
+
+use crate::{article::Article, downloads::DownloadDir, progress};
+
+/// Extracts the text of the (previously downloaded) pdf of the last version and saves it
+/// in the fulltext table, unless this version has already been indexed.
+/// Returns whether the article was indexed.
+pub fn index(
+    download_dir: &DownloadDir,
+    tr: &Transaction,
+    article: &Article,
+) -> anyhow::Result<bool> {
+    let pdf_path = article.pdf_path(download_dir);
+    if !pdf_path.is_file() {
+        return Ok(false);
+    }
+    let id = article.id().to_string();
+    let version = article.last_version().number;
+    let mut get = tr.prepare_cached("SELECT version FROM fulltext WHERE id = ?1")?;
+    let indexed_version: Option<u32> = get
+        .query(params![id])?
+        .next()?
+        .map(|row| row.get(0))
+        .transpose()?;
+    if indexed_version == Some(version) {
+        return Ok(false);
+    }
+    progress::status(&format!("Indexing {}v{}...", article.id(), version));
+    let output = Command::new("pdftotext")
+        .arg(&pdf_path)
+        .arg("-")
+        .output()
+        .context("running pdftotext")?;
+    if !output.status.success() {
+        bail!("pdftotext failed on {pdf_path:?}");
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    tr.execute("DELETE FROM fulltext WHERE id = ?1", params![id])?;
+    tr.execute(
+        "INSERT INTO fulltext (id, version, text) VALUES (?1, ?2, ?3)",
+        params![id, version, text],
+    )?;
+    Ok(true)
+}
+
+/// Returns the ids of all articles whose indexed text contains the given word.
+pub fn search(tr: &Transaction, word: &str) -> anyhow::Result<BTreeSet<String>> {
+    // Quote the word so that it is interpreted as a phrase and not as a query expression.
+    let query = format!("\"{}\"", word.replace('"', "\"\""));
+    let mut get = tr.prepare_cached("SELECT id FROM fulltext WHERE fulltext MATCH ?1")?;
+    let mut rows = get.query(params![query])?;
+    let mut res = BTreeSet::new();
+    while let Some(row) = rows.next()? {
+        res.insert(row.get(0)?);
+    }
+    Ok(res)
+}
+
+/// Returns the ids of all articles whose title, abstract, authors or comments match the
+/// given FTS5 query, such as `elliptic curves rank` (all words) or `"class field" OR
+/// tower`.
+pub fn search_metadata(tr: &Transaction, query: &str) -> anyhow::Result<BTreeSet<String>> {
+    let mut get = tr.prepare_cached(
+        "SELECT article.id FROM metadata_fts JOIN article ON article.rowid = metadata_fts.rowid WHERE metadata_fts MATCH ?1",
+    )?;
+    let mut rows = get
+        .query(params![query])
+        .with_context(|| format!("invalid full-text query {query:?}"))?;
+    let mut res = BTreeSet::new();
+    while let Some(row) = rows
+        .next()
+        .with_context(|| format!("invalid full-text query {query:?}"))?
+    {
+        res.insert(row.get(0)?);
+    }
+    Ok(res)
+}
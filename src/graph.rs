@@ -0,0 +1,164 @@
+//! The co-author graph of a collection of articles, for visualization with Graphviz or
+//! Gephi.
+
+use std::collections::{BTreeMap, HashMap};
+
+use clap::ValueEnum;
+
+use crate::{
+    article::Article,
+    authors::{AuthorClusters, split_authors},
+};
+
+#[derive(ValueEnum, Copy, Clone)]
+pub enum GraphFormat {
+    /// Graphviz.
+    Dot,
+    /// Gephi.
+    Gexf,
+}
+
+/// Authors with the number of their articles, and pairs of co-authors with the number of
+/// their joint articles.
+pub struct Graph {
+    nodes: BTreeMap<String, usize>,
+    edges: BTreeMap<(String, String), usize>,
+}
+
+impl Graph {
+    /// Builds the co-author graph of the given articles. Different spellings of a name
+    /// are merged as in `clusters`. `convert` is applied to the names, for example to
+    /// convert latex to unicode.
+    pub fn coauthors<'a>(
+        articles: impl IntoIterator<Item = &'a Article>,
+        clusters: &AuthorClusters,
+        convert: impl Fn(&str) -> String,
+    ) -> Self {
+        // The authors are numbered, and the articles and joint articles are counted by
+        // number, so that the names are converted and copied only once.
+        let mut names: Vec<String> = Vec::new();
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        let mut by_canonical: HashMap<String, usize> = HashMap::new();
+        let mut counts: Vec<usize> = Vec::new();
+        let mut pairs: HashMap<(usize, usize), usize> = HashMap::new();
+        for article in articles {
+            let mut authors: Vec<usize> = split_authors(article.authors())
+                .iter()
+                .map(|name| {
+                    let canonical = clusters.canonical(name);
+                    if let Some(&i) = by_canonical.get(canonical) {
+                        return i;
+                    }
+                    let name = convert(canonical);
+                    let i = *by_name.entry(name.clone()).or_insert_with(|| {
+                        names.push(name);
+                        counts.push(0);
+                        names.len() - 1
+                    });
+                    by_canonical.insert(canonical.to_string(), i);
+                    i
+                })
+                .collect();
+            authors.sort();
+            authors.dedup();
+            for (i, &a) in authors.iter().enumerate() {
+                counts[a] += 1;
+                for &b in &authors[i + 1..] {
+                    *pairs.entry((a, b)).or_default() += 1;
+                }
+            }
+        }
+        let edges = pairs
+            .into_iter()
+            .map(|((a, b), count)| {
+                let (a, b) = if names[a] <= names[b] { (a, b) } else { (b, a) };
+                ((names[a].clone(), names[b].clone()), count)
+            })
+            .collect();
+        let nodes = names.into_iter().zip(counts).collect();
+        Self { nodes, edges }
+    }
+
+    pub fn write(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(),
+            GraphFormat::Gexf => self.gexf(),
+        }
+    }
+
+    fn dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut res = "graph coauthors {\n".to_string();
+        for (name, articles) in &self.nodes {
+            res += &format!("  {} [weight={articles}];\n", quote(name));
+        }
+        for ((a, b), articles) in &self.edges {
+            res += &format!("  {} -- {} [weight={articles}];\n", quote(a), quote(b));
+        }
+        res += "}\n";
+        res
+    }
+
+    fn gexf(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+        let ids: BTreeMap<&str, usize> = self
+            .nodes
+            .keys()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect();
+        let mut res = String::new();
+        res += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+        res += "<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n";
+        res += "  <graph defaultedgetype=\"undirected\">\n";
+        res += "    <attributes class=\"node\">\n";
+        res += "      <attribute id=\"articles\" title=\"articles\" type=\"integer\"/>\n";
+        res += "    </attributes>\n";
+        res += "    <nodes>\n";
+        for (name, articles) in &self.nodes {
+            res += &format!(
+                "      <node id=\"{}\" label=\"{}\"><attvalues><attvalue for=\"articles\" value=\"{articles}\"/></attvalues></node>\n",
+                ids[name.as_str()],
+                escape(name)
+            );
+        }
+        res += "    </nodes>\n";
+        res += "    <edges>\n";
+        for (i, ((a, b), articles)) in self.edges.iter().enumerate() {
+            res += &format!(
+                "      <edge id=\"{i}\" source=\"{}\" target=\"{}\" weight=\"{articles}\"/>\n",
+                ids[a.as_str()],
+                ids[b.as_str()]
+            );
+        }
+        res += "    </edges>\n";
+        res += "  </graph>\n";
+        res += "</gexf>\n";
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dot() {
+        let graph = Graph {
+            nodes: BTreeMap::from([
+                ("C. F. Gau\\ss".to_string(), 2),
+                ("L. Euler".to_string(), 1),
+            ]),
+            edges: BTreeMap::from([(("C. F. Gau\\ss".to_string(), "L. Euler".to_string()), 1)]),
+        };
+        assert_eq!(
+            graph.write(GraphFormat::Dot),
+            "graph coauthors {\n  \"C. F. Gau\\\\ss\" [weight=2];\n  \"L. Euler\" [weight=1];\n  \"C. F. Gau\\\\ss\" -- \"L. Euler\" [weight=1];\n}\n"
+        );
+    }
+}
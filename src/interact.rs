@@ -1,30 +1,40 @@
 use std::{
-    cmp::max,
-    collections::VecDeque,
-    fs::File,
-    io::{Write, stdin, stdout},
+    cmp::{max, min},
+    collections::{BTreeSet, HashMap, VecDeque},
+    io::{Stdout, Write, stdin, stdout},
     panic::{set_hook, take_hook},
     path::Path,
 };
 
-use anyhow::Context;
-use rusqlite::Transaction;
+use anyhow::{Context, bail};
+use chrono::NaiveDate;
+use rusqlite::{Connection, Transaction};
 use termion::{
     cursor::HideCursor,
     event::Key,
     input::TermRead,
-    raw::IntoRawMode,
-    screen::{IntoAlternateScreen, ToMainScreen},
+    raw::{IntoRawMode, RawTerminal},
+    screen::{AlternateScreen, IntoAlternateScreen, ToMainScreen},
 };
 
 use crate::{
     Order,
-    article::{Article, ArxivId},
-    config::{Config, Highlight},
-    filter::Filter,
-    rate_limited_client::Client,
+    article::{Article, ArticleState, ArxivId},
+    authors::{author_sort_key, collation_key},
+    bibtex,
+    config::{Config, Highlight, TagName},
+    db,
+    downloads::{self, DownloadDir},
+    filter::{Filter, PreparedFilter},
+    message, oai, output, progress,
+    rate_limited_client::{self, Client},
+    share::{self, ShareFormat},
+    source,
+    util::{self, OpenAs, fuzzy_score},
 };
 
+type Screen = HideCursor<AlternateScreen<RawTerminal<Stdout>>>;
+
 pub fn init_panic_hook() -> anyhow::Result<()> {
     let screen = stdout().into_raw_mode()?;
     screen.suspend_raw_mode()?;
@@ -39,30 +49,68 @@ pub fn init_panic_hook() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Runs `f` in a write transaction that is committed right away, so that changes to the
+/// state cache are saved after each action and the database is not locked while the user
+/// reads.
+fn save<T>(
+    conn: &mut Connection,
+    base_dir: &Path,
+    f: impl FnOnce(&Transaction) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    db::with_write_transaction(conn, base_dir, |tr| {
+        let res = f(&tr)?;
+        tr.commit()?;
+        Ok(res)
+    })
+}
+
+/// Whether `news` shows the article as unseen (`Some(false)`), as updated (`Some(true)`: it
+/// matches the update filter and has a new version, journal ref or doi) or among the seen
+/// articles (`None`).
+pub fn news_status(article: &Article, update_filter: &PreparedFilter) -> Option<bool> {
+    if article.last_seen_version() == 0 {
+        Some(false)
+    } else if update_filter.matches(article) && article.has_update() {
+        Some(true)
+    } else {
+        None
+    }
+}
+
 /// Interactively show one article at a time.
 ///
 /// Only articles matching the filter will be shown.
 /// If update_filter is Some(...), it means that we are reading new
 /// articles, which will be marked as seen. The update_filter specifies
 /// for which articles we also want to see updates (new versions, etc.).
+/// If reverse is true, the order of the seen articles is reversed.
+/// If limit is Some(n), only the n oldest unseen or updated articles are shown, and
+/// we quit once all of them have been marked as seen.
+/// If groups is not empty, the unseen or updated articles are grouped by the first of
+/// the given (name, OAI sets) pairs one of whose sets delivered them.
+/// If sections is true, the seen articles are divided into sections: the bookmarked ones
+/// first, and then the others by primary category.
 #[allow(clippy::too_many_arguments)]
 pub fn interact(
     base_dir: &Path,
-    conn: &Transaction,
+    conn: &mut Connection,
     highlight: &Highlight,
     config: &Config,
     client: &mut Client,
-    filter: &Filter,
-    update_filter: Option<&Filter>,
+    filter: &PreparedFilter,
+    update_filter: Option<&PreparedFilter>,
     sort_by: Order,
+    reverse: bool,
+    limit: Option<usize>,
+    groups: &[(String, Vec<String>)],
+    sections: bool,
 ) -> anyhow::Result<()> {
-    let mut articles = Article::load(base_dir, conn)?;
+    let mut articles = db::with_transaction(conn, base_dir, |tr| {
+        Article::load_matching(base_dir, &tr, filter)
+    })?;
+    let download_dir = DownloadDir::new(base_dir, &config.downloads);
 
-    let mut seen_file = File::options()
-        .append(true)
-        .create(true)
-        .open(base_dir.join("seen-articles"))
-        .context("opening seen-articles file")?;
+    let mut seen_file = Article::open_seen_file(base_dir)?;
 
     let mut seen: Vec<ArxivId> = Vec::new();
     let mut unseen: Vec<ArxivId> = Vec::new();
@@ -71,16 +119,10 @@ pub fn interact(
     for article in articles.values() {
         if filter.matches(article) {
             if let Some(update_filter) = update_filter {
-                if article.last_seen_version() == 0 {
-                    unseen.push(article.id().clone());
-                } else if update_filter.matches(article)
-                    && (article.last_seen_version() < article.last_version().number
-                        || (article.journal_ref().is_some() && !article.seen_journal())
-                        || (article.doi().is_some() && !article.seen_doi()))
-                {
-                    updated.push(article.id().clone());
-                } else {
-                    seen.push(article.id().clone());
+                match news_status(article, update_filter) {
+                    Some(false) => unseen.push(article.id().clone()),
+                    Some(true) => updated.push(article.id().clone()),
+                    None => seen.push(article.id().clone()),
                 }
             } else {
                 seen.push(article.id().clone());
@@ -97,15 +139,69 @@ pub fn interact(
             // Sort seen articles in the order in which they were seen.
             seen.sort_by_cached_key(|id| articles[id].last_seen_at());
         }
+        Order::Title => {
+            seen.sort_by_cached_key(|id| collation_key(articles[id].title()));
+        }
+        Order::Authors => {
+            seen.sort_by_cached_key(|id| author_sort_key(articles[id].authors()));
+        }
+    }
+    if reverse {
+        seen.reverse();
+    }
+    if sections {
+        // The sort is stable, so each section is sorted as specified.
+        seen.sort_by_cached_key(|id| section_of(&articles[id]));
     }
     unseen.sort_by_cached_key(|id| articles[id].first_version().date);
     updated.sort_by_cached_key(|id| articles[id].first_version().date);
 
+    // The index in `groups` of the group of an article, or groups.len() for the articles
+    // not delivered by any of them.
+    let group_of = |article: &Article| {
+        groups
+            .iter()
+            .position(|(_, subscribed)| {
+                article.sets().is_some_and(|sets| {
+                    sets.iter()
+                        .any(|set| subscribed.iter().any(|s| oai::set_contains(s, set)))
+                })
+            })
+            .unwrap_or(groups.len())
+    };
+    let group_name = |group: usize| match groups.get(group) {
+        Some((category, _)) => category.as_str(),
+        None => "other",
+    };
+
     // Convert to a VecDeque so that we can efficiently remove the first unseen or updated article
     // when marking it as seen.
-    let mut unseen_or_updated: VecDeque<(ArxivId, bool)> =
-        unseen.into_iter().map(|a| (a, false)).collect();
-    unseen_or_updated.extend(updated.into_iter().map(|a| (a, true)));
+    let mut unseen_or_updated: VecDeque<(ArxivId, bool, usize)> = unseen
+        .into_iter()
+        .map(|a| (a, false))
+        .chain(updated.into_iter().map(|a| (a, true)))
+        .map(|(a, show_updates)| {
+            let group = if groups.is_empty() {
+                0
+            } else {
+                group_of(&articles[&a])
+            };
+            (a, show_updates, group)
+        })
+        .collect();
+    // Sort by announcement day and then by group. The sort is stable, so each group
+    // starts with the unseen articles.
+    unseen_or_updated
+        .make_contiguous()
+        .sort_by_cached_key(|(id, show_updates, group)| {
+            (
+                announcement_day(&articles[id], *show_updates).to_string(),
+                *group,
+            )
+        });
+    if let Some(limit) = limit {
+        unseen_or_updated.truncate(limit);
+    }
 
     // Currently displayed article.
     enum Current {
@@ -113,6 +209,53 @@ pub fn interact(
         FirstUnseen, // the first unseen article
     }
 
+    // Where to continue after the queue changed: at the same article if it is still there.
+    fn locate(
+        id: &ArxivId,
+        seen: &[ArxivId],
+        unseen_or_updated: &VecDeque<(ArxivId, bool, usize)>,
+    ) -> Current {
+        if let Some(i) = seen.iter().position(|s| s == id) {
+            Current::Read(i)
+        } else if !unseen_or_updated.is_empty() {
+            Current::FirstUnseen
+        } else {
+            Current::Read(seen.len() - 1)
+        }
+    }
+
+    // Shows the article at the position found by `find_match`. An article in the queue is
+    // moved to its front.
+    fn jump_to(
+        position: usize,
+        seen: &[ArxivId],
+        unseen_or_updated: &mut VecDeque<(ArxivId, bool, usize)>,
+    ) -> Current {
+        if position < seen.len() {
+            Current::Read(position)
+        } else {
+            let entry = unseen_or_updated.remove(position - seen.len()).unwrap();
+            unseen_or_updated.push_front(entry);
+            Current::FirstUnseen
+        }
+    }
+
+    // While the queue is refined with the f key, `seen` and `unseen_or_updated` only contain
+    // the articles matching the refinement, and this contains the complete queue.
+    struct Unrefined {
+        description: String,
+        filter: PreparedFilter,
+        seen: Vec<ArxivId>,
+        unseen_or_updated: VecDeque<(ArxivId, bool, usize)>,
+    }
+    let mut unrefined: Option<Unrefined> = None;
+
+    // The articles selected with the space key, in the order in which they were selected.
+    let mut selection: Vec<ArxivId> = Vec::new();
+
+    // The query entered with the / key. While it is set, n and N jump between the matches.
+    let mut search: Option<String> = None;
+
     // If possible, show first unseen article.
     // Otherwise, if possible, show last seen article.
     // Otherwise, quit.
@@ -123,18 +266,27 @@ pub fn interact(
         } else if !seen.is_empty() {
             Current::Read(seen.len() - 1)
         } else {
-            println!("No articles. You should probably run `arxiv-reader pull`.");
+            message!("No articles. You should probably run `arxiv-reader pull`.");
             return Ok(());
         }
     } else {
         if !seen.is_empty() {
             Current::Read(0)
         } else {
-            println!("No articles.");
+            message!("No articles.");
             return Ok(());
         }
     };
+    // All tags, for the tag picker.
+    let mut known_tags: BTreeSet<TagName> = config.tags.iter().map(|(_, t)| t.clone()).collect();
+    known_tags.extend(db::with_transaction(conn, base_dir, |tr| {
+        ArticleState::all_tags(base_dir, &tr)
+    })?);
+
     let mut latex_to_unicode = config.latex_to_unicode;
+    // How many rows of the current article are scrolled out of view at the top.
+    let mut scroll = 0;
+    let mut scrolled_article: Option<ArxivId> = None;
     let mut error_message = String::new();
 
     init_panic_hook().context("initializing panic hook")?;
@@ -142,14 +294,39 @@ pub fn interact(
     // Suspend raw mode as it interferes with printing.
     screen.suspend_raw_mode()?;
     let mut screen = HideCursor::from(screen);
+    // Large downloads are declined instead of asking on the screen.
+    let _full_screen = progress::full_screen();
 
     loop {
+        // The section of the current seen article, the position of the article in it and
+        // its size.
+        let section = match state {
+            Current::Read(i) if sections => {
+                let (start, end) = section_bounds(&articles, &seen, i);
+                Some((section_of(&articles[&seen[i]]).1, i - start, end - start))
+            }
+            _ => None,
+        };
+
+        // The announcement day of the first article in the queue and the number of
+        // articles in the queue from this day.
+        let day = unseen_or_updated.front().map(|(id, show_updates, _)| {
+            let day = announcement_day(&articles[id], *show_updates);
+            let count = unseen_or_updated
+                .iter()
+                .take_while(|(id, show_updates, _)| {
+                    announcement_day(&articles[id], *show_updates) == day
+                })
+                .count();
+            (day.to_string(), count)
+        });
+
         // Currently displayed article and its index in the list of all articles (whether
         // seen or unseen).
         let (article, show_updates, index) = match state {
             Current::Read(i) => (articles.get_mut(&seen[i]).unwrap(), false, i),
             Current::FirstUnseen => {
-                let (id, show_updates) = unseen_or_updated.front().unwrap();
+                let (id, show_updates, _) = unseen_or_updated.front().unwrap();
                 (articles.get_mut(id).unwrap(), *show_updates, seen.len())
             }
         };
@@ -181,7 +358,28 @@ pub fn interact(
         } else {
             info += "            ";
         }
+        if selection.contains(article.id()) {
+            info += "  (selected)";
+        }
         status_items.push(info);
+        status_items.push(article.download_status(&download_dir));
+        if !selection.is_empty() {
+            status_items.push(format!("{} selected", selection.len()));
+        }
+        if let Some(unrefined) = &unrefined {
+            let mut description = unrefined.description.clone();
+            if description.chars().count() > 30 {
+                description = description.chars().take(29).collect::<String>() + "…";
+            }
+            status_items.push(format!("refined: {description}"));
+        }
+        if let Some(query) = &search {
+            let mut query = query.clone();
+            if query.chars().count() > 30 {
+                query = query.chars().take(29).collect::<String>() + "…";
+            }
+            status_items.push(format!("search: {query}"));
+        }
         if update_filter.is_some() {
             status_items.push(format!("{} unseen left", unseen_or_updated.len()));
         }
@@ -192,7 +390,7 @@ pub fn interact(
         ));
         let mut status_line = String::new();
         let mut remaining_length = max(
-            width - status_items.iter().map(|s| s.len()).sum::<usize>(),
+            width.saturating_sub(status_items.iter().map(|s| s.len()).sum::<usize>()),
             status_items.len() - 1,
         );
         for (i, item) in status_items.iter().enumerate() {
@@ -204,11 +402,43 @@ pub fn interact(
             status_line += item;
         }
 
-        println!("{}", status_line);
-        println!();
-
-        // Print the article.
-        article.print(highlight, show_updates, latex_to_unicode);
+        let mut header = vec![status_line];
+        if let Some((name, position, size)) = &section {
+            header.push(format!(
+                "Section {name}: article {} of {size}",
+                position + 1
+            ));
+        }
+        if let (Current::FirstUnseen, Some((day, count))) = (&state, &day) {
+            let plural = if *count == 1 { "" } else { "s" };
+            header.push(format!("{} — {count} article{plural}", format_day(day)));
+        }
+        if !groups.is_empty() && !unseen_or_updated.is_empty() {
+            // The number of articles left in each group (of the current day), starting with
+            // the current one.
+            let mut counts: Vec<(usize, usize)> = Vec::new();
+            let day_count = day.as_ref().map_or(0, |(_, count)| *count);
+            for (_, _, group) in unseen_or_updated.iter().take(day_count) {
+                match counts.last_mut() {
+                    Some((g, count)) if g == group => *count += 1,
+                    _ => counts.push((*group, 1)),
+                }
+            }
+            let counts = counts
+                .iter()
+                .enumerate()
+                .map(|(i, (group, count))| {
+                    let name = group_name(*group);
+                    if i == 0 && matches!(state, Current::FirstUnseen) {
+                        format!("[{name}: {count}]")
+                    } else {
+                        format!("{name}: {count}")
+                    }
+                })
+                .collect::<Vec<_>>();
+            header.push(format!("Unseen by category: {}", counts.join("  ")));
+        }
+        header.push(String::new());
 
         // Print list of keyboard shortcuts.
         let append_shortcut_lines = |shortcuts: Vec<String>, shortcut_lines: &mut Vec<String>| {
@@ -229,16 +459,52 @@ pub fn interact(
         let mut shortcuts = vec![
             "[q] quit",
             "[o] open webpage",
+            "[h] open HTML version",
             "[p] open pdf",
             "[d] open directory",
-            "[n] edit notes",
+            "[i] open figures",
+            "[y] copy the id, URL, pdf path or BibTeX entry",
+        ];
+        if search.is_some() {
+            shortcuts.extend([
+                "[n] next match",
+                "[N] previous match",
+                "[ESC] end the search (to edit notes with [n])",
+            ]);
+        } else {
+            shortcuts.push("[n] edit notes");
+        }
+        shortcuts.extend([
+            "[/] search title, authors and abstract",
+            "[g] go to an article by id or number",
+            "[TAB] list of all articles",
             "[u] turn on/off latex-to-unicode",
+            "[#] pick a tag",
+            "[f] refine the filter",
+            "[SPACE] select (to act on all selected articles when quitting)",
             "[RIGHT] next article",
             "[LEFT] previous article",
-        ];
+        ]);
         if update_filter.is_none() {
             shortcuts.extend(vec!["[END] last article", "[HOME] first article"]);
         }
+        if unrefined.is_some() {
+            shortcuts.push("[F] clear the refinement");
+        }
+        if section.is_some() {
+            shortcuts.extend(["[PGDN] next section", "[PGUP] previous section"]);
+        }
+        if !unseen_or_updated.is_empty() {
+            shortcuts.push("[A] mark all articles left in the queue as seen");
+        }
+        if matches!(state, Current::FirstUnseen) {
+            shortcuts.push("[s] skip to the end of the queue for now");
+            if groups.is_empty() {
+                shortcuts.push("[PGDN] skip the rest of this day for now");
+            } else {
+                shortcuts.push("[PGDN] skip the rest of this category and day for now");
+            }
+        }
         let mut shortcut_lines = Vec::new();
         append_shortcut_lines(
             shortcuts.into_iter().map(|s| s.to_string()).collect(),
@@ -251,6 +517,58 @@ pub fn interact(
             shortcuts.push(format!("[{}] {}", shortcut, name).to_string());
         }
         append_shortcut_lines(shortcuts, &mut shortcut_lines);
+        if !config.commands.is_empty() {
+            shortcut_lines.push(String::new());
+            shortcut_lines.push("Run commands:".to_string());
+            let shortcuts = config
+                .commands
+                .iter()
+                .map(|(shortcut, command)| format!("[{shortcut}] {command}"))
+                .collect();
+            append_shortcut_lines(shortcuts, &mut shortcut_lines);
+        }
+
+        // Print the header and the visible part of the article.
+        let header_rows = header
+            .iter()
+            .map(|line| util::screen_rows(line, width).len())
+            .sum::<usize>();
+        for line in &header {
+            println!("{line}");
+        }
+        let body: Vec<String> = article
+            .lines(highlight, show_updates, latex_to_unicode)
+            .iter()
+            .flat_map(|line| util::screen_rows(line, width))
+            .collect();
+        if scrolled_article.as_ref() != Some(article.id()) {
+            scroll = 0;
+            scrolled_article = Some(article.id().clone());
+        }
+        // The rows above the error message and the shortcuts.
+        let available = height.saturating_sub(header_rows + shortcut_lines.len() + 3);
+        // The number of rows of the article shown at a time, and the largest possible
+        // scroll position.
+        let (page, max_scroll) = if body.len() > available {
+            // Leave a row for the position indicator.
+            let page = max(available, 2) - 1;
+            (page, body.len().saturating_sub(page))
+        } else {
+            (body.len(), 0)
+        };
+        scroll = min(scroll, max_scroll);
+        for row in &body[scroll..min(scroll + page, body.len())] {
+            println!("{row}");
+        }
+        if max_scroll > 0 {
+            println!(
+                "-- rows {}-{} of {} ([UP]/[DOWN]/[PGUP]/[PGDN] to scroll) --",
+                scroll + 1,
+                min(scroll + page, body.len()),
+                body.len()
+            );
+        }
+
         write!(
             screen,
             "{}{}",
@@ -303,25 +621,289 @@ pub fn interact(
                 article.open_abs()?;
                 error_message = String::new();
             }
+            Key::Char('h') => {
+                // Open HTML version (or webpage).
+                article.open_html()?;
+                error_message = if article.html_url().is_none() {
+                    "arXiv probably has no HTML version, opened the webpage.".to_string()
+                } else {
+                    String::new()
+                };
+            }
             Key::Char('p') => {
-                // Download and then open pdf.
-                if article.last_version().probably_has_pdf() {
-                    match article.download_pdf(base_dir, client) {
-                        Ok(_) => {
-                            article.open_pdf(base_dir)?;
-                            error_message = String::new();
-                        }
-                        Err(err) => {
-                            error_message = format!("{err:#}");
-                        }
+                // Download (or build) and then open pdf.
+                match source::download_or_build_pdf(
+                    article,
+                    &download_dir,
+                    client,
+                    config.build_pdf_from_source,
+                ) {
+                    Ok(true) => {
+                        article.open_pdf(&download_dir)?;
+                        error_message = String::new();
+                    }
+                    Ok(false) => {
+                        error_message = "arXiv has no pdf for this article.".to_string();
+                    }
+                    Err(err) => {
+                        error_message = format!("{err:#}");
                     }
                 }
             }
+            Key::Char('i') => {
+                // Extract the figures from the sources and open the directory containing them.
+                let res = (|| -> anyhow::Result<()> {
+                    article.download_src(&download_dir, client)?;
+                    if source::extract_figures(article, &download_dir)? == 0 {
+                        bail!("found no figures in the sources");
+                    }
+                    util::open(
+                        OpenAs::Directory,
+                        source::figures_dir(article, &download_dir),
+                    )
+                })();
+                error_message = match res {
+                    Ok(()) => String::new(),
+                    Err(err) => format!("{err:#}"),
+                };
+            }
+            Key::Char('y') => {
+                // Copy a reference to the article to the clipboard.
+                const ID: &str = "arXiv id";
+                const URL: &str = "abs URL";
+                const PDF: &str = "pdf path";
+                const BIBTEX: &str = "BibTeX entry";
+                let formats = [ID, URL, PDF, BIBTEX].map(String::from);
+                let picked = pick(
+                    &mut screen,
+                    "Copy to the clipboard (escape for nothing):",
+                    &formats,
+                    |_| false,
+                )?;
+                let text = (|| -> anyhow::Result<Option<String>> {
+                    Ok(match picked.as_deref() {
+                        Some(ID) => Some(article.id().to_string()),
+                        Some(URL) => Some(article.abs_url()),
+                        Some(PDF) => {
+                            if !source::download_or_build_pdf(
+                                article,
+                                &download_dir,
+                                client,
+                                config.build_pdf_from_source,
+                            )? {
+                                bail!("arXiv has no pdf for this article.");
+                            }
+                            Some(article.pdf_path(&download_dir).display().to_string())
+                        }
+                        Some(BIBTEX) => Some(bibtex::entry(
+                            article,
+                            &bibtex::key(
+                                article,
+                                config.bibtex.key.as_deref().unwrap_or(bibtex::DEFAULT_KEY),
+                            )?,
+                        )),
+                        _ => None,
+                    })
+                })();
+                error_message = match text {
+                    Ok(Some(text)) => match util::copy_to_clipboard(&text) {
+                        Ok(()) => format!("Copied the {} to the clipboard.", picked.unwrap()),
+                        Err(err) => format!("{err:#}"),
+                    },
+                    Ok(None) => String::new(),
+                    Err(err) => format!("{err:#}"),
+                };
+            }
             Key::Char('d') => {
                 // Open the data directory.
                 article.open_dir(base_dir)?;
                 error_message = String::new();
             }
+            Key::Char('/') => {
+                // Search the articles of this session and jump to the next match.
+                error_message = String::new();
+                let text = read_line(
+                    &mut screen,
+                    "Search the title, authors and abstract (empty to end the search):",
+                )?;
+                match text {
+                    Some(text) if !text.is_empty() => {
+                        let position = match state {
+                            Current::Read(i) => i,
+                            Current::FirstUnseen => seen.len(),
+                        };
+                        // Start the search at the current article.
+                        match find_match(
+                            &articles,
+                            &seen,
+                            &unseen_or_updated,
+                            &text,
+                            position + seen.len() + unseen_or_updated.len() - 1,
+                            true,
+                        ) {
+                            Some(target) => {
+                                state = jump_to(target, &seen, &mut unseen_or_updated);
+                            }
+                            None => {
+                                error_message =
+                                    format!("No articles in this session match {text:?}.");
+                            }
+                        }
+                        search = Some(text);
+                    }
+                    Some(_) => search = None,
+                    None => {}
+                }
+            }
+            Key::Char(c @ ('n' | 'N')) if search.is_some() => {
+                // Jump to the next or previous match.
+                let position = match state {
+                    Current::Read(i) => i,
+                    Current::FirstUnseen => seen.len(),
+                };
+                match find_match(
+                    &articles,
+                    &seen,
+                    &unseen_or_updated,
+                    search.as_ref().unwrap(),
+                    position,
+                    c == 'n',
+                ) {
+                    Some(target) => {
+                        state = jump_to(target, &seen, &mut unseen_or_updated);
+                        error_message = String::new();
+                    }
+                    None => {
+                        error_message = "No articles in this session match the search.".to_string()
+                    }
+                }
+            }
+            Key::Char('s') if matches!(state, Current::FirstUnseen) => {
+                // Move the article to the end of the queue without marking it as seen.
+                if unseen_or_updated.len() > 1 {
+                    let entry = unseen_or_updated.pop_front().unwrap();
+                    if let Some(unrefined) = &mut unrefined
+                        && let Some(i) = unrefined
+                            .unseen_or_updated
+                            .iter()
+                            .position(|(id, _, _)| *id == entry.0)
+                    {
+                        let entry = unrefined.unseen_or_updated.remove(i).unwrap();
+                        unrefined.unseen_or_updated.push_back(entry);
+                    }
+                    unseen_or_updated.push_back(entry);
+                    error_message = String::new();
+                } else {
+                    error_message = "This is the last article in the queue.".to_string();
+                }
+            }
+            Key::Char('A') if !unseen_or_updated.is_empty() => {
+                // Mark the whole queue as seen, after asking.
+                error_message = String::new();
+                let count = unseen_or_updated.len();
+                let answer = read_line(
+                    &mut screen,
+                    &format!("Mark all {count} articles left in the queue as seen? [y/N]"),
+                )?;
+                if answer.is_some_and(|a| matches!(a.trim(), "y" | "Y" | "yes")) {
+                    while let Some((id, _, _)) = unseen_or_updated.pop_front() {
+                        articles
+                            .get_mut(&id)
+                            .unwrap()
+                            .mark_as_seen(&mut seen_file)?;
+                        if let Some(unrefined) = &mut unrefined {
+                            unrefined.seen.push(id.clone());
+                            unrefined.unseen_or_updated.retain(|(i, _, _)| *i != id);
+                        }
+                        seen.push(id);
+                    }
+                    if limit.is_some()
+                        && unrefined
+                            .as_ref()
+                            .is_none_or(|u| u.unseen_or_updated.is_empty())
+                    {
+                        // We are done with this session.
+                        break;
+                    }
+                    state = Current::Read(seen.len() - 1);
+                    error_message = format!("Marked {count} articles as seen.");
+                }
+            }
+            Key::Char('g') => {
+                // Go to an article of this session by id or by its number in the status line.
+                error_message = String::new();
+                let text = read_line(
+                    &mut screen,
+                    "Go to the article with this arXiv id or number (as in \"article 12 of 300\"):",
+                )?;
+                if let Some(text) = text {
+                    let text = text.trim();
+                    let count = seen.len() + unseen_or_updated.len();
+                    let target = if let Ok(number) = text.parse::<usize>() {
+                        if (1..=count).contains(&number) {
+                            Ok(number - 1)
+                        } else {
+                            Err(format!("There are only {count} articles in this session."))
+                        }
+                    } else {
+                        match ArxivId::parse_ignoring_version(text) {
+                            Ok(id) => seen
+                                .iter()
+                                .chain(unseen_or_updated.iter().map(|(id, _, _)| id))
+                                .position(|other| *other == id)
+                                .ok_or_else(|| format!("{id} is not in this session.")),
+                            Err(err) => Err(format!("{err:#}")),
+                        }
+                    };
+                    match target {
+                        Ok(target) => state = jump_to(target, &seen, &mut unseen_or_updated),
+                        Err(err) => error_message = err,
+                    }
+                }
+            }
+            Key::Char('\t') => {
+                // Show one line per article and go to the one the user picks.
+                error_message = String::new();
+                let position = match state {
+                    Current::Read(i) => i,
+                    Current::FirstUnseen => seen.len(),
+                };
+                let lines: Vec<String> = seen
+                    .iter()
+                    .map(|id| (id, false))
+                    .chain(
+                        unseen_or_updated
+                            .iter()
+                            .map(|(id, show_updates, _)| (id, *show_updates)),
+                    )
+                    .enumerate()
+                    .map(|(i, (id, show_updates))| {
+                        let article = &articles[id];
+                        let status = if show_updates {
+                            'u'
+                        } else if article.last_seen_version() > 0 {
+                            's'
+                        } else {
+                            ' '
+                        };
+                        let bookmark = if article.is_bookmarked() { 'b' } else { ' ' };
+                        let title = share::one_line(article.title());
+                        let title = if latex_to_unicode {
+                            unicodeit::replace(&title)
+                        } else {
+                            title
+                        };
+                        format!("{:>5} {status}{bookmark} {id:<16} {title}", i + 1)
+                    })
+                    .collect();
+                if let Some(target) = overview(&mut screen, &lines, position)? {
+                    state = jump_to(target, &seen, &mut unseen_or_updated);
+                }
+            }
+            Key::Esc if search.is_some() => {
+                search = None;
+                error_message = String::new();
+            }
             Key::Char('n') => {
                 // Show cursor and switch to main screen before starting the editor.
                 write!(
@@ -343,6 +925,7 @@ pub fn interact(
                 screen.flush()?;
                 // Relay any errors from the editor.
                 res?;
+                save(conn, base_dir, |tr| article.reload_state(base_dir, tr))?;
                 error_message = String::new();
             }
             Key::Char('u') => {
@@ -350,6 +933,26 @@ pub fn interact(
                 latex_to_unicode = !latex_to_unicode;
                 error_message = String::new();
             }
+            Key::Char('#') => {
+                // Let the user search for a tag (or enter a new one) and toggle it.
+                let candidates: Vec<String> = known_tags.iter().map(|t| t.to_string()).collect();
+                let picked = pick(
+                    &mut screen,
+                    "Toggle a tag (type to search or to enter a new tag):",
+                    &candidates,
+                    |tag| article.tags().iter().any(|t| t.0 == tag),
+                )?;
+                error_message = String::new();
+                if let Some(picked) = picked {
+                    match picked.parse::<TagName>() {
+                        Ok(tag) => {
+                            save(conn, base_dir, |tr| article.toggle_tag(base_dir, tr, &tag))?;
+                            known_tags.insert(tag);
+                        }
+                        Err(err) => error_message = format!("{err:#}"),
+                    }
+                }
+            }
             Key::End if update_filter.is_none() => {
                 state = Current::Read(seen.len() - 1);
                 error_message = String::new();
@@ -375,8 +978,21 @@ pub fn interact(
                         article.mark_as_seen(&mut seen_file)?;
                         seen.push(article.id().clone());
                         unseen_or_updated.pop_front();
+                        if let Some(unrefined) = &mut unrefined {
+                            unrefined.seen.push(article.id().clone());
+                            unrefined
+                                .unseen_or_updated
+                                .retain(|(id, _, _)| id != article.id());
+                        }
                         if !unseen_or_updated.is_empty() {
                             Current::FirstUnseen
+                        } else if limit.is_some()
+                            && unrefined
+                                .as_ref()
+                                .is_none_or(|u| u.unseen_or_updated.is_empty())
+                        {
+                            // We are done with this session.
+                            break;
                         } else {
                             Current::Read(seen.len() - 1)
                         }
@@ -384,6 +1000,164 @@ pub fn interact(
                 };
                 error_message = String::new();
             }
+            Key::Up => {
+                scroll = scroll.saturating_sub(1);
+                error_message = String::new();
+            }
+            Key::Down => {
+                scroll += 1;
+                error_message = String::new();
+            }
+            // Scroll first if the article does not fit on the screen.
+            Key::PageDown if scroll < max_scroll => {
+                scroll += page;
+                error_message = String::new();
+            }
+            Key::PageUp if scroll > 0 => {
+                scroll = scroll.saturating_sub(page);
+                error_message = String::new();
+            }
+            Key::PageDown if matches!(state, Current::FirstUnseen) => {
+                // Skip the remaining articles of this day and group without marking them
+                // as seen.
+                let (id, show_updates, group) = unseen_or_updated.front().unwrap();
+                let day = announcement_day(&articles[id], *show_updates).to_string();
+                let group = *group;
+                while unseen_or_updated
+                    .front()
+                    .is_some_and(|(id, show_updates, g)| {
+                        *g == group && announcement_day(&articles[id], *show_updates) == day
+                    })
+                {
+                    let (id, _, _) = unseen_or_updated.pop_front().unwrap();
+                    if let Some(unrefined) = &mut unrefined {
+                        unrefined.unseen_or_updated.retain(|(i, _, _)| *i != id);
+                    }
+                }
+                if unseen_or_updated.is_empty()
+                    && seen.is_empty()
+                    && let Some(u) = unrefined.take()
+                {
+                    // Nothing matching the refinement is left.
+                    seen = u.seen;
+                    unseen_or_updated = u.unseen_or_updated;
+                }
+                if !unseen_or_updated.is_empty() {
+                    state = Current::FirstUnseen;
+                } else if limit.is_some() || seen.is_empty() {
+                    break;
+                } else {
+                    state = Current::Read(seen.len() - 1);
+                }
+                error_message = String::new();
+            }
+            Key::PageDown if sections && matches!(state, Current::Read(_)) => {
+                let Current::Read(i) = state else {
+                    unreachable!()
+                };
+                let (_, end) = section_bounds(&articles, &seen, i);
+                if end < seen.len() {
+                    state = Current::Read(end);
+                } else if !unseen_or_updated.is_empty() {
+                    state = Current::FirstUnseen;
+                }
+                error_message = String::new();
+            }
+            Key::PageUp if sections && matches!(state, Current::Read(_)) => {
+                let Current::Read(i) = state else {
+                    unreachable!()
+                };
+                let (start, _) = section_bounds(&articles, &seen, i);
+                // Go to the start of this section, or of the previous one if we are there.
+                state = if start < i || start == 0 {
+                    Current::Read(start)
+                } else {
+                    Current::Read(section_bounds(&articles, &seen, start - 1).0)
+                };
+                error_message = String::new();
+            }
+            Key::Char(' ') => {
+                // Toggle whether the article is selected.
+                match selection.iter().position(|id| id == article.id()) {
+                    Some(i) => {
+                        selection.remove(i);
+                    }
+                    None => selection.push(article.id().clone()),
+                }
+                error_message = String::new();
+            }
+            Key::Char('f') => {
+                // Only show the articles in the queue that also match a filter.
+                let current_id = article.id().clone();
+                error_message = String::new();
+                let text = read_line(
+                    &mut screen,
+                    "Only show articles also matching (see `arxiv-reader help find`):",
+                )?;
+                if let Some(text) = text {
+                    match text
+                        .parse::<Filter>()
+                        .and_then(|f| db::with_transaction(conn, base_dir, |tr| f.prepare(&tr)))
+                    {
+                        Ok(new) => {
+                            let (description, filter, all_seen, all_unseen_or_updated) =
+                                match &unrefined {
+                                    Some(u) => (
+                                        format!("{} && {text}", u.description),
+                                        u.filter.clone().and(new),
+                                        &u.seen,
+                                        &u.unseen_or_updated,
+                                    ),
+                                    None => (text, new, &seen, &unseen_or_updated),
+                                };
+                            let refined_seen: Vec<ArxivId> = all_seen
+                                .iter()
+                                .filter(|id| filter.matches(&articles[*id]))
+                                .cloned()
+                                .collect();
+                            let refined_unseen_or_updated: VecDeque<_> = all_unseen_or_updated
+                                .iter()
+                                .filter(|(id, _, _)| filter.matches(&articles[id]))
+                                .cloned()
+                                .collect();
+                            if refined_seen.is_empty() && refined_unseen_or_updated.is_empty() {
+                                error_message =
+                                    "No articles in the queue match this filter.".to_string();
+                            } else {
+                                match &mut unrefined {
+                                    Some(u) => {
+                                        u.description = description;
+                                        u.filter = filter;
+                                    }
+                                    None => {
+                                        unrefined = Some(Unrefined {
+                                            description,
+                                            filter,
+                                            seen: std::mem::take(&mut seen),
+                                            unseen_or_updated: std::mem::take(
+                                                &mut unseen_or_updated,
+                                            ),
+                                        })
+                                    }
+                                }
+                                seen = refined_seen;
+                                unseen_or_updated = refined_unseen_or_updated;
+                                state = locate(&current_id, &seen, &unseen_or_updated);
+                            }
+                        }
+                        Err(err) => error_message = format!("{err:#}"),
+                    }
+                }
+            }
+            Key::Char('F') if unrefined.is_some() => {
+                // Show the complete queue again.
+                let current_id = article.id().clone();
+                let u = unrefined.take().unwrap();
+                seen = u.seen;
+                unseen_or_updated = u.unseen_or_updated;
+                state = locate(&current_id, &seen, &unseen_or_updated);
+                error_message = String::new();
+            }
             Key::Left => {
                 // Go the the previous article.
                 state = match state {
@@ -405,16 +1179,375 @@ pub fn interact(
                 error_message = String::new();
             }
             Key::Char(c) => {
+                let mut is_tag = false;
                 for (shortcut, name) in &config.tags {
                     if c == *shortcut {
                         // Toggle tag.
-                        article.toggle_tag(base_dir, name)?;
+                        save(conn, base_dir, |tr| article.toggle_tag(base_dir, tr, name))?;
                         error_message = String::new();
+                        is_tag = true;
                     }
                 }
+                if !is_tag && let Some(command) = config.commands.get(&c) {
+                    // Run the user's command and show its output.
+                    error_message = match article.run_command(base_dir, &download_dir, command) {
+                        Ok(output) => output,
+                        Err(err) => format!("{err:#}"),
+                    };
+                    // The command might have changed the tags or notes.
+                    save(conn, base_dir, |tr| article.reload_state(base_dir, tr))?;
+                }
             }
             _ => {}
         }
     }
+
+    if selection.is_empty() {
+        return Ok(());
+    }
+    const ADD_TAG: &str = "add a tag";
+    const MARK_SEEN: &str = "mark as seen";
+    const PREFETCH: &str = "download the pdfs";
+    const EXPORT: &str = "print as a Markdown list";
+    let actions = [ADD_TAG, MARK_SEEN, PREFETCH, EXPORT].map(String::from);
+    let action = pick(
+        &mut screen,
+        &format!(
+            "What to do with the {} selected articles (escape for nothing)?",
+            selection.len()
+        ),
+        &actions,
+        |_| false,
+    )?;
+    match action.as_deref() {
+        Some(ADD_TAG) => {
+            let candidates: Vec<String> = known_tags.iter().map(|t| t.to_string()).collect();
+            if let Some(tag) = pick(
+                &mut screen,
+                "Add which tag (type to search or to enter a new tag)?",
+                &candidates,
+                |_| false,
+            )? {
+                drop(screen);
+                let tag: TagName = tag.parse()?;
+                save(conn, base_dir, |tr| {
+                    for id in &selection {
+                        let article = articles.get_mut(id).unwrap();
+                        if !article.tags().contains(&tag) {
+                            article.toggle_tag(base_dir, tr, &tag)?;
+                        }
+                    }
+                    Ok(())
+                })?;
+                message!("Tagged {} articles with {tag}.", selection.len());
+            }
+        }
+        Some(MARK_SEEN) => {
+            drop(screen);
+            for id in &selection {
+                articles.get_mut(id).unwrap().mark_as_seen(&mut seen_file)?;
+            }
+            message!("Marked {} articles as seen.", selection.len());
+        }
+        Some(PREFETCH) => {
+            drop(screen);
+            // Articles without a pdf on arXiv are built from the sources one at a time.
+            let (with_pdf, without_pdf): (Vec<&Article>, Vec<&Article>) = selection
+                .iter()
+                .map(|id| &articles[id])
+                .partition(|a| a.last_version().probably_has_pdf());
+            let files: Vec<(&Article, bool)> = with_pdf.into_iter().map(|a| (a, false)).collect();
+            let (mut downloaded, mut failed) = save(conn, base_dir, |tr| {
+                downloads::download_all(
+                    tr,
+                    &download_dir,
+                    client,
+                    &files,
+                    config
+                        .network
+                        .concurrency
+                        .unwrap_or(rate_limited_client::DEFAULT_CONCURRENCY),
+                )
+            })?;
+            for article in without_pdf {
+                match source::download_or_build_pdf(
+                    article,
+                    &download_dir,
+                    client,
+                    config.build_pdf_from_source,
+                ) {
+                    Ok(true) => downloaded += 1,
+                    Ok(false) => message!("arXiv has no pdf for {}.", article.id()),
+                    Err(err) => {
+                        message!("{}: {err:#}", article.id());
+                        failed += 1;
+                    }
+                }
+            }
+            message!("Downloaded {downloaded} pdfs.");
+            if failed > 0 {
+                message!("{failed} downloads failed.");
+            }
+        }
+        Some(EXPORT) => {
+            drop(screen);
+            for id in &selection {
+                let article = &articles[id];
+                let convert = |s: &str| {
+                    if latex_to_unicode {
+                        unicodeit::replace(s)
+                    } else {
+                        s.to_string()
+                    }
+                };
+                output::write(format_args!(
+                    "- {}",
+                    share::text(
+                        article,
+                        ShareFormat::Markdown,
+                        None,
+                        &config.authors,
+                        convert
+                    )
+                ));
+            }
+        }
+        _ => {}
+    }
     Ok(())
 }
+
+/// The day on which `pull` first encountered the article, or its last version if
+/// `show_updates` is set, as in "2025-11-03". This is usually the day arXiv announced it.
+fn announcement_day(article: &Article, show_updates: bool) -> &str {
+    let version = if show_updates {
+        article.last_version()
+    } else {
+        article.first_version()
+    };
+    &version.first_encounter
+}
+
+/// Formats a day such as "2025-11-03" as "Monday, 3 Nov".
+fn format_day(day: &str) -> String {
+    match NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+        Ok(date) => date.format("%A, %-d %b").to_string(),
+        Err(_) => day.to_string(),
+    }
+}
+
+/// Whether the title, authors or abstract of the article contain the query (ignoring case).
+fn matches_search(article: &Article, query: &str) -> bool {
+    let query = query.to_lowercase();
+    [article.title(), article.authors(), article.abstract_()]
+        .iter()
+        .any(|text| text.to_lowercase().contains(&query))
+}
+
+/// The position of the next (or previous, if `forward` is false) article after the one at
+/// `position` that matches the search, wrapping around. Positions count the seen articles
+/// and then the queue. The article at `position` itself is only found if it is the only
+/// match.
+fn find_match(
+    articles: &HashMap<ArxivId, Article>,
+    seen: &[ArxivId],
+    unseen_or_updated: &VecDeque<(ArxivId, bool, usize)>,
+    query: &str,
+    position: usize,
+    forward: bool,
+) -> Option<usize> {
+    let count = seen.len() + unseen_or_updated.len();
+    (1..=count)
+        .map(|step| {
+            if forward {
+                (position + step) % count
+            } else {
+                (position + count - step % count) % count
+            }
+        })
+        .find(|&i| {
+            let id = match seen.get(i) {
+                Some(id) => id,
+                None => &unseen_or_updated[i - seen.len()].0,
+            };
+            matches_search(&articles[id], query)
+        })
+}
+
+/// The section of a seen article (and its name) if the seen articles are divided into
+/// sections.
+fn section_of(article: &Article) -> ((bool, String), String) {
+    if article.is_bookmarked() {
+        ((false, String::new()), "bookmarked".to_string())
+    } else {
+        let category = article.primary_category().clone();
+        ((true, category.clone()), category)
+    }
+}
+
+/// The range of indices in `seen` of the articles in the same section as the i-th one.
+fn section_bounds(
+    articles: &HashMap<ArxivId, Article>,
+    seen: &[ArxivId],
+    i: usize,
+) -> (usize, usize) {
+    let section = section_of(&articles[&seen[i]]).0;
+    let same = |j: &usize| section_of(&articles[&seen[*j]]).0 == section;
+    let start = (0..i).rev().take_while(same).last().unwrap_or(i);
+    let end = (i + 1..seen.len()).take_while(same).last().unwrap_or(i) + 1;
+    (start, end)
+}
+
+/// Lets the user choose one of the candidates by typing some of its characters in order.
+/// The candidates for which `mark` returns true are shown with a cross. Returns the
+/// selected candidate, the typed text if no candidate matches, or `None` if the user
+/// cancelled with escape.
+fn pick(
+    screen: &mut Screen,
+    title: &str,
+    candidates: &[String],
+    mark: impl Fn(&str) -> bool,
+) -> anyhow::Result<Option<String>> {
+    let mut query = String::new();
+    let mut selected = 0;
+    screen.activate_raw_mode()?;
+    let res = loop {
+        let (_, height) = termion::terminal_size().context("retrieving terminal size")?;
+        let mut matches: Vec<(usize, &String)> = candidates
+            .iter()
+            .filter_map(|c| fuzzy_score(&query, c).map(|score| (score, c)))
+            .collect();
+        matches.sort_by_key(|(score, c)| (*score, c.len(), *c));
+        matches.truncate((height as usize).saturating_sub(3).max(1));
+        selected = selected.min(matches.len().saturating_sub(1));
+        write!(
+            screen,
+            "{}{}{title}\r\n> {query}\r\n",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+        )?;
+        for (i, (_, candidate)) in matches.iter().enumerate() {
+            let cursor = if i == selected { '>' } else { ' ' };
+            let cross = if mark(candidate) { 'x' } else { ' ' };
+            write!(screen, "\r\n{cursor} [{cross}] {candidate}")?;
+        }
+        screen.flush()?;
+        let key = match stdin().keys().next() {
+            Some(key) => key?,
+            None => break None,
+        };
+        match key {
+            Key::Esc => break None,
+            Key::Char('\n') => {
+                break match matches.get(selected) {
+                    Some((_, candidate)) => Some(candidate.to_string()),
+                    None if !query.is_empty() => Some(query),
+                    None => None,
+                };
+            }
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down => selected += 1,
+            Key::Backspace => {
+                query.pop();
+                selected = 0;
+            }
+            Key::Char(c) => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => {}
+        }
+    };
+    screen.suspend_raw_mode()?;
+    Ok(res)
+}
+
+/// Shows the lines (one per article of the session) and lets the user move a cursor,
+/// starting at `current`. Returns the index of the line picked with enter, or `None` if
+/// the user cancelled with escape or tab.
+fn overview(
+    screen: &mut Screen,
+    lines: &[String],
+    current: usize,
+) -> anyhow::Result<Option<usize>> {
+    let mut selected = current.min(lines.len().saturating_sub(1));
+    // The index of the first line shown.
+    let mut top = 0;
+    screen.activate_raw_mode()?;
+    let res = loop {
+        let (width, height) = termion::terminal_size().context("retrieving terminal size")?;
+        let page = (height as usize).saturating_sub(3).max(1);
+        if selected < top {
+            top = selected;
+        } else if selected >= top + page {
+            top = selected + 1 - page;
+        }
+        write!(
+            screen,
+            "{}{}Articles {}-{} of {} (s: seen, u: updated, b: bookmarked; enter to show, escape to go back)\r\n",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+            top + 1,
+            min(top + page, lines.len()),
+            lines.len()
+        )?;
+        for (i, line) in lines.iter().enumerate().skip(top).take(page) {
+            let cursor = if i == selected { '>' } else { ' ' };
+            let line: String = format!("{cursor}{line}")
+                .chars()
+                .take(width as usize)
+                .collect();
+            write!(screen, "\r\n{line}")?;
+        }
+        screen.flush()?;
+        let key = match stdin().keys().next() {
+            Some(key) => key?,
+            None => break None,
+        };
+        match key {
+            Key::Esc | Key::Char('\t') => break None,
+            Key::Char('\n') => break Some(selected),
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down => selected = min(selected + 1, lines.len() - 1),
+            Key::PageUp => selected = selected.saturating_sub(page),
+            Key::PageDown => selected = min(selected + page, lines.len() - 1),
+            Key::Home => selected = 0,
+            Key::End => selected = lines.len() - 1,
+            _ => {}
+        }
+    };
+    screen.suspend_raw_mode()?;
+    Ok(res)
+}
+
+/// Reads a line of text. Returns `None` if the user cancelled with escape.
+fn read_line(screen: &mut Screen, prompt: &str) -> anyhow::Result<Option<String>> {
+    let mut line = String::new();
+    screen.activate_raw_mode()?;
+    write!(screen, "{}", termion::cursor::Show)?;
+    let res = loop {
+        write!(
+            screen,
+            "{}{}{prompt}\r\n> {line}",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+        )?;
+        screen.flush()?;
+        let key = match stdin().keys().next() {
+            Some(key) => key?,
+            None => break None,
+        };
+        match key {
+            Key::Esc => break None,
+            Key::Char('\n') => break Some(line),
+            Key::Backspace => {
+                line.pop();
+            }
+            Key::Char(c) => line.push(c),
+            _ => {}
+        }
+    };
+    write!(screen, "{}", termion::cursor::Hide)?;
+    screen.suspend_raw_mode()?;
+    Ok(res)
+}
@@ -0,0 +1,371 @@
+//! Keywords of articles, chosen by tf-idf from the title and abstract.
+//!
+//! A word is a good keyword of an article if it appears often in the article and rarely
+//! in the other articles in the database. The keywords are saved in the keyword table
+//! by [`update`] and therefore depend on the articles that were in the database then.
+//! If the way they are chosen changes (such as the stopwords), all of them are computed
+//! again by the next [`update`].
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use rusqlite::{Transaction, params};
+
+use crate::article::{ArticleMetadata, ArxivId};
+
+/// The number of keywords saved per article.
+pub const KEYWORDS_PER_ARTICLE: usize = 5;
+
+/// Words that say nothing about the topic of an article.
+const STOPWORDS: &[&str] = &[
+    "about",
+    "above",
+    "after",
+    "again",
+    "all",
+    "also",
+    "among",
+    "and",
+    "any",
+    "are",
+    "arbitrary",
+    "based",
+    "been",
+    "before",
+    "being",
+    "between",
+    "both",
+    "but",
+    "can",
+    "case",
+    "cases",
+    "certain",
+    "consider",
+    "could",
+    "does",
+    "each",
+    "either",
+    "establish",
+    "every",
+    "first",
+    "following",
+    "for",
+    "from",
+    "further",
+    "general",
+    "give",
+    "given",
+    "gives",
+    "has",
+    "have",
+    "here",
+    "how",
+    "however",
+    "into",
+    "its",
+    "large",
+    "main",
+    "many",
+    "may",
+    "more",
+    "most",
+    "much",
+    "new",
+    "not",
+    "note",
+    "obtain",
+    "one",
+    "only",
+    "other",
+    "our",
+    "over",
+    "paper",
+    "particular",
+    "present",
+    "problem",
+    "prove",
+    "provide",
+    "result",
+    "results",
+    "second",
+    "several",
+    "show",
+    "some",
+    "such",
+    "than",
+    "that",
+    "the",
+    "their",
+    "them",
+    "then",
+    "there",
+    "these",
+    "they",
+    "this",
+    "those",
+    "three",
+    "through",
+    "two",
+    "under",
+    "use",
+    "used",
+    "using",
+    "various",
+    "very",
+    "was",
+    "we",
+    "well",
+    "were",
+    "what",
+    "when",
+    "where",
+    "whether",
+    "which",
+    "while",
+    "who",
+    "whose",
+    "will",
+    "with",
+    "within",
+    "without",
+    "work",
+    "would",
+];
+
+/// Splits a title or abstract into lowercase words, leaving out formulas, latex commands,
+/// short words and stopwords.
+pub fn words(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut in_math = false;
+    let mut word = String::new();
+    let mut command = false;
+    let finish = |word: &mut String, command: bool, in_math: bool, words: &mut Vec<String>| {
+        if !command && !in_math && word.chars().count() >= 3 {
+            let lower = word.to_lowercase();
+            if !STOPWORDS.contains(&lower.as_str()) {
+                words.push(lower);
+            }
+        }
+        word.clear();
+    };
+    for c in text.chars() {
+        if c.is_alphabetic() {
+            word.push(c);
+            continue;
+        }
+        finish(&mut word, command, in_math, &mut words);
+        command = c == '\\';
+        if c == '$' {
+            in_math = !in_math;
+        }
+    }
+    finish(&mut word, command, in_math, &mut words);
+    words
+}
+
+/// The words of the title (which count twice) and the abstract of an article.
+fn article_words(metadata: &ArticleMetadata) -> Vec<String> {
+    let title = words(&metadata.title);
+    let mut res = title.clone();
+    res.extend(title);
+    res.extend(words(&metadata.abstract_));
+    res
+}
+
+/// The words of an article without repetitions, in the order of first appearance.
+fn distinct_words(words: &[String]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    words
+        .iter()
+        .map(String::as_str)
+        .filter(|w| seen.insert(*w))
+        .collect()
+}
+
+/// Chooses the keywords of an article among its words that appear in at least two (but
+/// not all) of the `total` articles, given the number of articles containing each of its
+/// words. Returns the keywords with the most important one first.
+fn choose(
+    words: &[String],
+    document_frequency: &HashMap<&str, usize>,
+    total: usize,
+) -> Vec<String> {
+    let mut term_frequency: HashMap<&str, usize> = HashMap::new();
+    for w in words {
+        *term_frequency.entry(w).or_default() += 1;
+    }
+    let mut scored: Vec<(f64, &str)> = term_frequency
+        .into_iter()
+        .filter(|(w, _)| document_frequency[w] >= 2)
+        .map(|(w, tf)| {
+            let idf = (total as f64 / document_frequency[w] as f64).ln();
+            (tf as f64 * idf, w)
+        })
+        // Words appearing in all articles do not help.
+        .filter(|(score, _)| *score > 0.0)
+        .collect();
+    scored.sort_by(|(s1, w1), (s2, w2)| s2.total_cmp(s1).then(w1.cmp(w2)));
+    scored
+        .into_iter()
+        .take(KEYWORDS_PER_ARTICLE)
+        .map(|(_, w)| w.to_string())
+        .collect()
+}
+
+/// What the keywords depend on besides the articles, saved with them in the setting table.
+fn settings() -> String {
+    serde_json::json!({
+        "keywords_per_article": KEYWORDS_PER_ARTICLE,
+        "stopwords": STOPWORDS,
+    })
+    .to_string()
+}
+
+/// Computes the keywords of the articles with the given ids (or of all articles) against
+/// all articles in the database and saves them. If the saved keywords were chosen with
+/// other settings, those of all articles are computed. Returns the number of articles.
+///
+/// The number of articles containing each word is kept in the keyword_frequency table,
+/// so that only the given articles have to be read.
+pub fn update(tr: &Transaction, ids: Option<&HashSet<ArxivId>>) -> anyhow::Result<usize> {
+    let settings = settings();
+    let mut get = tr.prepare_cached("SELECT value FROM setting WHERE key = 'keywords'")?;
+    let saved: Option<String> = get.query(())?.next()?.map(|row| row.get(0)).transpose()?;
+    let Some(ids) = ids.filter(|_| saved.as_deref() == Some(settings.as_str())) else {
+        return update_all(tr, &settings);
+    };
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let mut get_words = tr.prepare_cached("SELECT words FROM keyword WHERE id = ?1")?;
+    let mut add = tr.prepare_cached(
+        "INSERT INTO keyword_frequency (word, articles) VALUES (?1, ?2) ON CONFLICT (word) DO UPDATE SET articles = articles + ?2",
+    )?;
+    let mut save = tr.prepare_cached(
+        "INSERT OR REPLACE INTO keyword (id, terms, words) VALUES (?1, '[]', ?2)",
+    )?;
+    // First count the words of all given articles, which may be new or have changed.
+    let mut words_of = HashMap::new();
+    for id in ids {
+        let Some(metadata) = ArticleMetadata::load_one(tr, id)? else {
+            continue;
+        };
+        let old: Option<String> = get_words
+            .query(params![id.to_string()])?
+            .next()?
+            .map(|row| row.get(0))
+            .transpose()?;
+        if let Some(old) = old {
+            let old: Vec<String> =
+                serde_json::from_str(&old).with_context(|| format!("parsing words of {id}"))?;
+            for w in old {
+                add.execute(params![w, -1])?;
+            }
+        }
+        let words = article_words(&metadata);
+        let distinct = distinct_words(&words);
+        for w in &distinct {
+            add.execute(params![w, 1])?;
+        }
+        save.execute(params![id.to_string(), serde_json::to_string(&distinct)?])?;
+        words_of.insert(id, words);
+    }
+    tr.execute("DELETE FROM keyword_frequency WHERE articles <= 0", ())?;
+    let total: usize = tr.query_one("SELECT COUNT(*) FROM keyword", (), |row| row.get(0))?;
+    let mut get_frequency =
+        tr.prepare_cached("SELECT articles FROM keyword_frequency WHERE word = ?1")?;
+    let mut set_terms = tr.prepare_cached("UPDATE keyword SET terms = ?2 WHERE id = ?1")?;
+    for (id, words) in &words_of {
+        let mut document_frequency = HashMap::new();
+        for w in distinct_words(words) {
+            let articles = get_frequency.query_one(params![w], |row| row.get(0))?;
+            document_frequency.insert(w, articles);
+        }
+        let terms = choose(words, &document_frequency, total);
+        set_terms.execute(params![id.to_string(), serde_json::to_string(&terms)?])?;
+    }
+    Ok(words_of.len())
+}
+
+/// Computes the keywords and word counts of all articles again.
+fn update_all(tr: &Transaction, settings: &str) -> anyhow::Result<usize> {
+    tr.execute(
+        "INSERT OR REPLACE INTO setting (key, value) VALUES ('keywords', ?1)",
+        params![settings],
+    )?;
+    tr.execute("DELETE FROM keyword", ())?;
+    tr.execute("DELETE FROM keyword_frequency", ())?;
+    let words: HashMap<ArxivId, Vec<String>> = ArticleMetadata::load(tr)?
+        .into_iter()
+        .map(|(id, m)| {
+            let words = article_words(&m);
+            (id, words)
+        })
+        .collect();
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for ws in words.values() {
+        for w in distinct_words(ws) {
+            *document_frequency.entry(w).or_default() += 1;
+        }
+    }
+    let mut add =
+        tr.prepare_cached("INSERT INTO keyword_frequency (word, articles) VALUES (?1, ?2)")?;
+    for (w, articles) in &document_frequency {
+        add.execute(params![w, articles])?;
+    }
+    let mut save =
+        tr.prepare_cached("INSERT INTO keyword (id, terms, words) VALUES (?1, ?2, ?3)")?;
+    for (id, ws) in &words {
+        let terms = choose(ws, &document_frequency, words.len());
+        save.execute(params![
+            id.to_string(),
+            serde_json::to_string(&terms)?,
+            serde_json::to_string(&distinct_words(ws))?
+        ])?;
+    }
+    Ok(words.len())
+}
+
+/// Loads the saved keywords of all articles.
+pub fn load(tr: &Transaction) -> anyhow::Result<HashMap<ArxivId, Vec<String>>> {
+    let mut get = tr.prepare_cached("SELECT id, terms FROM keyword")?;
+    let mut rows = get.query(())?;
+    let mut res = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let terms: String = row.get(1)?;
+        let terms =
+            serde_json::from_str(&terms).with_context(|| format!("parsing keywords of {id}"))?;
+        res.insert(id.parse()?, terms);
+    }
+    Ok(res)
+}
+
+/// Loads the saved keywords of a single article.
+pub fn load_one(tr: &Transaction, id: &ArxivId) -> anyhow::Result<Vec<String>> {
+    let mut get = tr.prepare_cached("SELECT terms FROM keyword WHERE id = ?1")?;
+    let terms: Option<String> = get
+        .query(params![id.to_string()])?
+        .next()?
+        .map(|row| row.get(0))
+        .transpose()?;
+    match terms {
+        Some(terms) => {
+            serde_json::from_str(&terms).with_context(|| format!("parsing keywords of {id}"))
+        }
+        None => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_words() {
+        assert_eq!(
+            words(r"We prove that $\zeta(s) \neq 0$ for \emph{all} Dirichlet L-functions"),
+            vec!["dirichlet", "functions"]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Library behind the `arxiv-reader` command-line program.
+//!
+//! It can be used to harvest article metadata from arXiv ([`oai`]), store it in a local
+//! sqlite database ([`db`], [`article::ArticleMetadata`]), combine it with the user's
+//! tags and notes ([`article::Article`]), and select articles with [`filter::Filter`].
+//! The `arxiv-reader` binary is a command-line interface on top of it.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use arxiv_reader::{
+//!     article::Article,
+//!     db,
+//!     filter::Filter,
+//!     oai::{self, Harvest},
+//!     rate_limited_client::Client,
+//! };
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let base_dir = Path::new("/home/me/.arxiv-reader");
+//! let mut conn = db::open(base_dir)?;
+//! let mut client = Client::new();
+//! let new_ids = oai::download_changes(base_dir, &mut conn, "math.NT", &mut client, Harvest::Records)?;
+//! println!("{} new articles", new_ids.len());
+//! let filter: Filter = "title 'twin primes' && !seen".parse()?;
+//! db::with_transaction(&mut conn, base_dir, |tr| {
+//!     let filter = filter.prepare(&tr)?;
+//!     for article in Article::load_matching(base_dir, &tr, &filter)?.values() {
+//!         println!("{}  {}", article.id(), article.title());
+//!     }
+//!     Ok(())
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod article;
+pub mod arxiv_api;
+pub mod authors;
+pub mod bibtex;
+pub mod classes;
+pub mod compress;
+pub mod config;
+pub mod crossref;
+pub mod db;
+pub mod doctor;
+pub mod downloads;
+pub mod error;
+pub mod feed;
+pub mod filter;
+pub mod fulltext;
+pub mod graph;
+pub mod interact;
+pub mod keywords;
+pub mod markdown;
+pub mod oai;
+pub mod output;
+pub mod progress;
+pub mod pull;
+pub mod rate_limited_client;
+pub mod report;
+pub mod share;
+pub mod source;
+pub mod state;
+pub mod stats;
+pub mod sync;
+pub mod trends;
+pub mod util;
+pub mod webhooks;
+
+use clap::ValueEnum;
+
+use crate::{
+    article::Article,
+    authors::{author_sort_key, collation_key},
+};
+
+/// How to sort articles.
+#[derive(ValueEnum, Copy, Clone)]
+pub enum Order {
+    /// By the date of submission of the first version.
+    Date,
+    /// In the order in which the user first saw them.
+    Seen,
+    /// Alphabetically by title.
+    Title,
+    /// Alphabetically by the family names of the authors.
+    Authors,
+}
+
+impl Order {
+    pub fn sort(self, articles: &mut [Article]) {
+        match self {
+            Order::Date => articles.sort_by_key(|a| a.first_version().date),
+            Order::Seen => articles.sort_by_key(|a| a.last_seen_at()),
+            Order::Title => articles.sort_by_cached_key(|a| collation_key(a.title())),
+            Order::Authors => articles.sort_by_cached_key(|a| author_sort_key(a.authors())),
+        }
+    }
+}
@@ -1,36 +1,74 @@
-mod article;
-mod bibtex;
-mod config;
-mod db;
-mod filter;
-mod interact;
-mod oai;
-mod rate_limited_client;
-mod util;
+mod completions;
 
 use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashSet},
     fs::{OpenOptions, create_dir},
     io::{Write, stdout},
     path::{Path, PathBuf},
-    process::Command,
+    process::ExitCode,
+    time::Duration,
 };
 
-use anyhow::{Context, bail};
+use anyhow::{Context, anyhow, bail};
 use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 
-use crate::{
-    article::{Article, ArxivId},
-    config::{Config, Highlight, TagName},
-    filter::Filter,
-    rate_limited_client::Client,
+use arxiv_reader::{
+    Order,
+    article::{Article, ArticleMetadata, ArticleState, ArxivId, gzip_looks_valid, pdf_looks_valid},
+    authors::{AuthorClusters, format_authors},
+    bibtex,
+    classes::Scheme,
+    compress::{self, Compression},
+    config::{AuthorList, Config, GcPolicy, Highlight, TagName},
+    crossref,
+    db::{self, DumpFormat},
+    doctor,
+    downloads::{self, DownloadDir},
+    error::{self, ConfigError, NoMatches},
+    feed::{self, FeedFormat, LastPull},
+    filter::{self, Filter},
+    fulltext,
+    graph::{Graph, GraphFormat},
+    interact, keywords, message, oai, output, pull,
+    rate_limited_client::{self, Client},
+    report,
+    share::{self, ShareFormat},
+    source, state,
+    stats::Stats,
+    sync, trends,
+    util::{self, OpenAs},
 };
 
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version, about, after_help = error::EXIT_CODES)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Save all responses from arXiv in this directory (for debugging and testing).
+    #[arg(long, global = true, value_hint = clap::ValueHint::DirPath, value_name = "DIR")]
+    record: Option<PathBuf>,
+    /// Do not access the network, but replay the responses recorded with --record.
+    #[arg(long, global = true, value_hint = clap::ValueHint::DirPath, value_name = "DIR", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+    /// With json, print machine-readable results on stdout and everything else on stderr.
+    /// Supported by pull, news --print, news --feed, find, show, du, gc, verify, published, authors, trends, stats, todos, msc-tree, state import, bibtex check, doctor, sync and database merge-seen.
+    #[arg(long, global = true, default_value = "text")]
+    output: OutputFormat,
+    /// Download large files without asking (see downloads.confirm_above_mb in the config file).
+    #[arg(long, global = true)]
+    allow_large: bool,
+    /// Do not show progress bars and status lines, only results and errors (for example
+    /// in cron jobs).
+    #[arg(long, short, global = true)]
+    quiet: bool,
+}
+
+#[derive(ValueEnum, Copy, Clone)]
+enum OutputFormat {
+    Text,
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -44,6 +82,37 @@ enum Commands {
         /// How to sort the older (seen) articles.
         #[arg(long, default_value = "seen")]
         sort_by: Order,
+        /// Reverse the sort order of the older (seen) articles.
+        #[arg(short, long)]
+        reverse: bool,
+        /// Only show the N oldest unseen articles and quit once they have been seen.
+        #[arg(short, long, value_name = "N")]
+        limit: Option<usize>,
+        /// Only show articles additionally satisfying this condition.
+        ///
+        /// See `arxiv-reader help find` for the syntax.
+        #[arg(short, long, value_hint = clap::ValueHint::Other)]
+        filter: Option<Filter>,
+        /// Use the condition given by --filter instead of filters.new from the config file.
+        #[arg(long, requires = "filter")]
+        replace_filter: bool,
+        /// Show the unseen articles grouped by the category in the config file that
+        /// delivered them.
+        #[arg(short, long)]
+        group: bool,
+        /// Divide the seen articles into sections: bookmarked ones first, then the others
+        /// by primary category.
+        #[arg(long)]
+        sections: bool,
+        /// Print the unseen and updated articles (one per line, or as JSON with --output json)
+        /// instead of showing them. Nothing is marked as seen.
+        #[arg(short, long, conflicts_with_all = ["group", "sections", "sort_by", "reverse"])]
+        print: bool,
+        /// Print a feed of the articles that arrived in the pulls of the last 7 days and match
+        /// the filter (whether seen or not), for feed readers, or the articles as JSON with
+        /// --output json. Nothing is marked as seen.
+        #[arg(long, value_name = "FORMAT", conflicts_with_all = ["group", "sections", "sort_by", "reverse", "print"])]
+        feed: Option<FeedFormat>,
     },
     /// Find articles matching certain patterns.
     Find {
@@ -55,21 +124,293 @@ enum Commands {
         /// "seen" also filters out articles that have not been seen in the news.
         #[arg(long, default_value = "date")]
         sort_by: Order,
+        /// Reverse the sort order.
+        #[arg(short, long)]
+        reverse: bool,
+        /// Only use the first N matching articles (after sorting).
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        /// Skip the first N matching articles (after sorting).
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        offset: usize,
+        /// Open the pdf, directory, webpage or HTML version of each matching article (if there
+        /// are at most 20).
+        #[arg(long)]
+        all: bool,
+        /// With --show int, divide the articles into sections: bookmarked ones first, then
+        /// the others by primary category.
+        #[arg(long)]
+        sections: bool,
+        /// With --show medium, the number of lines of the abstract to show.
+        #[arg(long, default_value_t = 3)]
+        abstract_lines: usize,
+        /// Copy this information about the matching article to the clipboard instead (if
+        /// there is only one).
+        #[arg(long, value_enum, conflicts_with = "show")]
+        copy: Option<CopyFormat>,
+        #[command(flatten, next_help_heading = "Patterns")]
+        filters: Filters,
+    },
+    /// Download the metadata of articles by id, for example ones outside the subscribed
+    /// categories.
+    Fetch {
+        #[arg(required = true)]
+        #[arg(value_parser = ArxivId::parse_ignoring_version)]
+        ids: Vec<ArxivId>,
+        /// Bookmark the articles with this tag.
+        #[arg(short, long, value_hint = clap::ValueHint::Other)]
+        tag: Option<TagName>,
+    },
+    /// Print all details of an article, including its tags and notes.
+    Show {
+        #[arg(value_parser = ArxivId::parse_ignoring_version)]
+        id: ArxivId,
+    },
+    /// Delete downloaded files according to downloads.keep_days in the config file, like
+    /// `gc --unused-days N --keep-bookmarked`.
+    Clean {
+        /// Only report how much would be deleted.
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Delete downloaded pdfs, sources and extracted figures to free disk space, and report
+    /// how much was freed.
+    ///
+    /// Without options, the files selected by downloads.gc in the config file are deleted
+    /// (by default, the pdfs and sources of old versions). Tags, notes, attachments and other
+    /// files in the article directories are never deleted.
+    Gc {
+        /// Delete the pdfs and sources of versions other than the last one.
+        #[arg(long)]
+        old_versions: bool,
+        /// Delete all downloaded files of articles that are not bookmarked.
+        #[arg(long)]
+        non_bookmarked: bool,
+        /// Delete all downloaded files that have been neither downloaded nor opened for this
+        /// many days.
+        #[arg(long, value_name = "N")]
+        unused_days: Option<u64>,
+        /// Never delete the files of bookmarked articles.
+        #[arg(long)]
+        keep_bookmarked: bool,
+        /// Only report how much would be deleted.
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Retry the downloads of pdfs and sources that failed during pull (pull also does this first).
+    RetryDownloads,
+    /// Show the disk space used by the downloaded files of the matching articles.
+    Du {
+        /// Only list this many articles (the largest ones).
+        #[arg(long)]
+        top: Option<usize>,
+        #[command(flatten, next_help_heading = "Patterns")]
+        filters: Filters,
+    },
+    /// Check downloaded pdfs and sources, and download corrupt ones again.
+    Verify,
+    /// Check that the database and the articles directory agree: article directories and
+    /// seen-articles lines of unknown articles, bookmarked articles without pdf, and temporary
+    /// files left behind by interrupted writes.
+    Doctor {
+        /// Download the metadata of unknown articles and the missing pdfs, and remove empty
+        /// directories of unknown articles and temporary files.
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Commit the tags, notes, attachments, seen articles and config file to the git
+    /// repository in the arxiv-reader directory, merge the changes from its upstream branch
+    /// and push.
+    ///
+    /// Downloads and the database are not committed. The seen-articles log is merged in the
+    /// order of the timestamps of its lines and the tags files keep the tags of both sides,
+    /// so they never conflict. Set hooks.git_sync in the config file to do this
+    /// automatically instead of running the pre_pull and push hooks.
+    Sync,
+    /// Extract the text of downloaded pdfs for the fulltext filter (requires pdftotext).
+    Index,
+    /// Look up bookmarked articles without journal reference on Crossref, for bibtex check.
+    ///
+    /// Articles are looked up by DOI or by title and first author. Articles that were looked
+    /// up in the last 30 days are skipped.
+    Crossref {
+        /// Also look up the articles that were looked up recently.
+        #[arg(long)]
+        recheck: bool,
+    },
+    /// Compute the keywords of all articles again (pull only computes those of new articles,
+    /// unless the way keywords are chosen changed).
+    Keywords,
+    /// Download pdfs (and optionally sources) of all matching articles.
+    Prefetch {
+        /// Also download the sources.
+        #[arg(long)]
+        src: bool,
+        /// Do not ask for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten, next_help_heading = "Patterns")]
+        filters: Filters,
+    },
+    /// List the figures in the sources of an article.
+    Figures {
+        #[arg(value_parser = ArxivId::parse_ignoring_version)]
+        id: ArxivId,
+        /// Extract the figures into the article's download directory.
+        #[arg(short, long)]
+        extract: bool,
+        /// Extract the figures and open the given one.
+        #[arg(short, long, value_hint = clap::ValueHint::Other)]
+        open: Option<String>,
+    },
+    /// Compare two versions of an article with latexdiff (or as text, if latexdiff is not
+    /// installed) and open the result.
+    Diff {
+        #[arg(value_parser = ArxivId::parse_ignoring_version)]
+        id: ArxivId,
+        /// The old and the new version (default: the last two).
+        #[arg(num_args = 2, value_names = ["OLD", "NEW"])]
+        versions: Vec<u32>,
+    },
+    /// Copy a file (such as slides or a referee report) into the attachments directory of an article.
+    Attach {
+        #[arg(value_parser = ArxivId::parse_ignoring_version)]
+        id: ArxivId,
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: PathBuf,
+    },
+    /// Print a short text recommending an article to others.
+    Share {
+        #[arg(value_parser = ArxivId::parse_ignoring_version)]
+        id: ArxivId,
+        #[arg(short, long, default_value = "email")]
+        format: ShareFormat,
+        /// A note to include, such as why the article is interesting.
+        #[arg(short, long, value_hint = clap::ValueHint::Other)]
+        note: Option<String>,
+        /// Copy the text to the clipboard instead of printing it.
+        #[arg(short, long)]
+        copy: bool,
+    },
+    /// Write an HTML page listing the matching articles with their abstracts, tags and
+    /// notes, for example to share a reading list.
+    ///
+    /// Formulas are rendered with MathJax (loaded from a CDN when the page is opened).
+    Report {
+        /// The file to write the page to (default: stdout).
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: Option<PathBuf>,
+        /// The heading of the page.
+        #[arg(long, default_value = "Reading list", value_hint = clap::ValueHint::Other)]
+        heading: String,
+        /// How to sort the matching articles.
+        ///
+        /// "seen" also filters out articles that have not been seen in the news.
+        #[arg(long, default_value = "date")]
+        sort_by: Order,
+        /// Reverse the sort order.
+        #[arg(short, long)]
+        reverse: bool,
         #[command(flatten, next_help_heading = "Patterns")]
         filters: Filters,
     },
+    /// List the lines starting with "TODO" or "- [ ]" in the notes of all articles.
+    Todos,
+    /// Forget that articles were seen, so that they show up again in `arxiv-reader news`.
+    Unsee {
+        #[arg(required = true)]
+        #[arg(value_parser = ArxivId::parse_ignoring_version)]
+        ids: Vec<ArxivId>,
+        /// Only forget that versions after this one were seen (default: the version before the last one).
+        #[arg(long)]
+        version: Option<u32>,
+    },
+    /// List bookmarked articles that got a journal reference or DOI since they were last seen.
+    Published {
+        /// Mark the listed articles as seen.
+        #[arg(short, long)]
+        acknowledge: bool,
+    },
+    /// Show the different spellings of authors whose name contains the given string.
+    Authors {
+        #[arg(value_hint = clap::ValueHint::Other)]
+        name: String,
+    },
+    /// Show statistics about the articles and about reading them: new articles per week, top
+    /// categories, tags, the backlog, and what the archive contains (articles per primary
+    /// category and per month, seen articles, downloaded pdfs, disk usage).
+    Stats {
+        /// Show a dashboard with one panel at a time.
+        #[arg(short, long)]
+        interactive: bool,
+        /// The number of weeks to show.
+        #[arg(short, long, default_value_t = 12)]
+        weeks: u64,
+    },
+    /// Show the tree of MSC (or ACM) classes of the articles in the database.
+    MscTree {
+        /// Only show this class and the classes below it.
+        #[arg(value_hint = clap::ValueHint::Other)]
+        class: Option<String>,
+        /// Show ACM classes instead of MSC classes.
+        #[arg(long)]
+        acm: bool,
+    },
+    /// Show how many articles per month contain the given keywords in the title or abstract.
+    Trends {
+        /// The keywords (default: the keywords to highlight in the configuration file).
+        #[arg(value_hint = clap::ValueHint::Other)]
+        keywords: Vec<String>,
+        /// The number of months to show.
+        #[arg(short, long, default_value_t = 12)]
+        months: u32,
+        /// Count articles in all categories, not only in the ones in the configuration file.
+        #[arg(long)]
+        all_categories: bool,
+    },
     /// Interact with a bibtex file.
     #[command(subcommand)]
     Bibtex(BibtexCommand),
     /// Save or load metadata.
     #[command(subcommand)]
     Database(DatabaseCommand),
+    /// Back up or restore tags, notes and seen articles (but not the metadata).
+    #[command(subcommand)]
+    State(StateCommand),
+    /// Export data about the matching articles for other programs.
+    #[command(subcommand)]
+    Export(ExportCommand),
     #[command(hide = true)]
     GenerateCompletions { generator: Shell },
+    /// Print completion candidates (used by the completion scripts).
+    #[command(name = "complete-values", hide = true)]
+    Complete {
+        kind: CompletionKind,
+        #[arg(default_value = "")]
+        prefix: String,
+    },
+}
+
+#[derive(ValueEnum, Copy, Clone)]
+enum CompletionKind {
+    /// arXiv identifiers of known articles.
+    Ids,
+    /// Tag names from the config file and from tagged articles.
+    Tags,
+    /// Subscribed and known categories.
+    Categories,
 }
 
 #[derive(Subcommand)]
 enum BibtexCommand {
+    /// Write bibtex entries for the matching articles (by default the bookmarked ones).
+    Export {
+        /// The file to write the entries to (default: stdout).
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: Option<PathBuf>,
+        #[command(flatten, next_help_heading = "Patterns")]
+        filters: Filters,
+    },
     /// Create bookmarks from a bibtex file.
     Bookmark {
         #[arg(value_hint = clap::ValueHint::FilePath)]
@@ -84,18 +425,90 @@ enum BibtexCommand {
     },
 }
 
+#[derive(Subcommand)]
+enum StateCommand {
+    /// Write the tags, notes and seen articles to a json file.
+    Export {
+        /// Files ending in .gz or .zst are compressed.
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: PathBuf,
+        /// Compress with gzip or zstd (which must be installed).
+        #[arg(long)]
+        compress: Option<Compression>,
+    },
+    /// Add the tags, notes and seen articles from a file written by `state export` (possibly
+    /// compressed).
+    Import {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: PathBuf,
+    },
+    /// Keep a copy of the tags, notes, attachments and seen articles in the database, so that
+    /// they can be loaded without reading all article directories and all of seen-articles.
+    /// Files changed outside of arxiv-reader are noticed by their modification time and read
+    /// instead of the copy.
+    Cache {
+        /// Stop using the copy in the database.
+        #[arg(long)]
+        disable: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportCommand {
+    /// Write the graph of the authors of the matching articles, in which co-authors are connected.
+    Graph {
+        #[arg(long, value_enum, default_value = "dot")]
+        format: GraphFormat,
+        /// The file to write the graph to (default: stdout).
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: Option<PathBuf>,
+        #[command(flatten, next_help_heading = "Patterns")]
+        filters: Filters,
+    },
+}
+
 #[derive(Subcommand)]
 enum DatabaseCommand {
-    /// Write metadata of all articles to stdout in json format.
-    Dump,
-    /// Load metadata of articles from stdin.
-    Load,
+    /// Write the metadata of all articles (or of the matching ones) in json format.
+    Dump {
+        /// The file to write the metadata to (default: stdout). Files ending in .gz or .zst
+        /// are compressed.
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: Option<PathBuf>,
+        #[arg(long, default_value = "json")]
+        format: DumpFormat,
+        /// Compress with gzip or zstd (which must be installed).
+        #[arg(long)]
+        compress: Option<Compression>,
+        /// Only write the articles matching this filter (see `arxiv-reader help find`). Such
+        /// partial dumps do not contain the dates of the last updates.
+        #[arg(short, long, value_hint = clap::ValueHint::Other)]
+        filter: Option<Filter>,
+    },
+    /// Merge the seen-articles file of another machine into the local one. The lines of
+    /// both are merged in the order of their timestamps, so the later of two changes to
+    /// the seen state of an article wins.
+    MergeSeen {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: PathBuf,
+        /// Merge into this file instead of the seen-articles file of the arxiv-reader
+        /// directory (`arxiv-reader sync` registers this as git merge driver).
+        #[arg(long, value_name = "FILE", hide = true)]
+        into: Option<PathBuf>,
+    },
+    /// Load metadata of articles written by `database dump` (in any format, possibly
+    /// compressed).
+    Load {
+        /// The file to read the metadata from (default: stdin).
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: Option<PathBuf>,
+    },
 }
 
 #[derive(Args)]
 struct Filters {
     /// Find articles with these ids.
-    #[arg(long)]
+    #[arg(long, value_parser = ArxivId::parse_ignoring_version)]
     id: Vec<ArxivId>,
     /// Also include non-bookmarked articles.
     #[arg(short, long, conflicts_with = "id")]
@@ -109,6 +522,10 @@ struct Filters {
     /// Find articles containing these strings in the notes.
     #[arg(long, conflicts_with = "id", value_hint = clap::ValueHint::Other)]
     notes: Vec<String>,
+    /// Find articles whose title, abstract, authors or comments match this full-text query
+    /// (see the fts condition below).
+    #[arg(long, conflicts_with = "id", value_hint = clap::ValueHint::Other)]
+    fts: Vec<String>,
     /// Find articles containing these words in the title, abstract, authors, notes, ...
     #[arg(conflicts_with = "id", value_hint = clap::ValueHint::Other)]
     word: Vec<String>,
@@ -135,36 +552,95 @@ struct Filters {
     ///   title word1 word2 ...
     ///       matches articles whose title contains the given strings (case-insensitive)
     ///
+    ///   title_regex '\bmodular forms?\b' ...
+    ///       matches articles whose title matches the given regular expressions (case-insensitive)
+    ///       (see https://docs.rs/regex/latest/regex/#syntax for the syntax).
+    ///       Quote regular expressions containing spaces, parentheses, '!', '&' or '|'.
+    ///
     ///   author name1 name2 ...
     ///       matches articles whose authors include the given names
     ///       Note:
-    ///         The same author may sometimes be referred to in different ways, such as "C. F. Gauss", "Gauss, Carl-Friedrich", ...
-    ///         The search is literal, so you might have to specify different spellings.
-    ///         Accents are latex encoded. Remember to escape quotes and backslashes.
+    ///         The same author may sometimes be referred to in different ways, such as "C. F. Gauss", "Carl-Friedrich Gau\\ss", ...
+    ///         Spellings that presumably refer to the same person are recognized (see `arxiv-reader authors`),
+    ///         and the filter matches all of them.
+    ///         Accents, case and punctuation are ignored, so "Gauß" also finds "C. F. Gau\\ss", and names can
+    ///         also be written as "Gauss, C. F.". A single word is a family name, and names are compared as a
+    ///         whole, so "Li" does not find "Julia" or "Lie".
+    ///
+    ///   author_regex regex1 regex2 ...
+    ///       matches articles whose list of authors matches the given regular expressions
+    ///       (case-insensitive, without recognizing different spellings of names)
     ///
-    ///   acm 11R32
-    ///       matches articles with this acm class
+    ///   acm F.2
+    ///       matches articles with this acm class or a class below it, such as F.2.2
     ///
-    ///   msc 11R32
-    ///       matches articles with this msc class
+    ///   msc 11R
+    ///       matches articles with this msc class or a class below it, such as 11R32
+    ///       (11Rxx and 11-XX are also understood, see `arxiv-reader msc-tree`)
     ///
     ///   abstract word1 word2 ...
     ///       matches articles whose abstract contains the given strings (case-insensitive)
     ///
+    ///   abstract_regex regex1 regex2 ...
+    ///       matches articles whose abstract matches the given regular expressions (case-insensitive)
+    ///
     ///   comments word1 word2 ...
     ///       matches articles whose comments contain the given strings (case-insensitive)
     ///
+    ///   has_journal
+    ///       matches articles with a journal reference, that is, ones that have been published
+    ///
+    ///   has_doi
+    ///       matches articles with a DOI
+    ///
+    ///   license word1 word2 ...
+    ///       matches articles whose license (a URL such as http://creativecommons.org/licenses/by/4.0/)
+    ///       contains the given strings (case-insensitive)
+    ///
+    ///   withdrawn
+    ///       matches articles whose last version has been withdrawn
+    ///
     ///   bookmarked
     ///       matches bookmarked articles
     ///
     ///   seen
     ///       matches articles marked as seen by `arxiv-reader news`
     ///
+    ///   has_update
+    ///       matches seen articles with a newer version, journal reference or DOI that has not been seen
+    ///       (the updates that `arxiv-reader news` shows, apart from filters.update in the config file)
+    ///
     ///   tag tag1 tag2 ...
     ///       matches articles marked with all the given tags
     ///
+    ///   tag_not tag1 tag2 ...
+    ///       matches articles marked with a tag other than the given ones, such as bookmarked articles
+    ///       that are not only tagged read with `tag_not read`
+    ///
+    ///   untagged
+    ///       matches articles without tags (use --non-bookmarked with `arxiv-reader find`)
+    ///
+    ///   tag_count N
+    ///       matches articles with exactly N tags, or with more or fewer with >N, >=N, <N or <=N
+    ///
     ///   notes word1 word2 ...
     ///       matches articles whose notes contain the given strings (case-insensitive)
+    ///
+    ///   has_todo
+    ///       matches articles whose notes contain a line starting with "TODO" or "- [ ]" (see `arxiv-reader todos`)
+    ///
+    ///   keyword word1 word2 ...
+    ///       matches articles having all the given keywords (see `arxiv-reader keywords`)
+    ///
+    ///   fulltext word1 word2 ...
+    ///       matches articles whose pdf contains the given words (see `arxiv-reader index`)
+    ///
+    ///   fts 'elliptic curves rank' ...
+    ///       matches articles whose title, abstract, authors or comments match the given sqlite full-text queries
+    ///       (all words, in any order; "phrases" in double quotes, OR, NOT and prefix* are also understood)
+    ///
+    ///   @name
+    ///       matches articles satisfying the filter with this name in filters.named in the config file
     #[arg(short, long, conflicts_with = "id", value_hint = clap::ValueHint::Other, verbatim_doc_comment)]
     filter: Option<Filter>,
 }
@@ -188,6 +664,9 @@ impl Filters {
             for w in self.notes {
                 res = Filter::And(Box::new(res), Box::new(Filter::Notes(w)));
             }
+            for q in self.fts {
+                res = Filter::And(Box::new(res), Box::new(Filter::Fts(q)));
+            }
             for w in self.word {
                 res = Filter::And(Box::new(res), Box::new(Filter::Any(w)));
             }
@@ -203,70 +682,209 @@ impl Filters {
 }
 
 #[derive(ValueEnum, Copy, Clone)]
-pub enum Order {
-    /// By the date of submission of the first version.
-    Date,
-    /// In the order in which the user first saw them.
-    Seen,
-}
-
-#[derive(ValueEnum, Copy, Clone)]
-pub enum LsFormat {
+enum LsFormat {
     /// Print their arXiv ids.
     Quiet,
     /// Print one line per article.
     OneLine,
     /// Print two lines per article.
     Short,
+    /// Print the authors and title and the beginning of the abstract (see --abstract-lines).
+    Medium,
+    /// Print all details of each article.
+    Full,
+    /// Print the addresses of their arXiv webpages.
+    Url,
+    /// Print the addresses of the pdfs of their last versions on arXiv.
+    PdfUrl,
+    /// Print the paths of their downloaded pdfs (or of their directories if there is no pdf).
+    Path,
+    /// Print bibtex entries (see bibtex.key in the config file).
+    Bibtex,
     /// Interactively show one article at a time.
     Int,
-    /// Open the pdf (if there is only one matching article).
+    /// Open the pdf (if there is only one matching article or --all is given).
     Pdf,
-    /// Open the directory (if there is only one matching article).
+    /// Open the directory (if there is only one matching article or --all is given).
     Dir,
-    /// Open the webpage (if there is only one matching article).
+    /// Open the webpage (if there is only one matching article or --all is given).
     Web,
+    /// Open the HTML version on arXiv or ar5iv, or the webpage if there is probably none
+    /// (if there is only one matching article or --all is given).
+    Html,
+}
+
+/// What `find --copy` copies to the clipboard.
+#[derive(ValueEnum, Copy, Clone)]
+enum CopyFormat {
+    /// The arXiv identifier.
+    Id,
+    /// The address of the arXiv webpage.
+    Url,
+    /// A bibtex entry.
+    Bibtex,
 }
 
-fn main() -> anyhow::Result<()> {
+/// Deletes the downloaded files selected by the policy and reports how much was freed.
+fn gc(base_dir: &Path, config: &Config, policy: &GcPolicy, dry_run: bool) -> anyhow::Result<()> {
+    let download_dir = DownloadDir::new(base_dir, &config.downloads);
+    let metadata = db::with_transaction(&mut db::open(base_dir)?, base_dir, |tr| {
+        ArticleMetadata::load(&tr)
+    })?;
+    let summary = download_dir.gc(
+        policy,
+        |id| metadata.get(id).map(|m| m.last_version().number),
+        dry_run,
+    )?;
+    if output::json() {
+        output::print_json(&summary)?;
+    }
+    let verb = if dry_run { "Would delete" } else { "Deleted" };
+    message!(
+        "{verb} {} files ({}).",
+        summary.files,
+        util::format_size(summary.bytes)
+    );
+    Ok(())
+}
+
+/// Synchronizes the state with git and rescans the state cache if remote changes were
+/// merged.
+fn git_sync(base_dir: &Path) -> anyhow::Result<sync::Summary> {
+    let summary = sync::sync(base_dir)?;
+    if summary.pulled {
+        ArticleState::refresh_cache(&mut db::open(base_dir)?, base_dir)?;
+    }
+    if summary.committed {
+        message!("Committed the local changes.");
+    }
+    if summary.pulled {
+        message!("Merged the remote changes.");
+    }
+    if summary.pushed {
+        message!("Pushed the changes.");
+    }
+    if !summary.has_upstream {
+        message!("The branch has no upstream branch, so nothing was pulled or pushed.");
+    } else if !summary.committed && !summary.pulled && !summary.pushed {
+        message!("Everything is up to date.");
+    }
+    Ok(summary)
+}
+
+fn main() -> ExitCode {
     let res = inner_main();
     // Termion does not flush stdout by itself after returning to the main screen.
     // This is needed to ensure that error messages printed on stderr will be displayed.
-    stdout().flush()?;
-    res
+    let _ = stdout().flush();
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::from(error::exit_code(&err))
+        }
+    }
 }
 
 fn inner_main() -> anyhow::Result<()> {
     let get_base_dir = || -> anyhow::Result<_> {
         let base_dir = match std::env::var_os("ARXIV_READER_DIR") {
             Some(dir) => PathBuf::from(dir),
-            None => PathBuf::from(std::env::var_os("HOME").unwrap()).join("arxiv-reader"),
+            None => std::env::home_dir()
+                .context("could not determine the home directory")?
+                .join("arxiv-reader"),
         };
         Ok(base_dir)
     };
 
+    let cli = Cli::parse();
+    output::set_json(matches!(cli.output, OutputFormat::Json));
+    output::set_quiet(cli.quiet);
+
     let prepare = || -> anyhow::Result<_> {
         let base_dir = get_base_dir()?;
 
         let config_file = base_dir.join("config.toml");
         let config = std::fs::read_to_string(&config_file)
-            .with_context(|| format!("reading {config_file:?}"))?;
-        let config: Config =
-            toml::from_str(&config).with_context(|| format!("parsing {config_file:?}"))?;
+            .with_context(|| format!("reading {config_file:?}"))
+            .context(ConfigError)?;
+        let config: Config = toml::from_str(&config)
+            .with_context(|| format!("parsing {config_file:?}"))
+            .context(ConfigError)?;
 
-        let client = Client::new();
+        util::set_programs(config.programs.clone());
+        filter::set_named(config.filters.named.clone());
+
+        let mut client = if let Some(dir) = &cli.record {
+            Client::recording(dir.clone())?
+        } else if let Some(dir) = &cli.replay {
+            Client::replaying(dir.clone())
+        } else {
+            Client::new()
+        };
+        client.set_max_retries(
+            config
+                .network
+                .max_retries
+                .unwrap_or(rate_limited_client::DEFAULT_MAX_RETRIES),
+        );
+        let network = &config.network;
+        client.set_contact(network.contact.as_deref());
+        let seconds = |secs: f64| {
+            Duration::try_from_secs_f64(secs)
+                .with_context(|| format!("invalid number of seconds {secs} in [network]"))
+                .context(ConfigError)
+        };
+        // arXiv asks for at least DEFAULT_MIN_INTERVAL between requests.
+        let interval = |setting: &str, arxiv: bool, secs: f64| {
+            let interval = seconds(secs)?;
+            if arxiv && interval < rate_limited_client::DEFAULT_MIN_INTERVAL {
+                return Err(anyhow!(
+                    "arXiv asks for at least {} seconds between requests ({setting} = {secs} in [network])",
+                    rate_limited_client::DEFAULT_MIN_INTERVAL.as_secs()
+                )
+                .context(ConfigError));
+            }
+            Ok(interval)
+        };
+        if let Some(secs) = network.min_interval_secs {
+            client.set_min_interval(None, interval("min_interval_secs", true, secs)?);
+        }
+        for (host, secs) in &network.host_min_interval_secs {
+            let setting = format!("host_min_interval_secs.{host:?}");
+            let arxiv = rate_limited_client::is_arxiv(host);
+            client.set_min_interval(Some(host), interval(&setting, arxiv, *secs)?);
+        }
+        if let Some(secs) = network.jitter_secs {
+            client.set_jitter(seconds(secs)?);
+        }
+        if let Some(burst) = network.burst {
+            client.set_burst(burst);
+        }
+        if let Some(secs) = network.timeout_secs {
+            let timeout = seconds(secs)?;
+            if timeout.is_zero() {
+                return Err(
+                    anyhow!("timeout_secs in [network] must be positive").context(ConfigError)
+                );
+            }
+            client.set_timeout(timeout)?;
+        }
+        if !cli.allow_large {
+            client.set_confirm_above(config.downloads.confirm_above_mb.map(|mb| mb * 1_000_000));
+        }
         Ok((base_dir, config, client))
     };
 
     let run_push_command = |base_dir: &Path, config: &Config| {
+        if config.hooks.git_sync {
+            git_sync(base_dir)?;
+            return Ok(());
+        }
         // Run the push command.
         if let Some(push) = &config.hooks.push {
-            println!("Running push command");
-            let status = Command::new("/usr/bin/bash")
-                .arg("-c")
-                .arg(push)
-                .current_dir(base_dir)
-                .status()?;
+            message!("Running push command");
+            let status = util::shell_command(push).current_dir(base_dir).status()?;
             if !status.success() {
                 bail!("push failed");
             }
@@ -274,8 +892,6 @@ fn inner_main() -> anyhow::Result<()> {
         Ok(())
     };
 
-    let cli = Cli::parse();
-
     match cli.command {
         Commands::Pull => {
             let (base_dir, config, mut client) = prepare()?;
@@ -285,169 +901,1159 @@ fn inner_main() -> anyhow::Result<()> {
             // it before making the first request.
             db::with_transaction(&mut conn, &base_dir, |_| Ok(()))?;
             // Run the pre-pull command.
-            if let Some(pre_pull) = &config.hooks.pre_pull {
-                println!("Running pre-pull command");
-                let status = Command::new("/usr/bin/bash")
-                    .arg("-c")
-                    .arg(pre_pull)
+            if config.hooks.git_sync {
+                message!("Synchronizing with git");
+                git_sync(&base_dir)?;
+            } else if let Some(pre_pull) = &config.hooks.pre_pull {
+                message!("Running pre-pull command");
+                let status = util::shell_command(pre_pull)
                     .current_dir(&base_dir)
                     .status()?;
                 if !status.success() {
                     bail!("pre-pull command failed");
                 }
+                // The command might have changed the tags or notes.
+                ArticleState::refresh_cache(&mut conn, &base_dir)?;
             }
-            // Update article metadata.
-            for categories in &config.categories {
-                println!("Getting records in category {categories}.");
-                oai::download_changes(&base_dir, &mut conn, categories, &mut client)?;
+            let auto_tagged = pull::run(&base_dir, &config, &mut client)?.auto_tagged;
+            if auto_tagged > 0 {
+                run_push_command(&base_dir, &config)?;
             }
-            // Download pdfs and sources for all bookmarked articles.
-            db::with_transaction(&mut conn, &base_dir, |tr| {
-                let articles = Article::load(&base_dir, &tr)?;
-                for article in articles.values() {
-                    if article.is_bookmarked() {
-                        if article.last_version().probably_has_pdf() {
-                            article.download_pdf(&base_dir, &mut client)?;
-                        }
-                        if article.last_version().probably_has_src() {
-                            article.download_src(&base_dir, &mut client)?;
-                        }
-                    }
-                }
-                Ok(())
-            })?;
         }
         Commands::Find {
             filters,
             sort_by,
+            reverse,
+            limit,
+            offset,
+            all,
+            sections,
+            abstract_lines,
+            copy,
             show: do_,
         } => {
             let (base_dir, config, mut client) = prepare()?;
-            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
-                let mut filter = filters.get();
-                if let Order::Seen = sort_by {
-                    filter = Filter::And(Box::new(filter), Box::new(Filter::Seen));
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            let mut conn = db::open(&base_dir)?;
+            let mut filter = filters.get();
+            if let Order::Seen = sort_by {
+                filter = Filter::And(Box::new(filter), Box::new(Filter::Seen));
+            }
+            let filter = db::with_transaction(&mut conn, &base_dir, |tr| filter.prepare(&tr))?;
+            if let LsFormat::Int = do_ {
+                if limit.is_some() || offset > 0 {
+                    bail!("--limit and --offset do not work with --show int");
                 }
-                if let LsFormat::Int = do_ {
-                    interact::interact(
-                        &base_dir,
-                        &conn,
-                        &Highlight::default(),
-                        &config,
-                        &mut client,
-                        &filter,
-                        None,
-                        sort_by,
-                    )?;
-                    // Run the push command in case some article's state was changed.
-                    run_push_command(&base_dir, &config)?;
-                } else {
-                    let articles = Article::load(&base_dir, &conn)?;
-                    // All articles matching the filters.
-                    let mut articles: Vec<Article> = articles
-                        .into_values()
-                        .filter(|a| filter.matches(a))
-                        .collect();
-                    match sort_by {
-                        Order::Date => {
-                            articles.sort_by_key(|a| a.first_version().date);
+                interact::interact(
+                    &base_dir,
+                    &mut conn,
+                    &Highlight::default(),
+                    &config,
+                    &mut client,
+                    &filter,
+                    None,
+                    sort_by,
+                    reverse,
+                    None,
+                    &[],
+                    sections,
+                )?;
+                ArticleState::update_seen_cache(&mut conn, &base_dir)?;
+                // Run the push command in case some article's state was changed.
+                run_push_command(&base_dir, &config)?;
+                return Ok(());
+            }
+            db::with_transaction(&mut conn, &base_dir, |conn| {
+                // All articles matching the filters.
+                let mut articles: Vec<Article> = Article::load_matching(&base_dir, &conn, &filter)?
+                    .into_values()
+                    .collect();
+                sort_by.sort(&mut articles);
+                if reverse {
+                    articles.reverse();
+                }
+                articles.drain(..offset.min(articles.len()));
+                if let Some(limit) = limit {
+                    articles.truncate(limit);
+                }
+                if articles.is_empty() {
+                    if output::json() {
+                        output::print_json(&Vec::<()>::new())?;
+                    }
+                    return Err(NoMatches.into());
+                }
+                fn short(articles: &[Article], author_list: &AuthorList) {
+                    for article in articles.iter() {
+                        message!(
+                            "{}  {}",
+                            article.id(),
+                            format_authors(article.authors(), author_list)
+                        );
+                        message!("{}", article.title());
+                        message!();
+                    }
+                }
+                /// Calls f for the only matching article, or for every matching article
+                /// if `all` is set and there are not too many of them.
+                fn do_for_one(
+                    articles: &[Article],
+                    all: bool,
+                    author_list: &AuthorList,
+                    mut f: impl FnMut(&Article) -> anyhow::Result<()>,
+                ) -> anyhow::Result<()> {
+                    const MAX_ALL: usize = 20;
+                    if articles.len() == 1 {
+                        f(&articles[0])
+                    } else if all && articles.len() <= MAX_ALL {
+                        for article in articles {
+                            f(article)?;
                         }
-                        Order::Seen => {
-                            articles.sort_by_key(|a| a.last_seen_at());
+                        Ok(())
+                    } else {
+                        if all {
+                            message!(
+                                "Found {} articles, but --all only works for at most {MAX_ALL}. Please make a more specific search.",
+                                articles.len()
+                            );
+                        } else {
+                            message!(
+                                "Found {} articles. Please make a more specific search or pass --all.",
+                                articles.len()
+                            );
                         }
+                        message!();
+                        short(articles, author_list);
+                        Ok(())
+                    }
+                }
+                if let Some(copy) = copy {
+                    if articles.len() == 1 {
+                        let article = &articles[0];
+                        let text = match copy {
+                            CopyFormat::Id => article.id().to_string(),
+                            CopyFormat::Url => article.abs_url(),
+                            CopyFormat::Bibtex => bibtex::entry(
+                                article,
+                                &bibtex::key(
+                                    article,
+                                    config.bibtex.key.as_deref().unwrap_or(bibtex::DEFAULT_KEY),
+                                )?,
+                            ),
+                        };
+                        util::copy_to_clipboard(&text)?;
+                        message!("Copied to the clipboard.");
+                    } else {
+                        message!(
+                            "Found {} articles, but --copy only works for one. Please make a more specific search.",
+                            articles.len()
+                        );
+                        message!();
+                        short(&articles, &config.authors);
+                    }
+                    return Ok(());
+                }
+                match do_ {
+                    LsFormat::Quiet
+                    | LsFormat::OneLine
+                    | LsFormat::Short
+                    | LsFormat::Medium
+                    | LsFormat::Full
+                    | LsFormat::Url
+                    | LsFormat::PdfUrl
+                    | LsFormat::Path
+                    | LsFormat::Bibtex
+                        if output::json() =>
+                    {
+                        output::print_json(
+                            &articles.iter().map(Article::to_json).collect::<Vec<_>>(),
+                        )?;
                     }
-                    fn short(articles: &[Article]) {
+                    LsFormat::Quiet => {
                         for article in articles.iter() {
-                            println!("{}  {}", article.id(), article.authors());
-                            println!("{}", article.title());
-                            println!();
+                            message!("{}", article.id());
                         }
                     }
-                    fn do_for_one(
-                        articles: &[Article],
-                        f: impl FnOnce(&Article) -> anyhow::Result<()>,
-                    ) -> anyhow::Result<()> {
-                        if articles.len() == 1 {
-                            f(&articles[0])
-                        } else if articles.is_empty() {
-                            println!("No articles found.");
-                            Ok(())
-                        } else {
-                            println!(
-                                "Found {} articles. Please make a more specific search.",
-                                articles.len()
+                    LsFormat::OneLine => {
+                        for article in articles.iter() {
+                            message!(
+                                "{} {}: {}",
+                                article.id(),
+                                format_authors(article.authors(), &config.authors),
+                                article.title()
                             );
-                            println!();
-                            short(articles);
-                            Ok(())
                         }
                     }
-                    match do_ {
-                        LsFormat::Quiet => {
-                            for article in articles.iter() {
-                                println!("{}", article.id());
-                            }
-                        }
-                        LsFormat::OneLine => {
-                            for article in articles.iter() {
-                                println!(
-                                    "{} {}: {}",
-                                    article.id(),
-                                    article.authors(),
-                                    article.title()
-                                );
+                    LsFormat::Short => {
+                        short(&articles, &config.authors);
+                    }
+                    LsFormat::Medium => {
+                        let width =
+                            termion::terminal_size().map_or(80, |(columns, _)| columns as usize);
+                        for article in articles.iter() {
+                            message!(
+                                "{}  {}",
+                                article.id(),
+                                format_authors(article.authors(), &config.authors)
+                            );
+                            message!("{}", share::one_line(article.title()));
+                            // Leave room for the indentation and the ellipsis.
+                            let lines = util::wrap(article.abstract_(), width.saturating_sub(4));
+                            for (i, line) in lines.iter().take(abstract_lines).enumerate() {
+                                if i + 1 == abstract_lines && lines.len() > abstract_lines {
+                                    message!("  {line} …");
+                                } else {
+                                    message!("  {line}");
+                                }
                             }
+                            message!();
                         }
-                        LsFormat::Short => {
-                            short(&articles);
+                    }
+                    LsFormat::Full => {
+                        for article in articles.iter() {
+                            article.print(&Highlight::default(), false, config.latex_to_unicode);
+                            message!();
                         }
-                        LsFormat::Int => panic!("logic error"),
-                        LsFormat::Pdf => {
-                            do_for_one(&articles, |article| {
-                                article.download_pdf(&base_dir, &mut client)?;
-                                article.open_pdf(&base_dir)
-                            })?;
+                    }
+                    LsFormat::Url => {
+                        for article in articles.iter() {
+                            message!("{}", article.abs_url());
                         }
-                        LsFormat::Dir => {
-                            do_for_one(&articles, |article| article.open_dir(&base_dir))?;
+                    }
+                    LsFormat::PdfUrl => {
+                        for article in articles.iter() {
+                            message!("{}", article.pdf_url());
                         }
-                        LsFormat::Web => {
-                            do_for_one(&articles, |article| article.open_abs())?;
+                    }
+                    LsFormat::Path => {
+                        for article in articles.iter() {
+                            let pdf_path = article.pdf_path(&download_dir);
+                            let path = if pdf_path.is_file() {
+                                pdf_path
+                            } else {
+                                article.id().directory(&base_dir)
+                            };
+                            message!("{}", path.display());
                         }
                     }
+                    LsFormat::Bibtex => {
+                        print!(
+                            "{}",
+                            bibtex::export(
+                                &articles,
+                                config.bibtex.key.as_deref().unwrap_or(bibtex::DEFAULT_KEY)
+                            )?
+                        );
+                    }
+                    LsFormat::Int => panic!("logic error"),
+                    LsFormat::Pdf => {
+                        do_for_one(&articles, all, &config.authors, |article| {
+                            if source::download_or_build_pdf(
+                                article,
+                                &download_dir,
+                                &mut client,
+                                config.build_pdf_from_source,
+                            )? {
+                                article.open_pdf(&download_dir)
+                            } else {
+                                message!("arXiv has no pdf for {}.", article.id());
+                                Ok(())
+                            }
+                        })?;
+                    }
+                    LsFormat::Dir => {
+                        do_for_one(&articles, all, &config.authors, |article| {
+                            article.open_dir(&base_dir)
+                        })?;
+                    }
+                    LsFormat::Web => {
+                        do_for_one(&articles, all, &config.authors, |article| {
+                            article.open_abs()
+                        })?;
+                    }
+                    LsFormat::Html => {
+                        do_for_one(&articles, all, &config.authors, |article| {
+                            article.open_html()
+                        })?;
+                    }
                 }
                 Ok(())
             })?
         }
-        Commands::News { sort_by } => {
+        Commands::News {
+            sort_by,
+            reverse,
+            limit,
+            filter,
+            replace_filter,
+            group,
+            sections,
+            print,
+            feed,
+        } => {
+            if output::json() && !print && feed.is_none() {
+                bail!("news is interactive and has no json output (try news --print)");
+            }
             let (base_dir, config, mut client) = prepare()?;
-            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
-                interact::interact(
-                    &base_dir,
-                    &conn,
-                    &config.highlight,
-                    &config,
+            let new_filter = match filter {
+                Some(filter) if replace_filter => filter,
+                Some(filter) => Filter::And(Box::new(config.filters.new.clone()), Box::new(filter)),
+                None => config.filters.new.clone(),
+            };
+            if let Some(format) = feed {
+                return db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
+                    let pulls = LastPull::load_recent(&conn)?;
+                    let updated = pulls
+                        .iter()
+                        .map(|pull| pull.time)
+                        .max()
+                        .context("no new articles recorded yet (run arxiv-reader pull)")?;
+                    let new_filter = new_filter.prepare(&conn)?;
+                    let articles = Article::load(&base_dir, &conn)?;
+                    let ids: HashSet<&ArxivId> = pulls.iter().flat_map(|pull| &pull.ids).collect();
+                    let mut new_articles: Vec<&Article> = ids
+                        .into_iter()
+                        .filter_map(|id| articles.get(id))
+                        .filter(|a| new_filter.matches(a))
+                        .collect();
+                    // Newest first, as usual in feeds.
+                    new_articles.sort_by_key(|a| std::cmp::Reverse(a.first_version().date));
+                    if let Some(limit) = limit {
+                        new_articles.truncate(limit);
+                    }
+                    if output::json() {
+                        output::print_json(&serde_json::json!({
+                            "updated": updated,
+                            "articles": new_articles.iter().map(|a| a.to_json()).collect::<Vec<_>>(),
+                        }))?;
+                        return Ok(());
+                    }
+                    print!(
+                        "{}",
+                        feed::write(format, &updated, &new_articles, |text| {
+                            if config.latex_to_unicode {
+                                unicodeit::replace(text)
+                            } else {
+                                text.to_string()
+                            }
+                        })
+                    );
+                    Ok(())
+                });
+            }
+            if print {
+                return db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
+                    let new_filter = new_filter.prepare(&conn)?;
+                    let update_filter = config.filters.update.prepare(&conn)?;
+                    let articles = Article::load_matching(&base_dir, &conn, &new_filter)?;
+                    let mut news: Vec<(&Article, bool)> = articles
+                        .values()
+                        .filter_map(|a| Some((a, interact::news_status(a, &update_filter)?)))
+                        .collect();
+                    // Unseen articles first, as in the interactive viewer.
+                    news.sort_by_key(|(a, updated)| (*updated, a.first_version().date));
+                    if let Some(limit) = limit {
+                        news.truncate(limit);
+                    }
+                    if output::json() {
+                        let news: Vec<_> = news
+                            .iter()
+                            .map(|(article, updated)| {
+                                let mut value = article.to_json();
+                                value["updated"] = (*updated).into();
+                                value
+                            })
+                            .collect();
+                        output::print_json(&news)?;
+                    }
+                    for (article, updated) in news {
+                        message!(
+                            "{} {}{}: {}",
+                            article.id(),
+                            if updated { "(updated) " } else { "" },
+                            format_authors(article.authors(), &config.authors),
+                            article.title()
+                        );
+                    }
+                    Ok(())
+                });
+            }
+            let mut conn = db::open(&base_dir)?;
+            let (groups, new_filter, update_filter) =
+                db::with_transaction(&mut conn, &base_dir, |conn| {
+                    let mut groups = Vec::new();
+                    if group {
+                        for category in &config.categories {
+                            let set =
+                                oai::set_for_category(&conn, category)?.with_context(|| {
+                                    format!("category {category:?} was never pulled")
+                                })?;
+                            groups.push((category.clone(), vec![set]));
+                        }
+                        for subscription in &config.subscriptions {
+                            groups.push((subscription.name.clone(), subscription.sets.clone()));
+                        }
+                    }
+                    Ok((
+                        groups,
+                        new_filter.prepare(&conn)?,
+                        config.filters.update.prepare(&conn)?,
+                    ))
+                })?;
+            interact::interact(
+                &base_dir,
+                &mut conn,
+                &config.highlight,
+                &config,
+                &mut client,
+                &new_filter,
+                Some(&update_filter),
+                sort_by,
+                reverse,
+                limit,
+                &groups,
+                sections,
+            )?;
+            ArticleState::update_seen_cache(&mut conn, &base_dir)?;
+            // Run the push command in case some article's state was changed.
+            run_push_command(&base_dir, &config)?;
+        }
+        Commands::Clean { dry_run } => {
+            let (base_dir, config, _client) = prepare()?;
+            let keep_days = config
+                .downloads
+                .keep_days
+                .context("please set downloads.keep_days in the config file")?;
+            gc(&base_dir, &config, &GcPolicy::keep_days(keep_days), dry_run)?;
+        }
+        Commands::Gc {
+            old_versions,
+            non_bookmarked,
+            unused_days,
+            keep_bookmarked,
+            dry_run,
+        } => {
+            let (base_dir, config, _client) = prepare()?;
+            let mut policy = if old_versions || non_bookmarked || unused_days.is_some() {
+                GcPolicy {
+                    old_versions,
+                    non_bookmarked,
+                    unused_days,
+                    keep_bookmarked: false,
+                }
+            } else {
+                config.downloads.gc
+            };
+            policy.keep_bookmarked |= keep_bookmarked;
+            gc(&base_dir, &config, &policy, dry_run)?;
+        }
+        Commands::RetryDownloads => {
+            let (base_dir, config, mut client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let (succeeded, failed) =
+                    pull::retry_failed_downloads(&tr, &base_dir, &download_dir, &mut client)?;
+                if succeeded + failed == 0 {
+                    message!("No failed downloads.");
+                }
+                tr.commit()?;
+                Ok(())
+            })?;
+        }
+        Commands::Du { top, filters } => {
+            let (base_dir, config, _client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let filter = filters.get().prepare(&tr)?;
+                let articles = Article::load(&base_dir, &tr)?;
+                let mut usages = Vec::new();
+                let mut total = downloads::Usage::default();
+                for article in articles.values().filter(|a| filter.matches(a)) {
+                    let usage = downloads::usage(&download_dir, article)?;
+                    if usage.total() > 0 {
+                        total.add(&usage);
+                        usages.push((article, usage));
+                    }
+                }
+                usages.sort_by_key(|(article, usage)| {
+                    (Reverse(usage.total()), article.id().to_string())
+                });
+                if let Some(top) = top {
+                    usages.truncate(top);
+                }
+                if output::json() {
+                    output::print_json(&serde_json::json!({
+                        "articles": usages
+                            .iter()
+                            .map(|(article, usage)| {
+                                serde_json::json!({
+                                    "id": article.id(),
+                                    "usage": usage,
+                                    "total": usage.total(),
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                        "usage": total,
+                        "total": total.total(),
+                    }))?;
+                }
+                message!(
+                    "{:>10} {:>10} {:>10} {:>10}  article",
+                    "total",
+                    "pdf",
+                    "source",
+                    "extracted"
+                );
+                for (article, usage) in &usages {
+                    message!(
+                        "{:>10} {:>10} {:>10} {:>10}  {} {}",
+                        util::format_size(usage.total()),
+                        util::format_size(usage.pdf),
+                        util::format_size(usage.src),
+                        util::format_size(usage.extracted),
+                        article.id(),
+                        share::one_line(article.title())
+                    );
+                }
+                message!(
+                    "{:>10} {:>10} {:>10} {:>10}  in total",
+                    util::format_size(total.total()),
+                    util::format_size(total.pdf),
+                    util::format_size(total.src),
+                    util::format_size(total.extracted),
+                );
+                Ok(())
+            })?;
+        }
+        Commands::Doctor { fix } => {
+            let (base_dir, config, mut client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            let problems =
+                db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                    let mut problems = doctor::check(&base_dir, &tr, &download_dir)?;
+                    if fix {
+                        doctor::fix(&base_dir, &tr, &download_dir, &mut client, &mut problems)?;
+                        tr.commit()?;
+                    }
+                    Ok(problems)
+                })?;
+            for problem in &problems {
+                if problem.fixed {
+                    message!("{} (fixed)", problem.describe());
+                } else {
+                    message!("{}", problem.describe());
+                }
+            }
+            if problems.is_empty() {
+                message!("Found no problems.");
+            }
+            if output::json() {
+                output::print_json(&problems)?;
+            }
+        }
+        Commands::Sync => {
+            let base_dir = get_base_dir()?;
+            let summary = git_sync(&base_dir)?;
+            if output::json() {
+                output::print_json(&summary)?;
+            }
+        }
+        Commands::Verify => {
+            let (base_dir, config, mut client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let articles = Article::load(&base_dir, &tr)?;
+                let mut corrupt = Vec::new();
+                for article in articles.values() {
+                    // Downloading a file that already exists checks it and only
+                    // downloads it again if it is corrupt.
+                    let pdf_path = article.pdf_path(&download_dir);
+                    if pdf_path.is_file() {
+                        if !pdf_looks_valid(&pdf_path)? {
+                            corrupt.push(serde_json::json!({"id": article.id(), "file": "pdf"}));
+                        }
+                        article.download_pdf(&download_dir, &mut client)?;
+                    }
+                    let src_path = article.src_path(&download_dir);
+                    if src_path.is_file() {
+                        if !gzip_looks_valid(&src_path)? {
+                            corrupt.push(serde_json::json!({"id": article.id(), "file": "src"}));
+                        }
+                        article.download_src(&download_dir, &mut client)?;
+                    }
+                }
+                if output::json() {
+                    output::print_json(&serde_json::json!({ "redownloaded": corrupt }))?;
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Index => {
+            let (base_dir, config, _client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let articles = Article::load(&base_dir, &tr)?;
+                let mut count = 0;
+                for article in articles.values() {
+                    if fulltext::index(&download_dir, &tr, article)? {
+                        count += 1;
+                    }
+                }
+                tr.commit()?;
+                message!("Indexed {count} pdfs.");
+                Ok(())
+            })?;
+        }
+        Commands::Crossref { recheck } => {
+            let (base_dir, _config, client) = prepare()?;
+            // Crossref is not arXiv, so there is no need to wait between the requests.
+            let mut client = client.unthrottled()?;
+            db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let articles = Article::load(&base_dir, &tr)?;
+                let bookmarked: Vec<&Article> =
+                    articles.values().filter(|a| a.is_bookmarked()).collect();
+                let found = crossref::update(&tr, &mut client, &bookmarked, recheck)?;
+                tr.commit()?;
+                message!("Found {found} published articles on Crossref.");
+                Ok(())
+            })?;
+        }
+        Commands::Keywords => {
+            let base_dir = get_base_dir()?;
+            db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let count = keywords::update(&tr, None)?;
+                tr.commit()?;
+                message!("Computed the keywords of {count} articles.");
+                Ok(())
+            })?;
+        }
+        Commands::Prefetch { src, yes, filters } => {
+            let (base_dir, config, mut client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let filter = filters.get().prepare(&tr)?;
+                let articles = Article::load(&base_dir, &tr)?;
+                let mut articles: Vec<&Article> =
+                    articles.values().filter(|a| filter.matches(a)).collect();
+                articles.sort_by_key(|a| a.first_version().date);
+                let pdfs: Vec<&Article> = articles
+                    .iter()
+                    .filter(|a| {
+                        a.last_version().probably_has_pdf() && !a.pdf_path(&download_dir).is_file()
+                    })
+                    .copied()
+                    .collect();
+                let srcs: Vec<&Article> = articles
+                    .iter()
+                    .filter(|a| {
+                        src && a.last_version().probably_has_src()
+                            && !a.src_path(&download_dir).is_file()
+                    })
+                    .copied()
+                    .collect();
+                if pdfs.is_empty() && srcs.is_empty() {
+                    message!("Nothing to download.");
+                    return Ok(());
+                }
+                // arXiv only tells us the size of the sources, which we also use as an
+                // estimate for the size of the pdf.
+                let estimate: u64 = pdfs
+                    .iter()
+                    .chain(srcs.iter())
+                    .filter_map(|a| a.last_version().size_in_bytes())
+                    .sum();
+                message!(
+                    "Downloading {} pdfs and {} sources (roughly {}).",
+                    pdfs.len(),
+                    srcs.len(),
+                    util::format_size(estimate)
+                );
+                if !yes && !util::confirm("Continue?")? {
+                    return Ok(());
+                }
+                let files: Vec<(&Article, bool)> = pdfs
+                    .into_iter()
+                    .map(|a| (a, false))
+                    .chain(srcs.into_iter().map(|a| (a, true)))
+                    .collect();
+                let (downloaded, failed) = downloads::download_all(
+                    &tr,
+                    &download_dir,
                     &mut client,
-                    &config.filters.new,
-                    Some(&config.filters.update),
-                    sort_by,
-                )
+                    &files,
+                    config
+                        .network
+                        .concurrency
+                        .unwrap_or(rate_limited_client::DEFAULT_CONCURRENCY),
+                )?;
+                tr.commit()?;
+                message!("Downloaded {downloaded} pdfs and sources.");
+                if failed > 0 {
+                    message!(
+                        "{failed} downloads failed. Use `arxiv-reader retry-downloads` to try them again."
+                    );
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Fetch { ids, tag } => {
+            let (base_dir, config, mut client) = prepare()?;
+            db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                for id in &ids {
+                    if oai::fetch(&tr, &mut client, id)? {
+                        message!("Fetched {id}.");
+                    } else {
+                        message!("Updated {id}.");
+                    }
+                }
+                keywords::update(&tr, Some(&ids.iter().cloned().collect()))?;
+                if let Some(tag) = &tag {
+                    for id in &ids {
+                        Article::load_one(&base_dir, &tr, id)?.set_tag(&base_dir, &tr, tag)?;
+                    }
+                }
+                tr.commit()?;
+                Ok(())
+            })?;
+            if tag.is_some() {
+                run_push_command(&base_dir, &config)?;
+            }
+        }
+        Commands::Show { id } => {
+            let (base_dir, config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let article = Article::load_one(&base_dir, &tr, &id)?;
+                if output::json() {
+                    output::print_json(&article.to_json())?;
+                } else {
+                    article.print(&Highlight::default(), false, config.latex_to_unicode);
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Attach { id, file } => {
+            let (base_dir, config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let mut article = Article::load_one(&base_dir, &tr, &id)?;
+                let name = article.attach(&base_dir, &tr, &file)?;
+                message!("Attached {file:?} as attachments/{name}.");
+                tr.commit()?;
+                Ok(())
             })?;
-            // Run the push command in case some article's state was changed.
             run_push_command(&base_dir, &config)?;
         }
+        Commands::Figures { id, extract, open } => {
+            let (base_dir, config, mut client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let article = Article::load_one(&base_dir, &tr, &id)?;
+                if !article.last_version().probably_has_src() {
+                    bail!("arXiv has no sources for {id}");
+                }
+                article.download_src(&download_dir, &mut client)?;
+                let figures = source::list_figures(&article, &download_dir)?;
+                if extract || open.is_some() {
+                    let count = source::extract_figures(&article, &download_dir)?;
+                    let dir = source::figures_dir(&article, &download_dir);
+                    message!("Extracted {count} figures into {dir:?}.");
+                    if let Some(name) = open {
+                        if !figures.contains(&name) {
+                            bail!("there is no figure named {name:?}");
+                        }
+                        util::open(OpenAs::File, dir.join(name))?;
+                    }
+                } else {
+                    for figure in figures {
+                        message!("{figure}");
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Diff { id, versions } => {
+            let (base_dir, config, mut client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            let path = db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let article = Article::load_one(&base_dir, &tr, &id)?;
+                let last = article.last_version().number;
+                let (old, new) = match versions[..] {
+                    [old, new] => (old, new),
+                    _ if last < 2 => bail!("{id} has only one version"),
+                    _ => (last - 1, last),
+                };
+                if old == new {
+                    bail!("cannot compare v{old} with itself");
+                }
+                for number in [old, new] {
+                    let Some(version) = article.versions().iter().find(|v| v.number == number)
+                    else {
+                        bail!("{id} has no version v{number} (the last one is v{last})");
+                    };
+                    if !version.probably_has_src() {
+                        bail!("arXiv has no sources for {id}v{number}");
+                    }
+                }
+                source::diff(&article, &download_dir, &mut client, old, new)
+            })?;
+            message!("Saved the differences as {path:?}.");
+            let kind = if path.extension().is_some_and(|e| e == "pdf") {
+                OpenAs::Pdf
+            } else {
+                OpenAs::File
+            };
+            util::open(kind, &path)?;
+        }
+        Commands::Share {
+            id,
+            format,
+            note,
+            copy,
+        } => {
+            let (base_dir, config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let article = Article::load_one(&base_dir, &tr, &id)?;
+                let text =
+                    share::text(&article, format, note.as_deref(), &config.authors, |text| {
+                        if config.latex_to_unicode {
+                            unicodeit::replace(text)
+                        } else {
+                            text.to_string()
+                        }
+                    });
+                if copy {
+                    util::copy_to_clipboard(&text)?;
+                    message!("Copied to the clipboard.");
+                } else {
+                    print!("{text}");
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Report {
+            file,
+            heading,
+            sort_by,
+            reverse,
+            filters,
+        } => {
+            let (base_dir, config, _client) = prepare()?;
+            let mut articles = db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let mut filter = filters.get();
+                if let Order::Seen = sort_by {
+                    filter = Filter::And(Box::new(filter), Box::new(Filter::Seen));
+                }
+                let filter = filter.prepare(&tr)?;
+                Ok(Article::load_matching(&base_dir, &tr, &filter)?
+                    .into_values()
+                    .collect::<Vec<_>>())
+            })?;
+            sort_by.sort(&mut articles);
+            if reverse {
+                articles.reverse();
+            }
+            if articles.is_empty() {
+                return Err(NoMatches.into());
+            }
+            let page = report::html(&heading, &articles, &config.authors, |text| {
+                if config.latex_to_unicode {
+                    unicodeit::replace(text)
+                } else {
+                    text.to_string()
+                }
+            });
+            match file {
+                Some(file) => {
+                    std::fs::write(&file, page).with_context(|| format!("writing {file:?}"))?;
+                    message!("Wrote {} articles to {file:?}.", articles.len());
+                }
+                None => print!("{page}"),
+            }
+        }
+        Commands::Todos => {
+            let (base_dir, _config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let articles = Article::load(&base_dir, &tr)?;
+                let mut with_todos: Vec<&Article> = articles
+                    .values()
+                    .filter(|a| !a.todos().is_empty())
+                    .collect();
+                with_todos.sort_by_key(|a| a.first_version().date);
+                if output::json() {
+                    output::print_json(
+                        &with_todos
+                            .iter()
+                            .map(|a| {
+                                serde_json::json!({
+                                    "id": a.id(),
+                                    "title": a.title(),
+                                    "todos": a.todos(),
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    )?;
+                }
+                if with_todos.is_empty() {
+                    message!("No todos in the notes.");
+                }
+                for article in with_todos {
+                    message!("{}  {}", article.id(), share::one_line(article.title()));
+                    for todo in article.todos() {
+                        message!("    {todo}");
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Unsee { ids, version } => {
+            let (base_dir, config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let mut seen_file = Article::open_seen_file(&base_dir)?;
+                for id in ids {
+                    let mut article = Article::load_one(&base_dir, &tr, &id)?;
+                    let last_version = article.last_version().number;
+                    let version = version.unwrap_or(last_version - 1);
+                    if version > last_version {
+                        bail!("{id} only has {last_version} versions");
+                    }
+                    article.unsee(&mut seen_file, version)?;
+                    if version == 0 {
+                        message!("{id} will show up as a new article.");
+                    } else {
+                        message!("{id} will show up as updated since version {version}.");
+                    }
+                }
+                Ok(())
+            })?;
+            run_push_command(&base_dir, &config)?;
+        }
+        Commands::Published { acknowledge } => {
+            let (base_dir, config, _client) = prepare()?;
+            let acknowledged = db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let mut articles = Article::load(&base_dir, &tr)?;
+                let mut published: Vec<&mut Article> = articles
+                    .values_mut()
+                    .filter(|a| a.is_bookmarked() && a.has_unseen_publication())
+                    .collect();
+                published.sort_by_key(|a| a.first_version().date);
+                if output::json() {
+                    output::print_json(&published.iter().map(|a| a.to_json()).collect::<Vec<_>>())?;
+                }
+                if published.is_empty() {
+                    message!("No newly published bookmarks.");
+                    return Ok(false);
+                }
+                for article in &published {
+                    message!("{}  {}", article.id(), article.title());
+                    if let Some(journal_ref) = article.journal_ref() {
+                        message!("  Journal: {journal_ref}");
+                    }
+                    if let Some(doi) = article.doi() {
+                        message!("  DOI: https://doi.org/{doi}");
+                    }
+                }
+                if acknowledge {
+                    let mut seen_file = Article::open_seen_file(&base_dir)?;
+                    for article in &mut published {
+                        article.mark_publication_as_seen(&mut seen_file)?;
+                    }
+                    message!("Marked {} articles as seen.", published.len());
+                }
+                Ok(acknowledge)
+            })?;
+            if acknowledged {
+                run_push_command(&base_dir, &config)?;
+            }
+        }
+        Commands::Authors { name } => {
+            let (base_dir, _config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let metadata = ArticleMetadata::load(&tr)?;
+                let clusters = AuthorClusters::new(metadata.values().map(|m| m.authors.as_str()));
+                let found = clusters.search(&name);
+                if output::json() {
+                    output::print_json(
+                        &found
+                            .iter()
+                            .map(|c| {
+                                serde_json::json!({
+                                    "canonical": c.canonical,
+                                    "variants": c.variants,
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    )?;
+                }
+                if found.is_empty() {
+                    message!("No author found.");
+                }
+                for cluster in found {
+                    message!("{}", cluster.canonical);
+                    for (spelling, count) in &cluster.variants {
+                        message!("  {spelling} ({count} articles)");
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Stats { interactive, weeks } => {
+            if interactive && output::json() {
+                bail!("the dashboard has no json output (leave out --interactive)");
+            }
+            let (base_dir, config, _client) = prepare()?;
+            let download_dir = DownloadDir::new(&base_dir, &config.downloads);
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let articles = Article::load(&base_dir, &tr)?;
+                let new_filter = config.filters.new.prepare(&tr)?;
+                let today = chrono::Local::now().date_naive();
+                let mut stats = Stats::compute(&articles, &new_filter, today, weeks);
+                stats.archive.measure(&articles, &base_dir, &download_dir)?;
+                if interactive {
+                    stats.interact()
+                } else if output::json() {
+                    output::print_json(&stats)
+                } else {
+                    stats.print();
+                    Ok(())
+                }
+            })?;
+        }
+        Commands::MscTree { class, acm } => {
+            let scheme = if acm { Scheme::Acm } else { Scheme::Msc };
+            let class = class
+                .map(|c| {
+                    scheme
+                        .normalize(&c)
+                        .with_context(|| format!("{c:?} is not a valid class"))
+                })
+                .transpose()?;
+            let (base_dir, _config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let metadata = ArticleMetadata::load(&tr)?;
+                let counts = scheme.count(metadata.values().filter_map(|m| match scheme {
+                    Scheme::Msc => m.msc_classes.as_deref(),
+                    Scheme::Acm => m.acm_classes.as_deref(),
+                }));
+                let shown = counts
+                    .iter()
+                    .filter(|(code, _)| class.as_ref().is_none_or(|c| scheme.is_below(code, c)))
+                    .collect::<Vec<_>>();
+                if output::json() {
+                    output::print_json(
+                        &shown
+                            .iter()
+                            .map(|(code, count)| {
+                                serde_json::json!({
+                                    "class": scheme.display(code),
+                                    "description": scheme.description(code),
+                                    "articles": count,
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    )?;
+                }
+                if shown.is_empty() {
+                    message!("No articles with such a class.");
+                }
+                for (code, count) in shown {
+                    let indent = "  ".repeat(scheme.depth(code));
+                    let display = scheme.display(code);
+                    match scheme.description(code) {
+                        Some(description) => {
+                            message!("{indent}{display} {description} ({count} articles)")
+                        }
+                        None => message!("{indent}{display} ({count} articles)"),
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Commands::Trends {
+            keywords,
+            months,
+            all_categories,
+        } => {
+            let (base_dir, config, _client) = prepare()?;
+            let keywords = if keywords.is_empty() {
+                // Trends are counted by literal matches.
+                config
+                    .highlight
+                    .keywords
+                    .rules
+                    .iter()
+                    .filter(|rule| !rule.is_regex)
+                    .map(|rule| rule.pattern.clone())
+                    .collect()
+            } else {
+                keywords
+            };
+            if keywords.is_empty() {
+                bail!("no keywords given and none specified in the configuration file");
+            }
+            let categories = if all_categories {
+                Vec::new()
+            } else {
+                config.categories.clone()
+            };
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let metadata = ArticleMetadata::load(&tr)?;
+                let months = trends::last_months(chrono::Local::now().date_naive(), months);
+                let mut json = serde_json::Map::new();
+                for keyword in &keywords {
+                    let counts = trends::count_per_month(&metadata, keyword, &categories);
+                    trends::print(keyword, &counts, &months);
+                    json.insert(
+                        keyword.clone(),
+                        months
+                            .iter()
+                            .map(|m| (m.clone(), counts.get(m).copied().unwrap_or(0).into()))
+                            .collect::<serde_json::Map<_, _>>()
+                            .into(),
+                    );
+                }
+                if output::json() {
+                    output::print_json(&json)?;
+                }
+                Ok(())
+            })?;
+        }
         Commands::Bibtex(cmd) => match cmd {
+            BibtexCommand::Export { file, filters } => {
+                let (base_dir, config, _client) = prepare()?;
+                let text = db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                    let filter = filters.get().prepare(&tr)?;
+                    let mut articles: Vec<Article> =
+                        Article::load_matching(&base_dir, &tr, &filter)?
+                            .into_values()
+                            .collect();
+                    articles.sort_by_key(|a| (a.first_version().date, a.id().to_string()));
+                    bibtex::export(
+                        &articles,
+                        config.bibtex.key.as_deref().unwrap_or(bibtex::DEFAULT_KEY),
+                    )
+                })?;
+                match file {
+                    Some(file) => {
+                        std::fs::write(&file, text).with_context(|| format!("writing {file:?}"))?
+                    }
+                    None => print!("{text}"),
+                }
+            }
             BibtexCommand::Bookmark { file, tag_name } => {
                 let (base_dir, _config, _client) = prepare()?;
                 db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
-                    bibtex::bookmark(&base_dir, &conn, &file, &tag_name)
+                    bibtex::bookmark(&base_dir, &conn, &file, &tag_name)?;
+                    conn.commit()?;
+                    Ok(())
                 })?
             }
             BibtexCommand::Check { file } => {
                 let (base_dir, _config, _client) = prepare()?;
-                db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
-                    bibtex::check(&base_dir, &conn, &file)
-                })?
+                let findings =
+                    db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
+                        bibtex::check(&base_dir, &conn, &file)
+                    })?;
+                if output::json() {
+                    output::print_json(&findings)?;
+                }
             }
         },
         Commands::Init => {
@@ -468,7 +2074,7 @@ fn inner_main() -> anyhow::Result<()> {
                 .create_new(true)
                 .open(&config_filename)
                 .with_context(|| format!("opening {config_filename:?}"))?;
-            write!(file, include_str!("sample/config.toml"))
+            write!(file, "{}", include_str!("sample/config.toml"))
                 .with_context(|| format!("writing {config_filename:?}"))?;
 
             // Create the .gitignore file.
@@ -484,24 +2090,136 @@ fn inner_main() -> anyhow::Result<()> {
             // Create the database.
             db::create(&base_dir)?;
 
-            println!("Now, please edit the configuration file at {config_filename:?}.");
-            println!();
-            println!(
+            message!("Now, please edit the configuration file at {config_filename:?}.");
+            message!();
+            message!(
                 "Then, run `arxiv-reader pull` to download articles from the specified categories."
             );
-            println!(
+            message!(
                 "Look at new articles with `arxiv-reader news` and find articles with `arxiv-reader find`."
             );
-            println!("Run `arxiv-reader help` for more information.");
+            message!("Run `arxiv-reader help` for more information.");
         }
         Commands::Database(cmd) => match cmd {
-            DatabaseCommand::Dump => {
+            DatabaseCommand::Dump {
+                file,
+                format,
+                compress,
+                filter,
+            } => {
                 let (base_dir, _config, _client) = prepare()?;
-                db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| db::dump(&conn))?;
+                db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |conn| {
+                    compress::write(file.as_deref(), compress, |writer| {
+                        db::dump(&conn, &base_dir, filter.as_ref(), format, writer)
+                    })
+                })?;
+            }
+            DatabaseCommand::MergeSeen {
+                file,
+                into: Some(into),
+            } => {
+                state::merge_seen_files(&into, &file)?;
             }
-            DatabaseCommand::Load => {
+            DatabaseCommand::MergeSeen { file, into: None } => {
+                let (base_dir, config, _client) = prepare()?;
+                let summary = state::merge_seen(&base_dir, &file)?;
+                if output::json() {
+                    output::print_json(&summary)?;
+                }
+                message!(
+                    "Changed the seen state of {} articles; seen-articles now has {} lines instead of {}.",
+                    summary.changed,
+                    summary.lines_after,
+                    summary.lines_before
+                );
+                run_push_command(&base_dir, &config)?;
+            }
+            DatabaseCommand::Load { file } => {
                 let (base_dir, _config, _client) = prepare()?;
-                db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, db::load)?;
+                db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                    compress::read(file.as_deref(), |reader| db::load(tr, reader))
+                })?;
+            }
+        },
+        Commands::State(cmd) => match cmd {
+            StateCommand::Export { file, compress } => {
+                let base_dir = get_base_dir()?;
+                let bundle = state::export(&base_dir)?;
+                compress::write(Some(&file), compress, |writer| {
+                    serde_json::to_writer_pretty(&mut *writer, &bundle)?;
+                    writeln!(writer)?;
+                    Ok(())
+                })?;
+            }
+            StateCommand::Import { file } => {
+                let (base_dir, config, _client) = prepare()?;
+                let bundle: state::Bundle = compress::read(Some(&file), |reader| {
+                    serde_json::from_reader(reader).with_context(|| format!("parsing {file:?}"))
+                })?;
+                let summary = state::import(&base_dir, &bundle)?;
+                ArticleState::refresh_cache(&mut db::open(&base_dir)?, &base_dir)?;
+                if output::json() {
+                    output::print_json(&summary)?;
+                }
+                message!(
+                    "Added tags to {} articles, notes to {} articles, and {} lines to seen-articles.",
+                    summary.tagged,
+                    summary.notes,
+                    summary.seen_lines
+                );
+                for id in &summary.conflicting_notes {
+                    message!(
+                        "{id} already has different notes; the imported ones are in notes-imported.txt."
+                    );
+                }
+                run_push_command(&base_dir, &config)?;
+            }
+            StateCommand::Cache { disable } => {
+                let (base_dir, _config, _client) = prepare()?;
+                let count =
+                    db::with_write_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                        let count = ArticleState::set_cache_enabled(&tr, &base_dir, !disable)?;
+                        tr.commit()?;
+                        Ok(count)
+                    })?;
+                if disable {
+                    message!("Disabled the state cache.");
+                } else {
+                    message!("Cached the tags, notes and attachments of {count} articles.");
+                }
+            }
+        },
+        Commands::Export(cmd) => match cmd {
+            ExportCommand::Graph {
+                format,
+                file,
+                filters,
+            } => {
+                let (base_dir, config, _client) = prepare()?;
+                let graph = db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                    let filter = filters.get().prepare(&tr)?;
+                    let articles = Article::load(&base_dir, &tr)?;
+                    let clusters =
+                        AuthorClusters::new(articles.values().map(|a| a.authors().as_str()));
+                    Ok(Graph::coauthors(
+                        articles.values().filter(|a| filter.matches(a)),
+                        &clusters,
+                        |name| {
+                            if config.latex_to_unicode {
+                                unicodeit::replace(name)
+                            } else {
+                                name.to_string()
+                            }
+                        },
+                    ))
+                })?;
+                let text = graph.write(format);
+                match file {
+                    Some(file) => {
+                        std::fs::write(&file, text).with_context(|| format!("writing {file:?}"))?
+                    }
+                    None => print!("{text}"),
+                }
             }
         },
         Commands::GenerateCompletions { generator } => {
@@ -511,6 +2229,48 @@ fn inner_main() -> anyhow::Result<()> {
                 "arxiv-reader",
                 &mut std::io::stdout(),
             );
+            print!("{}", completions::dynamic(generator));
+        }
+        Commands::Complete { kind, prefix } => {
+            let (base_dir, config, _client) = prepare()?;
+            db::with_transaction(&mut db::open(&base_dir)?, &base_dir, |tr| {
+                let mut candidates = BTreeSet::new();
+                match kind {
+                    CompletionKind::Ids => {
+                        // There may be millions of articles, so we only list a few.
+                        let mut get = tr.prepare(
+                            "SELECT id FROM article WHERE substr(id, 1, length(?1)) = ?1 ORDER BY id DESC LIMIT 200",
+                        )?;
+                        let mut rows = get.query([&prefix])?;
+                        while let Some(row) = rows.next()? {
+                            candidates.insert(row.get::<_, String>(0)?);
+                        }
+                    }
+                    CompletionKind::Tags => {
+                        candidates.extend(config.tags.iter().map(|(_, tag)| tag.to_string()));
+                        candidates.extend(
+                            ArticleState::all_tags(&base_dir, &tr)?
+                                .iter()
+                                .map(|tag| tag.to_string()),
+                        );
+                    }
+                    CompletionKind::Categories => {
+                        candidates.extend(config.categories.iter().cloned());
+                        let mut get =
+                            tr.prepare("SELECT category FROM set_ WHERE category IS NOT NULL")?;
+                        let mut rows = get.query(())?;
+                        while let Some(row) = rows.next()? {
+                            candidates.insert(row.get::<_, String>(0)?);
+                        }
+                    }
+                }
+                for candidate in candidates {
+                    if candidate.starts_with(&prefix) {
+                        message!("{candidate}");
+                    }
+                }
+                Ok(())
+            })?;
         }
     }
     Ok(())
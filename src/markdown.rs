@@ -0,0 +1,184 @@
+//! Rendering of the notes (which are Markdown) for the terminal.
+//!
+//! Only the parts of Markdown that help to scan notes are rendered: headings, emphasis,
+//! lists and block quotes. Code and math, fenced or inline (`$...$`), are left verbatim,
+//! so that underscores and asterisks in formulas are not mistaken for emphasis.
+
+use termion::style;
+
+/// Ends bold text. (termion's `NoBold` is a double underline on many terminals.)
+const NO_BOLD: &str = "\x1b[22m";
+
+/// The notes with terminal styling instead of the Markdown markup.
+pub fn render(text: &str) -> String {
+    let mut res = Vec::new();
+    // The line ending the current verbatim block, if we are in one.
+    let mut verbatim_until: Option<&str> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(end) = verbatim_until {
+            if trimmed.starts_with(end) {
+                verbatim_until = None;
+            }
+            res.push(line.to_string());
+            continue;
+        }
+        if let Some(fence) = ["```", "~~~", "$$"]
+            .into_iter()
+            .find(|fence| trimmed.starts_with(fence))
+        {
+            // A display formula written on one line, such as `$$x^2$$`, ends right away.
+            if !(fence == "$$" && trimmed.len() > 4 && trimmed.ends_with("$$")) {
+                verbatim_until = Some(fence);
+            }
+            res.push(line.to_string());
+            continue;
+        }
+        res.push(render_line(line));
+    }
+    res.join("\n")
+}
+
+fn render_line(line: &str) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let rest = line.trim_start();
+    // Headings.
+    let level = rest.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level)
+        && let Some(title) = rest[level..].strip_prefix(' ')
+    {
+        let underline = if level == 1 {
+            style::Underline.to_string()
+        } else {
+            String::new()
+        };
+        return format!(
+            "{indent}{}{underline}{}{}{NO_BOLD}",
+            style::Bold,
+            inline(title.trim_end_matches(['#', ' '])),
+            style::NoUnderline,
+        );
+    }
+    // Lists.
+    if let Some(item) = ["- ", "* ", "+ "]
+        .into_iter()
+        .find_map(|bullet| rest.strip_prefix(bullet))
+    {
+        return format!("{indent}• {}", inline(item));
+    }
+    // Block quotes.
+    if let Some(quote) = rest.strip_prefix('>') {
+        return format!("{indent}│ {}", inline(quote.trim_start()));
+    }
+    format!("{indent}{}", inline(rest))
+}
+
+/// Renders emphasis (`**bold**`, `__bold__`, `*italic*`, `_italic_`), leaving code spans
+/// and inline math verbatim.
+fn inline(text: &str) -> String {
+    let mut res = String::new();
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap();
+        // Escaped markup.
+        if c == '\\'
+            && let Some(next) = rest[1..].chars().next()
+            && "*_`$#\\".contains(next)
+        {
+            res.push(next);
+            i += 1 + next.len_utf8();
+            continue;
+        }
+        // Code spans and inline math.
+        if c == '`' || c == '$' {
+            let delimiter = if rest.starts_with("$$") {
+                "$$"
+            } else {
+                &rest[..1]
+            };
+            if let Some(end) = rest[delimiter.len()..].find(delimiter) {
+                let len = 2 * delimiter.len() + end;
+                res += &rest[..len];
+                i += len;
+                continue;
+            }
+        }
+        // Emphasis.
+        if c == '*' || c == '_' {
+            let double = rest[1..].starts_with(c);
+            let delimiter = &rest[..if double { 2 } else { 1 }];
+            let preceded_by_word = text[..i]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric);
+            let inner = &rest[delimiter.len()..];
+            // Underscores inside words, such as in snake_case, are not emphasis.
+            let in_word = c == '_' && preceded_by_word;
+            if !in_word
+                && !inner.starts_with(char::is_whitespace)
+                && let Some(end) = find_closing(inner, delimiter)
+            {
+                let (start, stop) = if double {
+                    (style::Bold.to_string(), NO_BOLD.to_string())
+                } else {
+                    (style::Italic.to_string(), style::NoItalic.to_string())
+                };
+                res += &format!("{start}{}{stop}", inline(&inner[..end]));
+                i += 2 * delimiter.len() + end;
+                continue;
+            }
+        }
+        res.push(c);
+        i += c.len_utf8();
+    }
+    res
+}
+
+/// The position of the delimiter closing emphasis in `text`: not preceded by whitespace
+/// and, for underscores, not followed by a letter.
+fn find_closing(text: &str, delimiter: &str) -> Option<usize> {
+    let mut start = 0;
+    while let Some(pos) = text[start..].find(delimiter) {
+        let pos = start + pos;
+        let after = &text[pos + delimiter.len()..];
+        let after_text = pos > 0 && !text[..pos].ends_with(char::is_whitespace);
+        // `**` does not close `*`.
+        let continues = after.starts_with(&delimiter[..1])
+            || delimiter.starts_with('_') && after.starts_with(char::is_alphanumeric);
+        if after_text && !continues {
+            return Some(pos);
+        }
+        start = pos + delimiter.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn notes() {
+        let bold = style::Bold.to_string();
+        let italic = style::Italic.to_string();
+        let no_italic = style::NoItalic.to_string();
+        assert_eq!(
+            render("## Main result"),
+            format!("{bold}Main result{}{NO_BOLD}", style::NoUnderline)
+        );
+        assert_eq!(
+            render("- a **key** lemma\n  * see _below_"),
+            format!("• a {bold}key{NO_BOLD} lemma\n  • see {italic}below{no_italic}")
+        );
+        // Math, code and words with underscores stay as they are.
+        assert_eq!(render("$a_1 * b_2$ and `x_*`"), "$a_1 * b_2$ and `x_*`");
+        assert_eq!(render("snake_case_name"), "snake_case_name");
+        assert_eq!(render("2 * 3 * 4"), "2 * 3 * 4");
+        let fenced = "```math\n# not a heading\n**x**\n```\n**y**";
+        assert_eq!(
+            render(fenced),
+            format!("```math\n# not a heading\n**x**\n```\n{bold}y{NO_BOLD}")
+        );
+    }
+}
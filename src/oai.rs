@@ -1,19 +1,48 @@
+//! Harvesting of article metadata with arXiv's OAI-PMH interface.
+//!
+//! [`download_changes`] and [`download_set`] fetch the records changed since the last
+//! update of a category or set and store them in the database (see [`Harvest`] for the two
+//! ways of doing so). Interrupted harvests are resumed where they stopped (see
+//! [`Continuation`]).
+
 use std::{cmp::min, collections::HashMap, fs::remove_file, io::Write, path::Path, time::Instant};
 
-use anyhow::{Context, bail};
+use anyhow::{Context, anyhow, bail};
 use chrono::{DateTime, Days, NaiveDate};
-use reqwest::header::HeaderValue;
 use rusqlite::{Connection, Transaction, params};
 use serde::{Deserialize, Serialize};
 
-use crate::{db, rate_limited_client::Client, util::write_then_rename};
+use crate::{
+    article::ArxivId,
+    db, message,
+    progress::{self, Progress},
+    rate_limited_client::Client,
+    util::write_then_rename,
+};
+
+/// How `download_set` asks for the records changed since the last update.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Harvest {
+    /// Download all changed records with ListRecords.
+    #[default]
+    Records,
+    /// List the changed records with ListIdentifiers and only download those whose
+    /// datestamp is newer than the saved one, with one GetRecord request each. This needs
+    /// more requests, but much less data if most of the changed records are already known,
+    /// for example those changed on the day that successive harvests overlap by.
+    Identifiers,
+}
 
+/// How far the harvesting of an OAI set got: the date of the last complete update and,
+/// if a harvest was interrupted, the data needed to resume it.
 pub struct Continuation {
     pub last_update: Option<String>,
     resumption_data: Option<ResumptionData>,
 }
 
 impl Continuation {
+    /// The continuations of all known sets, by set name.
     pub fn read_all(tr: &Transaction) -> anyhow::Result<HashMap<String, Self>> {
         let mut get = tr.prepare("SELECT name, date, resumption_data FROM set_")?;
         let mut rows = get.query(())?;
@@ -129,39 +158,134 @@ impl Continuation {
 #[derive(Serialize, Deserialize)]
 struct ResumptionData {
     request_number: usize,
-    resumption_request: String,
+    /// The next list request, or `None` if only the pending records are left.
+    resumption_request: Option<String>,
     /// The response date of the first response.
     response_date: Option<String>,
+    /// The articles that ListIdentifiers listed as changed and whose records have not been
+    /// downloaded yet.
+    #[serde(default)]
+    pending: Vec<String>,
+}
+
+/// Sends a request to the OAI-PMH interface and returns the xml response.
+fn request(client: &mut Client, body: &str) -> anyhow::Result<Vec<u8>> {
+    let before_request = Instant::now();
+    let res = client
+        .post_form("https://oaipmh.arxiv.org/oai", body)
+        .context("requesting data from oaipmh.arxiv.org")?;
+    let request_duration = Instant::now().duration_since(before_request);
+    progress::status(&format!(
+        "Received response after {:.2} seconds.",
+        request_duration.as_secs_f32()
+    ));
+    if res.content_type.as_deref() != Some("text/xml") {
+        bail!(
+            "wrong content type (expected text/xml, received {:?})",
+            res.content_type
+        );
+    }
+    Ok(res.body)
 }
 
+/// Downloads all changes to articles in the given category since the last update.
+/// Returns the ids of the articles that were not in the database before.
 pub fn download_changes(
     base_dir: &Path,
     conn: &mut Connection,
     category: &str,
     client: &mut Client,
-) -> anyhow::Result<()> {
+    harvest: Harvest,
+) -> anyhow::Result<Vec<ArxivId>> {
+    // Find the name of the set corresponding to this category.
+    let set = db::with_write_transaction(conn, base_dir, |tr| {
+        if let Some(set) = Continuation::set_for_category(&tr, category)? {
+            return Ok(set);
+        }
+        // Try downloading a list of all sets.
+        update_sets(base_dir, &tr, client)?;
+        // Then, look for the category again.
+        let set = Continuation::set_for_category(&tr, category)?;
+        tr.commit()?;
+        set.with_context(|| format!("category {category:?} not found"))
+    })?;
+    download_set(base_dir, conn, &set, client, harvest)
+}
+
+/// Downloads all changes to articles in the OAI set with the given spec (such as
+/// "math:math:NT", or "math" for a whole archive) since the last update.
+/// Returns the ids of the articles that were not in the database before.
+pub fn download_set(
+    base_dir: &Path,
+    conn: &mut Connection,
+    set: &str,
+    client: &mut Client,
+    harvest: Harvest,
+) -> anyhow::Result<Vec<ArxivId>> {
+    let mut new_ids = Vec::new();
+    let progress = Progress::new(format!("Records in {set}:"), None);
+    // Remember the date of the last update even for sets that are not categories.
+    db::with_write_transaction(conn, base_dir, |tr| {
+        tr.execute(
+            "INSERT OR IGNORE INTO set_ (name) VALUES (?1)",
+            params![set],
+        )?;
+        tr.commit()?;
+        Ok(())
+    })?;
     // Keep making requests until done.
     loop {
+        // First download the records that ListIdentifiers listed as changed, one GetRecord
+        // request at a time and outside of any transaction. Each record is saved together
+        // with the remaining list.
+        let resumption_data = db::with_transaction(conn, base_dir, |tr| {
+            Ok(Continuation::read(&tr, set)?.resumption_data)
+        })?;
+        if let Some(mut resumption_data) = resumption_data
+            && let Some(id) = resumption_data.pending.first()
+        {
+            let id: ArxivId = id
+                .parse()
+                .with_context(|| format!("invalid pending article id {id:?}"))?;
+            let (record, _) = get_record(client, &id)?;
+            let done = db::with_write_transaction(conn, base_dir, |tr| {
+                let response_date = resumption_data
+                    .response_date
+                    .clone()
+                    .context("missing response date of the harvest")?;
+                if let Some(id) = save_record(&tr, record, &response_date)? {
+                    new_ids.push(id);
+                }
+                resumption_data.pending.remove(0);
+                let done = resumption_data.pending.is_empty()
+                    && resumption_data.resumption_request.is_none();
+                if done {
+                    Continuation::update_last_update(&tr, set, &response_date)?;
+                } else {
+                    Continuation::update_resumption_data(&tr, set, &resumption_data)?;
+                }
+                tr.commit()?;
+                Ok(done)
+            })?;
+            if done {
+                break;
+            }
+            continue;
+        }
         // We start a new transaction on each request.
         // This way, intermediate progress will be saved.
         let continue_ = db::with_write_transaction(conn, base_dir, |tr| {
-            // Find the name of the set corresponding to this category.
-            let set = if let Some(set) = Continuation::set_for_category(&tr, category)? {
-                set
-            } else {
-                // Try downloading a list of all sets.
-                update_sets(base_dir, &tr, client)?;
-                // Then, look for the category again.
-                Continuation::set_for_category(&tr, category)?
-                    .with_context(|| format!("category {category:?} not found"))?
-            };
             // Check whether there is resumption data for this set.
-            let cont = Continuation::read(&tr, &set)?;
+            let cont = Continuation::read(&tr, set)?;
             // If not, create a new request.
             let mut resumption_data = if let Some(r) = cont.resumption_data {
                 r
             } else {
-                let mut resumption_request = "verb=ListRecords&metadataPrefix=arXivRaw".to_string();
+                let verb = match harvest {
+                    Harvest::Records => "ListRecords",
+                    Harvest::Identifiers => "ListIdentifiers",
+                };
+                let mut resumption_request = format!("verb={verb}&metadataPrefix=arXivRaw");
                 // Restrict to the sets specified in the configuration file.
                 if !set.is_empty() {
                     resumption_request += &format!("&set={}", set);
@@ -179,43 +303,29 @@ pub fn download_changes(
                     let from = from
                         .checked_sub_days(Days::new(1))
                         .with_context(|| format!("parsing date {from}"))?;
-                    println!("Retrieving changes since {}.", from.format("%Y-%m-%d"));
+                    progress.set_message(format!(
+                        "Records in {set} since {}:",
+                        from.format("%Y-%m-%d")
+                    ));
                     resumption_request += &format!("&from={}", from.format("%Y-%m-%d"));
                 }
                 ResumptionData {
                     request_number: 1,
-                    resumption_request,
+                    resumption_request: Some(resumption_request),
                     response_date: None,
+                    pending: Vec::new(),
                 }
             };
+            let resumption_request = resumption_data
+                .resumption_request
+                .clone()
+                .context("no request left to resume")?;
             // Make the request.
-            let res = client.with(|client| {
-                println!("Getting changeset {}...", resumption_data.request_number);
-                let before_request = Instant::now();
-                let res = client
-                    .post("https://oaipmh.arxiv.org/oai".to_string())
-                    .header(
-                        reqwest::header::CONTENT_TYPE,
-                        "application/x-www-form-urlencoded",
-                    )
-                    .body(resumption_data.resumption_request.clone())
-                    .send()
-                    .and_then(|res| res.error_for_status())
-                    .context("requesting data from oaipmh.arxiv.org")?;
-                let request_duration = Instant::now().duration_since(before_request);
-                println!(
-                    "Received response after {:.2} seconds.",
-                    request_duration.as_secs_f32()
-                );
-                let content_type = res.headers().get("Content-Type");
-                if content_type != Some(&HeaderValue::from_static("text/xml")) {
-                    bail!("wrong content type (expected text/xml, received {content_type:?})");
-                }
-                let res = res
-                    .bytes()
-                    .context("requesting data from oaipmh.arxiv.org")?;
-                Ok(res)
-            })?;
+            progress::status(&format!(
+                "Getting changeset {}...",
+                resumption_data.request_number
+            ));
+            let res = request(client, &resumption_request)?;
             // Save a copy of the response to update.xml for debugging in case something goes wrong.
             let xml_file = base_dir.join("update.xml");
             write_then_rename(xml_file.clone(), |writer| {
@@ -226,7 +336,7 @@ pub fn download_changes(
             let res =
                 str::from_utf8(&res).context("reading data from oaipmh.arxiv.org (non-utf8)")?;
             // Parse the response.
-            let oai_pmh: OaipmhListRecords =
+            let oai_pmh: OaipmhList =
                 quick_xml::de::from_str(res).context("parsing response from oaipmh.arxiv.org")?;
             // Extract the response date for the first request.
             if resumption_data.response_date.is_none() {
@@ -248,7 +358,7 @@ pub fn download_changes(
                     .iter()
                     .any(|error| error.code == "badResumptionToken")
                 {
-                    Continuation::clear_resumption_data(&tr, &set)?;
+                    Continuation::clear_resumption_data(&tr, set)?;
                     tr.commit()?;
                     bail!("Bad or expired resumption token. Please retry.");
                 }
@@ -257,7 +367,7 @@ pub fn download_changes(
                     .iter()
                     .any(|error| error.code == "noRecordsMatch")
                 {
-                    println!("Received 0 records.");
+                    progress::status("Received 0 records.");
                     // Nothing went wrong, so we delete update.xml.
                     remove_file(xml_file).context("removing update.xml")?;
                     // Clear the resumption data as we are done.
@@ -265,7 +375,7 @@ pub fn download_changes(
                     // date need to be taken into account in later requests.
                     Continuation::update_last_update(
                         &tr,
-                        &set,
+                        set,
                         &resumption_data.response_date.unwrap(),
                     )?;
                     tr.commit()?;
@@ -273,7 +383,7 @@ pub fn download_changes(
                 }
                 // Otherwise, just print all errors and abort.
                 for error in &oai_pmh.errors {
-                    println!(
+                    message!(
                         "{}: {}",
                         error.code,
                         error.value.clone().unwrap_or_default()
@@ -281,115 +391,103 @@ pub fn download_changes(
                 }
                 bail!("Download failed.");
             }
-            let list_records = oai_pmh
-                .list_records
-                .context("parsing response from oaipmh.arxiv.org")
-                .context("missing <ListRecords>")?;
-            let records = list_records.records;
-            println!("Received {} records.", records.len());
-            // Save the records (= articles) from the response.
-            for article in records {
-                let header = article.header;
-                let article = article.metadata.arxiv_raw;
-                let id = article
-                    .id
-                    .parse()
-                    .context("parsing response from oaipmh.arxiv.org")
-                    .with_context(|| format!("invalid article id {:?}", article.id))?;
-                // If this article was already encountered before, retrieve it.
-                let old_article = crate::article::ArticleMetadata::load_one(&tr, &id)?;
-                let old_versions = old_article.map(|a| a.versions);
-                let mut versions = Vec::new();
-                // The number of versions should never go down.
-                if let Some(old_versions) = old_versions.as_ref()
-                    && old_versions.len() > article.versions.len()
-                {
-                    bail!("more versions in old metadata update");
+            let response_date = resumption_data.response_date.clone().unwrap();
+            // An interrupted harvest is resumed with the verb it was started with, even if
+            // `harvest` has changed since.
+            let (verb, listed, records, resumption_token) = match (
+                oai_pmh.list_records,
+                oai_pmh.list_identifiers,
+            ) {
+                (Some(list_records), _) => {
+                    progress::status(&format!(
+                        "Received {} records in changeset {}.",
+                        list_records.records.len(),
+                        resumption_data.request_number
+                    ));
+                    (
+                        "ListRecords",
+                        list_records.records.len(),
+                        list_records.records,
+                        list_records.resumption_token,
+                    )
                 }
-                for (i, version) in article.versions.into_iter().enumerate() {
-                    let old_version = old_versions
-                        .as_ref()
-                        .and_then(|old_versions| old_versions.get(i));
-                    let number = version
-                        .version
-                        .strip_prefix('v')
-                        .context("parsing response from oaipmh.arxiv.org")
-                        .with_context(|| format!("invalid version number {:?}", version.version))?
-                        .parse()?;
-                    let date = DateTime::parse_from_rfc2822(&version.date)
-                        .context("parsing response from oaipmh.arxiv.org")
-                        .with_context(|| format!("invalid date: {:?}", version.date))?;
-                    // Compute the first response date in which we have seen this article version.
-                    let first_encounter = match old_version {
-                        Some(old_version) => min(
-                            old_version.first_encounter.clone(),
-                            resumption_data.response_date.clone().unwrap(),
-                        ),
-                        None => resumption_data.response_date.clone().unwrap(),
-                    };
-                    versions.push(crate::article::Version {
-                        number,
-                        date,
-                        size: version.size,
-                        source_type: version.source_type,
-                        first_encounter,
-                    });
+                (None, Some(list_identifiers)) => {
+                    let listed = list_identifiers.headers.len();
+                    // Deleted records are handed to `save_record` like with ListRecords.
+                    // The changed records are only downloaded after this transaction.
+                    let mut records = Vec::new();
+                    for header in list_identifiers.headers {
+                        if header.is_deleted() {
+                            records.push(Set {
+                                header,
+                                metadata: None,
+                            });
+                            continue;
+                        }
+                        let id: ArxivId = header
+                            .identifier
+                            .strip_prefix("oai:arXiv.org:")
+                            .and_then(|id| id.parse().ok())
+                            .context("parsing response from oaipmh.arxiv.org")
+                            .with_context(|| {
+                                format!("invalid identifier {:?}", header.identifier)
+                            })?;
+                        if saved_datestamp(&tr, &id)?.is_none_or(|saved| saved < header.datestamp) {
+                            resumption_data.pending.push(id.to_string());
+                        }
+                    }
+                    progress::status(&format!(
+                        "Received {} identifiers in changeset {}, {} changed records to download.",
+                        listed,
+                        resumption_data.request_number,
+                        resumption_data.pending.len()
+                    ));
+                    (
+                        "ListIdentifiers",
+                        listed,
+                        records,
+                        list_identifiers.resumption_token,
+                    )
+                }
+                (None, None) => {
+                    return Err(anyhow!("parsing response from oaipmh.arxiv.org")
+                        .context("missing <ListRecords> or <ListIdentifiers>"));
+                }
+            };
+            // The number of records in all changesets is only known with a resumption token.
+            if let Some(total) = resumption_token.as_ref().and_then(|t| t.complete_list_size) {
+                progress.set_total(Some(total));
+            }
+            progress.inc(listed as u64);
+            // Save the records (= articles) from the response.
+            for record in records {
+                let id = save_record(&tr, record, &response_date)?;
+                if let Some(id) = id {
+                    new_ids.push(id);
                 }
-                let categories = article
-                    .categories
-                    .split(' ')
-                    .map(|s| s.to_string())
-                    .collect();
-                let article = crate::article::ArticleMetadata {
-                    id: id.clone(),
-                    submitter: article.submitter,
-                    versions,
-                    title: article.title,
-                    authors: article.authors,
-                    categories,
-                    comments: article.comments,
-                    proxy: article.proxy,
-                    report_no: article.report_no,
-                    acm_classes: article.acm_classes,
-                    msc_classes: article.msc_classes,
-                    journal_ref: article.journal_ref,
-                    doi: article.doi,
-                    license: article.license,
-                    abstract_: article.abstract_,
-                    last_change: Some(header.datestamp),
-                    sets: Some(header.sets),
-                };
-                // Validate and then save the article metadata.
-                article
-                    .validate()
-                    .with_context(|| format!("invalid metadata of article {id}"))?;
-                article.write(&tr)?;
             }
-            let response_date = resumption_data.response_date.as_ref().unwrap();
             // Nothing went wrong, so we delete update.xml.
             remove_file(xml_file).context("removing update.xml")?;
             // We have updated some articles with this response date.
             // Any later record updates may have been overwritten.
-            Continuation::reset_last_update(&tr, response_date)?;
+            Continuation::reset_last_update(&tr, &response_date)?;
             // If the response contains a non-empty resumption token element, use
-            // it for the next response. Otherwise, stop.
-            if let Some(resumption_token) = list_records.resumption_token
-                && let Some(resumption_token_value) = resumption_token.value
-            {
+            // it for the next response. Otherwise, stop once the pending records are
+            // downloaded.
+            resumption_data.resumption_request = resumption_token
+                .and_then(|resumption_token| resumption_token.value)
+                .map(|value| format!("verb={verb}&resumptionToken={value}"));
+            if resumption_data.resumption_request.is_some() || !resumption_data.pending.is_empty() {
                 resumption_data.request_number += 1;
-                resumption_data.resumption_request = format!(
-                    "verb=ListRecords&resumptionToken={}",
-                    resumption_token_value
-                );
                 // Write the resumption data in case of problems with the next request.
-                Continuation::update_resumption_data(&tr, &set, &resumption_data)?;
+                Continuation::update_resumption_data(&tr, set, &resumption_data)?;
                 tr.commit()?;
                 Ok(true)
             } else {
                 // Clear the resumption data as we are done.
                 // Save the date of the first response. Only changes on or after this
                 // date need to be taken into account in later requests.
-                Continuation::update_last_update(&tr, &set, response_date)?;
+                Continuation::update_last_update(&tr, set, &response_date)?;
                 tr.commit()?;
                 Ok(false)
             }
@@ -398,7 +496,158 @@ pub fn download_changes(
             break;
         }
     }
-    Ok(())
+    Ok(new_ids)
+}
+
+/// Downloads the metadata of a single article, for example one outside the subscribed
+/// categories, and saves it. Returns whether the article was not in the database before.
+pub fn fetch(tr: &Transaction, client: &mut Client, id: &ArxivId) -> anyhow::Result<bool> {
+    let (record, response_date) = get_record(client, id)?;
+    Ok(save_record(tr, record, &response_date)?.is_some())
+}
+
+/// Downloads the record of a single article. Returns it with the date of the response.
+fn get_record(client: &mut Client, id: &ArxivId) -> anyhow::Result<(Set, String)> {
+    progress::status(&format!("Getting metadata of {id}..."));
+    let res = request(
+        client,
+        &format!("verb=GetRecord&identifier=oai:arXiv.org:{id}&metadataPrefix=arXivRaw"),
+    )?;
+    let res = str::from_utf8(&res).context("reading data from oaipmh.arxiv.org (non-utf8)")?;
+    let oai_pmh: OaipmhGetRecord =
+        quick_xml::de::from_str(res).context("parsing response from oaipmh.arxiv.org")?;
+    if oai_pmh
+        .errors
+        .iter()
+        .any(|error| error.code == "idDoesNotExist")
+    {
+        bail!("arXiv has no article {id}");
+    }
+    if !oai_pmh.errors.is_empty() {
+        for error in &oai_pmh.errors {
+            message!(
+                "{}: {}",
+                error.code,
+                error.value.clone().unwrap_or_default()
+            );
+        }
+        bail!("Download failed.");
+    }
+    let record = oai_pmh
+        .get_record
+        .context("parsing response from oaipmh.arxiv.org")
+        .context("missing <GetRecord>")?
+        .record;
+    let response_date = oai_pmh
+        .response_date
+        .split_at_checked(10)
+        .context("parsing response from oaipmh.arxiv.org")
+        .context("invalid response date")?
+        .0;
+    Ok((record, response_date.to_string()))
+}
+
+/// The datestamp of the saved record of an article, if there is one.
+fn saved_datestamp(tr: &Transaction, id: &ArxivId) -> anyhow::Result<Option<String>> {
+    let mut get = tr.prepare_cached("SELECT last_change FROM article WHERE id = ?1")?;
+    let mut rows = get.query(params![id.to_string()])?;
+    Ok(match rows.next()? {
+        Some(row) => row.get(0)?,
+        None => None,
+    })
+}
+
+/// Validates and saves the metadata of an article from a response of the OAI-PMH
+/// interface. The date of the response (such as "2025-10-01") is recorded as the first
+/// encounter of new versions. Returns the id if the article was not in the database before.
+fn save_record(
+    tr: &Transaction,
+    record: Set,
+    response_date: &str,
+) -> anyhow::Result<Option<ArxivId>> {
+    let header = record.header;
+    // arXiv keeps the metadata of withdrawn articles, so deleted records should not occur.
+    // If they do, the saved metadata is kept.
+    let Some(metadata) = record.metadata.filter(|_| !header.is_deleted()) else {
+        progress::status(&format!("Skipping deleted record {}.", header.identifier));
+        return Ok(None);
+    };
+    let article = metadata.arxiv_raw;
+    let id = article
+        .id
+        .parse()
+        .context("parsing response from oaipmh.arxiv.org")
+        .with_context(|| format!("invalid article id {:?}", article.id))?;
+    // If this article was already encountered before, retrieve it.
+    let old_article = crate::article::ArticleMetadata::load_one(tr, &id)?;
+    let is_new = old_article.is_none();
+    let old_versions = old_article.map(|a| a.versions);
+    let mut versions = Vec::new();
+    // The number of versions should never go down.
+    if let Some(old_versions) = old_versions.as_ref()
+        && old_versions.len() > article.versions.len()
+    {
+        bail!("more versions in old metadata update");
+    }
+    for (i, version) in article.versions.into_iter().enumerate() {
+        let old_version = old_versions
+            .as_ref()
+            .and_then(|old_versions| old_versions.get(i));
+        let number = version
+            .version
+            .strip_prefix('v')
+            .context("parsing response from oaipmh.arxiv.org")
+            .with_context(|| format!("invalid version number {:?}", version.version))?
+            .parse()?;
+        let date = DateTime::parse_from_rfc2822(&version.date)
+            .context("parsing response from oaipmh.arxiv.org")
+            .with_context(|| format!("invalid date: {:?}", version.date))?;
+        // Compute the first response date in which we have seen this article version.
+        let first_encounter = match old_version {
+            Some(old_version) => min(
+                old_version.first_encounter.clone(),
+                response_date.to_string(),
+            ),
+            None => response_date.to_string(),
+        };
+        versions.push(crate::article::Version {
+            number,
+            date,
+            size: version.size,
+            source_type: version.source_type,
+            first_encounter,
+        });
+    }
+    let categories = article
+        .categories
+        .split(' ')
+        .map(|s| s.to_string())
+        .collect();
+    let article = crate::article::ArticleMetadata {
+        id: id.clone(),
+        submitter: article.submitter,
+        versions,
+        title: article.title,
+        authors: article.authors,
+        categories,
+        comments: article.comments,
+        proxy: article.proxy,
+        report_no: article.report_no,
+        acm_classes: article.acm_classes,
+        msc_classes: article.msc_classes,
+        journal_ref: article.journal_ref,
+        doi: article.doi,
+        license: article.license,
+        abstract_: article.abstract_,
+        last_change: Some(header.datestamp),
+        sets: Some(header.sets),
+    };
+    // Validate and then save the article metadata.
+    article
+        .validate()
+        .with_context(|| format!("invalid metadata of article {id}"))?;
+    article.write(tr)?;
+    Ok(is_new.then_some(id))
 }
 
 // Below are structs that can be deserialized from the server's responses.
@@ -408,13 +657,30 @@ pub fn download_changes(
 // https://arxiv.org/OAI/arXivRaw.xsd
 
 #[derive(Deserialize)]
-struct OaipmhListRecords {
+struct OaipmhList {
     #[serde(rename = "responseDate")]
     response_date: String,
     #[serde(default, rename = "error")]
     errors: Vec<OaiError>,
     #[serde(rename = "ListRecords")]
     list_records: Option<ListRecords>,
+    #[serde(rename = "ListIdentifiers")]
+    list_identifiers: Option<ListIdentifiers>,
+}
+
+#[derive(Deserialize)]
+struct OaipmhGetRecord {
+    #[serde(rename = "responseDate")]
+    response_date: String,
+    #[serde(default, rename = "error")]
+    errors: Vec<OaiError>,
+    #[serde(rename = "GetRecord")]
+    get_record: Option<GetRecord>,
+}
+
+#[derive(Deserialize)]
+struct GetRecord {
+    record: Set,
 }
 
 #[derive(Deserialize)]
@@ -425,19 +691,38 @@ struct ListRecords {
     resumption_token: Option<ResumptionToken>,
 }
 
+#[derive(Deserialize)]
+struct ListIdentifiers {
+    #[serde(default, rename = "header")]
+    headers: Vec<Header>,
+    #[serde(rename = "resumptionToken")]
+    resumption_token: Option<ResumptionToken>,
+}
+
 #[derive(Deserialize)]
 struct Set {
     header: Header,
-    metadata: Metadata,
+    /// Missing for deleted records.
+    metadata: Option<Metadata>,
 }
 
 #[derive(Deserialize)]
 struct Header {
+    identifier: String,
+    /// "deleted" for deleted records.
+    #[serde(rename = "@status")]
+    status: Option<String>,
     datestamp: String,
     #[serde(rename = "setSpec")]
     sets: Vec<String>,
 }
 
+impl Header {
+    fn is_deleted(&self) -> bool {
+        self.status.as_deref() == Some("deleted")
+    }
+}
+
 #[derive(Deserialize)]
 struct Metadata {
     #[serde(rename = "arXivRaw")]
@@ -484,6 +769,8 @@ struct ResumptionToken {
     #[allow(unused)]
     #[serde(rename = "@expirationDate")]
     expiration_date: Option<String>,
+    #[serde(rename = "@completeListSize")]
+    complete_list_size: Option<u64>,
     #[serde(rename = "$value")]
     value: Option<String>,
 }
@@ -497,35 +784,28 @@ struct OaiError {
     value: Option<String>,
 }
 
+/// The name of the OAI set corresponding to a category in the configuration file, if it is
+/// known (that is, after the category was pulled once).
+pub fn set_for_category(tr: &Transaction, category: &str) -> anyhow::Result<Option<String>> {
+    Continuation::set_for_category(tr, category)
+}
+
+/// Whether an article in the OAI set `set` (such as "math:math:NT") belongs to the set
+/// `subscribed` (such as "math:math:NT" or "math", or "" for all of arXiv).
+pub fn set_contains(subscribed: &str, set: &str) -> bool {
+    subscribed.is_empty()
+        || set == subscribed
+        || set
+            .strip_prefix(subscribed)
+            .is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Downloads the list of OAI sets and saves it in the database, so that categories can
+/// be translated to sets.
 pub fn update_sets(base_dir: &Path, tr: &Transaction, client: &mut Client) -> anyhow::Result<()> {
     // Make the request.
-    let res = client.with(|client| {
-        println!("Getting list of sets...");
-        let before_request = Instant::now();
-        let res = client
-            .post("https://oaipmh.arxiv.org/oai".to_string())
-            .header(
-                reqwest::header::CONTENT_TYPE,
-                "application/x-www-form-urlencoded",
-            )
-            .body("verb=ListSets")
-            .send()
-            .and_then(|res| res.error_for_status())
-            .context("requesting data from oaipmh.arxiv.org")?;
-        let request_duration = Instant::now().duration_since(before_request);
-        println!(
-            "Received response after {:.2} seconds.",
-            request_duration.as_secs_f32()
-        );
-        let content_type = res.headers().get("Content-Type");
-        if content_type != Some(&HeaderValue::from_static("text/xml")) {
-            bail!("wrong content type (expected text/xml, received {content_type:?})");
-        }
-        let res = res
-            .bytes()
-            .context("requesting data from oaipmh.arxiv.org")?;
-        Ok(res)
-    })?;
+    message!("Getting list of sets...");
+    let res = request(client, "verb=ListSets")?;
 
     // Save a copy of the response to update.xml for debugging in case something goes wrong.
     let xml_file = base_dir.join("update.xml");
@@ -543,7 +823,7 @@ pub fn update_sets(base_dir: &Path, tr: &Transaction, client: &mut Client) -> an
     if !oai_pmh.errors.is_empty() {
         // Print all errors and abort.
         for error in &oai_pmh.errors {
-            println!(
+            message!(
                 "{}: {}",
                 error.code,
                 error.value.clone().unwrap_or_default()
@@ -562,7 +842,7 @@ pub fn update_sets(base_dir: &Path, tr: &Transaction, client: &mut Client) -> an
     }
 
     let sets = list_sets.sets;
-    println!("Received {} sets.", sets.len());
+    message!("Received {} sets.", sets.len());
 
     let mut ins = tr.prepare("INSERT OR IGNORE INTO set_ (name, category) VALUES (?1, ?2)")?;
     for set in sets.iter() {
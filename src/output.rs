@@ -0,0 +1,71 @@
+//! Where output goes. With `--output json`, stdout is reserved for machine-readable
+//! output, and the text meant for humans is printed on stderr instead.
+
+use std::{
+    fmt::Arguments,
+    io::{IsTerminal, Write, stderr, stdout},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde::Serialize;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Whether the output on stdout should be JSON.
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether progress bars and status lines are suppressed (see `progress`).
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints text for humans (see `message!`), without a newline, and flushes.
+pub fn write(args: Arguments) {
+    if json() {
+        eprint!("{args}");
+        let _ = stderr().flush();
+    } else {
+        print!("{args}");
+        let _ = stdout().flush();
+    }
+}
+
+/// Whether the text for humans goes to a terminal.
+pub fn is_terminal() -> bool {
+    if json() {
+        stderr().is_terminal()
+    } else {
+        stdout().is_terminal()
+    }
+}
+
+/// Prints a value as JSON on stdout.
+pub fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    let mut out = stdout().lock();
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Like `println!`, but for text meant for humans, which goes to stderr with
+/// `--output json`.
+#[macro_export]
+macro_rules! message {
+    () => {
+        $crate::output::write(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
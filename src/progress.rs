@@ -0,0 +1,249 @@
+//! A progress bar on the last line of the terminal for long operations.
+//!
+//! At most one bar is shown at a time. Next to the counter, long operations can report
+//! what they are currently doing with [`status`] and [`set_detail`], for example the
+//! number of bytes of the file that is being downloaded.
+//!
+//! With `--quiet`, neither the bar nor the status lines are shown, only the lines printed
+//! with [`println`] (such as errors).
+
+use std::{
+    io::{IsTerminal, stdin},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    message, output,
+    util::{self, format_size},
+};
+
+struct Bar {
+    message: String,
+    position: u64,
+    total: Option<u64>,
+    detail: String,
+    /// Whether the output is a terminal (and not `--quiet`). Otherwise, the bar is not
+    /// drawn.
+    tty: bool,
+    last_draw: Option<Instant>,
+}
+
+static BAR: Mutex<Option<Bar>> = Mutex::new(None);
+
+/// Whether the full-screen interface of `interact` is shown.
+static FULL_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// The width of the bar in characters.
+const WIDTH: u64 = 30;
+
+impl Bar {
+    fn line(&self) -> String {
+        let counter = match self.total {
+            Some(total) if total > 0 => {
+                let filled = (self.position.min(total) * WIDTH / total) as usize;
+                format!(
+                    "[{}{}] {}/{}",
+                    "#".repeat(filled),
+                    " ".repeat(WIDTH as usize - filled),
+                    self.position,
+                    total
+                )
+            }
+            _ => format!("{}", self.position),
+        };
+        let mut line = format!("{} {counter}", self.message);
+        if !self.detail.is_empty() {
+            line += "  ";
+            line += &self.detail;
+        }
+        // Do not wrap, since we can only redraw the last line.
+        if let Ok((columns, _)) = termion::terminal_size() {
+            line = line
+                .chars()
+                .take((columns as usize).saturating_sub(1))
+                .collect();
+        }
+        line
+    }
+
+    fn draw(&mut self, force: bool) {
+        if !self.tty {
+            return;
+        }
+        let now = Instant::now();
+        if !force
+            && self
+                .last_draw
+                .is_some_and(|last| now.duration_since(last) < Duration::from_millis(100))
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+        output::write(format_args!(
+            "\r{}{}",
+            termion::clear::CurrentLine,
+            self.line()
+        ));
+    }
+
+    fn clear(&self) {
+        if self.tty {
+            output::write(format_args!("\r{}", termion::clear::CurrentLine));
+        }
+    }
+}
+
+fn with_bar(f: impl FnOnce(&mut Bar)) {
+    if let Some(bar) = BAR.lock().unwrap().as_mut() {
+        f(bar);
+    }
+}
+
+/// A handle to the progress bar. The bar disappears when the handle is dropped.
+pub struct Progress(());
+
+impl Progress {
+    /// Shows a new progress bar (replacing the previous one, if any) counting up to
+    /// `total`, if known.
+    pub fn new(message: impl Into<String>, total: Option<u64>) -> Self {
+        let mut bar = Bar {
+            message: message.into(),
+            position: 0,
+            total,
+            detail: String::new(),
+            tty: output::is_terminal() && !output::quiet(),
+            last_draw: None,
+        };
+        if !bar.tty && !output::quiet() {
+            message!("{}", bar.message);
+        }
+        bar.draw(true);
+        let mut current = BAR.lock().unwrap();
+        if let Some(old) = current.as_ref() {
+            old.clear();
+        }
+        *current = Some(bar);
+        Self(())
+    }
+
+    pub fn set_message(&self, message: impl Into<String>) {
+        with_bar(|bar| {
+            bar.message = message.into();
+            bar.draw(true);
+        });
+    }
+
+    pub fn set_total(&self, total: Option<u64>) {
+        with_bar(|bar| {
+            bar.total = total;
+            bar.draw(false);
+        });
+    }
+
+    pub fn set_position(&self, position: u64) {
+        with_bar(|bar| {
+            bar.position = position;
+            bar.draw(false);
+        });
+    }
+
+    pub fn inc(&self, delta: u64) {
+        with_bar(|bar| {
+            bar.position += delta;
+            bar.draw(false);
+        });
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = BAR.lock().unwrap().take() {
+            bar.clear();
+        }
+    }
+}
+
+/// Prints a line above the progress bar (or just prints it if there is none).
+pub fn println(line: &str) {
+    let mut current = BAR.lock().unwrap();
+    match current.as_mut() {
+        Some(bar) => {
+            bar.clear();
+            message!("{line}");
+            bar.draw(true);
+        }
+        None => message!("{line}"),
+    }
+}
+
+/// Marks the full-screen interface as shown until the returned guard is dropped, so that
+/// [`confirm`] does not ask questions that would garble it.
+pub fn full_screen() -> FullScreen {
+    FULL_SCREEN.store(true, Ordering::Relaxed);
+    FullScreen(())
+}
+
+/// See [`full_screen`].
+pub struct FullScreen(());
+
+impl Drop for FullScreen {
+    fn drop(&mut self) {
+        FULL_SCREEN.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Asks a yes/no question above the progress bar (or just asks it if there is none).
+/// If stdin is not a terminal or the full-screen interface is shown, there is nobody to
+/// ask, so the answer is no.
+pub fn confirm(question: &str) -> anyhow::Result<bool> {
+    if !stdin().is_terminal() || FULL_SCREEN.load(Ordering::Relaxed) {
+        return Ok(false);
+    }
+    let mut current = BAR.lock().unwrap();
+    match current.as_mut() {
+        Some(bar) => {
+            bar.clear();
+            let res = util::confirm(question);
+            bar.draw(true);
+            res
+        }
+        None => util::confirm(question),
+    }
+}
+
+/// Reports what is currently happening: next to the progress bar if there is one,
+/// and otherwise (or if the output is not a terminal) on a line of its own.
+pub fn status(line: &str) {
+    let mut current = BAR.lock().unwrap();
+    match current.as_mut() {
+        Some(bar) if bar.tty => {
+            bar.detail = line.to_string();
+            bar.draw(true);
+        }
+        _ if output::quiet() => {}
+        _ => message!("{line}"),
+    }
+}
+
+/// Shows a detail next to the progress bar, if there is one. Unlike [`status`], this
+/// prints nothing otherwise, so it can be called often.
+pub fn set_detail(detail: impl FnOnce() -> String) {
+    with_bar(|bar| {
+        if bar.tty {
+            bar.detail = detail();
+            bar.draw(false);
+        }
+    });
+}
+
+/// Formats the progress of a download, such as "1.2 MB/3.4 MB".
+pub fn format_bytes(downloaded: u64, total: Option<u64>) -> String {
+    match total {
+        Some(total) => format!("{}/{}", format_size(downloaded), format_size(total)),
+        None => format_size(downloaded),
+    }
+}
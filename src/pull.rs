@@ -0,0 +1,289 @@
+//! The steps of `arxiv-reader pull` after the pre-pull hooks: harvesting the subscribed
+//! categories, sets and searches, tagging and downloading the files of bookmarked
+//! articles, and reporting the new articles.
+
+use std::{collections::HashSet, path::Path};
+
+use rusqlite::Transaction;
+
+use crate::{
+    article::{Article, ArxivId},
+    arxiv_api,
+    config::{Config, GcPolicy},
+    crossref, db,
+    downloads::{self, DownloadDir},
+    feed::LastPull,
+    filter::PreparedFilter,
+    fulltext, keywords, message, oai, output,
+    progress::{self, Progress},
+    rate_limited_client::{self, Client},
+    stats, util, webhooks,
+};
+
+/// What `run` did.
+pub struct Summary {
+    /// The articles that were not in the database before.
+    pub new_ids: HashSet<ArxivId>,
+    /// How many of the new articles were tagged by the auto_tag rules.
+    pub auto_tagged: usize,
+}
+
+/// Updates the metadata of the subscribed categories, sets and searches, tags the new
+/// articles according to the auto_tag rules, downloads the files of bookmarked articles
+/// and reports the new articles (on the terminal, to the feed and to the webhooks).
+pub fn run(base_dir: &Path, config: &Config, client: &mut Client) -> anyhow::Result<Summary> {
+    let mut conn = db::open(base_dir)?;
+    // Retry the downloads that failed last time.
+    let download_dir = DownloadDir::new(base_dir, &config.downloads);
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        retry_failed_downloads(&tr, base_dir, &download_dir, client)?;
+        tr.commit()?;
+        Ok(())
+    })?;
+    // Update article metadata.
+    let mut new_ids = HashSet::new();
+    for (i, categories) in config.categories.iter().enumerate() {
+        progress::status(&format!(
+            "Getting records in category {categories} ({} of {}).",
+            i + 1,
+            config.categories.len()
+        ));
+        new_ids.extend(oai::download_changes(
+            base_dir,
+            &mut conn,
+            categories,
+            client,
+            config.harvest,
+        )?);
+    }
+    let sets: Vec<&String> = config
+        .subscriptions
+        .iter()
+        .flat_map(|subscription| &subscription.sets)
+        .collect();
+    for (i, set) in sets.iter().enumerate() {
+        progress::status(&format!(
+            "Getting records in set {set} ({} of {}).",
+            i + 1,
+            sets.len()
+        ));
+        new_ids.extend(oai::download_set(
+            base_dir,
+            &mut conn,
+            set,
+            client,
+            config.harvest,
+        )?);
+    }
+    for (i, query) in config.searches.iter().enumerate() {
+        progress::status(&format!(
+            "Searching for {query} ({} of {}).",
+            i + 1,
+            config.searches.len()
+        ));
+        db::with_write_transaction(&mut conn, base_dir, |tr| {
+            new_ids.extend(arxiv_api::download_matches(&tr, client, query)?);
+            tr.commit()?;
+            Ok(())
+        })?;
+    }
+    // Download pdfs and sources for all bookmarked articles.
+    let auto_tagged = db::with_transaction(&mut conn, base_dir, |tr| {
+        keywords::update(&tr, Some(&new_ids))?;
+        let mut articles = Article::load(base_dir, &tr)?;
+        // Tag the new articles matching the auto_tag rules, so that their files are
+        // downloaded right away.
+        let mut auto_tagged = 0;
+        for rule in &config.auto_tag {
+            let filter = rule.filter.prepare(&tr)?;
+            let mut count = 0;
+            for id in &new_ids {
+                if let Some(article) = articles.get_mut(id)
+                    && filter.matches(article)
+                    && !article.tags().contains(&rule.tag)
+                {
+                    article.set_tag(base_dir, &tr, &rule.tag)?;
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                message!("Tagged {count} new articles with {}.", rule.tag);
+            }
+            auto_tagged += count;
+        }
+        let bookmarked: Vec<&Article> = articles.values().filter(|a| a.is_bookmarked()).collect();
+        let mut files = Vec::new();
+        for article in &bookmarked {
+            if article.last_version().probably_has_pdf() {
+                files.push((*article, false));
+            }
+            if article.last_version().probably_has_src() {
+                files.push((*article, true));
+            }
+        }
+        let (downloaded, _failed) = downloads::download_all(
+            &tr,
+            &download_dir,
+            client,
+            &files,
+            config
+                .network
+                .concurrency
+                .unwrap_or(rate_limited_client::DEFAULT_CONCURRENCY),
+        )?;
+        if downloaded > 0 {
+            message!("Downloaded {downloaded} pdfs and sources.");
+        }
+        if config.index_fulltext {
+            let progress = Progress::new(
+                "Indexing bookmarked articles:",
+                Some(bookmarked.len() as u64),
+            );
+            for article in &bookmarked {
+                fulltext::index(&download_dir, &tr, article)?;
+                progress.inc(1);
+            }
+        }
+        // Delete old downloads.
+        if let Some(keep_days) = config.downloads.keep_days {
+            let summary = download_dir.gc(
+                &GcPolicy::keep_days(keep_days),
+                |id| articles.get(id).map(|a| a.last_version().number),
+                false,
+            )?;
+            if summary.files > 0 {
+                message!(
+                    "Deleted {} files ({}).",
+                    summary.files,
+                    util::format_size(summary.bytes)
+                );
+            }
+        }
+        // Remember the new articles for news --feed.
+        LastPull {
+            time: chrono::Local::now().fixed_offset(),
+            ids: new_ids.iter().cloned().collect(),
+        }
+        .save(&tr)?;
+        // Summarize the new articles matching the filters.
+        let mut new_articles: Vec<&Article> =
+            new_ids.iter().filter_map(|id| articles.get(id)).collect();
+        new_articles.sort_by_key(|a| a.first_version().date);
+        message!();
+        let new_filter = config.filters.new.prepare(&tr)?;
+        let mut summaries = vec![print_summary(&new_articles, "filters.new", &new_filter)];
+        let per_day = stats::new_per_day(
+            &articles,
+            &new_filter,
+            chrono::Local::now().date_naive(),
+            stats::SPARKLINE_DAYS,
+        );
+        message!(
+            "Last {} days: {}",
+            stats::SPARKLINE_DAYS,
+            stats::sparkline(&per_day)
+        );
+        for (name, filter) in &config.filters.alerts {
+            summaries.push(print_summary(
+                &new_articles,
+                &format!("alert {name}"),
+                &filter.prepare(&tr)?,
+            ));
+        }
+        if output::json() {
+            output::print_json(&serde_json::json!({
+                "new": new_articles.iter().map(|a| a.id()).collect::<Vec<_>>(),
+                "summaries": summaries,
+                "new_per_day": per_day,
+            }))?;
+        }
+        // Notify the webhooks. A failing webhook should not prevent the others
+        // from being notified.
+        let mut webhook_client = client.unthrottled()?;
+        for webhook in &config.webhooks {
+            let (name, filter) = match &webhook.filter {
+                Some(filter) => ("webhook filter", filter),
+                None => ("filters.new", &config.filters.new),
+            };
+            let filter = filter.prepare(&tr)?;
+            let matching: Vec<&Article> = new_articles
+                .iter()
+                .filter(|a| filter.matches(a))
+                .copied()
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+            if let Err(err) = webhooks::notify(
+                &mut webhook_client,
+                webhook,
+                &config.authors,
+                name,
+                &matching,
+            ) {
+                message!("Notifying webhook failed: {err:#}");
+            }
+        }
+        tr.commit()?;
+        Ok(auto_tagged)
+    })?;
+    // Look up the bookmarked articles on Crossref. This is done after the above
+    // transaction is committed, since the lookups can take a while.
+    if config.crossref {
+        db::with_write_transaction(&mut conn, base_dir, |tr| {
+            let articles = Article::load(base_dir, &tr)?;
+            let bookmarked: Vec<&Article> =
+                articles.values().filter(|a| a.is_bookmarked()).collect();
+            let found = crossref::update(&tr, &mut client.unthrottled()?, &bookmarked, false)?;
+            tr.commit()?;
+            if found > 0 {
+                message!("Found {found} published articles on Crossref.");
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(Summary {
+        new_ids,
+        auto_tagged,
+    })
+}
+
+/// Retries the failed downloads and reports the result.
+pub fn retry_failed_downloads(
+    tr: &Transaction,
+    base_dir: &Path,
+    download_dir: &DownloadDir,
+    client: &mut Client,
+) -> anyhow::Result<(usize, usize)> {
+    let (succeeded, failed) = downloads::retry_failed(tr, base_dir, download_dir, client)?;
+    if succeeded + failed > 0 {
+        message!(
+            "Retried {} failed downloads: {succeeded} succeeded, {failed} failed again.",
+            succeeded + failed
+        );
+    }
+    Ok((succeeded, failed))
+}
+
+/// Prints how many of the given new articles match the filter, with a few example titles.
+/// Returns the summary for `--output json`.
+fn print_summary(
+    new_articles: &[&Article],
+    name: &str,
+    filter: &PreparedFilter,
+) -> serde_json::Value {
+    const EXAMPLES: usize = 3;
+    let matching: Vec<&&Article> = new_articles.iter().filter(|a| filter.matches(a)).collect();
+    message!("{} new articles match {name}.", matching.len());
+    for article in matching.iter().take(EXAMPLES) {
+        message!("  {}  {}", article.id(), article.title());
+    }
+    if matching.len() > EXAMPLES {
+        message!("  ...");
+    }
+    serde_json::json!({
+        "filter": name,
+        "ids": matching.iter().map(|a| a.id()).collect::<Vec<_>>(),
+    })
+}
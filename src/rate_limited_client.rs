@@ -1,31 +1,1034 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{File, copy, create_dir_all, read_to_string, write},
+    io::{BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, bail};
+
+use crate::{message, progress, util::format_size};
+
+/// How often a request is resent after a transient failure by default.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The time between two requests to the same host by default, as asked for by arXiv's
+/// robots policy (https://info.arxiv.org/help/robots.html). Requests to arXiv are never
+/// sent more often, whatever the settings.
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How many requests `download_all` sends at the same time by default.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Where the User-Agent header points server operators to.
+const HOMEPAGE: &str = "https://github.com/fagu/arxiv-reader";
+
+/// How long to wait before the first retry if the server does not say. The wait doubles
+/// with every retry.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The longest we wait before a retry, even if the server asks for more.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+/// What the client does with requests.
+enum Mode {
+    /// Send requests to the server.
+    Live,
+    /// Send requests to the server and save the responses in the given directory.
+    Record { dir: PathBuf, count: usize },
+    /// Do not send requests, but return the responses previously saved in the given directory.
+    Replay { dir: PathBuf, count: usize },
+}
+
+/// A response received from the server (or replayed from disk).
+pub struct Response {
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// A response whose body was written to a file by `download` or `download_all`.
+pub struct Download {
+    pub content_type: Option<String>,
+    /// The length of the body in bytes.
+    pub len: u64,
+}
+
+/// What `send` received: the content type, the body and its length.
+struct Received<B> {
+    content_type: Option<String>,
+    body: B,
+    len: u64,
+}
+
+impl From<Received<Vec<u8>>> for Response {
+    fn from(received: Received<Vec<u8>>) -> Self {
+        Self {
+            content_type: received.content_type,
+            body: received.body,
+        }
+    }
+}
+
+impl From<Received<FileBody>> for Download {
+    fn from(received: Received<FileBody>) -> Self {
+        Self {
+            content_type: received.content_type,
+            len: received.len,
+        }
+    }
+}
+
+/// Where `send` puts the body of a response: in memory or in a file.
+trait Body: Write {
+    /// Saves a copy of the complete body in a recording.
+    fn record(&mut self, file: &Path) -> anyhow::Result<()>;
+}
+
+impl Body for Vec<u8> {
+    fn record(&mut self, file: &Path) -> anyhow::Result<()> {
+        write(file, self).with_context(|| format!("writing {file:?}"))
+    }
+}
+
+/// A body written to a file, so that large downloads are not kept in memory.
+struct FileBody {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl FileBody {
+    /// Creates (or truncates) the file.
+    fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating {path:?}"))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+        })
+    }
+}
+
+impl Write for FileBody {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Body for FileBody {
+    fn record(&mut self, file: &Path) -> anyhow::Result<()> {
+        copy(&self.path, file).with_context(|| format!("writing {file:?}"))?;
+        Ok(())
+    }
+}
+
+/// The error returned if the user did not want to download a large file.
+#[derive(Debug)]
+pub struct DownloadDeclined;
+
+impl Display for DownloadDeclined {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "download declined")
+    }
+}
+
+impl std::error::Error for DownloadDeclined {}
+
+/// The error returned by `download_all` instead of receiving a body of more than
+/// `confirm_above` bytes, since it cannot ask while other requests are running. Send the
+/// request again with `download` to ask.
+#[derive(Debug)]
+pub struct ConfirmationNeeded {
+    pub bytes: u64,
+}
+
+impl Display for ConfirmationNeeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the file has {} and needs confirmation",
+            format_size(self.bytes)
+        )
+    }
+}
+
+impl std::error::Error for ConfirmationNeeded {}
 
 pub struct Client {
-    last_request: Option<Instant>,
+    /// When the last request to each host completed. All hosts of arXiv count as one (see
+    /// `limit_key`).
+    last_request: HashMap<String, Instant>,
+    /// The time between two requests to a host, if it differs from `default_interval`.
+    intervals: HashMap<String, Duration>,
+    default_interval: Duration,
+    /// Up to how much longer to wait between two requests, chosen at random each time.
+    jitter: Duration,
+    /// How many requests to a host `download_all` may send in quick succession after a pause.
+    burst: u32,
+    user_agent: String,
     inner: reqwest::blocking::Client,
+    /// How long connecting and waiting for data may take, if it differs from reqwest's
+    /// default (30 seconds for sequential requests, no limit for those of `download_all`).
+    timeout: Option<Duration>,
+    mode: Mode,
+    /// Ask before receiving bodies of more than this many bytes in `download`.
+    confirm_above: Option<u64>,
+    max_retries: u32,
 }
 
 impl Client {
+    /// A client that identifies itself as arxiv-reader in the User-Agent header.
     pub fn new() -> Self {
         Self {
-            last_request: None,
+            last_request: HashMap::new(),
+            intervals: HashMap::new(),
+            default_interval: DEFAULT_MIN_INTERVAL,
+            jitter: Duration::ZERO,
+            burst: 1,
+            user_agent: user_agent(None),
             inner: reqwest::blocking::Client::new(),
+            timeout: None,
+            mode: Mode::Live,
+            confirm_above: None,
+            max_retries: DEFAULT_MAX_RETRIES,
         }
     }
 
+    /// Sets how often a request is resent after a server error (5xx or 429), a timeout or
+    /// a lost connection. Requests sent with `post` are never resent.
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Adds an email address to the User-Agent header, so that server operators can
+    /// reach the user instead of blocking the program.
+    pub fn set_contact(&mut self, contact: Option<&str>) {
+        self.user_agent = user_agent(contact);
+    }
+
+    /// Sets the time between two requests to the given host, or to hosts without their
+    /// own interval if `host` is `None`. Since all hosts of arXiv share one limit, the
+    /// interval of an arXiv host is the time since the last request to any of them.
+    /// Intervals below `DEFAULT_MIN_INTERVAL` are raised to it for arXiv.
+    pub fn set_min_interval(&mut self, host: Option<&str>, interval: Duration) {
+        match host {
+            Some(host) => {
+                self.intervals.insert(host.to_string(), interval);
+            }
+            None => self.default_interval = interval,
+        }
+    }
+
+    /// Makes the client wait up to `jitter` longer between two requests, so that
+    /// harvests started at the same time (for example by cron) do not send requests in
+    /// lockstep.
+    pub fn set_jitter(&mut self, jitter: Duration) {
+        self.jitter = jitter;
+    }
+
+    /// Lets `download_all` send up to `burst` requests to a host without waiting if there were
+    /// no requests to it for `burst` times its minimum interval.
+    pub fn set_burst(&mut self, burst: u32) {
+        self.burst = burst.max(1);
+    }
+
+    /// Aborts requests (which are then retried) that take longer than `timeout` to connect
+    /// or to receive the next part of the response. Large downloads are not aborted as long
+    /// as data keeps arriving.
+    pub fn set_timeout(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        // The timeout of the blocking client applies to waiting for the response and to
+        // each read of the body separately.
+        self.inner = reqwest::blocking::Client::builder()
+            .connect_timeout(timeout)
+            .timeout(timeout)
+            .build()
+            .context("creating the http client")?;
+        self.timeout = Some(timeout);
+        Ok(())
+    }
+
+    /// Makes `download` ask for confirmation before receiving a body of more
+    /// than `bytes` bytes (according to the Content-Length header). If the user declines,
+    /// or cannot be asked (see `progress::confirm`), it fails with `DownloadDeclined`.
+    pub fn set_confirm_above(&mut self, bytes: Option<u64>) {
+        self.confirm_above = bytes;
+    }
+
+    /// A client for hosts other than arXiv (such as Crossref or webhooks), with the same
+    /// settings except that it does not wait between requests. A recording or replaying
+    /// client gives one that records to or replays from the subdirectory "other" of its
+    /// directory.
+    pub fn unthrottled(&self) -> anyhow::Result<Self> {
+        let mode = match &self.mode {
+            Mode::Live => Mode::Live,
+            Mode::Record { dir, .. } => {
+                let dir = dir.join("other");
+                create_dir_all(&dir).with_context(|| format!("creating {dir:?}"))?;
+                Mode::Record { dir, count: 0 }
+            }
+            Mode::Replay { dir, .. } => Mode::Replay {
+                dir: dir.join("other"),
+                count: 0,
+            },
+        };
+        Ok(Self {
+            last_request: HashMap::new(),
+            intervals: HashMap::new(),
+            default_interval: Duration::ZERO,
+            jitter: Duration::ZERO,
+            burst: self.burst,
+            user_agent: self.user_agent.clone(),
+            inner: self.inner.clone(),
+            timeout: self.timeout,
+            mode,
+            confirm_above: self.confirm_above,
+            max_retries: self.max_retries,
+        })
+    }
+
+    /// A client that saves all responses in `dir`, so that they can be replayed later.
+    pub fn recording(dir: PathBuf) -> anyhow::Result<Self> {
+        create_dir_all(&dir).with_context(|| format!("creating {dir:?}"))?;
+        Ok(Self {
+            mode: Mode::Record { dir, count: 0 },
+            ..Self::new()
+        })
+    }
+
+    /// A client that does not access the network, but replays the responses saved in `dir`
+    /// by a recording client, in the same order. The requests must be the same as the
+    /// recorded ones.
+    pub fn replaying(dir: PathBuf) -> Self {
+        Self {
+            mode: Mode::Replay { dir, count: 0 },
+            ..Self::new()
+        }
+    }
+
+    /// Sends a GET request.
+    pub fn get(&mut self, url: &str) -> anyhow::Result<Response> {
+        self.send(
+            url,
+            &format!("GET {url}\n"),
+            |client| client.get(url),
+            || Ok(Vec::new()),
+            |_, _| {},
+            None,
+            true,
+        )
+        .map(Response::from)
+    }
+
+    /// Sends a GET request for a file and writes the body to `path` (which should be a
+    /// temporary file that is renamed once the download is complete), calling `progress`
+    /// with the number of bytes received so far and the expected total. The file is only
+    /// created once the body starts arriving, but it may be left behind incomplete if the
+    /// download fails.
+    pub fn download(
+        &mut self,
+        url: &str,
+        path: &Path,
+        progress: impl FnMut(u64, Option<u64>),
+    ) -> anyhow::Result<Download> {
+        let confirm_above = self.confirm_above;
+        self.send(
+            url,
+            &format!("GET {url}\n"),
+            |client| client.get(url),
+            || FileBody::create(path),
+            progress,
+            confirm_above,
+            true,
+        )
+        .map(Download::from)
+    }
+
+    /// Sends GET requests for all urls, up to `concurrency` at the same time, writing the
+    /// bodies to the given paths like `download`, and calls `done` with the index of each
+    /// url and the response as soon as it has been received.
+    /// The requests to each host (or to arXiv as a whole) are spaced out by a token bucket
+    /// that refills at one token per minimum interval, so they start at the same rate as sequential requests,
+    /// but the transfers overlap. Bodies of more than `confirm_above` bytes are not
+    /// received (see `ConfirmationNeeded`).
+    ///
+    /// Recording and replaying clients send the requests one after the other, in order, to
+    /// keep the recordings deterministic. Like `download`, they ask before receiving large
+    /// bodies instead.
+    pub fn download_all(
+        &mut self,
+        downloads: &[(String, PathBuf)],
+        concurrency: usize,
+        mut done: impl FnMut(usize, anyhow::Result<Download>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        if !matches!(self.mode, Mode::Live) || concurrency <= 1 || downloads.len() <= 1 {
+            for (i, (url, path)) in downloads.iter().enumerate() {
+                let res = self.download(url, path, |_, _| {});
+                done(i, res)?;
+            }
+            return Ok(());
+        }
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("starting the download threads")?;
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.connect_timeout(timeout).read_timeout(timeout);
+        }
+        let shared = Arc::new(Shared {
+            client: builder.build().context("creating the http client")?,
+            limiter: RateLimiter::new(self),
+            semaphore: tokio::sync::Semaphore::new(concurrency),
+            user_agent: self.user_agent.clone(),
+            max_retries: self.max_retries,
+            confirm_above: self.confirm_above,
+        });
+        let res = runtime.block_on(async {
+            let mut tasks = tokio::task::JoinSet::new();
+            for (i, (url, path)) in downloads.iter().enumerate() {
+                let shared = shared.clone();
+                let url = url.clone();
+                let path = path.clone();
+                tasks.spawn(async move { (i, shared.download(&url, &path).await) });
+            }
+            while let Some(res) = tasks.join_next().await {
+                let (i, res) = res.context("running a download")?;
+                done(i, res)?;
+            }
+            anyhow::Ok(())
+        });
+        let now = Instant::now();
+        for (url, _) in downloads {
+            self.last_request.insert(limit_key(&host(url)), now);
+        }
+        res
+    }
+
+    /// Sends a POST request with a form-encoded body. Like GET requests, it is retried
+    /// after transient failures, so it must be a query without side effects (such as the
+    /// requests to the OAI-PMH interface).
+    pub fn post_form(&mut self, url: &str, body: &str) -> anyhow::Result<Response> {
+        self.send_post(url, "application/x-www-form-urlencoded", body, true)
+    }
+
+    /// Sends a POST request with the given body. It is not retried, since sending it twice
+    /// might have a different effect than sending it once.
+    pub fn post(&mut self, url: &str, content_type: &str, body: &str) -> anyhow::Result<Response> {
+        self.send_post(url, content_type, body, false)
+    }
+
+    fn send_post(
+        &mut self,
+        url: &str,
+        content_type: &str,
+        body: &str,
+        retry: bool,
+    ) -> anyhow::Result<Response> {
+        self.send(
+            url,
+            &format!("POST {url}\n{body}"),
+            |client| {
+                client
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, content_type)
+                    .body(body.to_string())
+            },
+            || Ok(Vec::new()),
+            |_, _| {},
+            None,
+            retry,
+        )
+        .map(Response::from)
+    }
+
+    /// Sends the request (or replays the response), failing on error status codes.
+    /// `description` identifies the request in recordings. The body is written to what
+    /// `open` returns, which is called again for every attempt. If the body is longer than
+    /// `confirm_above`, the user is asked whether to receive it. If `retry` is true,
+    /// transient failures are retried up to `max_retries` times with exponential backoff,
+    /// waiting as long as the Retry-After header says if there is one.
+    #[allow(clippy::too_many_arguments)]
+    fn send<B: Body>(
+        &mut self,
+        url: &str,
+        description: &str,
+        build: impl Fn(&reqwest::blocking::Client) -> reqwest::blocking::RequestBuilder,
+        mut open: impl FnMut() -> anyhow::Result<B>,
+        mut progress: impl FnMut(u64, Option<u64>),
+        confirm_above: Option<u64>,
+        retry: bool,
+    ) -> anyhow::Result<Received<B>> {
+        if let Mode::Replay { dir, count } = &mut self.mode {
+            *count += 1;
+            let prefix = dir.join(format!("{count:04}"));
+            let file = |ext: &str| prefix.with_extension(ext);
+            let recorded = read_to_string(file("request"))
+                .with_context(|| format!("reading {:?}", file("request")))?;
+            if recorded.trim_end() != description.trim_end() {
+                bail!("unexpected request {description:?} (recorded: {recorded:?})");
+            }
+            let content_type = read_to_string(file("content-type"))
+                .ok()
+                .map(|s| s.trim().to_string());
+            let mut recorded_body =
+                File::open(file("body")).with_context(|| format!("reading {:?}", file("body")))?;
+            let mut body = open()?;
+            let len = std::io::copy(&mut recorded_body, &mut body)
+                .with_context(|| format!("reading {:?}", file("body")))?;
+            body.flush()?;
+            progress(len, Some(len));
+            return Ok(Received {
+                content_type,
+                body,
+                len,
+            });
+        }
+        let max_retries = if retry { self.max_retries } else { 0 };
+        let mut attempt = 0;
+        let mut response = loop {
+            let mut retry_after = None;
+            let user_agent = self.user_agent.clone();
+            let result = self.with(url, |client| -> anyhow::Result<Received<B>> {
+                let res = build(client)
+                    .header(reqwest::header::USER_AGENT, user_agent)
+                    .send()?;
+                retry_after = retry_after_header(res.headers());
+                let res = res.error_for_status()?;
+                Self::receive(res, &mut open, &mut progress, confirm_above)
+            });
+            match result {
+                Err(err) if attempt < max_retries && is_transient(&err) => {
+                    let delay = retry_delay(retry_after, attempt);
+                    attempt += 1;
+                    message!(
+                        "{err:#}. Retrying in {} seconds ({attempt} of {max_retries}).",
+                        delay.as_secs()
+                    );
+                    std::thread::sleep(delay);
+                }
+                result => break result?,
+            }
+        };
+        if let Mode::Record { dir, count } = &mut self.mode {
+            *count += 1;
+            let prefix = dir.join(format!("{count:04}"));
+            let file = |ext: &str| prefix.with_extension(ext);
+            write(file("request"), description)
+                .with_context(|| format!("writing {:?}", file("request")))?;
+            if let Some(content_type) = &response.content_type {
+                write(file("content-type"), content_type)
+                    .with_context(|| format!("writing {:?}", file("content-type")))?;
+            }
+            response.body.record(&file("body"))?;
+        }
+        Ok(response)
+    }
+
+    /// Receives the body of a response into what `open` returns, reporting progress and
+    /// asking for confirmation if it is longer than `confirm_above`.
+    fn receive<B: Write>(
+        mut res: reqwest::blocking::Response,
+        open: &mut impl FnMut() -> anyhow::Result<B>,
+        progress: &mut impl FnMut(u64, Option<u64>),
+        confirm_above: Option<u64>,
+    ) -> anyhow::Result<Received<B>> {
+        let content_type = content_type(res.headers());
+        let total = res.content_length();
+        if let (Some(total), Some(limit)) = (total, confirm_above)
+            && total > limit
+            && !progress::confirm(&format!(
+                "The file has {}. Download it?",
+                format_size(total)
+            ))?
+        {
+            return Err(DownloadDeclined.into());
+        }
+        // Read the body in chunks to report progress.
+        let mut body = open()?;
+        let mut len = 0;
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            let n = res.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            body.write_all(&buffer[..n])?;
+            len += n as u64;
+            progress(len, total);
+        }
+        body.flush()?;
+        Ok(Received {
+            content_type,
+            body,
+            len,
+        })
+    }
+
     /// Calls f with the inner reqwest::blocking::Client.
-    /// Sleeps if necessary to make sure that at least 3 seconds passed since the completion of the last call to this function.
-    pub fn with<T>(&mut self, f: impl FnOnce(&reqwest::blocking::Client) -> T) -> T {
+    /// Sleeps if necessary to make sure that at least the minimum interval of the host of
+    /// `url` (plus jitter) passed since the completion of the last call to this function
+    /// for the same host (or any host of arXiv).
+    fn with<T>(&mut self, url: &str, f: impl FnOnce(&reqwest::blocking::Client) -> T) -> T {
+        let host = host(url);
+        let key = limit_key(&host);
         let now = Instant::now();
-        if let Some(last_request) = self.last_request
-            && let Some(remaining) =
-                Duration::from_secs(3).checked_sub(now.duration_since(last_request))
+        let interval = min_interval(&self.intervals, self.default_interval, &host)
+            + random_fraction(self.jitter);
+        if let Some(last_request) = self.last_request.get(&key)
+            && let Some(remaining) = interval.checked_sub(now.duration_since(*last_request))
         {
-            println!("Waiting for {:.2} seconds.", remaining.as_secs_f32());
+            progress::status(&format!(
+                "Waiting for {:.2} seconds.",
+                remaining.as_secs_f32()
+            ));
             std::thread::sleep(remaining);
         }
         let res = f(&self.inner);
-        self.last_request = Some(Instant::now());
+        self.last_request.insert(key, Instant::now());
         res
     }
 }
+
+/// What the concurrent requests of `download_all` share.
+struct Shared {
+    client: reqwest::Client,
+    limiter: RateLimiter,
+    semaphore: tokio::sync::Semaphore,
+    user_agent: String,
+    max_retries: u32,
+    confirm_above: Option<u64>,
+}
+
+impl Shared {
+    /// Sends a GET request once a slot and a token are available and writes the body to
+    /// `path`, retrying transient failures like `Client::send`.
+    async fn download(&self, url: &str, path: &Path) -> anyhow::Result<Download> {
+        let _permit = self.semaphore.acquire().await?;
+        let host = host(url);
+        let mut attempt = 0;
+        loop {
+            tokio::time::sleep(self.limiter.reserve(&host)).await;
+            let (retry_after, result) = self.try_download(url, path).await;
+            match result {
+                Err(err) if attempt < self.max_retries && is_transient(&err) => {
+                    let delay = retry_delay(retry_after, attempt);
+                    attempt += 1;
+                    message!(
+                        "{url}: {err:#}. Retrying in {} seconds ({attempt} of {}).",
+                        delay.as_secs(),
+                        self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends a GET request and writes the body to `path`. Also returns how long the server
+    /// asked to wait before retrying.
+    async fn try_download(
+        &self,
+        url: &str,
+        path: &Path,
+    ) -> (Option<Duration>, anyhow::Result<Download>) {
+        let res = match self
+            .client
+            .get(url)
+            .header(reqwest::header::USER_AGENT, &self.user_agent)
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => return (None, Err(err.into())),
+        };
+        let retry_after = retry_after_header(res.headers());
+        let result = async {
+            let mut res = res.error_for_status()?;
+            if let (Some(bytes), Some(limit)) = (res.content_length(), self.confirm_above)
+                && bytes > limit
+            {
+                return Err(ConfirmationNeeded { bytes }.into());
+            }
+            let content_type = content_type(res.headers());
+            let mut body = FileBody::create(path)?;
+            let mut len = 0;
+            while let Some(chunk) = res.chunk().await? {
+                body.write_all(&chunk)?;
+                len += chunk.len() as u64;
+            }
+            body.flush()?;
+            Ok(Download { content_type, len })
+        };
+        (retry_after, result.await)
+    }
+}
+
+/// A token bucket per host (and one for all of arXiv), from which every request of
+/// `download_all` takes a token before it starts. The buckets hold up to `burst` tokens and
+/// refill at one token per minimum interval of the host.
+struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    intervals: HashMap<String, Duration>,
+    default_interval: Duration,
+    jitter: Duration,
+    burst: u32,
+}
+
+struct Bucket {
+    /// Negative if tokens were promised to waiting requests.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(client: &Client) -> Self {
+        let limiter = Self {
+            buckets: Mutex::new(HashMap::new()),
+            intervals: client.intervals.clone(),
+            default_interval: client.default_interval,
+            jitter: client.jitter,
+            burst: client.burst,
+        };
+        // Hosts that were just sent a request by `Client::send` start with fewer tokens.
+        let now = Instant::now();
+        for (key, last_request) in &client.last_request {
+            let tokens = limiter.refill(key, 0., now.duration_since(*last_request));
+            limiter.buckets.lock().unwrap().insert(
+                key.clone(),
+                Bucket {
+                    tokens,
+                    updated: now,
+                },
+            );
+        }
+        limiter
+    }
+
+    fn interval(&self, host: &str) -> Duration {
+        min_interval(&self.intervals, self.default_interval, host)
+    }
+
+    /// The number of tokens after `elapsed` has passed.
+    fn refill(&self, host: &str, tokens: f64, elapsed: Duration) -> f64 {
+        let interval = self.interval(host);
+        let burst = f64::from(self.burst);
+        if interval.is_zero() {
+            return burst;
+        }
+        (tokens + elapsed.as_secs_f64() / interval.as_secs_f64()).min(burst)
+    }
+
+    /// Takes a token for a request to the host. Returns how long to wait until the token
+    /// is there.
+    fn reserve(&self, host: &str) -> Duration {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(limit_key(host)).or_insert(Bucket {
+            tokens: f64::from(self.burst),
+            updated: now,
+        });
+        bucket.tokens = self.refill(host, bucket.tokens, now.duration_since(bucket.updated)) - 1.;
+        bucket.updated = now;
+        if bucket.tokens >= 0. {
+            return Duration::ZERO;
+        }
+        self.interval(host).mul_f64(-bucket.tokens) + random_fraction(self.jitter)
+    }
+}
+
+/// Whether the host belongs to arXiv, such as "arxiv.org" or "oaipmh.arxiv.org".
+pub fn is_arxiv(host: &str) -> bool {
+    host == "arxiv.org" || host.ends_with(".arxiv.org")
+}
+
+/// The key under which requests to the host are limited: "arxiv.org" for all hosts of
+/// arXiv, so that they share one limit, and the host itself otherwise.
+fn limit_key(host: &str) -> String {
+    if is_arxiv(host) {
+        "arxiv.org".to_string()
+    } else {
+        host.to_string()
+    }
+}
+
+/// The time between two requests to the host: its own interval or the default, but at
+/// least `DEFAULT_MIN_INTERVAL` for arXiv.
+fn min_interval(intervals: &HashMap<String, Duration>, default: Duration, host: &str) -> Duration {
+    let interval = intervals.get(host).copied().unwrap_or(default);
+    if is_arxiv(host) {
+        interval.max(DEFAULT_MIN_INTERVAL)
+    } else {
+        interval
+    }
+}
+
+/// The host of the url, such as "arxiv.org".
+fn host(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn content_type(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+fn retry_after_header(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, chrono::Utc::now()))
+}
+
+/// How long to wait before the retry after `attempt` retries.
+fn retry_delay(retry_after: Option<Duration>, attempt: u32) -> Duration {
+    retry_after
+        .unwrap_or_else(|| {
+            2u32.checked_pow(attempt)
+                .and_then(|factor| FIRST_RETRY_DELAY.checked_mul(factor))
+                .unwrap_or(MAX_RETRY_DELAY)
+        })
+        .min(MAX_RETRY_DELAY)
+}
+
+/// The User-Agent header: the program, its version, where to find it and whom to contact.
+fn user_agent(contact: Option<&str>) -> String {
+    let mut res = format!("arxiv-reader/{} (+{HOMEPAGE}", env!("CARGO_PKG_VERSION"));
+    if let Some(contact) = contact {
+        res += &format!("; mailto:{contact}");
+    }
+    res + ")"
+}
+
+/// A duration between zero and `max`. The clock is a good enough source of randomness
+/// for spreading out requests.
+fn random_fraction(max: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    max.mul_f64(f64::from(nanos) / 1e9)
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a request that failed with this error might succeed if it is sent again.
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            err.is_timeout()
+                || err.is_connect()
+                || err
+                    .status()
+                    .is_some_and(|s| s.is_server_error() || s.as_u16() == 429)
+        } else if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+            matches!(
+                err.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::TimedOut
+                    | ErrorKind::UnexpectedEof
+            )
+        } else {
+            false
+        }
+    })
+}
+
+/// Parses the value of a Retry-After header, which is either a number of seconds or a
+/// date.
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    Some((date.to_utc() - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn user_agent_contact() {
+        assert!(user_agent(None).starts_with("arxiv-reader/"));
+        assert!(!user_agent(None).contains("mailto"));
+        assert!(user_agent(Some("me@example.org")).ends_with("; mailto:me@example.org)"));
+    }
+
+    #[test]
+    fn arxiv_hosts() {
+        assert!(is_arxiv("arxiv.org"));
+        assert!(is_arxiv("oaipmh.arxiv.org"));
+        assert!(!is_arxiv("notarxiv.org"));
+        assert!(!is_arxiv("api.crossref.org"));
+    }
+
+    #[test]
+    fn token_bucket() {
+        let mut client = Client::new();
+        client.set_min_interval(Some("arxiv.org"), Duration::from_secs(3));
+        client.set_min_interval(Some("example.org"), Duration::ZERO);
+        client.set_burst(2);
+        let limiter = RateLimiter::new(&client);
+        let waits: Vec<u64> = (0..4)
+            .map(|_| limiter.reserve("arxiv.org").as_secs_f64().round() as u64)
+            .collect();
+        assert_eq!(waits, [0, 0, 3, 6]);
+        assert_eq!(limiter.reserve("example.org"), Duration::ZERO);
+        assert_eq!(limiter.reserve("example.org"), Duration::ZERO);
+    }
+
+    #[test]
+    fn one_limit_for_arxiv() {
+        let mut client = Client::new();
+        client.set_min_interval(None, Duration::ZERO);
+        client.set_min_interval(Some("oaipmh.arxiv.org"), Duration::from_secs(1));
+        let limiter = RateLimiter::new(&client);
+        let waits: Vec<u64> = ["arxiv.org", "oaipmh.arxiv.org", "export.arxiv.org"]
+            .iter()
+            .map(|host| limiter.reserve(host).as_secs_f64().round() as u64)
+            .collect();
+        assert_eq!(waits, [0, 3, 6]);
+        assert_eq!(limiter.reserve("api.crossref.org"), Duration::ZERO);
+        let other = client.unthrottled().unwrap();
+        assert_eq!(
+            min_interval(&other.intervals, other.default_interval, "example.org"),
+            Duration::ZERO
+        );
+        assert_eq!(
+            min_interval(&other.intervals, other.default_interval, "arxiv.org"),
+            DEFAULT_MIN_INTERVAL
+        );
+    }
+
+    /// Runs `download_all` against a local server that takes a while to respond, and checks
+    /// that the requests start one minimum interval apart, but overlap.
+    #[test]
+    fn concurrent_requests_are_spaced_out() {
+        use std::{io::Write, net::TcpListener};
+
+        const REQUESTS: usize = 4;
+        const INTERVAL: Duration = Duration::from_millis(200);
+        const RESPONSE_TIME: Duration = Duration::from_millis(500);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let server = {
+            let starts = starts.clone();
+            std::thread::spawn(move || {
+                let mut handlers = Vec::new();
+                for stream in listener.incoming().take(REQUESTS) {
+                    let mut stream = stream.unwrap();
+                    let starts = starts.clone();
+                    handlers.push(std::thread::spawn(move || {
+                        // Read the request headers.
+                        let mut request = Vec::new();
+                        let mut buffer = [0; 1024];
+                        while !request.ends_with(b"\r\n\r\n") {
+                            let n = stream.read(&mut buffer).unwrap();
+                            assert!(n > 0);
+                            request.extend_from_slice(&buffer[..n]);
+                        }
+                        starts.lock().unwrap().push(Instant::now());
+                        std::thread::sleep(RESPONSE_TIME);
+                        stream
+                            .write_all(
+                                b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\
+                                  Content-Length: 2\r\nConnection: close\r\n\r\nok",
+                            )
+                            .unwrap();
+                    }));
+                }
+                for handler in handlers {
+                    handler.join().unwrap();
+                }
+            })
+        };
+
+        let mut client = Client::new();
+        client.set_min_interval(Some("127.0.0.1"), INTERVAL);
+        let tmp = crate::util::TempDir::new("concurrent-requests").unwrap();
+        let downloads: Vec<(String, PathBuf)> = (0..REQUESTS)
+            .map(|i| {
+                (
+                    format!("http://127.0.0.1:{port}/{i}"),
+                    tmp.path().join(i.to_string()),
+                )
+            })
+            .collect();
+        let before = Instant::now();
+        let mut received = 0;
+        client
+            .download_all(&downloads, REQUESTS, |i, res| {
+                assert_eq!(res?.len, 2);
+                assert_eq!(std::fs::read(&downloads[i].1)?, b"ok");
+                received += 1;
+                Ok(())
+            })
+            .unwrap();
+        let elapsed = before.elapsed();
+        server.join().unwrap();
+        assert_eq!(received, REQUESTS);
+
+        let mut starts = starts.lock().unwrap().clone();
+        starts.sort();
+        for pair in starts.windows(2) {
+            // Allow for some delay between sending a request and its arrival.
+            assert!(pair[1] - pair[0] >= INTERVAL - Duration::from_millis(50));
+        }
+        // Sequential requests would take at least REQUESTS * RESPONSE_TIME.
+        assert!(elapsed < RESPONSE_TIME * REQUESTS as u32);
+    }
+
+    #[test]
+    fn retry_delays() {
+        let delays: Vec<u64> = [0, 1, 2, 7, 31, 32, u32::MAX]
+            .into_iter()
+            .map(|attempt| retry_delay(None, attempt).as_secs())
+            .collect();
+        assert_eq!(delays, [5, 10, 20, 600, 600, 600, 600]);
+        assert_eq!(
+            retry_delay(Some(Duration::from_secs(7)), 3),
+            Duration::from_secs(7)
+        );
+        assert_eq!(
+            retry_delay(Some(Duration::from_secs(86400)), 0),
+            MAX_RETRY_DELAY
+        );
+    }
+
+    #[test]
+    fn retry_after() {
+        let now = chrono::DateTime::parse_from_rfc3339("2025-10-06T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Mon, 06 Oct 2025 12:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Mon, 06 Oct 2025 11:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
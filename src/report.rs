@@ -0,0 +1,96 @@
+//! A self-contained HTML page listing articles, for example a reading list to share with
+//! people who do not use arxiv-reader.
+
+use crate::{
+    article::Article, authors::format_authors, config::AuthorList, share::one_line,
+    util::escape_html,
+};
+
+/// Renders the formulas in titles, abstracts and notes (written as `$...$` or `\(...\)`).
+const MATHJAX: &str = r#"<script>
+MathJax = { tex: { inlineMath: [['$', '$'], ['\\(', '\\)']] } };
+</script>
+<script id="MathJax-script" async src="https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-chtml.js"></script>"#;
+
+const STYLE: &str = "<style>
+body { max-width: 50em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.4; }
+article { border-top: 1px solid #ccc; padding: 0.5em 0; }
+h2 { font-size: 1.15em; margin-bottom: 0.2em; }
+.meta { color: #555; font-size: 0.9em; }
+.tag { background: #e8eef8; border-radius: 0.3em; padding: 0 0.3em; margin-right: 0.3em; }
+.notes { background: #fafae8; border-left: 3px solid #dd8; padding: 0.3em 0.6em; white-space: pre-wrap; }
+</style>";
+
+/// Returns the page listing the given articles, in this order. `convert` is applied to the
+/// author lists, for example to convert latex accents to unicode (MathJax only renders
+/// formulas).
+pub fn html(
+    title: &str,
+    articles: &[Article],
+    author_list: &AuthorList,
+    convert: impl Fn(&str) -> String,
+) -> String {
+    let mut res = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n{STYLE}\n{MATHJAX}\n</head>\n<body>\n<h1>{}</h1>\n<p>{} articles</p>\n",
+        escape_html(title),
+        escape_html(title),
+        articles.len()
+    );
+    for article in articles {
+        res += &entry(article, author_list, &convert);
+    }
+    res += "</body>\n</html>\n";
+    res
+}
+
+fn entry(article: &Article, author_list: &AuthorList, convert: impl Fn(&str) -> String) -> String {
+    let url = article.abs_url();
+    let mut res = format!(
+        "<article id=\"{}\">\n<h2><a href=\"{}\">{}</a></h2>\n",
+        escape_html(&article.id().to_string()),
+        escape_html(&url),
+        escape_html(&one_line(article.title()))
+    );
+    res += &format!(
+        "<div>{}</div>\n",
+        escape_html(&convert(&format_authors(article.authors(), author_list)))
+    );
+    let mut meta = vec![
+        format!(
+            "<a href=\"{}\">arXiv:{}v{}</a>",
+            escape_html(&url),
+            article.id(),
+            article.last_version().number
+        ),
+        escape_html(article.primary_category()),
+        article.first_version().date.format("%Y-%m-%d").to_string(),
+        format!("<a href=\"{}\">pdf</a>", escape_html(&article.pdf_url())),
+    ];
+    if let Some(journal_ref) = article.journal_ref() {
+        meta.push(escape_html(&one_line(journal_ref)));
+    }
+    if let Some(doi) = article.doi() {
+        meta.push(format!(
+            "<a href=\"https://doi.org/{}\">doi:{}</a>",
+            escape_html(doi),
+            escape_html(doi)
+        ));
+    }
+    res += &format!("<div class=\"meta\">{}</div>\n", meta.join(" · "));
+    if !article.tags().is_empty() {
+        res += "<div>";
+        for tag in article.tags() {
+            res += &format!(
+                "<span class=\"tag\">{}</span>",
+                escape_html(&tag.to_string())
+            );
+        }
+        res += "</div>\n";
+    }
+    res += &format!("<p>{}</p>\n", escape_html(article.abstract_().trim()));
+    if let Some(notes) = article.notes().filter(|notes| !notes.trim().is_empty()) {
+        res += &format!("<div class=\"notes\">{}</div>\n", escape_html(notes.trim()));
+    }
+    res += "</article>\n";
+    res
+}
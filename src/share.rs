@@ -0,0 +1,73 @@
+//! Short texts recommending an article to others.
+
+use clap::ValueEnum;
+
+use crate::{article::Article, authors::format_authors, config::AuthorList};
+
+#[derive(ValueEnum, Copy, Clone)]
+pub enum ShareFormat {
+    /// Plain text for an email.
+    Email,
+    Markdown,
+    /// A toot (at most 500 characters).
+    Mastodon,
+    /// Slack's markup.
+    Slack,
+}
+
+/// The maximal length of a toot.
+const MASTODON_LIMIT: usize = 500;
+
+/// Joins the lines of a title or author list.
+pub fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns the text to share. `convert` is applied to the title and authors, for example
+/// to convert latex to unicode.
+pub fn text(
+    article: &Article,
+    format: ShareFormat,
+    note: Option<&str>,
+    author_list: &AuthorList,
+    convert: impl Fn(&str) -> String,
+) -> String {
+    let title = one_line(&convert(article.title()));
+    let authors = convert(&format_authors(article.authors(), author_list));
+    let url = article.abs_url();
+    match format {
+        ShareFormat::Email => {
+            let mut res = format!("{title}\nby {authors}\n{url}\n");
+            if let Some(note) = note {
+                res += &format!("\n{note}\n");
+            }
+            res
+        }
+        ShareFormat::Markdown => {
+            let mut res = format!("[{title}]({url}) by {authors}\n");
+            if let Some(note) = note {
+                res += &format!("\n> {note}\n");
+            }
+            res
+        }
+        ShareFormat::Slack => {
+            let mut res = format!("*<{url}|{title}>* by {authors}\n");
+            if let Some(note) = note {
+                res += &format!("> {note}\n");
+            }
+            res
+        }
+        ShareFormat::Mastodon => {
+            let mut res = format!("{title} by {authors}");
+            if let Some(note) = note {
+                res = format!("{note}\n\n{res}");
+            }
+            // Mastodon counts every link as 23 characters.
+            let available = MASTODON_LIMIT - 23 - 1;
+            if res.chars().count() > available {
+                res = res.chars().take(available - 1).collect::<String>() + "…";
+            }
+            format!("{res}\n{url}\n")
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! The sources of articles from arXiv: extracting them, building pdfs from them, comparing
+//! two versions and extracting the figures. Like the compression of dumps, this runs
+//! external programs (tar, gzip, latexmk, latexdiff and diff).
+
+use std::{
+    fs::{File, create_dir_all},
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, bail};
+
+use crate::{
+    article::Article,
+    downloads::DownloadDir,
+    message,
+    rate_limited_client::Client,
+    util::{TempDir, write_then_rename},
+};
+
+/// Extracts the downloaded sources into `dir`.
+///
+/// arXiv serves either a gzipped tarball or, for submissions consisting of a single
+/// file, a gzipped tex file.
+pub fn extract(src_path: &Path, dir: &Path) -> anyhow::Result<()> {
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(src_path)
+        .arg("-C")
+        .arg(dir)
+        .stderr(Stdio::null())
+        .status()
+        .context("running tar")?;
+    if status.success() {
+        return Ok(());
+    }
+    let output = Command::new("gzip")
+        .arg("-dc")
+        .arg(src_path)
+        .output()
+        .context("running gzip")?;
+    if !output.status.success() {
+        bail!("could not extract {src_path:?}");
+    }
+    std::fs::write(dir.join("main.tex"), output.stdout)
+        .with_context(|| format!("writing {:?}", dir.join("main.tex")))?;
+    Ok(())
+}
+
+/// Finds the tex file in `dir` (not in subdirectories) that contains `\documentclass`.
+fn main_tex_file(dir: &Path) -> anyhow::Result<PathBuf> {
+    for dir_entry in std::fs::read_dir(dir).with_context(|| format!("reading {dir:?}"))? {
+        let path = dir_entry
+            .with_context(|| format!("reading {dir:?}"))?
+            .path();
+        if path.extension().is_some_and(|e| e == "tex") && path.is_file() {
+            let mut contents = Vec::new();
+            File::open(&path)
+                .and_then(|mut f| f.read_to_end(&mut contents))
+                .with_context(|| format!("reading {path:?}"))?;
+            let contents = String::from_utf8_lossy(&contents);
+            if contents.contains("\\documentclass") || contents.contains("\\documentstyle") {
+                return Ok(path);
+            }
+        }
+    }
+    bail!("found no main tex file in the sources")
+}
+
+/// Compiles the (previously downloaded) sources of the last version with latexmk in a
+/// temporary directory and saves the result where the pdf from arXiv would be saved.
+pub fn build_pdf(article: &Article, download_dir: &DownloadDir) -> anyhow::Result<()> {
+    let src_path = article.src_path(download_dir);
+    let tmp = TempDir::new(&format!(
+        "{}v{}",
+        article.id().dir_name(),
+        article.last_version().number
+    ))?;
+    extract(&src_path, tmp.path())?;
+    let main_tex = main_tex_file(tmp.path())?;
+    message!(
+        "Building pdf for {}v{} from the sources...",
+        article.id(),
+        article.last_version().number
+    );
+    let built = latexmk(&main_tex)?;
+    copy_to(&built, article.pdf_path(download_dir))
+        .with_context(|| format!("saving pdf built from sources for {}", article.id()))?;
+    Ok(())
+}
+
+/// Compiles the tex file with latexmk in its directory and returns the path of the pdf.
+fn latexmk(tex_file: &Path) -> anyhow::Result<PathBuf> {
+    let output = Command::new("latexmk")
+        .arg("-pdf")
+        .arg("-interaction=nonstopmode")
+        .arg("-no-shell-escape")
+        .arg("-f")
+        .arg(tex_file.file_name().unwrap())
+        .current_dir(tex_file.parent().unwrap())
+        .stdin(Stdio::null())
+        .output()
+        .context("running latexmk")?;
+    let built = tex_file.with_extension("pdf");
+    if !built.is_file() {
+        bail!(
+            "latexmk failed ({}):\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+    Ok(built)
+}
+
+fn copy_to(from: &Path, to: PathBuf) -> anyhow::Result<()> {
+    write_then_rename(to, |writer| {
+        std::io::copy(&mut File::open(from)?, writer)?;
+        Ok(())
+    })
+}
+
+/// The file into which `diff` saves the differences between two versions, with extension
+/// pdf, tex or diff.
+pub fn diff_path(
+    article: &Article,
+    download_dir: &DownloadDir,
+    old: u32,
+    new: u32,
+    extension: &str,
+) -> PathBuf {
+    download_dir
+        .directory(article.id())
+        .join(format!("diff-v{old}-v{new}.{extension}"))
+}
+
+/// Downloads the sources of two versions and compares them. With latexdiff, the result is a
+/// pdf marking the changes (or the tex file, if it does not compile); without, it is the
+/// output of `diff -ru`. Returns the path of the result, saved next to the downloads.
+pub fn diff(
+    article: &Article,
+    download_dir: &DownloadDir,
+    client: &mut Client,
+    old: u32,
+    new: u32,
+) -> anyhow::Result<PathBuf> {
+    let tmp = TempDir::new(&format!("{}-diff", article.id().dir_name()))?;
+    let mut dirs = Vec::new();
+    for version in [old, new] {
+        article.download_src_version(download_dir, client, version)?;
+        let dir = tmp.path().join(format!("v{version}"));
+        create_dir_all(&dir).with_context(|| format!("creating {dir:?}"))?;
+        extract(&article.src_path_version(download_dir, version), &dir)?;
+        dirs.push(dir);
+    }
+    let (old_dir, new_dir) = (&dirs[0], &dirs[1]);
+    let old_tex = main_tex_file(old_dir)?;
+    let new_tex = main_tex_file(new_dir)?;
+    message!("Comparing {}v{old} and v{new}...", article.id());
+    let latexdiff = Command::new("latexdiff")
+        .arg("--flatten")
+        .arg(&old_tex)
+        .arg(&new_tex)
+        .current_dir(new_dir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match latexdiff {
+        Ok(output) if output.status.success() => {
+            // Compile next to the new sources so that figures and styles are found.
+            let diff_tex = new_dir.join("arxiv-reader-diff.tex");
+            std::fs::write(&diff_tex, &output.stdout)
+                .with_context(|| format!("writing {diff_tex:?}"))?;
+            match latexmk(&diff_tex) {
+                Ok(built) => {
+                    let path = diff_path(article, download_dir, old, new, "pdf");
+                    copy_to(&built, path.clone()).with_context(|| format!("saving {path:?}"))?;
+                    Ok(path)
+                }
+                Err(err) => {
+                    message!("Could not compile the output of latexdiff: {err:#}");
+                    let path = diff_path(article, download_dir, old, new, "tex");
+                    copy_to(&diff_tex, path.clone()).with_context(|| format!("saving {path:?}"))?;
+                    Ok(path)
+                }
+            }
+        }
+        Ok(output) => bail!("latexdiff failed ({})", output.status),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            message!("latexdiff is not installed, comparing the sources as text.");
+            let output = Command::new("diff")
+                .arg("-ru")
+                .arg(format!("v{old}"))
+                .arg(format!("v{new}"))
+                .current_dir(tmp.path())
+                .stdin(Stdio::null())
+                .output()
+                .context("running diff")?;
+            // diff exits with 1 if the files differ and with 2 on errors.
+            if output.status.code().is_none_or(|code| code > 1) {
+                bail!(
+                    "diff failed ({}):\n{}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+            let path = diff_path(article, download_dir, old, new, "diff");
+            write_then_rename(path.clone(), |writer| {
+                std::io::Write::write_all(writer, &output.stdout)?;
+                Ok(())
+            })
+            .with_context(|| format!("saving {path:?}"))?;
+            Ok(path)
+        }
+        Err(err) => Err(err).context("running latexdiff"),
+    }
+}
+
+/// Downloads the pdf if arXiv has one. Otherwise, if `build_from_source` is set, downloads
+/// the sources and builds the pdf from them (unless this was done before).
+/// Returns whether there is a pdf afterwards.
+pub fn download_or_build_pdf(
+    article: &Article,
+    download_dir: &DownloadDir,
+    client: &mut Client,
+    build_from_source: bool,
+) -> anyhow::Result<bool> {
+    if article.last_version().probably_has_pdf() {
+        article.download_pdf(download_dir, client)?;
+        Ok(true)
+    } else if build_from_source && article.last_version().probably_has_src() {
+        if !article.pdf_path(download_dir).is_file() {
+            article.download_src(download_dir, client)?;
+            build_pdf(article, download_dir)?;
+        }
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// File extensions of images that might be used as figures.
+const FIGURE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "pdf", "eps", "ps", "svg"];
+
+/// Lists the image files in the (previously downloaded) sources of the last version.
+pub fn list_figures(article: &Article, download_dir: &DownloadDir) -> anyhow::Result<Vec<String>> {
+    let src_path = article.src_path(download_dir);
+    let output = Command::new("tar")
+        .arg("-tzf")
+        .arg(&src_path)
+        .stderr(Stdio::null())
+        .output()
+        .context("running tar")?;
+    if !output.status.success() {
+        // The sources consist of a single gzipped tex file.
+        return Ok(Vec::new());
+    }
+    let listing = String::from_utf8_lossy(&output.stdout);
+    Ok(listing
+        .lines()
+        .filter(|name| {
+            Path::new(name).extension().is_some_and(|e| {
+                FIGURE_EXTENSIONS.contains(&e.to_string_lossy().to_ascii_lowercase().as_str())
+            })
+        })
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// The directory into which `extract_figures` extracts the figures.
+pub fn figures_dir(article: &Article, download_dir: &DownloadDir) -> PathBuf {
+    download_dir
+        .directory(article.id())
+        .join(format!("v{}-figures", article.last_version().number))
+}
+
+/// Extracts the image files in the (previously downloaded) sources of the last version
+/// into `figures_dir`. Returns the number of extracted files.
+pub fn extract_figures(article: &Article, download_dir: &DownloadDir) -> anyhow::Result<usize> {
+    let figures = list_figures(article, download_dir)?;
+    if figures.is_empty() {
+        return Ok(0);
+    }
+    let dir = figures_dir(article, download_dir);
+    create_dir_all(&dir).with_context(|| format!("creating {dir:?}"))?;
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(article.src_path(download_dir))
+        .arg("-C")
+        .arg(&dir)
+        .arg("--")
+        .args(&figures)
+        .status()
+        .context("running tar")?;
+    if !status.success() {
+        bail!("extracting figures failed");
+    }
+    Ok(figures.len())
+}
@@ -0,0 +1,347 @@
+//! Bundles of the user's state (tags, notes and the seen-articles log) that can be backed
+//! up or moved to another machine. The metadata is not included since it can always be
+//! pulled again.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::Path,
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    article::{ArticleState, ArxivId, SeenLine},
+    config::TagName,
+    util::write_then_rename,
+};
+
+/// The version of the bundle format.
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    version: u32,
+    /// The tags and notes, by arXiv id.
+    articles: BTreeMap<String, ArticleBundle>,
+    /// The lines of the seen-articles file.
+    seen_articles: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArticleBundle {
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+}
+
+/// What `import` changed.
+#[derive(Default, Serialize)]
+pub struct ImportSummary {
+    /// Articles that got new tags.
+    pub tagged: usize,
+    /// Articles whose notes were imported.
+    pub notes: usize,
+    /// Articles that already had different notes. The imported notes were saved next to them
+    /// in notes-imported.txt.
+    pub conflicting_notes: Vec<String>,
+    /// New lines in seen-articles.
+    pub seen_lines: usize,
+}
+
+/// The lines of a seen-articles file, or none if it does not exist.
+fn read_seen_lines(path: &Path) -> anyhow::Result<Vec<String>> {
+    match File::open(path) {
+        Ok(file) => BufReader::new(file)
+            .lines()
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("reading {path:?}")),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("opening {path:?}")),
+    }
+}
+
+fn write_seen_lines(path: &Path, lines: &[String]) -> anyhow::Result<()> {
+    write_then_rename(path.to_path_buf(), |writer| {
+        for line in lines {
+            writeln!(writer, "{line}")?;
+        }
+        Ok(())
+    })
+    .with_context(|| format!("writing {path:?}"))
+}
+
+/// Collects the state of all articles with an article directory.
+pub fn export(base_dir: &Path) -> anyhow::Result<Bundle> {
+    let mut articles = BTreeMap::new();
+    let dir = base_dir.join("articles");
+    for dir_entry in std::fs::read_dir(&dir).with_context(|| format!("reading {dir:?}"))? {
+        let dir_entry = dir_entry.with_context(|| format!("reading {dir:?}"))?;
+        let Some(id) = ArxivId::from_os_dir_name(&dir_entry.file_name()) else {
+            continue;
+        };
+        let tags = ArticleState::get_tags(base_dir, &id)?;
+        let notes = ArticleState::get_notes(base_dir, &id)?;
+        if tags.is_empty() && notes.is_none() {
+            continue;
+        }
+        articles.insert(
+            id.to_string(),
+            ArticleBundle {
+                tags: tags.into_iter().map(|t| t.0).collect(),
+                notes,
+            },
+        );
+    }
+    Ok(Bundle {
+        version: VERSION,
+        articles,
+        seen_articles: read_seen_lines(&base_dir.join("seen-articles"))?,
+    })
+}
+
+/// Merges a bundle into the state in `base_dir`: tags are added, notes are only written
+/// for articles without notes, and missing lines are merged into seen-articles (see
+/// `merge_lines`).
+pub fn import(base_dir: &Path, bundle: &Bundle) -> anyhow::Result<ImportSummary> {
+    if bundle.version != VERSION {
+        bail!("unsupported bundle version {}", bundle.version);
+    }
+    let mut summary = ImportSummary::default();
+    for (id, imported) in &bundle.articles {
+        let id: ArxivId = id.parse().with_context(|| format!("invalid id {id:?}"))?;
+        let dir = id.directory(base_dir);
+        let mut tags = ArticleState::get_tags(base_dir, &id)?;
+        let count = tags.len();
+        for tag in &imported.tags {
+            tags.insert(tag.parse::<TagName>()?);
+        }
+        if tags.len() > count {
+            id.mkdir(base_dir)?;
+            write_then_rename(dir.join("tags"), |writer| {
+                for tag in &tags {
+                    writeln!(writer, "{tag}").context("writing tag")?;
+                }
+                Ok(())
+            })
+            .with_context(|| format!("writing tags for {id}"))?;
+            summary.tagged += 1;
+        }
+        if let Some(notes) = &imported.notes {
+            let (path, conflict) = match ArticleState::get_notes(base_dir, &id)? {
+                None => (dir.join("notes.txt"), false),
+                Some(existing) if existing == *notes => continue,
+                Some(_) => (dir.join("notes-imported.txt"), true),
+            };
+            id.mkdir(base_dir)?;
+            write_then_rename(path.clone(), |writer| {
+                writer.write_all(notes.as_bytes())?;
+                Ok(())
+            })
+            .with_context(|| format!("writing {path:?}"))?;
+            if conflict {
+                summary.conflicting_notes.push(id.to_string());
+            } else {
+                summary.notes += 1;
+            }
+        }
+    }
+    let path = base_dir.join("seen-articles");
+    let local = read_seen_lines(&path)?;
+    let merged = merge_lines(&local, &bundle.seen_articles)?;
+    summary.seen_lines = merged.len() - local.len();
+    if summary.seen_lines > 0 {
+        write_seen_lines(&path, &merged)?;
+    }
+    Ok(summary)
+}
+
+/// Merges the lines of two seen-articles files in the order of their timestamps, so that
+/// the later of two changes to the seen state of an article wins. Lines of `other` that
+/// are already in `local` are skipped. Since each file is in chronological order, the
+/// lines of each keep their order; lines without timestamp (written by older versions)
+/// come first.
+fn merge_lines(local: &[String], other: &[String]) -> anyhow::Result<Vec<String>> {
+    let existing: HashSet<&String> = local.iter().collect();
+    let other: Vec<&String> = other
+        .iter()
+        .filter(|line| !existing.contains(line))
+        .collect();
+    let time = |line: &str| SeenLine::parse(line).map(|line| line.time);
+    let mut merged = Vec::with_capacity(local.len() + other.len());
+    let (mut i, mut j) = (0, 0);
+    while i < local.len() && j < other.len() {
+        if time(&local[i])? <= time(other[j])? {
+            merged.push(local[i].clone());
+            i += 1;
+        } else {
+            merged.push(other[j].clone());
+            j += 1;
+        }
+    }
+    merged.extend(local[i..].iter().cloned());
+    merged.extend(other[j..].iter().map(|line| line.to_string()));
+    Ok(merged)
+}
+
+/// What `merge_seen` changed.
+#[derive(Default, Serialize)]
+pub struct MergeSummary {
+    /// Articles whose seen state changed.
+    pub changed: usize,
+    /// The number of lines of seen-articles before and after merging.
+    pub lines_before: usize,
+    pub lines_after: usize,
+}
+
+/// The seen version of each article in the lines of a seen-articles file and whether its
+/// journal reference and DOI were seen.
+fn seen_states(lines: &[String]) -> anyhow::Result<HashMap<ArxivId, (u32, bool, bool)>> {
+    let mut states: HashMap<ArxivId, (u32, bool, bool)> = HashMap::new();
+    for line in lines {
+        let line = SeenLine::parse(line)?;
+        let (version, journal, doi) = states.entry(line.id.clone()).or_default();
+        line.apply(version, journal, doi);
+    }
+    Ok(states)
+}
+
+/// Merges the seen-articles file `other` (for example, of another machine) into `local`
+/// (see `merge_lines`).
+pub fn merge_seen_files(local: &Path, other: &Path) -> anyhow::Result<MergeSummary> {
+    let local_lines = read_seen_lines(local)?;
+    let other_lines = read_seen_lines(other)?;
+    let merged =
+        merge_lines(&local_lines, &other_lines).with_context(|| format!("merging {other:?}"))?;
+    let before = seen_states(&local_lines)?;
+    let after = seen_states(&merged)?;
+    let changed = after
+        .iter()
+        .filter(|(id, state)| before.get(*id).copied().unwrap_or_default() != **state)
+        .count();
+    if merged.len() > local_lines.len() {
+        write_seen_lines(local, &merged)?;
+    }
+    Ok(MergeSummary {
+        changed,
+        lines_before: local_lines.len(),
+        lines_after: merged.len(),
+    })
+}
+
+/// Merges the seen-articles file of another machine into the one in `base_dir`.
+pub fn merge_seen(base_dir: &Path, other: &Path) -> anyhow::Result<MergeSummary> {
+    if !other.exists() {
+        bail!("{other:?} does not exist");
+    }
+    merge_seen_files(&base_dir.join("seen-articles"), other)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir, create_dir_all, remove_dir_all};
+
+    use super::*;
+
+    #[test]
+    fn export_and_import() {
+        let dir = std::env::temp_dir().join(format!("arxiv-reader-state-{}", std::process::id()));
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        create_dir_all(a.join("articles/2301.01234")).unwrap();
+        create_dir_all(b.join("articles")).unwrap();
+        create_dir(b.join("articles/math_9901001")).unwrap();
+        std::fs::write(a.join("articles/2301.01234/tags"), "read\n").unwrap();
+        std::fs::write(a.join("articles/2301.01234/notes.txt"), "nice").unwrap();
+        std::fs::write(a.join("seen-articles"), "2301.01234 2 false false\n").unwrap();
+        std::fs::write(b.join("articles/math_9901001/notes.txt"), "old").unwrap();
+
+        let bundle = export(&a).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+        let bundle: Bundle = serde_json::from_str(&json).unwrap();
+        let summary = import(&b, &bundle).unwrap();
+        assert_eq!(
+            (summary.tagged, summary.notes, summary.seen_lines),
+            (1, 1, 1)
+        );
+        assert_eq!(
+            std::fs::read_to_string(b.join("articles/2301.01234/notes.txt")).unwrap(),
+            "nice"
+        );
+        // Importing again changes nothing.
+        let summary = import(&b, &bundle).unwrap();
+        assert_eq!(
+            (summary.tagged, summary.notes, summary.seen_lines),
+            (0, 0, 0)
+        );
+
+        // The imported lines of seen-articles are merged in the order of their timestamps.
+        std::fs::write(
+            b.join("seen-articles"),
+            "2301.01234 2 false false 2025-10-03T10:00:00+02:00\n",
+        )
+        .unwrap();
+        let bundle: Bundle = serde_json::from_str(
+            &json.replace(
+                r#""2301.01234 2 false false""#,
+                r#""2301.01234 1 false false 2025-10-02T10:00:00+02:00","2301.01234 0 false false 2025-10-04T10:00:00+02:00 reset""#,
+            ),
+        )
+        .unwrap();
+        let summary = import(&b, &bundle).unwrap();
+        assert_eq!(summary.seen_lines, 2);
+        assert_eq!(
+            std::fs::read_to_string(b.join("seen-articles")).unwrap(),
+            "2301.01234 1 false false 2025-10-02T10:00:00+02:00\n\
+             2301.01234 2 false false 2025-10-03T10:00:00+02:00\n\
+             2301.01234 0 false false 2025-10-04T10:00:00+02:00 reset\n"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_seen() {
+        let dir = std::env::temp_dir().join(format!("arxiv-reader-merge-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let (local, other) = (dir.join("local"), dir.join("other"));
+        std::fs::write(
+            &local,
+            "2301.01234 1 false false\n\
+             math/9901001 1 false false 2025-10-01T10:00:00+02:00\n\
+             2301.01234 2 false false 2025-10-03T10:00:00+02:00\n\
+             math/9901001 0 false false 2025-10-04T10:00:00+02:00 reset\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &other,
+            "2301.01234 1 false false\n\
+             2301.01234 1 true false 2025-10-02T10:00:00+02:00\n\
+             2502.00001 1 false false 2025-10-05T10:00:00+02:00\n\
+             2301.01234 1 false false 2025-10-06T10:00:00+02:00 reset\n",
+        )
+        .unwrap();
+        let summary = merge_seen_files(&local, &other).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&local).unwrap(),
+            "2301.01234 1 false false\n\
+             math/9901001 1 false false 2025-10-01T10:00:00+02:00\n\
+             2301.01234 1 true false 2025-10-02T10:00:00+02:00\n\
+             2301.01234 2 false false 2025-10-03T10:00:00+02:00\n\
+             math/9901001 0 false false 2025-10-04T10:00:00+02:00 reset\n\
+             2502.00001 1 false false 2025-10-05T10:00:00+02:00\n\
+             2301.01234 1 false false 2025-10-06T10:00:00+02:00 reset\n"
+        );
+        // The reset on the other machine is kept since it is the latest change.
+        assert_eq!(
+            (summary.changed, summary.lines_before, summary.lines_after),
+            (2, 4, 7)
+        );
+        // Merging again changes nothing.
+        let summary = merge_seen_files(&local, &other).unwrap();
+        assert_eq!((summary.changed, summary.lines_after), (0, 7));
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,466 @@
+//! Statistics about the articles in the database and about reading them.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Write, stdin, stdout},
+    path::Path,
+};
+
+use anyhow::Context;
+use chrono::{Datelike, Days, NaiveDate};
+use serde::Serialize;
+use termion::{
+    cursor::HideCursor, event::Key, input::TermRead, raw::IntoRawMode, screen::IntoAlternateScreen,
+};
+
+use crate::{
+    article::{Article, ArxivId},
+    downloads::{self, DownloadDir},
+    filter::PreparedFilter,
+    interact::init_panic_hook,
+    message,
+    util::format_size,
+};
+
+/// The number of categories in the list of top categories.
+const TOP_CATEGORIES: usize = 20;
+
+/// The number of days in the sparkline of new articles.
+pub const SPARKLINE_DAYS: u64 = 30;
+
+#[derive(Serialize)]
+pub struct Stats {
+    /// The number of new articles matching filters.new, by week (starting on Monday).
+    pub new_per_week: Vec<(NaiveDate, usize)>,
+    /// The number of new articles matching filters.new on each of the last
+    /// `SPARKLINE_DAYS` days.
+    pub new_per_day: Vec<(NaiveDate, usize)>,
+    /// The number of articles marked as seen, by week.
+    pub seen_per_week: Vec<(NaiveDate, usize)>,
+    /// The most frequent categories of the articles matching filters.new.
+    pub top_categories: Vec<(String, usize)>,
+    /// The number of articles with each tag.
+    pub tags: Vec<(String, usize)>,
+    /// The number of articles matching filters.new that have not been seen yet.
+    pub backlog: usize,
+    /// When the oldest unseen article was first downloaded.
+    pub oldest_unseen: Option<String>,
+    pub bookmarked: usize,
+    pub total: usize,
+    pub archive: Archive,
+}
+
+/// What the database and the articles directory contain, independently of filters.new.
+#[derive(Serialize, Default)]
+pub struct Archive {
+    /// The number of articles per primary category (the first one listed), the most
+    /// frequent first.
+    pub per_primary_category: Vec<(String, usize)>,
+    /// The number of articles by month of submission of the first version.
+    pub per_month: Vec<(String, usize)>,
+    /// The number of articles with a version marked as seen.
+    pub seen: usize,
+    /// The number of articles with a downloaded pdf (of any version). Only known after
+    /// [`Archive::measure`].
+    pub with_pdf: usize,
+    /// The disk space in bytes used by the articles directory and the download directory.
+    /// Only known after [`Archive::measure`].
+    pub disk_usage: u64,
+}
+
+impl Archive {
+    /// Counts the articles per primary category and per month, and the seen articles.
+    fn count(articles: &HashMap<ArxivId, Article>) -> Self {
+        let mut per_primary_category: HashMap<&String, usize> = HashMap::new();
+        let mut per_month: BTreeMap<String, usize> = BTreeMap::new();
+        let mut seen = 0;
+        for article in articles.values() {
+            if let Some(category) = article.categories().first() {
+                *per_primary_category.entry(category).or_default() += 1;
+            }
+            let month = article.first_version().date.format("%Y-%m").to_string();
+            *per_month.entry(month).or_default() += 1;
+            if article.last_seen_version() > 0 {
+                seen += 1;
+            }
+        }
+        let mut per_primary_category: Vec<(String, usize)> = per_primary_category
+            .into_iter()
+            .map(|(c, count)| (c.clone(), count))
+            .collect();
+        per_primary_category.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+        Self {
+            per_primary_category,
+            per_month: per_month.into_iter().collect(),
+            seen,
+            with_pdf: 0,
+            disk_usage: 0,
+        }
+    }
+
+    /// Counts the articles with downloaded pdfs and measures the disk usage, which needs
+    /// to read the download directory of every article.
+    pub fn measure(
+        &mut self,
+        articles: &HashMap<ArxivId, Article>,
+        base_dir: &Path,
+        download_dir: &DownloadDir,
+    ) -> anyhow::Result<()> {
+        self.with_pdf = 0;
+        for article in articles.values() {
+            let files = article.downloaded_files(download_dir)?;
+            if files
+                .iter()
+                .any(|f| f.extension().is_some_and(|e| e == "pdf"))
+            {
+                self.with_pdf += 1;
+            }
+        }
+        let articles_dir = base_dir.join("articles");
+        self.disk_usage = downloads::size(&articles_dir)?;
+        if download_dir.root() != articles_dir && download_dir.root().exists() {
+            self.disk_usage += downloads::size(download_dir.root())?;
+        }
+        Ok(())
+    }
+}
+
+/// The Monday of the week of `date`.
+fn week_of(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday() as u64)
+}
+
+impl Stats {
+    /// Computes the statistics for the `weeks` weeks until `today`. The filter must be
+    /// prepared.
+    pub fn compute(
+        articles: &HashMap<ArxivId, Article>,
+        new_filter: &PreparedFilter,
+        today: NaiveDate,
+        weeks: u64,
+    ) -> Self {
+        let this_week = week_of(today);
+        let weeks: Vec<NaiveDate> = (0..weeks)
+            .rev()
+            .filter_map(|i| this_week.checked_sub_days(Days::new(7 * i)))
+            .collect();
+        let mut new_per_week: BTreeMap<NaiveDate, usize> = weeks.iter().map(|w| (*w, 0)).collect();
+        let mut seen_per_week = new_per_week.clone();
+        let mut categories: HashMap<&String, usize> = HashMap::new();
+        let mut tags: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut backlog = 0;
+        let mut oldest_unseen: Option<&String> = None;
+        let mut bookmarked = 0;
+        for article in articles.values() {
+            if let Some(time) = article.last_seen_time()
+                && let Some(count) = seen_per_week.get_mut(&week_of(time.date_naive()))
+            {
+                *count += 1;
+            }
+            for tag in article.tags() {
+                *tags.entry(&tag.0).or_default() += 1;
+            }
+            if article.is_bookmarked() {
+                bookmarked += 1;
+            }
+            if !new_filter.matches(article) {
+                continue;
+            }
+            let first_encounter = &article.first_version().first_encounter;
+            if let Ok(date) = NaiveDate::parse_from_str(first_encounter, "%Y-%m-%d")
+                && let Some(count) = new_per_week.get_mut(&week_of(date))
+            {
+                *count += 1;
+            }
+            for category in article.categories() {
+                *categories.entry(category).or_default() += 1;
+            }
+            if article.last_seen_version() == 0 {
+                backlog += 1;
+                if oldest_unseen.is_none_or(|d| first_encounter < d) {
+                    oldest_unseen = Some(first_encounter);
+                }
+            }
+        }
+        let mut top_categories: Vec<(String, usize)> = categories
+            .into_iter()
+            .map(|(c, count)| (c.clone(), count))
+            .collect();
+        top_categories.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+        top_categories.truncate(TOP_CATEGORIES);
+        Self {
+            new_per_week: new_per_week.into_iter().collect(),
+            new_per_day: new_per_day(articles, new_filter, today, SPARKLINE_DAYS),
+            seen_per_week: seen_per_week.into_iter().collect(),
+            top_categories,
+            tags: tags.into_iter().map(|(t, n)| (t.to_string(), n)).collect(),
+            backlog,
+            oldest_unseen: oldest_unseen.cloned(),
+            bookmarked,
+            total: articles.len(),
+            archive: Archive::count(articles),
+        }
+    }
+
+    /// The lines of a panel of the dashboard, at most `width` characters wide.
+    fn panel(&self, panel: Panel, width: usize) -> Vec<String> {
+        let weeks = |per_week: &[(NaiveDate, usize)]| {
+            per_week
+                .iter()
+                .map(|(week, count)| (week.format("%Y-%m-%d").to_string(), *count))
+                .collect::<Vec<_>>()
+        };
+        match panel {
+            Panel::Weeks => {
+                let mut lines = vec!["New matching articles (by week):".to_string()];
+                lines.extend(bar_chart(&weeks(&self.new_per_week), width));
+                lines.push(String::new());
+                lines.push(format!(
+                    "New matching articles in the last {SPARKLINE_DAYS} days: {}",
+                    sparkline(&self.new_per_day)
+                ));
+                lines.push(String::new());
+                lines.push("Articles marked as seen (by week):".to_string());
+                lines.extend(bar_chart(&weeks(&self.seen_per_week), width));
+                lines
+            }
+            Panel::Categories => {
+                let mut lines = vec!["Categories of the matching articles:".to_string()];
+                lines.extend(bar_chart(&self.top_categories, width));
+                lines
+            }
+            Panel::Tags => {
+                if self.tags.is_empty() {
+                    return vec!["No tagged articles.".to_string()];
+                }
+                let mut lines = vec!["Articles per tag:".to_string()];
+                lines.extend(bar_chart(&self.tags, width));
+                lines
+            }
+            Panel::Backlog => {
+                let mut lines = vec![format!("Unseen matching articles: {}", self.backlog)];
+                if let Some(oldest) = &self.oldest_unseen {
+                    lines.push(format!("The oldest one was downloaded on {oldest}."));
+                }
+                lines.push(format!("Bookmarked articles: {}", self.bookmarked));
+                lines.push(format!("Articles in the database: {}", self.total));
+                lines
+            }
+            Panel::Archive => {
+                let archive = &self.archive;
+                let mut lines = vec![
+                    format!("Seen articles: {}", archive.seen),
+                    format!("Articles with downloaded pdfs: {}", archive.with_pdf),
+                    format!("Disk usage: {}", format_size(archive.disk_usage)),
+                    String::new(),
+                    "Articles per primary category:".to_string(),
+                ];
+                lines.extend(bar_chart(&archive.per_primary_category, width));
+                lines.push(String::new());
+                lines.push("Articles per month of submission:".to_string());
+                lines.extend(bar_chart(&archive.per_month, width));
+                lines
+            }
+        }
+    }
+
+    /// Prints all panels.
+    pub fn print(&self) {
+        let width = termion::terminal_size()
+            .map(|(w, _)| w as usize)
+            .unwrap_or(80);
+        for (i, panel) in Panel::ALL.iter().enumerate() {
+            if i > 0 {
+                message!();
+            }
+            for line in self.panel(*panel, width) {
+                message!("{line}");
+            }
+        }
+    }
+
+    /// Shows one panel at a time until the user quits.
+    pub fn interact(&self) -> anyhow::Result<()> {
+        let mut current = 0;
+        let mut scroll = 0;
+        init_panic_hook().context("initializing panic hook")?;
+        let screen = stdout().into_raw_mode()?.into_alternate_screen()?;
+        // Suspend raw mode as it interferes with printing.
+        screen.suspend_raw_mode()?;
+        let mut screen = HideCursor::from(screen);
+        loop {
+            let (width, height) = termion::terminal_size().context("retrieving terminal size")?;
+            let (width, height) = (width as usize, height as usize);
+            write!(
+                screen,
+                "{}{}",
+                termion::clear::All,
+                termion::cursor::Goto(1, 1),
+            )?;
+            let mut header = Vec::new();
+            for (i, panel) in Panel::ALL.iter().enumerate() {
+                if i == current {
+                    header.push(format!(
+                        "{}[{}] {}{}",
+                        termion::style::Invert,
+                        i + 1,
+                        panel.title(),
+                        termion::style::Reset
+                    ));
+                } else {
+                    header.push(format!("[{}] {}", i + 1, panel.title()));
+                }
+            }
+            println!("{}", header.join("  "));
+            println!();
+            let lines = self.panel(Panel::ALL[current], width);
+            // Leave space for the header and the list of shortcuts.
+            let visible = height.saturating_sub(4).max(1);
+            scroll = scroll.min(lines.len().saturating_sub(visible));
+            for line in lines.iter().skip(scroll).take(visible) {
+                println!("{line}");
+            }
+            write!(screen, "{}", termion::cursor::Goto(1, height as u16))?;
+            print!("[q] quit; [LEFT/RIGHT] switch panel; [UP/DOWN] scroll");
+            screen.flush()?;
+
+            screen.activate_raw_mode()?;
+            let key = match stdin().keys().next() {
+                Some(key) => key?,
+                None => break,
+            };
+            screen.suspend_raw_mode()?;
+            match key {
+                Key::Char('q') | Key::Esc => break,
+                Key::Right | Key::Char('\t') => {
+                    current = (current + 1) % Panel::ALL.len();
+                    scroll = 0;
+                }
+                Key::Left | Key::BackTab => {
+                    current = (current + Panel::ALL.len() - 1) % Panel::ALL.len();
+                    scroll = 0;
+                }
+                Key::Char(c) if ('1'..='9').contains(&c) => {
+                    let i = c as usize - '1' as usize;
+                    if i < Panel::ALL.len() {
+                        current = i;
+                        scroll = 0;
+                    }
+                }
+                Key::Down => scroll += 1,
+                Key::Up => scroll = scroll.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Panel {
+    Weeks,
+    Categories,
+    Tags,
+    Backlog,
+    Archive,
+}
+
+impl Panel {
+    const ALL: [Panel; 5] = [
+        Panel::Weeks,
+        Panel::Categories,
+        Panel::Tags,
+        Panel::Backlog,
+        Panel::Archive,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Panel::Weeks => "Articles per week",
+            Panel::Categories => "Top categories",
+            Panel::Tags => "Tags",
+            Panel::Backlog => "Backlog",
+            Panel::Archive => "Archive",
+        }
+    }
+}
+
+/// Counts the articles matching the (prepared) filter that were first downloaded on each
+/// of the `days` days until `today`.
+pub fn new_per_day(
+    articles: &HashMap<ArxivId, Article>,
+    filter: &PreparedFilter,
+    today: NaiveDate,
+    days: u64,
+) -> Vec<(NaiveDate, usize)> {
+    let mut counts: BTreeMap<NaiveDate, usize> = (0..days)
+        .filter_map(|i| today.checked_sub_days(Days::new(i)))
+        .map(|day| (day, 0))
+        .collect();
+    for article in articles.values() {
+        if let Ok(date) =
+            NaiveDate::parse_from_str(&article.first_version().first_encounter, "%Y-%m-%d")
+            && let Some(count) = counts.get_mut(&date)
+            && filter.matches(article)
+        {
+            *count += 1;
+        }
+    }
+    counts.into_iter().collect()
+}
+
+/// A line of block characters whose heights are proportional to the counts.
+pub fn sparkline(counts: &[(NaiveDate, usize)]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = counts.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    let line: String = counts
+        .iter()
+        .map(|(_, n)| {
+            if *n == 0 {
+                ' '
+            } else {
+                BLOCKS[(n * BLOCKS.len()).div_ceil(max) - 1]
+            }
+        })
+        .collect();
+    let total: usize = counts.iter().map(|(_, n)| n).sum();
+    format!("▕{line}▏ {total} in total, at most {max} per day")
+}
+
+/// A horizontal bar chart with one line per row, at most `width` characters wide.
+fn bar_chart(rows: &[(String, usize)], width: usize) -> Vec<String> {
+    let label_width = rows
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    let max = rows.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    let bar_width = width.saturating_sub(label_width + 10).max(1);
+    rows.iter()
+        .map(|(label, count)| {
+            let bar = if max == 0 {
+                0
+            } else {
+                (count * bar_width).div_ceil(max)
+            };
+            let line = format!("  {label:label_width$} {count:5} {}", "#".repeat(bar));
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn weeks() {
+        let date = NaiveDate::from_ymd_opt(2025, 10, 9).unwrap();
+        assert_eq!(week_of(date), NaiveDate::from_ymd_opt(2025, 10, 6).unwrap());
+        let chart = bar_chart(&[("a".to_string(), 2), ("bb".to_string(), 1)], 30);
+        assert_eq!(chart[0], format!("  a      2 {}", "#".repeat(18)));
+        assert_eq!(chart[1], format!("  bb     1 {}", "#".repeat(9)));
+        let counts = [0, 1, 8, 4].map(|n| (date, n));
+        assert_eq!(sparkline(&counts), "▕ ▁█▄▏ 13 in total, at most 8 per day");
+    }
+}
@@ -0,0 +1,219 @@
+//! Synchronization of the arxiv-reader directory with a git remote: commits the local
+//! changes (tags, notes, attachments, seen-articles and the config file), merges the
+//! remote ones and pushes. Downloads, the database and other files are ignored.
+//!
+//! The seen-articles log is merged by a merge driver running `arxiv-reader database
+//! merge-seen`, which interleaves the lines of both sides in the order of their
+//! timestamps. The tags files are merged with git's union merge driver, which keeps the
+//! tags of both sides instead of reporting a conflict. Conflicting notes are left for the
+//! user to resolve.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Output},
+};
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+
+use crate::util::write_then_rename;
+
+/// The lines of .gitattributes that `sync` makes sure of.
+const ATTRIBUTES: [&str; 2] = [
+    "seen-articles merge=arxiv-reader-seen",
+    "articles/*/tags merge=union",
+];
+
+/// Lines of .gitattributes written by earlier versions, which `sync` removes.
+const OBSOLETE_ATTRIBUTES: [&str; 1] = ["seen-articles merge=union"];
+
+/// The lines of .gitignore that `sync` makes sure of: everything is ignored except for the
+/// state of the articles and the config file.
+const IGNORED: [&str; 11] = [
+    "/*",
+    "!/.gitignore",
+    "!/.gitattributes",
+    "!/config.toml",
+    "!/seen-articles",
+    "!/articles/",
+    "/articles/*/*",
+    "!/articles/*/tags",
+    "!/articles/*/notes.txt",
+    "!/articles/*/notes-imported.txt",
+    "!/articles/*/attachments/",
+];
+
+/// The message of the commits made by `sync`.
+const COMMIT_MESSAGE: &str = "Sync arxiv-reader state";
+
+#[derive(Serialize, Default)]
+pub struct Summary {
+    /// Whether there were local changes to commit.
+    pub committed: bool,
+    /// Whether changes were merged from the remote.
+    pub pulled: bool,
+    /// Whether the branch was pushed.
+    pub pushed: bool,
+    /// Whether there is an upstream branch to pull from and push to.
+    pub has_upstream: bool,
+}
+
+fn git(base_dir: &Path, args: &[&str]) -> anyhow::Result<Output> {
+    Command::new("git")
+        .args(args)
+        .current_dir(base_dir)
+        .output()
+        .context("running git")
+}
+
+/// Runs git and fails with its error output if it fails.
+fn run(base_dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = git(base_dir, args)?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The current commit, or `None` before the first one.
+fn head(base_dir: &Path) -> anyhow::Result<Option<String>> {
+    let output = git(base_dir, &["rev-parse", "--verify", "--quiet", "HEAD"])?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// Makes sure that the file contains the given lines, appending the missing ones, and
+/// removes the obsolete lines.
+fn ensure_lines(path: &Path, lines: &[&str], obsolete: &[&str]) -> anyhow::Result<()> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("reading {path:?}")),
+    };
+    let missing: Vec<&str> = lines
+        .iter()
+        .copied()
+        .filter(|line| !existing.lines().any(|l| l.trim() == *line))
+        .collect();
+    let has_obsolete = existing.lines().any(|l| obsolete.contains(&l.trim()));
+    if missing.is_empty() && !has_obsolete {
+        return Ok(());
+    }
+    write_then_rename(path.to_path_buf(), |writer| {
+        for line in existing.lines() {
+            if !obsolete.contains(&line.trim()) {
+                writeln!(writer, "{line}")?;
+            }
+        }
+        for line in missing {
+            writeln!(writer, "{line}")?;
+        }
+        Ok(())
+    })
+    .with_context(|| format!("writing {path:?}"))
+}
+
+/// Registers the merge driver for seen-articles in the configuration of the repository.
+fn configure_merge_driver(base_dir: &Path) -> anyhow::Result<()> {
+    let exe = std::env::current_exe().context("finding the arxiv-reader executable")?;
+    // git runs the driver with the shell.
+    let exe = format!("'{}'", exe.to_string_lossy().replace('\'', r"'\''"));
+    run(
+        base_dir,
+        &[
+            "config",
+            "merge.arxiv-reader-seen.name",
+            "arxiv-reader seen-articles merge",
+        ],
+    )?;
+    run(
+        base_dir,
+        &[
+            "config",
+            "merge.arxiv-reader-seen.driver",
+            &format!("{exe} database merge-seen --into %A %B"),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Commits the local changes, merges the changes of the upstream branch (if there is one)
+/// and pushes the result.
+pub fn sync(base_dir: &Path) -> anyhow::Result<Summary> {
+    if !git(base_dir, &["rev-parse", "--git-dir"])?.status.success() {
+        bail!("{base_dir:?} is not a git repository (run `git init` there to create one)");
+    }
+    if git(
+        base_dir,
+        &["rev-parse", "--verify", "--quiet", "MERGE_HEAD"],
+    )?
+    .status
+    .success()
+    {
+        bail!(
+            "a merge is in progress in {base_dir:?}; resolve the conflicts and commit them with git first"
+        );
+    }
+    let mut summary = Summary::default();
+    ensure_lines(
+        &base_dir.join(".gitattributes"),
+        &ATTRIBUTES,
+        &OBSOLETE_ATTRIBUTES,
+    )?;
+    ensure_lines(&base_dir.join(".gitignore"), &IGNORED, &[])?;
+    configure_merge_driver(base_dir)?;
+
+    run(base_dir, &["add", "--all"])?;
+    if !git(base_dir, &["diff", "--cached", "--quiet"])?
+        .status
+        .success()
+    {
+        run(base_dir, &["commit", "--quiet", "-m", COMMIT_MESSAGE])?;
+        summary.committed = true;
+    }
+
+    summary.has_upstream = git(
+        base_dir,
+        &[
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ],
+    )?
+    .status
+    .success();
+    if !summary.has_upstream {
+        return Ok(summary);
+    }
+
+    let before = head(base_dir)?;
+    let output = git(base_dir, &["pull", "--quiet", "--no-rebase", "--no-edit"])?;
+    if !output.status.success() {
+        let conflicts = run(base_dir, &["diff", "--name-only", "--diff-filter=U"])?;
+        if !conflicts.is_empty() {
+            bail!(
+                "merging the remote changes failed because of conflicts in:\n{conflicts}\nResolve them, commit them with git and run `arxiv-reader sync` again."
+            );
+        }
+        bail!(
+            "git pull failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    summary.pulled = head(base_dir)? != before;
+
+    let ahead = run(base_dir, &["rev-list", "--count", "@{upstream}..HEAD"])?;
+    if ahead != "0" {
+        run(base_dir, &["push", "--quiet"])?;
+        summary.pushed = true;
+    }
+    Ok(summary)
+}
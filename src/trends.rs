@@ -0,0 +1,92 @@
+//! How often keywords appeared in new articles over time.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, Months, NaiveDate};
+
+use crate::{
+    article::{ArticleMetadata, ArxivId},
+    message,
+};
+
+/// The width of the longest bar in `print`.
+const WIDTH: usize = 50;
+
+/// The months (such as "2025-10") from `months - 1` months before `today` until the month
+/// of `today`.
+pub fn last_months(today: NaiveDate, months: u32) -> Vec<String> {
+    let first_of_month = today.with_day(1).unwrap();
+    (0..months)
+        .rev()
+        .filter_map(|i| first_of_month.checked_sub_months(Months::new(i)))
+        .map(|date| date.format("%Y-%m").to_string())
+        .collect()
+}
+
+/// Counts the articles containing `keyword` in the title or abstract (case-insensitive)
+/// for each month in which their first version was submitted. If `categories` is not
+/// empty, only articles in one of these categories (or archives, such as "math") are
+/// counted.
+pub fn count_per_month(
+    metadata: &HashMap<ArxivId, ArticleMetadata>,
+    keyword: &str,
+    categories: &[String],
+) -> BTreeMap<String, usize> {
+    let keyword = keyword.to_lowercase();
+    let mut counts = BTreeMap::new();
+    for article in metadata.values() {
+        let in_category = |c: &String| {
+            categories.iter().any(|cat| {
+                c == cat
+                    || c.strip_prefix(cat.as_str())
+                        .is_some_and(|r| r.starts_with('.'))
+            })
+        };
+        if !categories.is_empty() && !article.categories.iter().any(in_category) {
+            continue;
+        }
+        if article.title.to_lowercase().contains(&keyword)
+            || article.abstract_.to_lowercase().contains(&keyword)
+        {
+            let month = article.first_version().date.format("%Y-%m").to_string();
+            *counts.entry(month).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// Prints a bar chart of the counts in the given months.
+pub fn print(keyword: &str, counts: &BTreeMap<String, usize>, months: &[String]) {
+    let total: usize = months.iter().filter_map(|m| counts.get(m)).sum();
+    message!("{keyword} ({total} articles)");
+    let max = months
+        .iter()
+        .filter_map(|m| counts.get(m))
+        .max()
+        .copied()
+        .unwrap_or(0);
+    for month in months {
+        let count = counts.get(month).copied().unwrap_or(0);
+        let bar = if max == 0 {
+            0
+        } else {
+            (count * WIDTH).div_ceil(max)
+        };
+        let line = format!("  {month} {count:5} {}", "#".repeat(bar));
+        message!("{}", line.trim_end());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn months() {
+        let today = NaiveDate::from_ymd_opt(2025, 2, 14).unwrap();
+        assert_eq!(
+            last_months(today, 4),
+            vec!["2024-11", "2024-12", "2025-01", "2025-02"]
+        );
+    }
+}
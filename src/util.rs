@@ -1,11 +1,68 @@
 use std::{
     ffi::OsStr,
-    fs::{File, rename},
-    io::{BufReader, BufWriter, ErrorKind},
-    path::PathBuf,
+    fs::{File, create_dir_all, remove_dir_all, rename},
+    io::{BufReader, BufWriter, ErrorKind, Write, stdin},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
 };
 
-use aho_corasick::{AhoCorasick, MatchKind};
+use anyhow::{Context, bail};
+
+use crate::config::{HighlightRules, Programs};
+
+static PROGRAMS: OnceLock<Programs> = OnceLock::new();
+
+/// Makes `open` and `shell_command` use the programs from the config file.
+pub fn set_programs(programs: Programs) {
+    let _ = PROGRAMS.set(programs);
+}
+
+fn programs() -> &'static Programs {
+    PROGRAMS.get_or_init(Programs::default)
+}
+
+/// What `open` opens, which decides the configured program to use.
+#[derive(Clone, Copy)]
+pub enum OpenAs {
+    Pdf,
+    Url,
+    Directory,
+    /// Any other file, always opened with the default application.
+    File,
+}
+
+/// A temporary directory that is deleted (with its contents) when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> anyhow::Result<Self> {
+        let path =
+            std::env::temp_dir().join(format!("arxiv-reader-{}-{}", std::process::id(), name));
+        if path.exists() {
+            remove_dir_all(&path).with_context(|| format!("removing {path:?}"))?;
+        }
+        create_dir_all(&path).with_context(|| format!("creating {path:?}"))?;
+        Ok(Self(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+/// The temporary file `file~` in which `file` is written before it is renamed.
+pub fn tmp_path(file: &Path) -> PathBuf {
+    let mut tmp_file_name = file.file_name().unwrap().to_owned();
+    tmp_file_name.push(OsStr::new("~"));
+    file.with_file_name(tmp_file_name)
+}
 
 /// Opens `file~`, then lets f write to it, closes the file, and then renames it to `file`.
 /// This avoids problems with partially written files.
@@ -13,10 +70,7 @@ pub fn write_then_rename<F: FnOnce(&mut BufWriter<File>) -> anyhow::Result<()>>(
     file: PathBuf,
     f: F,
 ) -> anyhow::Result<()> {
-    let mut tmp_file_name = file.file_name().unwrap().to_owned();
-    tmp_file_name.push(OsStr::new("~"));
-    let mut tmp_file = file.clone();
-    tmp_file.set_file_name(tmp_file_name);
+    let tmp_file = tmp_path(&file);
     {
         let file = File::create(&tmp_file)?;
         let mut writer = BufWriter::new(file);
@@ -45,43 +99,365 @@ pub fn read_if_exists<R, F: FnOnce(&mut BufReader<File>) -> anyhow::Result<R>>(
     }
 }
 
-/// Mark matches in bold.
-pub fn highlight_matches(
-    line: &str,
-    ascii_case_insensitive: bool,
-    patterns: &Vec<String>,
-) -> String {
-    let mut builder = AhoCorasick::builder();
-    builder.match_kind(MatchKind::LeftmostLongest);
-    builder.ascii_case_insensitive(ascii_case_insensitive);
-    let ac = builder.build(patterns).unwrap();
+/// Asks a yes/no question on stdin. Anything but "y" or "yes" counts as no.
+pub fn confirm(question: &str) -> anyhow::Result<bool> {
+    crate::output::write(format_args!("{question} [y/N] "));
+    let mut response = String::new();
+    stdin().read_line(&mut response)?;
+    let response = response.trim().to_ascii_lowercase();
+    Ok(response == "y" || response == "yes")
+}
+
+/// Formats a number of bytes for humans, such as "1.5 MB".
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1000 {
+        format!("{bytes} B")
+    } else if bytes < 1000 * 1000 {
+        format!("{:.1} kB", bytes as f64 / 1000.0)
+    } else if bytes < 1000 * 1000 * 1000 {
+        format!("{:.1} MB", bytes as f64 / (1000.0 * 1000.0))
+    } else {
+        format!("{:.1} GB", bytes as f64 / (1000.0 * 1000.0 * 1000.0))
+    }
+}
+
+/// Opens a file, directory or URL with the program from the config file, or with the
+/// default application of the desktop environment.
+pub fn open(kind: OpenAs, target: impl AsRef<OsStr>) -> anyhow::Result<()> {
+    let configured = match kind {
+        OpenAs::Pdf => programs().pdf_viewer.as_deref(),
+        OpenAs::Url => programs().browser.as_deref(),
+        OpenAs::Directory => programs().file_manager.as_deref(),
+        OpenAs::File => None,
+    };
+    let (program, mut command) = if let Some(configured) = configured {
+        let mut words = configured.split_whitespace();
+        let program = words.next().unwrap_or_default();
+        let mut command = Command::new(program);
+        command.args(words);
+        (program, command)
+    } else if cfg!(target_os = "macos") {
+        ("open", Command::new("open"))
+    } else if cfg!(windows) {
+        // The first argument of start is the window title.
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        ("start", command)
+    } else {
+        ("xdg-open", Command::new("xdg-open"))
+    };
+    let status = command.arg(target).output()?.status;
+    if !status.success() {
+        bail!("{program} failed");
+    }
+    Ok(())
+}
+
+/// A command that runs `script` in the shell from the config file, or in the shell of the
+/// platform (bash if available).
+pub fn shell_command(script: &str) -> Command {
+    let mut command = if let Some(shell) = &programs().shell {
+        let mut command = Command::new(shell);
+        command.arg("-c");
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else if find_in_path("bash").is_some() {
+        let mut command = Command::new("bash");
+        command.arg("-c");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    command.arg(script);
+    command
+}
+
+/// Looks for an executable with the given name in the directories in $PATH.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Copies text to the clipboard with the program from the config file, or else with one
+/// of the usual command-line tools.
+pub fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    if let Some(configured) = &programs().clipboard {
+        let words: Vec<&str> = configured.split_whitespace().collect();
+        let Some((program, args)) = words.split_first() else {
+            bail!("programs.clipboard is empty");
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("running {program}"))?;
+        child.stdin.take().unwrap().write_all(text.as_bytes())?;
+        if !child.wait()?.success() {
+            bail!("{program} failed");
+        }
+        return Ok(());
+    }
+    let candidates: &[&[&str]] = if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    };
+    for candidate in candidates {
+        let child = Command::new(candidate[0])
+            .args(&candidate[1..])
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        child.stdin.take().unwrap().write_all(text.as_bytes())?;
+        if !child.wait()?.success() {
+            bail!("{} failed", candidate[0]);
+        }
+        return Ok(());
+    }
+    bail!(
+        "found no program to copy to the clipboard (tried {})",
+        candidates
+            .iter()
+            .map(|c| c[0])
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// The text editor to use if $EDITOR is not set.
+pub fn default_editor() -> &'static str {
+    if cfg!(windows) { "notepad" } else { "vi" }
+}
+
+/// Matches `query` against `candidate` as a subsequence, ignoring case. Returns a score
+/// (lower is better, 0 for a prefix) or `None` if `candidate` does not contain the
+/// characters of `query` in order.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    for q in query.to_lowercase().chars() {
+        let i = next + candidate[next..].iter().position(|c| *c == q)?;
+        // Every skipped character makes the match worse.
+        score += i - next;
+        next = i + 1;
+    }
+    Some(score)
+}
+
+/// Breaks a text into lines of at most `width` characters (unless a single word is
+/// longer), treating all whitespace as spaces.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line += word;
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Breaks a text into the rows it takes up in a terminal of the given width: at line
+/// breaks, and after `width` characters, not counting terminal escape sequences (such as
+/// colors).
+pub fn screen_rows(text: &str, width: usize) -> Vec<String> {
+    let mut rows = vec![];
+    for line in text.split('\n') {
+        let mut row = String::new();
+        let mut count = 0;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                // Copy the escape sequence up to its final character.
+                row.push(c);
+                for d in chars.by_ref() {
+                    row.push(d);
+                    if d.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+                continue;
+            }
+            if count == width {
+                rows.push(std::mem::take(&mut row));
+                count = 0;
+            }
+            row.push(c);
+            count += 1;
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// Marks the matches of the rules in their styles. If several rules match at the same
+/// place, the first one wins.
+pub fn highlight_matches(line: &str, rules: &HighlightRules) -> String {
+    let Some((re, groups)) = rules.any() else {
+        return line.to_string();
+    };
     let mut res = String::new();
     let mut i = 0;
-    for mat in ac.find_iter(line) {
-        assert!(mat.start() >= i);
+    for captures in re.captures_iter(line) {
+        let mat = captures.get(0).unwrap();
+        if mat.is_empty() {
+            continue;
+        }
+        let rule = groups
+            .iter()
+            .position(|group| captures.get(*group).is_some())
+            .unwrap();
+        let (start, end) = rules.rules[rule].style.escapes();
         res += &line[i..mat.start()];
-        res += termion::color::LightRed.fg_str();
-        res += &line[mat.start()..mat.end()];
-        res += termion::color::Reset.fg_str();
+        res += start;
+        res += mat.as_str();
+        res += end;
         i = mat.end();
     }
     res += &line[i..];
     res
 }
 
+/// Marks all of the text in the style of the first rule matching all of it, if any.
+pub fn highlight_whole(text: &str, rules: &HighlightRules) -> String {
+    match rules.matching_whole(text) {
+        Some(rule) => {
+            let (start, end) = rule.style.escapes();
+            format!("{start}{text}{end}")
+        }
+        None => text.to_string(),
+    }
+}
+
+/// Escapes text for use in HTML, also inside attribute values.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn rows() {
+        let red = termion::color::LightRed.fg_str();
+        let text = format!("ab{red}cd\nefg");
+        assert_eq!(
+            screen_rows(&text, 3),
+            vec![format!("ab{red}c"), "d".to_string(), "efg".to_string()]
+        );
+    }
+
+    #[test]
+    fn wrap_words() {
+        assert_eq!(
+            wrap("We prove the\n twin prime conjecture.", 12),
+            vec!["We prove the", "twin prime", "conjecture."]
+        );
+    }
+
+    #[test]
+    fn highlight_rules() {
+        let rules = |toml: &str| {
+            toml::from_str::<crate::config::Highlight>(&format!("keywords = [{toml}]"))
+                .map(|highlight| highlight.keywords)
+        };
+        let authors = |toml: &str| {
+            toml::from_str::<crate::config::Highlight>(&format!("authors = [{toml}]"))
+                .map(|highlight| highlight.authors)
+        };
+        let red = termion::color::LightRed.fg_str();
+        let green = termion::color::LightGreen.fg_str();
+        let reset = termion::color::Reset.fg_str();
+        let keywords =
+            rules(r#""Iwasawa", { regex = "p-?adic", whole_word = true, style = "green" }"#)
+                .unwrap();
+        assert_eq!(
+            highlight_matches("iwasawa theory of padic and p-adically", &keywords),
+            format!("{red}iwasawa{reset} theory of {green}padic{reset} and p-adically")
+        );
+        assert_eq!(
+            highlight_matches("iwasawa", &authors(r#""Iwasawa""#).unwrap()),
+            "iwasawa"
+        );
+        let groups = rules(r#"{ regex = "(a)(b)" }, { text = "c", style = "green" }"#).unwrap();
+        assert_eq!(
+            highlight_matches("abc", &groups),
+            format!("{red}ab{reset}{green}c{reset}")
+        );
+        assert_eq!(
+            highlight_whole("math.NT", &rules(r#""math.N""#).unwrap()),
+            "math.NT"
+        );
+        assert_eq!(
+            highlight_whole("math.NT", &rules(r#"{ regex = 'math\..*' }"#).unwrap()),
+            format!("{red}math.NT{reset}")
+        );
+        assert!(rules(r#"{ regex = "(" }"#).is_err());
+        assert!(rules(r#"{ text = "a", regex = "a" }"#).is_err());
+    }
+
+    #[test]
+    fn html() {
+        assert_eq!(
+            escape_html("<a href=\"x\">$a<b$ & c</a>"),
+            "&lt;a href=&quot;x&quot;&gt;$a&lt;b$ &amp; c&lt;/a&gt;"
+        );
+    }
+
     #[test]
     fn highlight() {
         assert_eq!(
             highlight_matches(
                 "abc def ghidef",
-                false,
-                &vec!["def".to_string(), "ghi".to_string()]
+                &toml::from_str::<crate::config::Highlight>(r#"keywords = ["def", "ghi"]"#)
+                    .unwrap()
+                    .keywords
             ),
             "abc \u{1b}[38;5;9mdef\u{1b}[39m \u{1b}[38;5;9mghi\u{1b}[39m\u{1b}[38;5;9mdef\u{1b}[39m"
         );
     }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("RE", "read"), Some(0));
+        assert_eq!(fuzzy_score("rd", "read"), Some(2));
+        assert_eq!(fuzzy_score("rd", "reread"), Some(4));
+        assert_eq!(fuzzy_score("dr", "read"), None);
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(1500), "1.5 kB");
+        assert_eq!(format_size(23_400_000), "23.4 MB");
+    }
 }
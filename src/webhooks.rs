@@ -0,0 +1,108 @@
+//! Notifications about new articles sent to web services after `arxiv-reader pull`.
+
+use anyhow::Context;
+use serde_json::json;
+
+use crate::{
+    article::Article,
+    authors::format_authors,
+    config::{AuthorList, Webhook, WebhookFormat},
+    rate_limited_client::Client,
+    share::one_line,
+    util::escape_html,
+};
+
+/// Returns the JSON body announcing the given articles. The author lists in messages
+/// (but not in the metadata of the json format) are formatted as in `author_list`.
+pub fn payload(
+    format: WebhookFormat,
+    author_list: &AuthorList,
+    name: &str,
+    articles: &[&Article],
+) -> serde_json::Value {
+    let url = |article: &Article| article.abs_url();
+    match format {
+        WebhookFormat::Json => json!({
+            "name": name,
+            "articles": articles.iter().map(|article| json!({
+                "id": article.id().to_string(),
+                "version": article.last_version().number,
+                "title": one_line(article.title()),
+                "authors": one_line(article.authors()),
+                "categories": article.categories(),
+                "abstract": article.abstract_().trim(),
+                "url": url(article),
+            })).collect::<Vec<_>>(),
+        }),
+        WebhookFormat::Slack => {
+            let mut text = format!("{} new articles ({}):", articles.len(), escape_slack(name));
+            for article in articles {
+                text += &format!(
+                    "\n• <{}|{}> by {}",
+                    url(article),
+                    escape_slack(&one_line(article.title())),
+                    escape_slack(&format_authors(article.authors(), author_list))
+                );
+            }
+            json!({ "text": text })
+        }
+        WebhookFormat::Matrix => {
+            let mut text = format!("{} new articles ({name}):", articles.len());
+            let mut html = format!(
+                "<p>{} new articles ({}):</p><ul>",
+                articles.len(),
+                escape_html(name)
+            );
+            for article in articles {
+                let title = one_line(article.title());
+                let authors = format_authors(article.authors(), author_list);
+                text += &format!("\n- {title} by {authors} {}", url(article));
+                html += &format!(
+                    "<li><a href=\"{}\">{}</a> by {}</li>",
+                    url(article),
+                    escape_html(&title),
+                    escape_html(&authors)
+                );
+            }
+            html += "</ul>";
+            json!({ "text": text, "html": html })
+        }
+    }
+}
+
+/// Escapes the characters that Slack's message formatting gives a meaning to, so that a
+/// title such as "A < B" is not taken for a link.
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Posts the announcement of the given articles to the webhook. `name` describes the
+/// filter.
+pub fn notify(
+    client: &mut Client,
+    webhook: &Webhook,
+    author_list: &AuthorList,
+    name: &str,
+    articles: &[&Article],
+) -> anyhow::Result<()> {
+    let body = payload(webhook.format, author_list, name, articles).to_string();
+    client
+        .post(&webhook.url, "application/json", &body)
+        .with_context(|| format!("posting to {}", webhook.url))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slack_escapes() {
+        assert_eq!(
+            escape_slack("<K> & the primes > 3"),
+            "&lt;K&gt; &amp; the primes &gt; 3"
+        );
+    }
+}
@@ -0,0 +1,595 @@
+//! Tests of the network code that replay previously recorded responses from arXiv.
+
+use std::{
+    collections::HashSet,
+    fs::{create_dir, remove_dir_all},
+    path::{Path, PathBuf},
+};
+
+use rusqlite::Connection;
+
+use arxiv_reader::{
+    article::{Article, ArticleMetadata, ArticleState},
+    arxiv_api,
+    authors::AuthorClusters,
+    bibtex,
+    config::{AuthorList, Config, Downloads, Webhook, WebhookFormat},
+    crossref,
+    db::{self, DumpFormat},
+    downloads::{self, DownloadDir},
+    feed::{self, FeedFormat, LastPull},
+    filter::Filter,
+    fulltext,
+    graph::{Graph, GraphFormat},
+    keywords,
+    oai::{self, Harvest},
+    pull,
+    rate_limited_client::Client,
+    util::TempDir,
+    webhooks,
+};
+
+/// Creates an empty arxiv-reader directory with a database, which is removed when the
+/// returned guard is dropped (also if the test fails).
+fn base_dir(name: &str) -> TempDir {
+    let dir = TempDir::new(&format!("test-{name}")).unwrap();
+    create_dir(dir.path().join("articles")).unwrap();
+    db::create(dir.path()).unwrap();
+    dir
+}
+
+/// Like `base_dir`, with the articles of the recorded pull of math.NT in the database.
+fn pulled_base_dir(name: &str) -> (TempDir, Connection) {
+    let dir = base_dir(name);
+    let mut conn = db::open(dir.path()).unwrap();
+    oai::download_changes(
+        dir.path(),
+        &mut conn,
+        "math.NT",
+        &mut Client::replaying(fixtures("pull")),
+        Harvest::Records,
+    )
+    .unwrap();
+    (dir, conn)
+}
+
+fn fixtures(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+#[test]
+fn pull_and_download_pdf() {
+    let tmp = base_dir("pull");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("pull"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    let new_ids = oai::download_changes(
+        base_dir,
+        &mut conn,
+        "math.NT",
+        &mut client,
+        Harvest::Records,
+    )
+    .unwrap();
+    assert_eq!(new_ids.len(), 2);
+    // Nothing went wrong, so there should be no copy of the response.
+    assert!(!base_dir.join("update.xml").exists());
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let id = "2301.01234".parse().unwrap();
+        let metadata = ArticleMetadata::load_one(&tr, &id)?.unwrap();
+        assert_eq!(metadata.title, "On the distribution of twin primes");
+        assert_eq!(metadata.categories, vec!["math.NT", "math.AG"]);
+        assert_eq!(metadata.versions.len(), 2);
+        assert_eq!(metadata.versions[0].first_encounter, "2025-10-05");
+        assert_eq!(metadata.last_change.as_deref(), Some("2025-10-04"));
+        let withdrawn = ArticleMetadata::load_one(&tr, &"math/9901001".parse().unwrap())?.unwrap();
+        assert!(withdrawn.last_version().probably_withdrawn());
+
+        let article = Article::load_one(base_dir, &tr, &id)?;
+        let download_dir = DownloadDir::new(base_dir, &Downloads::default());
+        article.download_pdf(&download_dir, &mut client)?;
+        assert!(base_dir.join("articles/2301.01234/v2.pdf").is_file());
+        // The pdf was received into a temporary file, which was renamed.
+        assert!(!base_dir.join("articles/2301.01234/v2.pdf~").exists());
+        // The pdf is only downloaded once.
+        article.download_pdf(&download_dir, &mut client)?;
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn pull_pipeline() {
+    let tmp = base_dir("pipeline");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("pull"));
+    let config: Config = toml::from_str(
+        r#"
+        categories = ["math.NT"]
+        [filters]
+        new = "title primes"
+        update = "bookmarked"
+        "#,
+    )
+    .unwrap();
+
+    let summary = pull::run(base_dir, &config, &mut client).unwrap();
+    assert_eq!(summary.new_ids.len(), 2);
+    assert_eq!(summary.auto_tagged, 0);
+    db::with_transaction(&mut db::open(base_dir).unwrap(), base_dir, |tr| {
+        let pulls = LastPull::load_recent(&tr)?;
+        assert_eq!(pulls.len(), 1);
+        assert_eq!(pulls[0].ids.len(), 2);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn download_all() {
+    let tmp = base_dir("download-all");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("pull"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    oai::download_changes(
+        base_dir,
+        &mut conn,
+        "math.NT",
+        &mut client,
+        Harvest::Records,
+    )
+    .unwrap();
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let article = Article::load_one(base_dir, &tr, &"2301.01234".parse()?)?;
+        let download_dir = DownloadDir::new(base_dir, &Downloads::default());
+        let files = [(&article, false)];
+        assert_eq!(
+            downloads::download_all(&tr, &download_dir, &mut client, &files, 4)?,
+            (1, 0)
+        );
+        assert!(base_dir.join("articles/2301.01234/v2.pdf").is_file());
+        // Files that have already been downloaded are not requested again.
+        assert_eq!(
+            downloads::download_all(&tr, &download_dir, &mut client, &files, 4)?,
+            (0, 0)
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn retry_failed_downloads_of_missing_articles() {
+    let tmp = base_dir("retry-failed");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("pull"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        tr.execute(
+            "INSERT INTO failed_download (id, kind, error, time) VALUES ('2301.01234', 'pdf', 'timeout', '2023-01-02T00:00:00+00:00')",
+            (),
+        )?;
+        let download_dir = DownloadDir::new(base_dir, &Downloads::default());
+        // The article is not in the database, so the row is removed without counting it
+        // as a failure.
+        assert_eq!(
+            downloads::retry_failed(&tr, base_dir, &download_dir, &mut client)?,
+            (0, 0)
+        );
+        let rows: usize = tr.query_one("SELECT COUNT(*) FROM failed_download", (), |row| row.get(0))?;
+        assert_eq!(rows, 0);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn unknown_category() {
+    let tmp = base_dir("unknown-category");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("pull"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    // The recorded responses only contain the set math:math:NT.
+    let res = oai::download_changes(
+        base_dir,
+        &mut conn,
+        "math.AG",
+        &mut client,
+        Harvest::Records,
+    );
+    assert!(res.is_err());
+}
+
+#[test]
+fn subscribe_to_set() {
+    let tmp = base_dir("set");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("set"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    // A set spec is used as it is, without looking up the list of sets.
+    let new_ids = oai::download_set(
+        base_dir,
+        &mut conn,
+        "math:math:NT",
+        &mut client,
+        Harvest::Records,
+    )
+    .unwrap();
+    assert_eq!(new_ids.len(), 2);
+}
+
+#[test]
+fn harvest_identifiers() {
+    let tmp = base_dir("identifiers");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("identifiers"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    oai::download_set(
+        base_dir,
+        &mut conn,
+        "math:math:NT",
+        &mut client,
+        Harvest::Records,
+    )
+    .unwrap();
+    // Only the two records whose datestamp changed are downloaded. The deleted record is
+    // skipped.
+    let new_ids = oai::download_set(
+        base_dir,
+        &mut conn,
+        "math:math:NT",
+        &mut client,
+        Harvest::Identifiers,
+    )
+    .unwrap();
+    assert_eq!(new_ids, vec!["2510.00002".parse().unwrap()]);
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let new = ArticleMetadata::load_one(&tr, &new_ids[0])?.unwrap();
+        assert_eq!(new.versions[0].first_encounter, "2025-10-07");
+        let changed = ArticleMetadata::load_one(&tr, &"math/9901001".parse()?)?.unwrap();
+        assert_eq!(changed.last_change.as_deref(), Some("2025-10-06"));
+        assert!(changed.comments.is_some());
+        assert!(ArticleMetadata::load_one(&tr, &"2510.00003".parse()?)?.is_none());
+        // The full-text index only has the new metadata of the changed article.
+        let lemma = fulltext::search_metadata(&tr, "lemma")?;
+        assert_eq!(lemma.into_iter().collect::<Vec<_>>(), ["math/9901001"]);
+        let entries: i64 = tr.query_row(
+            "SELECT count(*) FROM metadata_fts WHERE metadata_fts MATCH 'withdrawn'",
+            (),
+            |row| row.get(0),
+        )?;
+        assert_eq!(entries, 1);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn fetch_single_article() {
+    let tmp = base_dir("fetch");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("fetch"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let id = "2502.00001".parse().unwrap();
+        assert!(oai::fetch(&tr, &mut client, &id)?);
+        let metadata = ArticleMetadata::load_one(&tr, &id)?.unwrap();
+        assert_eq!(metadata.title, "Notes on the analytical engine");
+        assert_eq!(metadata.versions[0].first_encounter, "2025-10-06");
+        assert!(oai::fetch(&tr, &mut client, &"2502.99999".parse().unwrap()).is_err());
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn dump_and_load() {
+    let (tmp, mut conn) = pulled_base_dir("dump");
+    let base_dir = tmp.path();
+
+    let mut dumps = Vec::new();
+    for format in [DumpFormat::Json, DumpFormat::JsonLines] {
+        db::with_transaction(&mut conn, base_dir, |tr| {
+            let mut dump = Vec::new();
+            db::dump(&tr, base_dir, None, format, &mut dump)?;
+            dumps.push(dump);
+            Ok(())
+        })
+        .unwrap();
+    }
+    assert_eq!(String::from_utf8_lossy(&dumps[1]).lines().count(), 3);
+
+    for dump in dumps {
+        let other_dir = base_dir.join("other");
+        create_dir(&other_dir).unwrap();
+        db::create(&other_dir).unwrap();
+        let mut other = db::open(&other_dir).unwrap();
+        db::with_write_transaction(&mut other, &other_dir, |tr| db::load(tr, &mut &dump[..]))
+            .unwrap();
+        db::with_transaction(&mut other, &other_dir, |tr| {
+            let metadatas = ArticleMetadata::load(&tr)?;
+            assert_eq!(metadatas.len(), 2);
+            let id = "2301.01234".parse().unwrap();
+            assert_eq!(metadatas[&id].versions[0].first_encounter, "2025-10-05");
+            Ok(())
+        })
+        .unwrap();
+        remove_dir_all(&other_dir).unwrap();
+    }
+}
+
+#[test]
+fn state_cache() {
+    let tmp = base_dir("state-cache");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("fetch"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let id = "2502.00001".parse().unwrap();
+        oai::fetch(&tr, &mut client, &id)?;
+        assert_eq!(ArticleState::set_cache_enabled(&tr, base_dir, true)?, 0);
+        let tag = "lovelace".parse()?;
+        Article::load_one(base_dir, &tr, &id)?.set_tag(base_dir, &tr, &tag)?;
+        assert!(Article::load_one(base_dir, &tr, &id)?.tags().contains(&tag));
+        // Changes to the files of an article are read when the cache is refreshed (for
+        // example, after a git pull), not on every load.
+        std::fs::remove_file(id.directory(base_dir).join("tags"))?;
+        assert!(Article::load_one(base_dir, &tr, &id)?.tags().contains(&tag));
+        ArticleState::set_cache_enabled(&tr, base_dir, true)?;
+        assert!(Article::load_one(base_dir, &tr, &id)?.tags().is_empty());
+        // Article directories created from outside are noticed.
+        std::fs::remove_dir_all(id.directory(base_dir))?;
+        std::fs::create_dir_all(id.directory(base_dir))?;
+        std::fs::write(id.directory(base_dir).join("tags"), "lovelace\n")?;
+        assert!(Article::load_one(base_dir, &tr, &id)?.tags().contains(&tag));
+        ArticleState::set_cache_enabled(&tr, base_dir, true)?;
+
+        // The seen state is cached, and lines appended to seen-articles are read.
+        let seen = base_dir.join("seen-articles");
+        std::fs::write(
+            &seen,
+            "2502.00001 1 false false 2025-10-01T10:00:00+02:00\n",
+        )?;
+        ArticleState::set_cache_enabled(&tr, base_dir, true)?;
+        assert_eq!(
+            Article::load_one(base_dir, &tr, &id)?.last_seen_version(),
+            1
+        );
+        let mut file = Article::open_seen_file(base_dir)?;
+        Article::load_one(base_dir, &tr, &id)?.unsee(&mut file, 0)?;
+        assert_eq!(
+            Article::load_one(base_dir, &tr, &id)?.last_seen_version(),
+            0
+        );
+        // If the file is rewritten, it is read again.
+        std::fs::write(
+            &seen,
+            "2502.00001 2 false false 2025-10-01T10:00:00+02:00\n",
+        )?;
+        assert_eq!(
+            Article::load_one(base_dir, &tr, &id)?.last_seen_version(),
+            2
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn crossref_lookup() {
+    let tmp = base_dir("crossref");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("crossref"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let id = "2502.00001".parse().unwrap();
+        oai::fetch(&tr, &mut client, &id)?;
+        let article = Article::load_one(base_dir, &tr, &id)?;
+        // The preprint and the article with another title are skipped.
+        assert_eq!(crossref::update(&tr, &mut client, &[&article], false)?, 1);
+        let publication = crossref::load(&tr)?.remove(&id).unwrap();
+        assert_eq!(publication.doi, "10.5555/engine.1843");
+        assert_eq!(
+            publication.citation(),
+            "Scientific Memoirs 3 (2025), no. 29, 666-731"
+        );
+        // The article was looked up recently, so there is no request.
+        assert_eq!(crossref::update(&tr, &mut client, &[&article], false)?, 0);
+
+        let bib = base_dir.join("refs.bib");
+        std::fs::write(
+            &bib,
+            "@article{lovelace, author = {Ada Lovelace}, eprint = {2502.00001}, eprinttype = {arXiv}}",
+        )?;
+        let findings = bibtex::check(base_dir, &tr, &bib)?;
+        assert_eq!(findings.len(), 1);
+        assert!(matches!(
+            findings[0].kind,
+            bibtex::FindingKind::FoundOnCrossref { .. }
+        ));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn follow_search() {
+    let tmp = base_dir("search");
+    let base_dir = tmp.path();
+    let mut client = Client::replaying(fixtures("search"));
+    let mut conn = db::open(base_dir).unwrap();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let new_ids = arxiv_api::download_matches(&tr, &mut client, "au:Lovelace_A")?;
+        assert_eq!(new_ids, vec!["2502.00001".parse().unwrap()]);
+        // The second time, the latest version is already known and not fetched again.
+        assert!(arxiv_api::download_matches(&tr, &mut client, "au:Lovelace_A")?.is_empty());
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn slack_webhook() {
+    let (tmp, mut conn) = pulled_base_dir("webhook");
+    let base_dir = tmp.path();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let article = Article::load_one(base_dir, &tr, &"2301.01234".parse().unwrap())?;
+        let webhook = Webhook {
+            url: "https://hooks.example.com/arxiv".to_string(),
+            format: WebhookFormat::Slack,
+            filter: None,
+        };
+        let mut client = Client::replaying(fixtures("webhook"));
+        webhooks::notify(
+            &mut client,
+            &webhook,
+            &AuthorList::default(),
+            "filters.new",
+            &[&article],
+        )
+    })
+    .unwrap();
+}
+
+#[test]
+fn coauthor_graph() {
+    let (tmp, mut conn) = pulled_base_dir("graph");
+    let base_dir = tmp.path();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let articles = Article::load(base_dir, &tr)?;
+        let clusters = AuthorClusters::new(articles.values().map(|a| a.authors().as_str()));
+        let graph = Graph::coauthors(articles.values(), &clusters, |name| name.to_uppercase());
+        assert_eq!(
+            graph.write(GraphFormat::Dot),
+            "graph coauthors {\n  \"ADRIEN-MARIE LEGENDRE\" [weight=1];\n  \"CARL FRIEDRICH GAUSS\" [weight=1];\n  \"LEONHARD EULER\" [weight=1];\n  \"ADRIEN-MARIE LEGENDRE\" -- \"CARL FRIEDRICH GAUSS\" [weight=1];\n}\n"
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn keywords_of_all_articles_after_settings_change() {
+    let (tmp, mut conn) = pulled_base_dir("keywords");
+    let base_dir = tmp.path();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        let new_ids = ["2301.01234".parse()?].into_iter().collect();
+        // No keywords were saved yet, so all are computed.
+        assert_eq!(keywords::update(&tr, Some(&new_ids))?, 2);
+        assert_eq!(keywords::update(&tr, Some(&new_ids))?, 1);
+        assert_eq!(keywords::update(&tr, Some(&HashSet::new()))?, 0);
+        // Counting the words of some articles again gives the same keywords.
+        let computed = keywords::load(&tr)?;
+        keywords::update(&tr, None)?;
+        assert_eq!(keywords::load(&tr)?, computed);
+        // The keywords were chosen with other settings.
+        tr.execute("UPDATE setting SET value = '{}' WHERE key = 'keywords'", ())?;
+        assert_eq!(keywords::update(&tr, Some(&new_ids))?, 2);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn atom_feed_of_last_pull() {
+    let (tmp, mut conn) = pulled_base_dir("feed");
+    let base_dir = tmp.path();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        // All articles in the database are new.
+        let last_pull = LastPull {
+            time: "2025-10-05T08:00:00+00:00".parse().unwrap(),
+            ids: ArticleMetadata::load(&tr)?.into_keys().collect(),
+        };
+        last_pull.save(&tr)?;
+        // Later pulls do not replace the earlier ones, until they are a week old.
+        let later_pull = |time: &str| LastPull {
+            time: time.parse().unwrap(),
+            ids: vec![],
+        };
+        later_pull("2025-10-12T07:00:00+00:00").save(&tr)?;
+        let pulls = LastPull::load_recent(&tr)?;
+        assert_eq!(pulls.len(), 2);
+        let last_pull = &pulls[0];
+        assert_eq!(last_pull.ids.len(), 2);
+        let article = Article::load_one(base_dir, &tr, &"2301.01234".parse().unwrap())?;
+        let xml = feed::write(FeedFormat::Atom, &last_pull.time, &[&article], |s| {
+            s.to_string()
+        });
+        assert!(xml.contains("<updated>2025-10-05T08:00:00+00:00</updated>"));
+        assert!(xml.contains("<title>On the distribution of twin primes</title>"));
+        assert!(xml.contains("<id>https://arxiv.org/abs/2301.01234</id>"));
+        later_pull("2025-10-12T09:00:00+00:00").save(&tr)?;
+        let pulls = LastPull::load_recent(&tr)?;
+        assert_eq!(pulls.len(), 2);
+        assert!(pulls.iter().all(|pull| pull.ids.is_empty()));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn id_filter_matches_only_the_given_ids() {
+    let (tmp, mut conn) = pulled_base_dir("id_filter");
+    let base_dir = tmp.path();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        // math/9901001 is in the database, but not in the condition.
+        let filter: Filter = "id 2301.01234 2302.00001".parse()?;
+        let matching = Article::load_matching(base_dir, &tr, &filter.prepare(&tr)?)?;
+        assert_eq!(
+            matching.keys().map(|id| id.to_string()).collect::<Vec<_>>(),
+            vec!["2301.01234"]
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn author_filter_ignores_accents() {
+    let (tmp, mut conn) = pulled_base_dir("author");
+    let base_dir = tmp.path();
+
+    db::with_transaction(&mut conn, base_dir, |tr| {
+        for query in [
+            "author Gauß",
+            "author 'Legendre, A.-M.'",
+            "author 'C. F. Gauss'",
+        ] {
+            let filter: Filter = query.parse()?;
+            let matching = Article::load_matching(base_dir, &tr, &filter.prepare(&tr)?)?;
+            assert_eq!(
+                matching.keys().map(|id| id.to_string()).collect::<Vec<_>>(),
+                vec!["2301.01234"],
+                "{query}"
+            );
+        }
+        // Only whole names match.
+        for query in ["author Gaus", "author Eule", "author 'K. F. Gauss'"] {
+            let filter: Filter = query.parse()?;
+            let matching = Article::load_matching(base_dir, &tr, &filter.prepare(&tr)?)?;
+            assert!(matching.is_empty(), "{query}");
+        }
+        Ok(())
+    })
+    .unwrap();
+}